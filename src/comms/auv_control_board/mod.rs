@@ -1,10 +1,10 @@
 use core::fmt::Debug;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
-use tokio::{io::AsyncWriteExt, sync::Mutex};
+use tokio::{io::AsyncWriteExt, sync::Mutex, time::timeout};
 
-use self::util::{crc_itt16_false, AcknowledgeErr};
+use self::util::{crc_itt16_false, AckTimeout, AcknowledgeErr};
 
 use super::auv_control_board::util::{END_BYTE, ESCAPE_BYTE, START_BYTE};
 
@@ -14,6 +14,8 @@ pub mod util;
 #[allow(async_fn_in_trait)]
 pub trait GetAck {
     async fn get_ack(&self, id: u16) -> Result<Vec<u8>, AcknowledgeErr>;
+    /// Stops waiting on the acknowledge of `id`, dropping it now or when it arrives
+    async fn forget_ack(&self, id: u16);
 }
 
 const ID_LIMIT: u16 = 59999;
@@ -50,6 +52,7 @@ where
     comm_out: Arc<Mutex<T>>,
     responses: U,
     msg_id: MessageId,
+    ack_timeout: Mutex<Option<Duration>>,
 }
 
 impl<T: AsyncWriteExt + Unpin, U: GetAck> AUVControlBoard<T, U> {
@@ -58,6 +61,7 @@ impl<T: AsyncWriteExt + Unpin, U: GetAck> AUVControlBoard<T, U> {
             comm_out,
             responses,
             msg_id,
+            ack_timeout: Mutex::default(),
         }
     }
 
@@ -65,6 +69,28 @@ impl<T: AsyncWriteExt + Unpin, U: GetAck> AUVControlBoard<T, U> {
         &self.responses
     }
 
+    /// Bounds how long acknowledged writes wait, None waits forever
+    ///
+    /// A missed acknowledge fails the write with [`AckTimeout`].
+    pub async fn set_ack_timeout(&self, ack_timeout: Option<Duration>) {
+        *self.ack_timeout.lock().await = ack_timeout;
+    }
+
+    /// Waits for the acknowledge of message `id`, up to `ack_timeout`
+    async fn wait_ack(&self, id: u16, ack_timeout: Option<Duration>) -> Result<Vec<u8>> {
+        let ack = match ack_timeout {
+            Some(after) => match timeout(after, self.responses.get_ack(id)).await {
+                Ok(ack) => ack,
+                Err(_) => {
+                    self.responses.forget_ack(id).await;
+                    return Err(AckTimeout { id, after }.into());
+                }
+            },
+            None => self.responses.get_ack(id).await,
+        };
+        Ok(ack?)
+    }
+
     /// Adds protocol requirements (e.g. message id, escapes) to a message body
     /// Returns the id assigned to the message and the message
     async fn add_metadata(&self, message: &[u8]) -> (u16, Vec<u8>) {
@@ -106,17 +132,34 @@ impl<T: AsyncWriteExt + Unpin, U: GetAck> AUVControlBoard<T, U> {
         let (id, message) = self.add_metadata(&message_body).await;
         self.comm_out.lock().await.write_all(&message).await?;
         // Spec guarantees empty response
-        self.responses.get_ack(id).await?;
+        let ack_timeout = *self.ack_timeout.lock().await;
+        self.wait_ack(id, ack_timeout).await?;
         Ok(())
     }
 
+    /// Writes out a message body and waits up to `ack_timeout` for the board to
+    /// acknowledge it, regardless of [`Self::set_ack_timeout`]
+    ///
+    /// For critical commands that must be known to be applied.
+    /// Returns the id the board acknowledged.
+    pub async fn write_out_acked(
+        &self,
+        message_body: Vec<u8>,
+        ack_timeout: Duration,
+    ) -> Result<u16> {
+        let (id, message) = self.add_metadata(&message_body).await;
+        self.comm_out.lock().await.write_all(&message).await?;
+        self.wait_ack(id, Some(ack_timeout)).await?;
+        Ok(id)
+    }
+
     /// Writes out a message body and only gives acknowledge status
     /// Only for communications that return no data with acknowledge
     pub async fn write_out(&self, message_body: Vec<u8>) -> Result<Vec<u8>> {
         let (id, message) = self.add_metadata(&message_body).await;
         self.comm_out.lock().await.write_all(&message).await?;
-        // Spec guarantees empty response
-        Ok(self.responses.get_ack(id).await?)
+        let ack_timeout = *self.ack_timeout.lock().await;
+        self.wait_ack(id, ack_timeout).await
     }
 
     pub async fn write_out_no_response(&self, message_body: Vec<u8>) -> Result<()> {
@@ -128,3 +171,135 @@ impl<T: AsyncWriteExt + Unpin, U: GetAck> AUVControlBoard<T, U> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{duplex, DuplexStream},
+        time::sleep,
+    };
+
+    use super::{response::get_messages, *};
+    use crate::comms::control_board::response::KeyedAcknowledges;
+
+    /// Acknowledges every command except `DROP`, as a board ignoring it would,
+    /// and `LATE`, which is acknowledged after 100 ms
+    #[derive(Default)]
+    struct MockBoard {
+        acks: Arc<Mutex<KeyedAcknowledges>>,
+    }
+
+    impl MockBoard {
+        fn listen(&self, mut board_in: DuplexStream) {
+            let acks = self.acks.clone();
+            tokio::spawn(async move {
+                let mut buffer = Vec::with_capacity(512);
+                loop {
+                    for message in get_messages(
+                        &mut buffer,
+                        &mut board_in,
+                        #[cfg(feature = "logging")]
+                        "mock_board",
+                    )
+                    .await
+                    {
+                        let id = u16::from_be_bytes([message[0], message[1]]);
+                        let body = &message[2..message.len() - 2];
+                        if body == b"LATE" {
+                            let acks = acks.clone();
+                            tokio::spawn(async move {
+                                sleep(Duration::from_millis(100)).await;
+                                acks.lock().await.insert(id, Ok(vec![]));
+                            });
+                        } else if body != b"DROP" {
+                            acks.lock().await.insert(id, Ok(vec![]));
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    impl GetAck for MockBoard {
+        async fn get_ack(&self, id: u16) -> Result<Vec<u8>, AcknowledgeErr> {
+            loop {
+                if let Some(x) = self.acks.lock().await.remove(&id) {
+                    return x;
+                }
+                sleep(Duration::from_millis(5)).await;
+            }
+        }
+
+        async fn forget_ack(&self, id: u16) {
+            self.acks.lock().await.abandon(id);
+        }
+    }
+
+    fn mock_control_board() -> AUVControlBoard<DuplexStream, MockBoard> {
+        let (comm_out, board_in) = duplex(512);
+        let board = MockBoard::default();
+        board.listen(board_in);
+        AUVControlBoard::new(Mutex::from(comm_out).into(), board, MessageId::default())
+    }
+
+    #[tokio::test]
+    async fn matching_ack_resolves() {
+        let control_board = mock_control_board();
+        let timeout = Duration::from_secs(1);
+
+        assert_eq!(
+            control_board
+                .write_out_acked(b"RAW".to_vec(), timeout)
+                .await
+                .unwrap(),
+            0
+        );
+        // Escaped bytes in the body still carry the right id
+        assert_eq!(
+            control_board
+                .write_out_acked(vec![util::START_BYTE, util::END_BYTE], timeout)
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_ack_times_out() {
+        let control_board = mock_control_board();
+        control_board
+            .set_ack_timeout(Some(Duration::from_millis(50)))
+            .await;
+
+        control_board
+            .write_out_basic(b"RAW".to_vec())
+            .await
+            .unwrap();
+        let err = control_board
+            .write_out_basic(b"DROP".to_vec())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AckTimeout>(),
+            Some(&AckTimeout {
+                id: 1,
+                after: Duration::from_millis(50)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn late_ack_is_dropped() {
+        let control_board = mock_control_board();
+        control_board
+            .set_ack_timeout(Some(Duration::from_millis(50)))
+            .await;
+
+        assert!(control_board
+            .write_out_basic(b"LATE".to_vec())
+            .await
+            .is_err());
+        sleep(Duration::from_millis(200)).await;
+        assert!(control_board.responses().acks.lock().await.is_empty());
+    }
+}
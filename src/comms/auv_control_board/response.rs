@@ -5,7 +5,10 @@ use tokio::io::AsyncReadExt;
 use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Mutex};
 
 use super::util::{END_BYTE, ESCAPE_BYTE, START_BYTE};
-use crate::logln;
+use crate::{log_error, logln};
+
+/// Bytes held without a complete frame before the reader resyncs
+pub const MAX_UNFRAMED_LEN: usize = 4096;
 
 #[cfg(feature = "logging")]
 static LOG_NAMES: Mutex<Vec<String>> = Mutex::const_new(Vec::new());
@@ -61,6 +64,51 @@ pub fn check_start(buffer: &mut Vec<u8>, end_idx: usize) -> Option<usize> {
     }
 }
 
+/// Unescaped start bytes in `buffer`, by index
+fn start_indices(buffer: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    let mut prev_escaped = false;
+    buffer.iter().enumerate().filter_map(move |(idx, byte)| {
+        let is_start = *byte == START_BYTE && !prev_escaped;
+        prev_escaped = !prev_escaped && *byte == ESCAPE_BYTE;
+        is_start.then_some(idx)
+    })
+}
+
+/// Skips to the last frame start before `end_idx`, returning the adjusted end_idx
+///
+/// A second start byte inside a frame means the first frame lost its end byte
+/// (partial read or line noise), so only the final start begins a real frame.
+pub fn resync_frame(buffer: &mut Vec<u8>, end_idx: usize) -> usize {
+    match start_indices(&buffer[..end_idx]).last() {
+        Some(start_idx) if start_idx > 0 => {
+            logln!(
+                "Resyncing serial frame, discarding {:?}",
+                &buffer[0..start_idx]
+            );
+            buffer.drain(0..start_idx);
+            end_idx - start_idx
+        }
+        _ => end_idx,
+    }
+}
+
+/// Discards unframed bytes once the buffer grows past `max_len` without an end byte
+///
+/// Keeps everything from the last start byte, which may still be a frame in
+/// progress.
+pub fn resync_overflow(buffer: &mut Vec<u8>, max_len: usize) {
+    if buffer.len() <= max_len {
+        return;
+    }
+
+    let keep_from = start_indices(buffer).last().unwrap_or(buffer.len());
+    logln!(
+        "Serial buffer passed {max_len} bytes without a frame, resyncing by discarding {} bytes",
+        keep_from
+    );
+    buffer.drain(0..keep_from);
+}
+
 /// Discard start, end, and escape bytes
 pub fn clean_message(buffer: &mut Vec<u8>, end_idx: usize) -> Vec<u8> {
     let message: Vec<u8> = buffer.drain(0..=end_idx).collect();
@@ -112,9 +160,11 @@ where
 
         while let Some((end_idx, _)) = find_end(buffer) {
             if let Some(end_idx) = check_start(buffer, end_idx) {
+                let end_idx = resync_frame(buffer, end_idx);
                 messages.push(clean_message(buffer, end_idx));
             }
         }
+        resync_overflow(buffer, MAX_UNFRAMED_LEN);
 
         messages
     } else if buffer.has_remaining_mut() {
@@ -144,7 +194,7 @@ pub async fn write_log(messages: &[Vec<u8>], #[cfg(feature = "logging")] dump_fi
 
         file.flush().await.unwrap();
     } else {
-        logln!("ERROR OPENING FILE IN LOGGING");
+        log_error!("ERROR OPENING FILE IN LOGGING");
     }
 }
 
@@ -213,6 +263,49 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn garbage_then_valid_frame_resyncs() {
+        // Noise containing a stray start byte, then a valid frame
+        let input: Vec<u8> = vec![1, 2, START_BYTE, 9, 9, START_BYTE, 3, END_BYTE];
+        // Escaped start byte inside a frame is data, not a new frame
+        let input2: Vec<u8> = vec![START_BYTE, ESCAPE_BYTE, START_BYTE, 4, END_BYTE];
+        let mut buffer: Vec<u8> = Vec::with_capacity(512);
+
+        let _lock = MESSAGE_LOCK.lock().await;
+        assert_eq!(
+            get_messages(
+                &mut buffer,
+                &mut &*input,
+                #[cfg(feature = "logging")]
+                "test.dat"
+            )
+            .await,
+            vec![vec![3]]
+        );
+        assert_eq!(
+            get_messages(
+                &mut buffer,
+                &mut &*input2,
+                #[cfg(feature = "logging")]
+                "test.dat"
+            )
+            .await,
+            vec![vec![START_BYTE, 4]]
+        );
+    }
+
+    #[test]
+    fn overflow_keeps_partial_frame() {
+        let mut buffer = vec![7; 10];
+        buffer.extend([START_BYTE, 1, 2]);
+        resync_overflow(&mut buffer, 8);
+        assert_eq!(buffer, [START_BYTE, 1, 2]);
+
+        let mut buffer = vec![7; 10];
+        resync_overflow(&mut buffer, 8);
+        assert!(buffer.is_empty());
+    }
+
     #[tokio::test]
     #[cfg(feature = "logging")]
     async fn input_is_logged() {
@@ -1,5 +1,5 @@
 // Implementing <https://mb3hel.github.io/AUVControlBoard/user_guide/comm_protocol/>
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, time::Duration};
 
 pub const START_BYTE: u8 = 253;
 pub const END_BYTE: u8 = 254;
@@ -66,3 +66,23 @@ impl From<u8> for AcknowledgeErr {
         }
     }
 }
+
+/// The board did not acknowledge a command in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckTimeout {
+    /// Message id of the unacknowledged command
+    pub id: u16,
+    pub after: Duration,
+}
+
+impl Display for AckTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "No acknowledge for command (id: {}) after {:?}",
+            self.id, self.after
+        )
+    }
+}
+
+impl Error for AckTimeout {}
@@ -0,0 +1,196 @@
+use std::{
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Pitch and roll are speeds in GLOBAL mode and targets otherwise
+const HEADER: &str = "unix_ms,mode,x,y,z,pitch,roll,yaw,yaw_speed,depth,t1,t2,t3,t4,t5,t6,t7,t8\n";
+
+/// A thruster or stability command, as sent to the control board
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Raw([f32; 8]),
+    Global {
+        x: f32,
+        y: f32,
+        z: f32,
+        pitch_speed: f32,
+        roll_speed: f32,
+        yaw_speed: f32,
+    },
+    Stability1 {
+        x: f32,
+        y: f32,
+        yaw_speed: f32,
+        target_pitch: f32,
+        target_roll: f32,
+        target_depth: f32,
+    },
+    Stability2 {
+        x: f32,
+        y: f32,
+        target_pitch: f32,
+        target_roll: f32,
+        target_yaw: f32,
+        target_depth: f32,
+    },
+}
+
+impl Command {
+    /// Message name the command is sent as
+    pub const fn mode(&self) -> &'static str {
+        match self {
+            Self::Raw(_) => "RAW",
+            Self::Global { .. } => "GLOBAL",
+            Self::Stability1 { .. } => "SASSIST1",
+            Self::Stability2 { .. } => "SASSIST2",
+        }
+    }
+
+    /// Values in [`HEADER`] column order after the mode, None where unused
+    fn columns(&self) -> [Option<f32>; 16] {
+        let mut columns = [None; 16];
+        match *self {
+            Self::Raw(speeds) => {
+                for (column, speed) in columns[8..].iter_mut().zip(speeds) {
+                    *column = Some(speed);
+                }
+            }
+            Self::Global {
+                x,
+                y,
+                z,
+                pitch_speed,
+                roll_speed,
+                yaw_speed,
+            } => {
+                columns[0] = Some(x);
+                columns[1] = Some(y);
+                columns[2] = Some(z);
+                columns[3] = Some(pitch_speed);
+                columns[4] = Some(roll_speed);
+                columns[6] = Some(yaw_speed);
+            }
+            Self::Stability1 {
+                x,
+                y,
+                yaw_speed,
+                target_pitch,
+                target_roll,
+                target_depth,
+            } => {
+                columns[0] = Some(x);
+                columns[1] = Some(y);
+                columns[3] = Some(target_pitch);
+                columns[4] = Some(target_roll);
+                columns[6] = Some(yaw_speed);
+                columns[7] = Some(target_depth);
+            }
+            Self::Stability2 {
+                x,
+                y,
+                target_pitch,
+                target_roll,
+                target_yaw,
+                target_depth,
+            } => {
+                columns[0] = Some(x);
+                columns[1] = Some(y);
+                columns[3] = Some(target_pitch);
+                columns[4] = Some(target_roll);
+                columns[5] = Some(target_yaw);
+                columns[7] = Some(target_depth);
+            }
+        }
+        columns
+    }
+}
+
+/// Appends every command to a CSV, one timestamped row each
+#[derive(Debug)]
+pub struct CommandLog<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CommandLog<W> {
+    /// Writes the header to a fresh `writer`
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(HEADER.as_bytes())?;
+        writer.flush()?;
+        Ok(Self { writer })
+    }
+
+    pub fn log(&mut self, command: &Command) -> io::Result<()> {
+        let unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_millis())
+            .unwrap_or_default();
+        self.log_at(unix_ms, command)
+    }
+
+    fn log_at(&mut self, unix_ms: u128, command: &Command) -> io::Result<()> {
+        let values: Vec<_> = command
+            .columns()
+            .iter()
+            .map(|val| val.map(|val| val.to_string()).unwrap_or_default())
+            .collect();
+        writeln!(
+            self.writer,
+            "{unix_ms},{},{}",
+            command.mode(),
+            values.join(",")
+        )?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_become_rows() {
+        let mut log = CommandLog::new(Vec::new()).unwrap();
+        log.log_at(
+            1,
+            &Command::Stability1 {
+                x: 0.0,
+                y: 0.5,
+                yaw_speed: -0.25,
+                target_pitch: 0.0,
+                target_roll: 0.0,
+                target_depth: -1.5,
+            },
+        )
+        .unwrap();
+        log.log_at(
+            2,
+            &Command::Stability2 {
+                x: 0.0,
+                y: 0.0,
+                target_pitch: 0.0,
+                target_roll: 0.0,
+                target_yaw: 90.0,
+                target_depth: -1.0,
+            },
+        )
+        .unwrap();
+        log.log_at(3, &Command::Raw([0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0]))
+            .unwrap();
+
+        let csv = String::from_utf8(log.writer).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), HEADER.lines().next());
+        assert_eq!(
+            lines.next(),
+            Some("1,SASSIST1,0,0.5,,0,0,,-0.25,-1.5,,,,,,,,")
+        );
+        assert_eq!(lines.next(), Some("2,SASSIST2,0,0,,0,0,90,,-1,,,,,,,,"));
+        assert_eq!(lines.next(), Some("3,RAW,,,,,,,,,0,0,0,0,0,0,0,1"));
+        assert_eq!(lines.next(), None);
+
+        // Every row fills each column
+        let columns = HEADER.trim_end().split(',').count();
+        assert!(csv.lines().all(|line| line.split(',').count() == columns));
+    }
+}
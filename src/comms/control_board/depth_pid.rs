@@ -0,0 +1,133 @@
+use std::future::Future;
+
+use anyhow::Result;
+use tokio::time::{interval, Duration, Instant, MissedTickBehavior};
+
+use crate::{
+    config::{depth_pid::Config, pid},
+    missions::pid::Pid,
+};
+
+/// Software depth loop, turning depth error into a heave speed
+///
+/// Depths are negative down, and a positive heave speed rises. Heave is
+/// clamped to `max_speed`, which is also what limits integral windup.
+#[derive(Debug, Clone)]
+pub struct DepthPid {
+    pid: Pid,
+}
+
+impl DepthPid {
+    pub fn new(config: Config) -> Self {
+        let gains = pid::Config {
+            kp: config.kp,
+            ki: config.ki,
+            kd: config.kd,
+            max_integral: f32::INFINITY,
+        };
+        Self {
+            pid: Pid::new(gains).with_max_output(config.max_speed),
+        }
+    }
+
+    /// Heave speed to reach `target` from `depth`, `dt` seconds after the last update
+    pub fn update(&mut self, target: f32, depth: f32, dt: f32) -> f32 {
+        self.pid.update(target - depth, dt)
+    }
+}
+
+/// Holds `target` depth for `duration`, with depth reads and heave commands abstracted out
+///
+/// Periods without a depth reading send nothing. Zero heave is sent at the end.
+pub async fn hold_depth_with<R, RFut, S, SFut>(
+    mut read_depth: R,
+    mut set_heave: S,
+    config: &Config,
+    target: f32,
+    duration: Duration,
+) -> Result<()>
+where
+    R: FnMut() -> RFut,
+    RFut: Future<Output = Option<f32>>,
+    S: FnMut(f32) -> SFut,
+    SFut: Future<Output = Result<()>>,
+{
+    let period = config.period()?;
+    let mut pid = DepthPid::new(config.clone());
+    let mut ticks = interval(period);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let end = Instant::now() + duration;
+    let mut last = Instant::now();
+    while Instant::now() < end {
+        ticks.tick().await;
+        let Some(depth) = read_depth().await else {
+            continue;
+        };
+        let now = Instant::now();
+        let heave = pid.update(target, depth, (now - last).as_secs_f32());
+        last = now;
+        set_heave(heave).await?;
+    }
+    set_heave(0.0).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn output_is_clamped() {
+        let mut pid = DepthPid::new(Config::default());
+        assert_eq!(pid.update(-10.0, 0.0, 0.05), -0.5);
+        assert_eq!(pid.update(10.0, 0.0, 0.05), 0.5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn heave_reaches_setpoint() {
+        let config = Config::default();
+        // Depth changes at one meter per second per unit heave
+        let depth = &Mutex::new(0.0_f32);
+        let heave = &Mutex::new(0.0_f32);
+        hold_depth_with(
+            || {
+                let mut depth = depth.lock().unwrap();
+                *depth += *heave.lock().unwrap() * config.period_secs;
+                std::future::ready(Some(*depth))
+            },
+            |speed| {
+                *heave.lock().unwrap() = speed;
+                std::future::ready(Ok(()))
+            },
+            &config,
+            -1.5,
+            Duration::from_secs(20),
+        )
+        .await
+        .unwrap();
+
+        assert!((*depth.lock().unwrap() + 1.5).abs() < 0.05);
+        assert_eq!(*heave.lock().unwrap(), 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bad_period_is_an_error() {
+        for period_secs in [0.0, -1.0, f32::NAN] {
+            let config = Config {
+                period_secs,
+                ..Config::default()
+            };
+            let held = hold_depth_with(
+                || std::future::ready(Some(0.0)),
+                |_| std::future::ready(Ok(())),
+                &config,
+                -1.5,
+                Duration::from_secs(1),
+            )
+            .await;
+            assert!(held.is_err());
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Board end of a [`ControlBoard`] link, so tests can run the real command path
+
+use std::{f32::consts::PI, sync::Arc};
+
+use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+use super::{response::ResponseMap, ControlBoard};
+use crate::{
+    comms::auv_control_board::{
+        response::get_messages,
+        util::{crc_itt16_false_bitmath, END_BYTE, ESCAPE_BYTE, START_BYTE},
+        AUVControlBoard, MessageId,
+    },
+    test_util::Calls,
+};
+
+/// Control board wired to a mock that acknowledges every command and reports
+/// an IMU `yaw` in degrees
+///
+/// Skips the startup handshake. Command bodies are recorded as they arrive.
+pub async fn mock_control_board(yaw: f32) -> (ControlBoard<DuplexStream>, Arc<Calls<Vec<u8>>>) {
+    let (comm_out, mut board_in) = duplex(512);
+    let (mut board_out, comm_in) = duplex(512);
+    let commands = Arc::new(Calls::new());
+
+    let recorded = commands.clone();
+    tokio::spawn(async move {
+        let _ = board_out.write_all(&frame(0, &imu_reading(yaw))).await;
+        let mut buffer = Vec::with_capacity(512);
+        loop {
+            for message in get_messages(
+                &mut buffer,
+                &mut board_in,
+                #[cfg(feature = "logging")]
+                "mock_control_board",
+            )
+            .await
+            {
+                let id = [message[0], message[1]];
+                recorded.push(message[2..message.len() - 2].to_vec());
+
+                let ack: Vec<u8> = b"ACK".iter().chain(&id).chain(&[0]).copied().collect();
+                let _ = board_out.write_all(&frame(0, &ack)).await;
+            }
+        }
+    });
+
+    let control_board = ControlBoard {
+        inner: AUVControlBoard::new(
+            tokio::sync::Mutex::from(comm_out).into(),
+            ResponseMap::new(comm_in).await,
+            MessageId::default(),
+        )
+        .into(),
+        initial_angles: Arc::default(),
+        soft_start: Arc::default(),
+        command_log: Arc::default(),
+        dry_run: Arc::default(),
+    };
+    (control_board, commands)
+}
+
+/// The six floats of a recorded `command`, if `body` is one
+pub fn command_floats(body: &[u8], command: &[u8; 8]) -> Option<[f32; 6]> {
+    let floats = body.strip_prefix(command)?;
+    let floats: Vec<_> = floats
+        .chunks_exact(4)
+        .map(|float| f32::from_le_bytes(float.try_into().unwrap()))
+        .collect();
+    floats.try_into().ok()
+}
+
+/// BNO055 data body for a level board facing `yaw` degrees
+fn imu_reading(yaw: f32) -> Vec<u8> {
+    let half = yaw * PI / 360.0;
+    let quaternion = [half.cos(), 0.0, 0.0, half.sin()];
+    b"BNO055D"
+        .iter()
+        .copied()
+        .chain(
+            quaternion
+                .into_iter()
+                .chain([0.0; 3])
+                .flat_map(f32::to_le_bytes),
+        )
+        .collect()
+}
+
+/// `body` framed as the board sends it, with id, CRC and escapes
+fn frame(id: u16, body: &[u8]) -> Vec<u8> {
+    let payload: Vec<u8> = id.to_be_bytes().iter().chain(body).copied().collect();
+    let crc = crc_itt16_false_bitmath(&payload);
+
+    let mut frame = vec![START_BYTE];
+    for byte in payload.into_iter().chain(crc.to_be_bytes()) {
+        if [START_BYTE, END_BYTE, ESCAPE_BYTE].contains(&byte) {
+            frame.push(ESCAPE_BYTE);
+        }
+        frame.push(byte);
+    }
+    frame.push(END_BYTE);
+    frame
+}
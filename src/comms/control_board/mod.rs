@@ -1,5 +1,14 @@
 use core::fmt::Debug;
-use std::{ops::Deref, sync::Arc, time::Duration};
+use std::{
+    fs::{create_dir_all, File},
+    future::Future,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use anyhow::{anyhow, bail, Result};
 use tokio::{
@@ -11,14 +20,24 @@ use tokio::{
 use tokio_serial::{DataBits, Parity, SerialStream, StopBits};
 
 use self::{
+    command_log::{Command, CommandLog},
+    depth_pid::hold_depth_with,
     response::ResponseMap,
-    util::{Angles, BNO055AxisConfig},
+    soft_start::{ramp_with, SoftStart},
+    util::{Angles, BNO055AxisConfig, Degrees, Depth, DofSpeeds, Speed, ThrusterSpeeds},
 };
 
-use super::auv_control_board::{AUVControlBoard, MessageId};
+use super::auv_control_board::{util::AckTimeout, AUVControlBoard, MessageId};
+#[cfg(feature = "logging")]
 use crate::logln;
+use crate::{config::depth_pid as depth_pid_config, log_warn, BackgroundWriter, TIMESTAMP};
 
+pub mod command_log;
+pub mod depth_pid;
+#[cfg(test)]
+pub mod mock;
 pub mod response;
+pub mod soft_start;
 pub mod util;
 
 pub enum SensorStatuses {
@@ -27,7 +46,14 @@ pub enum SensorStatuses {
     AllGood,
 }
 
+/// How long missions wait for a first IMU reading before giving up
+pub const ANGLES_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time between polls for an IMU reading
+const ANGLES_POLL: Duration = Duration::from_millis(50);
+
 pub static LAST_YAW: std::sync::Mutex<Option<f32>> = std::sync::Mutex::new(None);
+/// Target depth of the last stability assist command
+pub static LAST_DEPTH: std::sync::Mutex<Option<f32>> = std::sync::Mutex::new(None);
 
 #[derive(Debug)]
 pub struct ControlBoard<T>
@@ -36,6 +62,9 @@ where
 {
     inner: Arc<AUVControlBoard<T, ResponseMap>>,
     initial_angles: Arc<Mutex<Option<Angles>>>,
+    soft_start: Arc<Mutex<SoftStart>>,
+    command_log: Arc<std::sync::Mutex<Option<CommandLog<BackgroundWriter>>>>,
+    dry_run: Arc<AtomicBool>,
 }
 
 impl<T: AsyncWriteExt + Unpin> Deref for ControlBoard<T> {
@@ -46,30 +75,59 @@ impl<T: AsyncWriteExt + Unpin> Deref for ControlBoard<T> {
 }
 
 impl<T: 'static + AsyncWriteExt + Unpin + Send> ControlBoard<T> {
-    pub async fn new<U>(comm_out: T, comm_in: U, msg_id: Option<MessageId>) -> Result<Self>
+    /// Connects and configures the board, with every command from the first
+    /// waiting up to `ack_timeout` for its acknowledge, see
+    /// [`AUVControlBoard::set_ack_timeout`]
+    pub async fn new<U>(
+        comm_out: T,
+        comm_in: U,
+        msg_id: Option<MessageId>,
+        ack_timeout: Option<Duration>,
+    ) -> Result<Self>
     where
         U: 'static + AsyncRead + Unpin + Send,
     {
         const THRUSTER_INVS: [bool; 8] = [true, true, false, false, true, false, false, true];
         #[allow(clippy::approx_constant)]
-        const DOF_SPEEDS: [f32; 6] = [0.7071, 0.7071, 1.0, 0.4413, 1.0, 0.8139];
+        const DOF_SPEEDS: DofSpeeds = DofSpeeds {
+            x: 0.7071,
+            y: 0.7071,
+            z: 1.0,
+            pitch: 0.4413,
+            roll: 1.0,
+            yaw: 0.8139,
+        };
 
         let msg_id = msg_id.unwrap_or_default();
         let responses = ResponseMap::new(comm_in).await;
         let this = Self {
             inner: AUVControlBoard::new(Mutex::from(comm_out).into(), responses, msg_id).into(),
             initial_angles: Arc::default(),
+            soft_start: Arc::default(),
+            command_log: Arc::default(),
+            dry_run: Arc::default(),
         };
+        this.set_ack_timeout(ack_timeout).await;
 
         this.init_matrices().await?;
         this.thruster_inversion_set(&THRUSTER_INVS).await?;
-        this.relative_dof_speed_set_batch(&DOF_SPEEDS).await?;
+        this.relative_dof_speed_set_batch(DOF_SPEEDS).await?;
         this.bno055_imu_axis_config(BNO055AxisConfig::P6).await?;
 
         loop {
-            if let Ok(ret) = timeout(Duration::from_secs(1), this.raw_speed_set([0.0; 8])).await {
-                ret?;
-                break;
+            match timeout(
+                Duration::from_secs(1),
+                this.raw_speed_set(ThrusterSpeeds::ZERO),
+            )
+            .await
+            {
+                // A missed acknowledge is retried like a slow one
+                Ok(Err(e)) if e.is::<AckTimeout>() => (),
+                Ok(ret) => {
+                    ret?;
+                    break;
+                }
+                Err(_) => (),
             }
         }
 
@@ -89,7 +147,7 @@ impl<T: 'static + AsyncWriteExt + Unpin + Send> ControlBoard<T> {
                 .await)
                     .is_err()
                 {
-                    logln!("Watchdog ACK timed out.");
+                    log_warn!("Watchdog ACK timed out.");
                 }
 
                 sleep(Duration::from_millis(200)).await;
@@ -137,7 +195,7 @@ impl<T: 'static + AsyncWriteExt + Unpin + Send> ControlBoard<T> {
 }
 
 impl ControlBoard<WriteHalf<SerialStream>> {
-    pub async fn serial(port_name: &str) -> Result<Self> {
+    pub async fn serial(port_name: &str, ack_timeout: Option<Duration>) -> Result<Self> {
         const BAUD_RATE: u32 = 9600;
         const DATA_BITS: DataBits = DataBits::Eight;
         const PARITY: Parity = Parity::None;
@@ -148,14 +206,19 @@ impl ControlBoard<WriteHalf<SerialStream>> {
             .parity(PARITY)
             .stop_bits(STOP_BITS);
         let (comm_in, comm_out) = io::split(SerialStream::open(&port_builder)?);
-        Self::new(comm_out, comm_in, None).await
+        Self::new(comm_out, comm_in, None, ack_timeout).await
     }
 }
 
 impl ControlBoard<WriteHalf<TcpStream>> {
     /// Both connections are necessary for the simulator to run,
     /// but the one that doesn't feed forward to control board is unnecessary
-    pub async fn tcp(host: &str, port: &str, dummy_port: String) -> Result<Self> {
+    pub async fn tcp(
+        host: &str,
+        port: &str,
+        dummy_port: String,
+        ack_timeout: Option<Duration>,
+    ) -> Result<Self> {
         let host = host.to_string();
         let host_clone = host.clone();
         tokio::spawn(async move {
@@ -170,7 +233,7 @@ impl ControlBoard<WriteHalf<TcpStream>> {
 
         let stream = TcpStream::connect(host.to_string() + ":" + port).await?;
         let (comm_in, comm_out) = io::split(stream);
-        Self::new(comm_out, comm_in, None).await
+        Self::new(comm_out, comm_in, None, ack_timeout).await
     }
 }
 
@@ -243,34 +306,113 @@ impl<T: AsyncWrite + Unpin> ControlBoard<T> {
         yrot: f32,
         zrot: f32,
     ) -> Result<()> {
-        self.relative_dof_speed_set_batch(&[x, y, z, xrot, yrot, zrot])
-            .await
+        self.relative_dof_speed_set_batch(DofSpeeds {
+            x,
+            y,
+            z,
+            pitch: xrot,
+            roll: yrot,
+            yaw: zrot,
+        })
+        .await
     }
 
-    pub async fn relative_dof_speed_set_batch(&self, values: &[f32; 6]) -> Result<()> {
+    pub async fn relative_dof_speed_set_batch(&self, values: DofSpeeds) -> Result<()> {
         const DOF_SET: [u8; 6] = *b"RELDOF";
         // Oversized to avoid reallocations
         let mut message = Vec::with_capacity(32 * 8);
         message.extend(DOF_SET);
 
-        values
+        <[f32; 6]>::from(values)
             .iter()
             .for_each(|val| message.extend(val.to_le_bytes()));
 
         self.write_out_basic(message).await
     }
 
-    pub async fn raw_speed_set(&self, speeds: [f32; 8]) -> Result<()> {
+    /// Ramp duration for the first speed command after arming, zero to disable
+    pub async fn set_soft_start(&self, ramp: Duration) {
+        self.soft_start.lock().await.set_ramp(ramp);
+    }
+
+    /// Tracks the thruster arm state so arming can trigger a soft-start
+    pub async fn update_arm_state(&self, armed: Option<bool>) {
+        self.soft_start.lock().await.update_arm(armed);
+    }
+
+    async fn soft_start_scales(&self) -> Vec<f32> {
+        self.soft_start.lock().await.take_scales()
+    }
+
+    /// When enabled, every thruster and stability command is appended to a CSV under `console/`.
+    /// Rows are written by a [`BackgroundWriter`], so commands never wait on the file.
+    pub fn set_command_csv(&self, enabled: bool) -> Result<()> {
+        let log = if enabled {
+            create_dir_all("console")?;
+            let file = File::create(format!("console/commands{}.csv", &*TIMESTAMP))?;
+            Some(CommandLog::new(BackgroundWriter::spawn(move || Ok(file)))?)
+        } else {
+            None
+        };
+        *self.command_log.lock().unwrap() = log;
+        Ok(())
+    }
+
+    /// Records `command` to the command CSV, if enabled
+    fn log_command(&self, command: Command) {
+        let mut command_log = self.command_log.lock().unwrap();
+        if let Some(log) = command_log.as_mut() {
+            if let Err(e) = log.log(&command) {
+                log_warn!("Failed to write command CSV, disabling: {e}");
+                *command_log = None;
+            }
+        }
+    }
+
+    /// When enabled, speed commands are logged instead of sent to the board.
+    /// Each is printed with the `logging` feature, and recorded to the command
+    /// CSV if [enabled](Self::set_command_csv).
+    ///
+    /// Suppresses [`raw_speed_set`](Self::raw_speed_set),
+    /// [`global_speed_set`](Self::global_speed_set),
+    /// [`stability_1_speed_set`](Self::stability_1_speed_set),
+    /// [`stability_2_speed_set`](Self::stability_2_speed_set) and
+    /// [`stability_2_speed_set_initial_yaw`](Self::stability_2_speed_set_initial_yaw),
+    /// along with everything built on them. Sensor reads and configuration still go out.
+    pub fn set_dry_run(&self, enabled: bool) {
+        self.dry_run.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Logs `command` and writes its `message`, unless in dry run
+    async fn send_command(&self, command: Command, message: Vec<u8>) -> Result<()> {
+        self.log_command(command);
+        if self.dry_run.load(Ordering::Relaxed) {
+            // Once per command, so only with logging
+            #[cfg(feature = "logging")]
+            logln!("DRY RUN: {command:?}");
+            Ok(())
+        } else {
+            self.write_out_basic(message).await
+        }
+    }
+
+    pub async fn raw_speed_set(&self, speeds: ThrusterSpeeds) -> Result<()> {
+        let scales = self.soft_start_scales().await;
+        ramp_with(scales, |scale| self.raw_speed_write(speeds.scaled(scale))).await
+    }
+
+    async fn raw_speed_write(&self, speeds: ThrusterSpeeds) -> Result<()> {
         const RAW_SET: [u8; 3] = *b"RAW";
         // Oversized to avoid reallocations
         let mut message = Vec::with_capacity(32 * 8);
         message.extend(RAW_SET);
 
+        let speeds = <[f32; 8]>::from(speeds).map(|val| Speed::new(val).value());
         speeds
             .iter()
             .for_each(|val| message.extend(val.to_le_bytes()));
 
-        self.write_out_basic(message).await
+        self.send_command(Command::Raw(speeds), message).await
     }
 
     pub async fn global_speed_set(
@@ -281,17 +423,53 @@ impl<T: AsyncWrite + Unpin> ControlBoard<T> {
         pitch_speed: f32,
         roll_speed: f32,
         yaw_speed: f32,
+    ) -> Result<()> {
+        let scales = self.soft_start_scales().await;
+        ramp_with(scales, |scale| {
+            self.global_speed_write(
+                x * scale,
+                y * scale,
+                z * scale,
+                pitch_speed * scale,
+                roll_speed * scale,
+                yaw_speed * scale,
+            )
+        })
+        .await
+    }
+
+    async fn global_speed_write(
+        &self,
+        x: f32,
+        y: f32,
+        z: f32,
+        pitch_speed: f32,
+        roll_speed: f32,
+        yaw_speed: f32,
     ) -> Result<()> {
         const GLOBAL_SET: [u8; 6] = *b"GLOBAL";
         // Oversized to avoid reallocations
         let mut message = Vec::with_capacity(32 * 8);
         message.extend(GLOBAL_SET);
 
+        let [x, y, z, pitch_speed, roll_speed, yaw_speed] =
+            [x, y, z, pitch_speed, roll_speed, yaw_speed].map(|val| Speed::new(val).value());
         [x, y, z, pitch_speed, roll_speed, yaw_speed]
             .iter()
             .for_each(|val| message.extend(val.to_le_bytes()));
 
-        self.write_out_basic(message).await
+        self.send_command(
+            Command::Global {
+                x,
+                y,
+                z,
+                pitch_speed,
+                roll_speed,
+                yaw_speed,
+            },
+            message,
+        )
+        .await
     }
 
     pub async fn stability_2_speed_set(
@@ -302,18 +480,56 @@ impl<T: AsyncWrite + Unpin> ControlBoard<T> {
         target_roll: f32,
         target_yaw: f32,
         target_depth: f32,
+    ) -> Result<()> {
+        let scales = self.soft_start_scales().await;
+        ramp_with(scales, |scale| {
+            self.stability_2_speed_write(
+                x * scale,
+                y * scale,
+                target_pitch,
+                target_roll,
+                target_yaw,
+                target_depth,
+            )
+        })
+        .await
+    }
+
+    async fn stability_2_speed_write(
+        &self,
+        x: f32,
+        y: f32,
+        target_pitch: f32,
+        target_roll: f32,
+        target_yaw: f32,
+        target_depth: f32,
     ) -> Result<()> {
         const SASSIST_2: [u8; 8] = *b"SASSIST2";
         // Oversized to avoid reallocations
         let mut message = Vec::with_capacity(32 * 8);
         message.extend(SASSIST_2);
 
+        let (x, y) = (Speed::new(x).value(), Speed::new(y).value());
+        let target_yaw = Degrees::new(target_yaw).value();
+        let target_depth = Depth::new(target_depth).value();
         [x, y, target_pitch, target_roll, target_yaw, target_depth]
             .iter()
             .for_each(|val| message.extend(val.to_le_bytes()));
 
         *LAST_YAW.lock().unwrap() = Some(target_yaw);
-        self.write_out_basic(message).await
+        *LAST_DEPTH.lock().unwrap() = Some(target_depth);
+        self.send_command(
+            Command::Stability2 {
+                x,
+                y,
+                target_pitch,
+                target_roll,
+                target_yaw,
+                target_depth,
+            },
+            message,
+        )
+        .await
     }
 
     pub async fn set_initial_angle(&self) -> Result<()> {
@@ -356,11 +572,25 @@ impl<T: AsyncWrite + Unpin> ControlBoard<T> {
             }
         };
 
+        let (x, y) = (Speed::new(x).value(), Speed::new(y).value());
+        let target_yaw = Degrees::new(target_yaw).value();
+        let target_depth = Depth::new(target_depth).value();
         [x, y, target_pitch, target_roll, target_yaw, target_depth]
             .iter()
             .for_each(|val| message.extend(val.to_le_bytes()));
 
-        self.write_out_basic(message).await
+        self.send_command(
+            Command::Stability2 {
+                x,
+                y,
+                target_pitch,
+                target_roll,
+                target_yaw,
+                target_depth,
+            },
+            message,
+        )
+        .await
     }
 
     pub async fn stability_1_speed_set(
@@ -371,17 +601,54 @@ impl<T: AsyncWrite + Unpin> ControlBoard<T> {
         target_pitch: f32,
         target_roll: f32,
         target_depth: f32,
+    ) -> Result<()> {
+        let scales = self.soft_start_scales().await;
+        ramp_with(scales, |scale| {
+            self.stability_1_speed_write(
+                x * scale,
+                y * scale,
+                yaw_speed * scale,
+                target_pitch,
+                target_roll,
+                target_depth,
+            )
+        })
+        .await
+    }
+
+    async fn stability_1_speed_write(
+        &self,
+        x: f32,
+        y: f32,
+        yaw_speed: f32,
+        target_pitch: f32,
+        target_roll: f32,
+        target_depth: f32,
     ) -> Result<()> {
         const SASSIST_1: [u8; 8] = *b"SASSIST1";
         // Oversized to avoid reallocations
         let mut message = Vec::with_capacity(32 * 8);
         message.extend(SASSIST_1);
 
+        let [x, y, yaw_speed] = [x, y, yaw_speed].map(|val| Speed::new(val).value());
+        let target_depth = Depth::new(target_depth).value();
         [x, y, yaw_speed, target_pitch, target_roll, target_depth]
             .iter()
             .for_each(|val| message.extend(val.to_le_bytes()));
 
-        self.write_out_basic(message).await
+        *LAST_DEPTH.lock().unwrap() = Some(target_depth);
+        self.send_command(
+            Command::Stability1 {
+                x,
+                y,
+                yaw_speed,
+                target_pitch,
+                target_roll,
+                target_depth,
+            },
+            message,
+        )
+        .await
     }
 
     pub async fn bno055_imu_axis_config(&self, config: BNO055AxisConfig) -> Result<()> {
@@ -404,6 +671,39 @@ impl<T: AsyncWrite + Unpin> ControlBoard<T> {
         Ok(())
     }
 
+    pub async fn ms5837_periodic_read(&self, enable: bool) -> Result<()> {
+        const MS5837P: [u8; 7] = *b"MS5837P";
+
+        let mut message = Vec::from(MS5837P);
+        message.push(enable.into());
+
+        self.write_out_basic(message).await?;
+        sleep(Duration::from_millis(300)).await; // Initialization time
+        Ok(())
+    }
+
+    /// Holds `target` depth for `duration` with the software depth PID
+    ///
+    /// Heave goes out as a GLOBAL z speed, so the board's stability assist
+    /// depth loop is not involved.
+    pub async fn software_depth_hold(
+        &self,
+        target: f32,
+        config: &depth_pid_config::Config,
+        duration: Duration,
+    ) -> Result<()> {
+        self.ms5837_periodic_read(true).await?;
+        let responses = self.responses();
+        hold_depth_with(
+            || responses.get_depth(),
+            |heave| self.global_speed_set(0.0, 0.0, heave, 0.0, 0.0, 0.0),
+            config,
+            target,
+            duration,
+        )
+        .await
+    }
+
     pub async fn stability_assist_pid_tune(
         &self,
         which: char,
@@ -463,4 +763,85 @@ impl<T: AsyncWrite + Unpin> ControlBoard<T> {
     pub async fn get_initial_angles(&self) -> Option<Angles> {
         *self.initial_angles.lock().await
     }
+
+    /// Latest IMU angles, waiting for the first reading
+    ///
+    /// Errors after `limit` without one, instead of hanging on a dead IMU.
+    /// `None` waits forever.
+    pub async fn wait_for_angles(&self, limit: Option<Duration>) -> Result<Angles> {
+        let responses = self.responses();
+        wait_for_with(|| responses.get_angles(), limit, "IMU angles").await
+    }
+
+    /// Yaw from [`Self::wait_for_angles`]
+    pub async fn wait_for_yaw(&self, limit: Option<Duration>) -> Result<f32> {
+        Ok(*self.wait_for_angles(limit).await?.yaw())
+    }
+
+    /// Latest depth sensor reading, waiting as [`Self::wait_for_angles`] does
+    ///
+    /// Needs [`Self::ms5837_periodic_read`] enabled.
+    pub async fn wait_for_depth(&self, limit: Option<Duration>) -> Result<f32> {
+        let responses = self.responses();
+        wait_for_with(|| responses.get_depth(), limit, "depth reading").await
+    }
+}
+
+/// [`ControlBoard::wait_for_angles`] with the reading abstracted out
+async fn wait_for_with<F, Fut, V>(mut read: F, limit: Option<Duration>, sensor: &str) -> Result<V>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<V>>,
+{
+    let poll = async {
+        loop {
+            if let Some(value) = read().await {
+                return value;
+            }
+            sleep(ANGLES_POLL).await;
+        }
+    };
+    match limit {
+        Some(limit) => timeout(limit, poll)
+            .await
+            .map_err(|_| anyhow!("No {sensor} within {limit:?}")),
+        None => Ok(poll.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::Instant;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_through_missing_readings() {
+        let polls = std::sync::Mutex::new(0);
+        let read = || async {
+            let mut polls = polls.lock().unwrap();
+            *polls += 1;
+            (*polls > 3).then_some(42.0)
+        };
+        let start = Instant::now();
+        let found = wait_for_with(read, Some(Duration::from_secs(1)), "IMU angles")
+            .await
+            .unwrap();
+        assert_eq!(found, 42.0);
+        assert_eq!(*polls.lock().unwrap(), 4);
+        assert_eq!(start.elapsed(), ANGLES_POLL * 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dead_imu_times_out() {
+        let start = Instant::now();
+        let res = wait_for_with(
+            || async { None::<f32> },
+            Some(Duration::from_secs(1)),
+            "IMU angles",
+        )
+        .await;
+        assert!(res.is_err());
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
 }
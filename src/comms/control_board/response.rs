@@ -13,7 +13,7 @@ use futures::StreamExt;
 use tokio::{
     io::{stderr, AsyncReadExt, AsyncWriteExt},
     sync::{Mutex, RwLock},
-    time::sleep,
+    time::{sleep, Instant},
 };
 
 use crate::{
@@ -23,7 +23,7 @@ use crate::{
 
 use crate::comms::auv_control_board::util::AcknowledgeErr;
 
-use super::util::Angles;
+use super::util::{Angles, Ms5837Reading};
 
 const ACK: [u8; 3] = *b"ACK";
 const WDGS: [u8; 4] = *b"WDGS";
@@ -34,7 +34,52 @@ const DEBUG: [u8; 5] = *b"DEBUG";
 #[allow(dead_code)]
 const DBGDAT: [u8; 6] = *b"DBGDAT";
 
-pub type KeyedAcknowledges = HashMap<u16, Result<Vec<u8>, AcknowledgeErr>>;
+/// How long an abandoned id drops its late acknowledge, well short of the
+/// message id wrapping around to it again
+const ABANDONED_EXPIRY: Duration = Duration::from_secs(10);
+
+/// Acknowledges received by message id, until a waiter takes them
+///
+/// Acknowledges for ids no one waits on anymore are dropped, so a late
+/// acknowledge doesn't stay in the map for good.
+#[derive(Debug, Default)]
+pub struct KeyedAcknowledges {
+    acks: HashMap<u16, Result<Vec<u8>, AcknowledgeErr>>,
+    /// When each wait was abandoned, see [`ABANDONED_EXPIRY`]
+    abandoned: HashMap<u16, Instant>,
+}
+
+impl KeyedAcknowledges {
+    /// Stores the acknowledge of `id`, unless its wait was recently abandoned
+    pub fn insert(&mut self, id: u16, ack: Result<Vec<u8>, AcknowledgeErr>) {
+        let late = self
+            .abandoned
+            .remove(&id)
+            .is_some_and(|at| at.elapsed() < ABANDONED_EXPIRY);
+        if !late {
+            self.acks.insert(id, ack);
+        }
+    }
+
+    /// Takes the acknowledge of `id`, if it has arrived
+    pub fn remove(&mut self, id: &u16) -> Option<Result<Vec<u8>, AcknowledgeErr>> {
+        self.abandoned.remove(id);
+        self.acks.remove(id)
+    }
+
+    /// Stops waiting on `id`, dropping its acknowledge now or if it arrives
+    /// within [`ABANDONED_EXPIRY`]
+    pub fn abandon(&mut self, id: u16) {
+        if self.acks.remove(&id).is_none() {
+            self.abandoned.retain(|_, at| at.elapsed() < ABANDONED_EXPIRY);
+            self.abandoned.insert(id, Instant::now());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.acks.is_empty()
+    }
+}
 
 #[derive(Debug, Getters)]
 pub struct ResponseMap {
@@ -164,6 +209,16 @@ impl ResponseMap {
     pub async fn get_angles(&self) -> Option<Angles> {
         (*self.bno055_status.read().await).map(Angles::from_raw)
     }
+
+    /// Latest MS5837 reading, if the last frame was plausible
+    pub async fn get_ms5837(&self) -> Option<Ms5837Reading> {
+        (*self.ms5837_status.read().await).and_then(Ms5837Reading::from_raw)
+    }
+
+    /// Latest MS5837 depth in meters, negative down like stability assist targets
+    pub async fn get_depth(&self) -> Option<f32> {
+        self.get_ms5837().await.map(|reading| *reading.depth())
+    }
 }
 
 impl GetAck for ResponseMap {
@@ -175,4 +230,41 @@ impl GetAck for ResponseMap {
             sleep(MAP_POLL_SLEEP).await; // Allow for new data from serial
         }
     }
+
+    async fn forget_ack(&self, id: u16) {
+        self.ack_map.lock().await.abandon(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_acknowledge_is_dropped() {
+        let mut acks = KeyedAcknowledges::default();
+        acks.insert(0, Ok(vec![]));
+        acks.abandon(0);
+        assert!(acks.is_empty());
+
+        // Arrives after its wait timed out
+        acks.abandon(1);
+        acks.insert(1, Ok(vec![]));
+        assert!(acks.is_empty());
+
+        // Only the late one is dropped, the id's next acknowledge is kept
+        acks.insert(1, Ok(vec![1]));
+        assert_eq!(acks.remove(&1).unwrap().unwrap(), [1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn abandoned_id_expires() {
+        let mut acks = KeyedAcknowledges::default();
+        acks.abandon(0);
+
+        // The id wrapped around and was reissued, its acknowledge is kept
+        tokio::time::advance(ABANDONED_EXPIRY).await;
+        acks.insert(0, Ok(vec![0]));
+        assert_eq!(acks.remove(&0).unwrap().unwrap(), [0]);
+    }
 }
@@ -0,0 +1,106 @@
+use std::future::Future;
+
+use anyhow::Result;
+use tokio::time::{sleep, Duration};
+
+/// Time between steps of a soft-start ramp
+pub const RAMP_STEP: Duration = Duration::from_millis(50);
+
+/// Ramps the first speed command after the thrusters arm.
+///
+/// Jumping straight to a commanded speed on arm can spike current and trip
+/// the ESCs, so that command is sent at increasing fractions of full speed
+/// over `ramp` instead. Later commands are sent as given.
+#[derive(Debug)]
+pub struct SoftStart {
+    ramp: Duration,
+    armed: Option<bool>,
+    pending: bool,
+}
+
+impl SoftStart {
+    pub const fn new(ramp: Duration) -> Self {
+        Self {
+            ramp,
+            armed: None,
+            pending: false,
+        }
+    }
+
+    pub fn set_ramp(&mut self, ramp: Duration) {
+        self.ramp = ramp;
+    }
+
+    /// Records the latest arm state, queueing a ramp when it becomes armed
+    pub fn update_arm(&mut self, armed: Option<bool>) {
+        if armed == Some(true) && self.armed != Some(true) {
+            self.pending = true;
+        }
+        self.armed = armed;
+    }
+
+    /// Speed fractions to send the next command at, `[1.0]` unless ramping
+    pub fn take_scales(&mut self) -> Vec<f32> {
+        let pending = std::mem::take(&mut self.pending);
+        if !pending || self.ramp < RAMP_STEP {
+            return vec![1.0];
+        }
+
+        let steps = (self.ramp.as_secs_f32() / RAMP_STEP.as_secs_f32()).ceil() as u32;
+        (1..=steps).map(|step| step as f32 / steps as f32).collect()
+    }
+}
+
+impl Default for SoftStart {
+    fn default() -> Self {
+        Self::new(Duration::ZERO)
+    }
+}
+
+/// Sends `command` once per scale, [`RAMP_STEP`] apart
+pub async fn ramp_with<F, Fut>(scales: Vec<f32>, mut command: F) -> Result<()>
+where
+    F: FnMut(f32) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let last = scales.len().saturating_sub(1);
+    for (idx, scale) in scales.into_iter().enumerate() {
+        command(scale).await?;
+        if idx != last {
+            sleep(RAMP_STEP).await;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::test_util::Calls;
+
+    #[tokio::test(start_paused = true)]
+    async fn first_command_after_arm_ramps() {
+        let sent = Calls::new();
+        let record = |scale| sent.ok(scale);
+
+        let mut soft_start = SoftStart::new(Duration::from_millis(200));
+        soft_start.update_arm(Some(false));
+        ramp_with(soft_start.take_scales(), record).await.unwrap();
+        assert_eq!(sent.values(), [1.0]);
+        sent.clear();
+
+        soft_start.update_arm(Some(true));
+        let start = Instant::now();
+        ramp_with(soft_start.take_scales(), record).await.unwrap();
+        assert_eq!(sent.values(), [0.25, 0.5, 0.75, 1.0]);
+        assert_eq!(Instant::now() - start, RAMP_STEP * 3);
+        sent.clear();
+
+        // Staying armed does not ramp again
+        soft_start.update_arm(Some(true));
+        ramp_with(soft_start.take_scales(), record).await.unwrap();
+        assert_eq!(sent.values(), [1.0]);
+    }
+}
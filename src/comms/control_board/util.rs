@@ -1,6 +1,9 @@
-use std::f32::consts::PI;
+use std::{
+    f32::consts::PI,
+    ops::{Add, Neg, RangeInclusive, Sub},
+};
 
-use anyhow::bail;
+use anyhow::{bail, Result};
 use derive_getters::Getters;
 
 /// See <https://cdn-shop.adafruit.com/datasheets/BST_BNO055_DS000_12.pdf>,
@@ -62,6 +65,11 @@ pub struct Angles {
 }
 
 impl Angles {
+    /// Yaw as a wrapped heading
+    pub fn heading(&self) -> Degrees {
+        Degrees::new(self.yaw)
+    }
+
     pub fn from_raw(raw: [u8; 4 * 7]) -> Self {
         let quat_w = f32::from_le_bytes(raw[0..4].try_into().unwrap());
         let quat_x = f32::from_le_bytes(raw[4..8].try_into().unwrap());
@@ -109,3 +117,407 @@ impl Angles {
         }
     }
 }
+
+/// Angle in degrees, always wrapped into [-180, 180)
+///
+/// Sums and differences wrap, so headings can be offset without leaving the
+/// range the control board expects.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Degrees(f32);
+
+impl Degrees {
+    pub fn new(degrees: f32) -> Self {
+        Self((degrees + 180.0).rem_euclid(360.0) - 180.0)
+    }
+
+    pub const fn value(&self) -> f32 {
+        self.0
+    }
+}
+
+impl From<f32> for Degrees {
+    fn from(value: f32) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Degrees> for f32 {
+    fn from(val: Degrees) -> Self {
+        val.0
+    }
+}
+
+impl Add for Degrees {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Degrees {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Degrees {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.0)
+    }
+}
+
+/// Normalized speed, clamped into [-1, 1]
+///
+/// NaN becomes 0.0 so a bad calculation stops the thruster instead of
+/// reaching the control board.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Speed(f32);
+
+impl Speed {
+    pub const MAX: f32 = 1.0;
+
+    pub fn new(speed: f32) -> Self {
+        if speed.is_nan() {
+            Self(0.0)
+        } else {
+            Self(speed.clamp(-Self::MAX, Self::MAX))
+        }
+    }
+
+    pub const fn value(&self) -> f32 {
+        self.0
+    }
+}
+
+impl From<f32> for Speed {
+    fn from(value: f32) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Speed> for f32 {
+    fn from(val: Speed) -> Self {
+        val.0
+    }
+}
+
+/// Depth in meters, negative below the surface
+///
+/// Targets above the surface are clamped to 0.0, as is NaN.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Depth(f32);
+
+impl Depth {
+    pub const SURFACE: Self = Self(0.0);
+
+    pub fn new(depth: f32) -> Self {
+        if depth.is_nan() {
+            Self::SURFACE
+        } else {
+            Self(depth.min(0.0))
+        }
+    }
+
+    pub const fn value(&self) -> f32 {
+        self.0
+    }
+}
+
+impl From<f32> for Depth {
+    fn from(value: f32) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<Depth> for f32 {
+    fn from(val: Depth) -> Self {
+        val.0
+    }
+}
+
+/// One MS5837D status frame from the control board
+///
+/// The 12 byte payload is three little endian f32s: depth in meters (negative
+/// down), pressure, and water temperature in Celsius. Readings the sensor
+/// cannot produce are rejected, which also catches a payload laid out in a
+/// different order than expected.
+#[derive(Debug, Clone, Copy, PartialEq, Getters)]
+pub struct Ms5837Reading {
+    depth: f32,
+    pressure: f32,
+    temperature: f32,
+}
+
+impl Ms5837Reading {
+    /// Rated range of the MS5837-30BA, in meters of water
+    pub const MAX_DEPTH: f32 = 300.0;
+    /// Slack above the surface for drift in the zero depth calibration
+    pub const MAX_HEIGHT: f32 = 1.0;
+    /// Operating range of the MS5837-30BA
+    pub const TEMPERATURE_RANGE: RangeInclusive<f32> = -20.0..=85.0;
+
+    pub fn from_raw(raw: [u8; 4 * 3]) -> Option<Self> {
+        let field =
+            |idx: usize| f32::from_le_bytes(raw[idx * 4..(idx + 1) * 4].try_into().unwrap());
+        let reading = Self {
+            depth: field(0),
+            pressure: field(1),
+            temperature: field(2),
+        };
+
+        let plausible = (-Self::MAX_DEPTH..=Self::MAX_HEIGHT).contains(&reading.depth)
+            && reading.pressure.is_finite()
+            && reading.pressure > 0.0
+            && Self::TEMPERATURE_RANGE.contains(&reading.temperature);
+        plausible.then_some(reading)
+    }
+}
+
+/// Raw speeds for thrusters 1-8, with thruster 1 at index 0
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ThrusterSpeeds([f32; 8]);
+
+impl ThrusterSpeeds {
+    pub const ZERO: Self = Self([0.0; 8]);
+
+    pub const fn new(speeds: [f32; 8]) -> Self {
+        Self(speeds)
+    }
+
+    /// Every thruster at `speed`
+    pub const fn splat(speed: f32) -> Self {
+        Self([speed; 8])
+    }
+
+    /// Only `thruster` (1-8) at `speed`, all others stopped
+    pub fn single(thruster: u8, speed: f32) -> Result<Self> {
+        let mut speeds = Self::ZERO;
+        *speeds.thruster_mut(thruster)? = speed;
+        Ok(speeds)
+    }
+
+    /// Speed of `thruster` (1-8)
+    pub fn thruster(&self, thruster: u8) -> Result<f32> {
+        Ok(self.0[Self::index(thruster)?])
+    }
+
+    pub fn thruster_mut(&mut self, thruster: u8) -> Result<&mut f32> {
+        Ok(&mut self.0[Self::index(thruster)?])
+    }
+
+    /// Every speed multiplied by `scale`
+    pub fn scaled(&self, scale: f32) -> Self {
+        Self(self.0.map(|speed| speed * scale))
+    }
+
+    fn index(thruster: u8) -> Result<usize> {
+        if !(1..=8).contains(&thruster) {
+            bail!("{thruster} is outside the allowed range 1-8.")
+        };
+        Ok(thruster as usize - 1)
+    }
+}
+
+impl From<[f32; 8]> for ThrusterSpeeds {
+    fn from(value: [f32; 8]) -> Self {
+        Self(value)
+    }
+}
+
+impl From<ThrusterSpeeds> for [f32; 8] {
+    fn from(val: ThrusterSpeeds) -> Self {
+        val.0
+    }
+}
+
+/// Speed per degree of freedom, in control board order
+///
+/// Rotations are about the x (pitch), y (roll), and z (yaw) axes.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DofSpeeds {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    pub yaw: f32,
+}
+
+impl DofSpeeds {
+    /// Same speed on every degree of freedom
+    pub const fn splat(speed: f32) -> Self {
+        Self {
+            x: speed,
+            y: speed,
+            z: speed,
+            pitch: speed,
+            roll: speed,
+            yaw: speed,
+        }
+    }
+}
+
+impl From<[f32; 6]> for DofSpeeds {
+    fn from(value: [f32; 6]) -> Self {
+        let [x, y, z, pitch, roll, yaw] = value;
+        Self {
+            x,
+            y,
+            z,
+            pitch,
+            roll,
+            yaw,
+        }
+    }
+}
+
+impl From<DofSpeeds> for [f32; 6] {
+    fn from(val: DofSpeeds) -> Self {
+        [val.x, val.y, val.z, val.pitch, val.roll, val.yaw]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degrees_wrap() {
+        assert_eq!(Degrees::new(190.0).value(), -170.0);
+        assert_eq!(Degrees::new(-190.0).value(), 170.0);
+        assert_eq!(Degrees::new(540.0).value(), -180.0);
+        assert_eq!(Degrees::new(45.0).value(), 45.0);
+    }
+
+    #[test]
+    fn degrees_arithmetic_wraps() {
+        assert_eq!(
+            Degrees::new(170.0) + Degrees::new(20.0),
+            Degrees::new(-170.0)
+        );
+        assert_eq!(
+            Degrees::new(-170.0) - Degrees::new(20.0),
+            Degrees::new(170.0)
+        );
+        assert_eq!(
+            Degrees::new(-170.0) + Degrees::new(20.0),
+            Degrees::new(-150.0)
+        );
+        assert_eq!(-Degrees::new(-90.0), Degrees::new(90.0));
+        assert_eq!(-Degrees::new(-180.0), Degrees::new(-180.0));
+    }
+
+    #[test]
+    fn thruster_speeds_ordering() {
+        let raw = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        let speeds = ThrusterSpeeds::from(raw);
+        assert_eq!(speeds.thruster(1).unwrap(), 1.0);
+        assert_eq!(speeds.thruster(8).unwrap(), 8.0);
+        assert_eq!(<[f32; 8]>::from(speeds), raw);
+    }
+
+    #[test]
+    fn thruster_speeds_single() {
+        let speeds = ThrusterSpeeds::single(8, 1.0).unwrap();
+        assert_eq!(
+            <[f32; 8]>::from(speeds),
+            [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0]
+        );
+        assert!(ThrusterSpeeds::single(0, 1.0).is_err());
+        assert!(ThrusterSpeeds::single(9, 1.0).is_err());
+    }
+
+    #[test]
+    fn dof_speeds_ordering() {
+        let raw = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let speeds = DofSpeeds::from(raw);
+        assert_eq!(
+            speeds,
+            DofSpeeds {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+                pitch: 4.0,
+                roll: 5.0,
+                yaw: 6.0,
+            }
+        );
+        assert_eq!(<[f32; 6]>::from(speeds), raw);
+    }
+}
+
+/// Physical conventions for every command sent to the control board
+///
+/// * Depth is in meters, negative down, never above 0.0
+/// * Yaw is in degrees, within [-180, 180)
+/// * Speed is normalized, within [-1, 1]
+#[cfg(test)]
+mod conventions {
+    use super::*;
+
+    const SAMPLES: [f32; 9] = [-1000.0, -190.0, -1.5, -0.25, 0.0, 0.25, 1.5, 190.0, 1000.0];
+
+    #[test]
+    fn depth_is_negative_down() {
+        assert_eq!(Depth::new(-1.25).value(), -1.25);
+        assert_eq!(Depth::new(0.5), Depth::SURFACE);
+        assert_eq!(Depth::new(f32::NAN), Depth::SURFACE);
+        assert!(SAMPLES.iter().all(|d| Depth::new(*d).value() <= 0.0));
+    }
+
+    #[test]
+    fn yaw_is_degrees_in_range() {
+        assert_eq!(Degrees::new(90.0).value(), 90.0);
+        assert!(SAMPLES
+            .iter()
+            .map(|yaw| Degrees::new(*yaw).value())
+            .all(|yaw| (-180.0..180.0).contains(&yaw)));
+    }
+
+    #[test]
+    fn speed_is_normalized() {
+        assert_eq!(Speed::new(0.25).value(), 0.25);
+        assert_eq!(Speed::new(1.5).value(), 1.0);
+        assert_eq!(Speed::new(-1.5).value(), -1.0);
+        assert_eq!(Speed::new(f32::NAN).value(), 0.0);
+        assert!(SAMPLES
+            .iter()
+            .map(|speed| Speed::new(*speed).value())
+            .all(|speed| (-1.0..=1.0).contains(&speed)));
+    }
+
+    fn ms5837_raw(depth: f32, pressure: f32, temperature: f32) -> [u8; 12] {
+        let mut raw = [0; 12];
+        raw[0..4].copy_from_slice(&depth.to_le_bytes());
+        raw[4..8].copy_from_slice(&pressure.to_le_bytes());
+        raw[8..12].copy_from_slice(&temperature.to_le_bytes());
+        raw
+    }
+
+    #[test]
+    fn ms5837_rejects_implausible_frames() {
+        let reading = Ms5837Reading::from_raw(ms5837_raw(-1.5, 1163.0, 18.0)).unwrap();
+        assert_eq!(*reading.depth(), -1.5);
+        assert_eq!(*reading.temperature(), 18.0);
+
+        // Temperature where depth is expected, as a reordered payload would give
+        assert_eq!(
+            Ms5837Reading::from_raw(ms5837_raw(18.0, 1163.0, -1.5)),
+            None
+        );
+        assert_eq!(
+            Ms5837Reading::from_raw(ms5837_raw(f32::NAN, 1163.0, 18.0)),
+            None
+        );
+        assert_eq!(Ms5837Reading::from_raw(ms5837_raw(-1.5, 0.0, 18.0)), None);
+        assert_eq!(
+            Ms5837Reading::from_raw(ms5837_raw(-1.5, 1163.0, 200.0)),
+            None
+        );
+    }
+}
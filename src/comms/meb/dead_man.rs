@@ -0,0 +1,150 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{ensure, Result};
+use tokio::time::{interval, sleep_until, Duration, Instant, MissedTickBehavior};
+
+use crate::log_error;
+
+/// Stops the thrusters if the main loop stops heartbeating.
+///
+/// The panic hook only covers failures that unwind. A mission stuck in a
+/// blocking loop never yields, so it also stops the heartbeat sent from the
+/// same task, and [`Self::watch`] disarms from its own task once the timeout
+/// passes. Nothing is watched until the first heartbeat.
+#[derive(Debug, Clone, Default)]
+pub struct DeadManSwitch {
+    last_beat: Arc<Mutex<Option<Instant>>>,
+}
+
+impl DeadManSwitch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn heartbeat(&self) {
+        *self.last_beat.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Stops watching until the next heartbeat
+    pub fn release(&self) {
+        *self.last_beat.lock().unwrap() = None;
+    }
+
+    /// Heartbeats every `period` while `fut` runs, from the task polling it
+    pub async fn beat_while<F: Future>(&self, fut: F, period: Duration) -> F::Output {
+        let mut ticks = interval(period);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        tokio::pin!(fut);
+
+        loop {
+            tokio::select! {
+                biased;
+                ret = &mut fut => return ret,
+                _ = ticks.tick() => self.heartbeat(),
+            }
+        }
+    }
+
+    /// Waits until `timeout` passes without a heartbeat, then runs `disarm`.
+    ///
+    /// Fails immediately on a zero `timeout`, which would disarm between
+    /// any two heartbeats.
+    pub async fn watch<F, Fut>(&self, timeout: Duration, disarm: F) -> Result<()>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        ensure!(!timeout.is_zero(), "dead-man timeout must be positive");
+
+        loop {
+            let deadline = self.last_beat.lock().unwrap().map(|beat| beat + timeout);
+            match deadline {
+                Some(deadline) if deadline <= Instant::now() => break,
+                Some(deadline) => sleep_until(deadline).await,
+                // Poll again once something could have started heartbeating
+                None => sleep_until(Instant::now() + timeout).await,
+            }
+        }
+
+        log_error!("No heartbeat for {timeout:?}, disarming thrusters");
+        disarm().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use tokio::time::sleep;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    #[tokio::test(start_paused = true)]
+    async fn disarms_when_heartbeats_stop() {
+        let switch = DeadManSwitch::new();
+        let disarmed = Arc::new(AtomicBool::new(false));
+
+        let watcher = {
+            let switch = switch.clone();
+            let disarmed = disarmed.clone();
+            tokio::spawn(async move {
+                switch
+                    .watch(TIMEOUT, || async move {
+                        disarmed.store(true, Ordering::SeqCst);
+                        Ok(())
+                    })
+                    .await
+            })
+        };
+
+        switch
+            .beat_while(sleep(Duration::from_secs(10)), Duration::from_millis(500))
+            .await;
+        assert!(!disarmed.load(Ordering::SeqCst));
+
+        // Heartbeats stopped with the future
+        sleep(TIMEOUT + Duration::from_millis(10)).await;
+        assert!(disarmed.load(Ordering::SeqCst));
+        watcher.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_until_first_heartbeat() {
+        let switch = DeadManSwitch::new();
+        let disarmed = Arc::new(AtomicBool::new(false));
+
+        let watcher = {
+            let switch = switch.clone();
+            let disarmed = disarmed.clone();
+            tokio::spawn(async move {
+                switch
+                    .watch(TIMEOUT, || async move {
+                        disarmed.store(true, Ordering::SeqCst);
+                        Ok(())
+                    })
+                    .await
+            })
+        };
+
+        sleep(TIMEOUT * 5).await;
+        assert!(!disarmed.load(Ordering::SeqCst));
+        assert!(!watcher.is_finished());
+        watcher.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_timeout_is_refused() {
+        let switch = DeadManSwitch::new();
+        switch.heartbeat();
+
+        let ret = switch
+            .watch(Duration::ZERO, || async { panic!("disarmed") })
+            .await;
+        assert!(ret.is_err());
+    }
+}
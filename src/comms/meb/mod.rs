@@ -11,6 +11,7 @@ use self::response::Statuses;
 
 use super::auv_control_board::{AUVControlBoard, MessageId};
 
+pub mod dead_man;
 pub mod response;
 
 #[derive(Debug)]
@@ -56,6 +57,7 @@ impl<C: AsyncWrite + Unpin> MainElectronicsBoard<C> {
         (*self.board.responses().humid().read().await).map(f32::from_le_bytes)
     }
 
+    /// Latest `LEAK` status, `None` until the board first reports it
     pub async fn leak(&self) -> Option<bool> {
         *self.board.responses().leak().read().await
     }
@@ -64,10 +66,12 @@ impl<C: AsyncWrite + Unpin> MainElectronicsBoard<C> {
         *self.board.responses().thruster_arm().read().await
     }
 
+    /// Latest `VSYS` reading in volts
     pub async fn system_voltage(&self) -> Option<f32> {
         (*self.board.responses().system_voltage().read().await).map(f32::from_le_bytes)
     }
 
+    /// Cause code from the last `SDOWN` message
     pub async fn shutdown_cause(&self) -> Option<u8> {
         *self.board.responses().shutdown().read().await
     }
@@ -146,17 +146,17 @@ impl Statuses {
                 } else if message_body.get(0..4) == Some(&TEMP) {
                     *temp.write().await = Some(message_body[4..8].try_into().unwrap());
                     *humid.write().await = Some(message_body[(4 + 4)..].try_into().unwrap());
-                } else if message_body.get(0..4) == Some(&LEAK) {
-                    *leak.write().await = Some(message_body[4] == 1);
+                } else if let Some(state) = message_body.strip_prefix(&LEAK).and_then(<[u8]>::first) {
+                    *leak.write().await = Some(*state == 1);
                 } else if message_body.get(0..4) == Some(&TARM) {
                     let tarm_status = Self::arm_debounce(tarm_count, Some(message_body[4] == 1)).await;
                     if tarm_status.is_some() {
                         *tarm.write().await = tarm_status;
                     }
-                } else if message_body.get(0..4) == Some(&VSYS) {
-                    *vsys.write().await = Some(message_body[4..].try_into().unwrap());
-                } else if message_body.get(0..4) == Some(&SDOWN) {
-                    *sdown.write().await = Some(message_body[4]);
+                } else if let Some(voltage) = message_body.strip_prefix(&VSYS).and_then(|rest| rest.get(0..4)) {
+                    *vsys.write().await = Some(voltage.try_into().unwrap());
+                } else if let Some(cause) = message_body.strip_prefix(&SDOWN).and_then(<[u8]>::first) {
+                    *sdown.write().await = Some(*cause);
                 } else if message_body.get(0..3) == Some(&ACK) {
                     let id = u16::from_be_bytes(message_body[3..=4].try_into().unwrap());
                     let error_code: u8 = message_body[5];
@@ -207,6 +207,10 @@ impl GetAck for Statuses {
             sleep(MAP_POLL_SLEEP).await; // Allow for new data from serial
         }
     }
+
+    async fn forget_ack(&self, id: u16) {
+        self.ack_map.lock().await.abandon(id);
+    }
 }
 
 #[cfg(test)]
@@ -220,6 +224,58 @@ mod test {
             *statuses.thruster_arm.write().await = tarm_status;
         }
     }
+    /// Feeds raw serial bytes through the parser into `statuses`
+    async fn feed(statuses: &Statuses, bytes: &[u8]) {
+        let mut buffer = Vec::with_capacity(DEFAULT_BUF_LEN);
+        Statuses::update_status(
+            &mut buffer,
+            &mut &*bytes,
+            &statuses.temp,
+            &statuses.humid,
+            &statuses.leak,
+            &statuses.thruster_arm,
+            &statuses.tarm_count,
+            &statuses.system_voltage,
+            &statuses.shutdown,
+            &statuses.ack_map,
+            &mut tokio::io::sink(),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn parses_recorded_frames() {
+        let statuses = Statuses::new(tokio::io::empty()).await;
+
+        // LEAK 1
+        feed(&statuses, &[253, 0, 12, 76, 69, 65, 75, 1, 89, 61, 254]).await;
+        assert_eq!(*statuses.leak.read().await, Some(true));
+
+        // VSYS 15.5
+        feed(
+            &statuses,
+            &[253, 0, 13, 86, 83, 89, 83, 0, 0, 120, 65, 220, 51, 254],
+        )
+        .await;
+        assert_eq!(
+            (*statuses.system_voltage.read().await).map(f32::from_le_bytes),
+            Some(15.5)
+        );
+
+        // SDOWN 2
+        feed(&statuses, &[253, 0, 14, 83, 68, 79, 87, 78, 2, 74, 1, 254]).await;
+        assert_eq!(*statuses.shutdown.read().await, Some(2));
+    }
+
+    #[tokio::test]
+    async fn truncated_frame_is_ignored() {
+        let statuses = Statuses::new(tokio::io::empty()).await;
+
+        // VSYS with only half of the float, but a valid CRC
+        feed(&statuses, &[253, 0, 15, 86, 83, 89, 83, 0, 0, 60, 144, 254]).await;
+        assert_eq!(*statuses.system_voltage.read().await, None);
+    }
+
     #[tokio::test]
     async fn thruster_is_armed() {
         let statuses = Statuses::new(tokio::io::empty()).await;
@@ -1,14 +1,22 @@
 use anyhow::Result;
 use futures::StreamExt;
 use ros2_client::{
-    Context, MessageTypeName, Name, NodeName, NodeOptions, DEFAULT_SUBSCRIPTION_QOS,
+    Context, MessageTypeName, Name, Node, NodeName, NodeOptions, DEFAULT_SUBSCRIPTION_QOS,
 };
 use ros2_interfaces_jazzy_serde::{geometry_msgs::msg::PoseStamped, sensor_msgs::msg::Image};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::{
+    sync::Mutex,
+    time::{sleep, Duration, Instant},
+};
+
+use crate::{config::ZedRos2Config, log_warn};
 
-use crate::config::ZedRos2Config;
+/// Tries at building the ROS2 node before giving up
+const CONNECT_ATTEMPTS: u32 = 5;
+/// Time between tries at building the ROS2 node
+const CONNECT_RETRY: Duration = Duration::from_secs(2);
 
 pub mod zed_interfaces {
     use super::*;
@@ -88,24 +96,46 @@ pub mod zed_interfaces {
 
 use zed_interfaces::ObjectsStamped;
 
+/// Latest message on a topic and when it arrived
+type Cache<T> = Arc<Mutex<Option<(T, Instant)>>>;
+
+/// Copy of the cached message and its age
+async fn with_age<T: Clone>(cache: &Cache<T>) -> Option<(T, Duration)> {
+    cache
+        .lock()
+        .await
+        .as_ref()
+        .map(|(msg, received)| (msg.clone(), received.elapsed()))
+}
+
+/// Copy of the cached message, unless it arrived more than `max_age` ago
+async fn if_fresh<T: Clone>(cache: &Cache<T>, max_age: Duration) -> Option<T> {
+    with_age(cache)
+        .await
+        .filter(|(_, age)| *age <= max_age)
+        .map(|(msg, _)| msg)
+}
+
 #[derive(Clone)]
 pub struct ZedRos2 {
-    image: Arc<Mutex<Option<Image>>>,
-    objects: Arc<Mutex<Option<ObjectsStamped>>>,
-    pose: Arc<Mutex<Option<PoseStamped>>>,
-    _context: Context,
-    _node: Arc<ros2_client::Node>,
+    image: Cache<Image>,
+    objects: Cache<ObjectsStamped>,
+    pose: Cache<PoseStamped>,
+    _context: Option<Context>,
+    _node: Option<Arc<Node>>,
 }
 
 impl ZedRos2 {
-    pub fn new(config: &ZedRos2Config) -> Result<Self> {
-        let context = Context::new()?;
-        let mut node =
-            context.new_node(NodeName::new("/", "sw8s_zed_client")?, NodeOptions::new())?;
+    /// Builds the ROS2 node and subscribes to the ZED topics
+    ///
+    /// Node creation is retried, since the ROS2 graph may still be coming up.
+    pub async fn new(config: &ZedRos2Config) -> Result<Self> {
+        let (context, mut node) =
+            retry_with(CONNECT_ATTEMPTS, CONNECT_RETRY, Self::build_node).await?;
 
-        let image = Arc::new(Mutex::new(None));
-        let objects = Arc::new(Mutex::new(None));
-        let pose = Arc::new(Mutex::new(None));
+        let image: Cache<Image> = Arc::default();
+        let objects: Cache<ObjectsStamped> = Arc::default();
+        let pose: Cache<PoseStamped> = Arc::default();
 
         let image_topic = node.create_topic(
             &Name::new("/", "image").unwrap(),
@@ -151,7 +181,7 @@ impl ZedRos2 {
                         let _ = rec.log("zed_ros2/image", &img);
                     }
 
-                    *image_cache.lock().await = Some(msg);
+                    *image_cache.lock().await = Some((msg, Instant::now()));
                 }
             }
         });
@@ -164,7 +194,7 @@ impl ZedRos2 {
                     let rec = crate::get_recording();
                     log_objects_to_rerun(&rec, &msg);
 
-                    *objects_cache.lock().await = Some(msg);
+                    *objects_cache.lock().await = Some((msg, Instant::now()));
                 }
             }
         });
@@ -177,7 +207,7 @@ impl ZedRos2 {
                     let rec = crate::get_recording();
                     log_pose_to_rerun(&rec, &msg);
 
-                    *pose_cache.lock().await = Some(msg);
+                    *pose_cache.lock().await = Some((msg, Instant::now()));
                 }
             }
         });
@@ -186,21 +216,84 @@ impl ZedRos2 {
             image,
             objects,
             pose,
-            _context: context,
-            _node: Arc::new(node),
+            _context: Some(context),
+            _node: Some(Arc::new(node)),
         })
     }
 
+    /// Client with no node, for running missions that do not need the ZED
+    pub fn offline() -> Self {
+        Self {
+            image: Arc::default(),
+            objects: Arc::default(),
+            pose: Arc::default(),
+            _context: None,
+            _node: None,
+        }
+    }
+
+    fn build_node() -> Result<(Context, Node)> {
+        let context = Context::new()?;
+        let node = context.new_node(NodeName::new("/", "sw8s_zed_client")?, NodeOptions::new())?;
+        Ok((context, node))
+    }
+
+    /// Whether any ZED message has arrived yet
+    pub async fn is_connected(&self) -> bool {
+        self.image.lock().await.is_some()
+            || self.objects.lock().await.is_some()
+            || self.pose.lock().await.is_some()
+    }
+
     pub async fn latest_image(&self) -> Option<Image> {
-        self.image.lock().await.clone()
+        self.latest_image_with_age().await.map(|(msg, _)| msg)
     }
 
     pub async fn latest_objects(&self) -> Option<ObjectsStamped> {
-        self.objects.lock().await.clone()
+        self.latest_objects_with_age().await.map(|(msg, _)| msg)
     }
 
     pub async fn latest_pose(&self) -> Option<PoseStamped> {
-        self.pose.lock().await.clone()
+        self.latest_pose_with_age().await.map(|(msg, _)| msg)
+    }
+
+    /// Latest image and how long ago it arrived
+    pub async fn latest_image_with_age(&self) -> Option<(Image, Duration)> {
+        with_age(&self.image).await
+    }
+
+    /// Latest detected objects and how long ago they arrived
+    pub async fn latest_objects_with_age(&self) -> Option<(ObjectsStamped, Duration)> {
+        with_age(&self.objects).await
+    }
+
+    /// Latest pose and how long ago it arrived
+    pub async fn latest_pose_with_age(&self) -> Option<(PoseStamped, Duration)> {
+        with_age(&self.pose).await
+    }
+
+    /// Latest pose, unless it arrived more than `max_age` ago
+    pub async fn latest_pose_if_fresh(&self, max_age: Duration) -> Option<PoseStamped> {
+        if_fresh(&self.pose, max_age).await
+    }
+}
+
+/// Calls `attempt` up to `attempts` times, `delay` apart, until it succeeds
+async fn retry_with<T, F>(attempts: u32, delay: Duration, mut attempt: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let mut tries = 1;
+    loop {
+        match attempt() {
+            Ok(val) => return Ok(val),
+            Err(e) if tries < attempts => {
+                log_warn!("ZED ROS2 node attempt {tries}/{attempts} failed: {e:#}");
+                tries += 1;
+                sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
     }
 }
 
@@ -350,3 +443,56 @@ fn log_pose_to_rerun(rec: &rerun::RecordingStream, msg: &PoseStamped) {
     );
     let _ = rec.log("zed_ros2/pose/position", &rerun::Points3D::new([position]));
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+    use tokio::time::Instant;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_until_success() {
+        let start = Instant::now();
+        let mut calls = 0;
+        let res = retry_with(5, Duration::from_secs(2), || {
+            calls += 1;
+            if calls < 3 {
+                bail!("ROS2 graph not up")
+            }
+            Ok(calls)
+        })
+        .await;
+        assert_eq!(res.unwrap(), 3);
+        assert_eq!(start.elapsed(), Duration::from_secs(4));
+
+        // Gives up after the last attempt, without waiting after it
+        let start = Instant::now();
+        let mut calls = 0;
+        let res: Result<()> = retry_with(3, Duration::from_secs(2), || {
+            calls += 1;
+            bail!("ROS2 graph not up")
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls, 3);
+        assert_eq!(start.elapsed(), Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn offline_is_not_connected() {
+        assert!(!ZedRos2::offline().is_connected().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cached_messages_go_stale() {
+        let cache: Cache<u32> = Arc::default();
+        assert_eq!(with_age(&cache).await, None);
+
+        *cache.lock().await = Some((7, Instant::now()));
+        sleep(Duration::from_secs(3)).await;
+        assert_eq!(with_age(&cache).await, Some((7, Duration::from_secs(3))));
+        assert_eq!(if_fresh(&cache, Duration::from_secs(5)).await, Some(7));
+        assert_eq!(if_fresh(&cache, Duration::from_secs(1)).await, None);
+    }
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// Forward speed that slows while the target is off-center, see
+/// [`approach_forward`](crate::missions::basic::approach_forward)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// Forward speed with the target centered
+    pub speed: f32,
+    /// Forward speed lost per unit of normalized offset
+    pub scale: f32,
+    pub min_speed: f32,
+}
@@ -1,9 +1,16 @@
 use serde::{Deserialize, Serialize};
 
+use super::DurationSecs;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub depth: f32,
     pub speed: f32,
+    /// Descend to `depth` with the software depth PID first, otherwise bin
+    /// leaves depth alone
+    pub software_depth: bool,
+    /// Seconds to descend to `depth` for
+    pub descend_secs: DurationSecs,
 }
 
 impl Default for Config {
@@ -11,6 +18,8 @@ impl Default for Config {
         Self {
             depth: -1.25,
             speed: 0.2,
+            software_depth: false,
+            descend_secs: DurationSecs(5.0),
         }
     }
 }
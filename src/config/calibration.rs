@@ -0,0 +1,31 @@
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::vision::image_prep::ContourSelect;
+
+use super::ProfileColor;
+
+/// Settings for the calibration overlay missions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    pub color: ProfileColor,
+    pub area_bounds: RangeInclusive<f64>,
+    pub contour_select: ContourSelect,
+    /// Color profile name, the global `color_profile` when unset
+    pub color_profile: Option<String>,
+    /// Maximum overlay rate
+    pub loop_hz: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            color: ProfileColor::Orange,
+            area_bounds: 500.0..=60000.0,
+            contour_select: ContourSelect::MaxArea,
+            color_profile: None,
+            loop_hz: 10.0,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::vision::image_prep::ContourSelect;
+
+use super::ProfileColor;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    pub depth: f32,
+    /// Thruster speed per unit of normalized offset
+    pub gain: f32,
+    /// Largest normalized offset, on either axis, that counts as centered
+    pub tolerance: f64,
+    /// Consecutive centered frames before the mission returns
+    pub centered_frames: u32,
+    /// Frames to try centering for before the mission fails
+    pub max_frames: u32,
+    pub color: ProfileColor,
+    pub area_bounds: RangeInclusive<f64>,
+    pub contour_select: ContourSelect,
+    /// Color profile name, the global `color_profile` when unset
+    pub color_profile: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            depth: -1.0,
+            gain: 0.3,
+            tolerance: 0.1,
+            centered_frames: 5,
+            max_frames: 300,
+            color: ProfileColor::Orange,
+            area_bounds: 500.0..=60000.0,
+            contour_select: ContourSelect::MaxArea,
+            color_profile: None,
+        }
+    }
+}
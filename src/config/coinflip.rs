@@ -4,7 +4,16 @@ use serde::{Deserialize, Serialize};
 pub struct Config {
     pub depth: f32,
     pub angle_correction: f32,
-    pub true_count: u32,
+    /// Frames of detections to vote over
+    pub vote_window: usize,
+    /// Summed confidence the leading class needs to end the search
+    pub vote_threshold: f64,
+    /// Maximum detection loop rate
+    pub loop_hz: f64,
+    /// Normalized distance under which same-class detections are merged
+    pub merge_distance: f64,
+    /// Weakest gate model detection voted on, see [`GatePoles::set_threshold`](crate::vision::gate_poles::GatePoles::set_threshold)
+    pub threshold: f64,
 }
 
 impl Default for Config {
@@ -12,7 +21,11 @@ impl Default for Config {
         Self {
             depth: -1.25,
             angle_correction: 15.0,
-            true_count: 4,
+            vote_window: 5,
+            vote_threshold: 2.0,
+            loop_hz: 10.0,
+            merge_distance: 0.05,
+            threshold: 0.75,
         }
     }
 }
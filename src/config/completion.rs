@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+/// When a timed maneuver that can also watch vision is declared done
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompletionPolicy {
+    /// After the minimum duration
+    #[default]
+    TimedOnly,
+    /// Once the target has been lost for enough frames
+    VisionOnly,
+    /// Whichever of the above happens first
+    TimedOrVision,
+    /// Only once both have happened
+    TimedAndVision,
+}
+
+impl CompletionPolicy {
+    pub const fn uses_vision(&self) -> bool {
+        !matches!(self, Self::TimedOnly)
+    }
+
+    /// Combines whether the duration has elapsed and whether vision has lost
+    /// the target
+    pub const fn complete(&self, timed: bool, vision: bool) -> bool {
+        match self {
+            Self::TimedOnly => timed,
+            Self::VisionOnly => vision,
+            Self::TimedOrVision => timed || vision,
+            Self::TimedAndVision => timed && vision,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_modes() {
+        use CompletionPolicy::*;
+
+        for (policy, expected) in [
+            (TimedOnly, [false, true, false, true]),
+            (VisionOnly, [false, false, true, true]),
+            (TimedOrVision, [false, true, true, true]),
+            (TimedAndVision, [false, false, false, true]),
+        ] {
+            let results = [(false, false), (true, false), (false, true), (true, true)]
+                .map(|(timed, vision)| policy.complete(timed, vision));
+            assert_eq!(results, expected, "{policy:?}");
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Gains for the software depth loop, see [`DepthPid`](crate::comms::control_board::depth_pid::DepthPid)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    /// Largest heave speed the loop commands
+    pub max_speed: f32,
+    /// Seconds between depth reads
+    pub period_secs: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            kp: 0.8,
+            ki: 0.1,
+            kd: 0.2,
+            max_speed: 0.5,
+            period_secs: 0.05,
+        }
+    }
+}
+
+impl Config {
+    /// Time between depth reads, failing unless `period_secs` is a positive duration
+    pub fn period(&self) -> Result<Duration> {
+        Duration::try_from_secs_f32(self.period_secs)
+            .ok()
+            .filter(|period| !period.is_zero())
+            .ok_or_else(|| {
+                anyhow!(
+                    "depth_pid period_secs must be positive, got {}",
+                    self.period_secs
+                )
+            })
+    }
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Meters per second the depth target rises toward the surface
+    pub ascent_rate: f32,
+    /// System voltage that triggers surfacing, battery is not watched if unset
+    pub min_voltage: Option<f32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ascent_rate: 0.2,
+            min_voltage: None,
+        }
+    }
+}
@@ -1,20 +1,60 @@
-use super::Side;
+use super::{approach, completion::CompletionPolicy, pid, DurationSecs, Side};
+use crate::vision::{class_map::ClassRemap, gate_poles::CLASS_COUNT, image_prep::ContourSelect};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub depth: f32,
     pub speed: f32,
+    /// Consecutive aligned frames the CV run needs before passing
     pub true_count: u32,
+    /// Frames of creature detections the YOLO run votes over
+    pub vote_window: usize,
+    /// Summed confidence the target needs, while leading the vote, before the YOLO run passes
+    pub vote_threshold: f64,
     pub false_count: u32,
     pub side: Side,
     pub yaw_speed: f32,
     pub strafe_speed: f32,
-    pub init_duration: f32,
-    pub strafe_duration: f32,
-    pub traversal_duration: f32,
+    pub init_duration: DurationSecs,
+    pub strafe_duration: DurationSecs,
+    pub traversal_duration: DurationSecs,
     pub yaw_adjustment: f32,
-    pub correction_factor: f32,
+    /// Correction from the normalized target offset, yaw in the CV run and
+    /// strafe in the procedural run
+    pub correction_pid: pid::Config,
+    pub contour_select: ContourSelect,
+    /// Color profile name, the global `color_profile` when unset
+    pub color_profile: Option<String>,
+    /// When traversal is done, see [`CompletionPolicy`]
+    pub approach_completion: CompletionPolicy,
+    /// Consecutive frames without a detection that count as passed
+    pub lost_frames: u32,
+    /// Maximum detection loop rate
+    pub loop_hz: f64,
+    /// Slow forward speed while off-center instead of stopping to correct
+    pub approach: Option<approach::Config>,
+    /// Normalized distance under which same-class detections are merged
+    pub merge_distance: f64,
+    /// Frames the alignment error is averaged over before correcting
+    pub error_window: usize,
+    /// Weakest contour detection acted on by the CV run (`gate_run_coinflip`),
+    /// see [`contour_confidence`](crate::vision::image_prep::contour_confidence).
+    /// Paired poles score their mean fill and partial poles half their fill,
+    /// so partial poles never score above 0.5. The YOLO run uses `threshold`.
+    pub min_confidence: f64,
+    /// Weakest model detection acted on by the YOLO run (`gate_run_yolo`), see
+    /// [`GatePoles::set_threshold`](crate::vision::gate_poles::GatePoles::set_threshold).
+    /// The CV run uses `min_confidence`.
+    pub threshold: f64,
+    /// Frames with a pole detection needed within `confirm_frames` before aligning, 0 skips confirmation
+    pub confirm_detections: usize,
+    pub confirm_frames: usize,
+    /// Frames spent confirming before the gate run gives up, 0 for no limit
+    pub confirm_give_up_frames: usize,
+    /// `[from, to]` gate model class id pairs, e.g. `[[4, 5], [5, 4]]` swaps the poles
+    pub class_remap: Vec<[i32; 2]>,
 }
 
 impl Default for Config {
@@ -23,15 +63,53 @@ impl Default for Config {
             depth: -1.25,
             speed: 0.2,
             true_count: 4,
+            vote_window: 5,
+            vote_threshold: 2.0,
             false_count: 1,
             side: Side::default(),
             yaw_speed: 0.2,
             strafe_speed: 0.2,
-            correction_factor: 0.2,
-            init_duration: 3.0,
-            strafe_duration: 2.0,
-            traversal_duration: 8.0,
+            correction_pid: pid::Config {
+                kp: 0.2,
+                ..Default::default()
+            },
+            init_duration: DurationSecs(3.0),
+            strafe_duration: DurationSecs(2.0),
+            traversal_duration: DurationSecs(8.0),
             yaw_adjustment: 20.0,
+            contour_select: ContourSelect::default(),
+            color_profile: None,
+            approach_completion: CompletionPolicy::TimedOnly,
+            lost_frames: 10,
+            loop_hz: 10.0,
+            approach: None,
+            merge_distance: 0.05,
+            error_window: 1,
+            min_confidence: 0.0,
+            threshold: 0.75,
+            confirm_detections: 3,
+            confirm_frames: 5,
+            confirm_give_up_frames: 100,
+            class_remap: vec![],
         }
     }
 }
+
+impl Config {
+    /// Same configuration, running on `side`
+    pub fn with_side(&self, side: Side) -> Self {
+        Self {
+            side,
+            ..self.clone()
+        }
+    }
+
+    /// Gate model remap built from `class_remap`, `None` when it is empty.
+    /// Fails unless the pairs are a bijection over the model's class ids.
+    pub fn class_remap(&self) -> Result<Option<ClassRemap>> {
+        if self.class_remap.is_empty() {
+            return Ok(None);
+        }
+        ClassRemap::new(&self.class_remap, CLASS_COUNT).map(Some)
+    }
+}
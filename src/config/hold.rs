@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+use super::DurationSecs;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Seconds, used when the mission argument does not give one
+    pub duration: DurationSecs,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            duration: DurationSecs(10.0),
+        }
+    }
+}
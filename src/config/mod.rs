@@ -1,17 +1,37 @@
+pub mod approach;
 pub mod bin;
+pub mod calibration;
+pub mod center;
 pub mod coinflip;
+pub mod completion;
+pub mod depth_pid;
+pub mod emergency;
 pub mod gate;
+pub mod hold;
 pub mod octagon;
 pub mod path_align;
+pub mod pid;
+pub mod plan;
+pub mod preamble;
+pub mod run_budget;
 pub mod slalom;
 pub mod sonar;
 pub mod spin;
+pub mod thruster_pattern;
+pub mod travel;
 
 use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::time::Duration;
 
-use crate::vision::Yuv;
-use anyhow::Result;
+use crate::video_source::appsink::FrameFormat;
+use crate::vision::{
+    calibration::NormalizedRoi, image_prep::FrameRotation, nn_cv2::REPORT_CONFIDENCE_FLOOR, Yuv,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::ops::RangeInclusive;
 
@@ -37,6 +57,10 @@ pub struct Config {
     pub meb_path: String,
     pub front_cam_path: String,
     pub bottom_cam_path: String,
+    /// Annotated stream output size, capture resolution if unset
+    pub annotated_dimensions: Option<(u32, u32)>,
+    /// Format camera frames reach vision in, see [`FrameFormat`]
+    pub frame_format: FrameFormat,
     pub sonar: sonar::Config,
     pub missions: Missions,
     pub color_profile: String,
@@ -44,19 +68,128 @@ pub struct Config {
     pub shark: Side,
     pub saw_fish: Side,
     pub zed_ros2: ZedRos2Config,
+    pub run_budget: run_budget::Config,
+    /// Mission arguments skipped when running a sequence
+    pub disabled_missions: Vec<String>,
+    /// Mission arguments that push annotated frames, every mission when unset
+    pub annotated_missions: Option<Vec<String>>,
+    /// Named run plans, each run by passing `plan:<name>` as a mission argument
+    pub plans: HashMap<String, Vec<plan::Step>>,
+    /// Seconds to ramp up the first thruster command after arming
+    pub soft_start_secs: DurationSecs,
+    /// Seconds to wait for the control board to acknowledge a command, forever if unset
+    pub ack_timeout_secs: Option<DurationSecs>,
+    /// Log every thruster and stability command to a CSV under `console/`
+    pub command_csv: bool,
+    /// Log thruster and stability commands without sending them, for bench testing
+    pub dry_run: bool,
+    /// Seconds without a main loop heartbeat before the thrusters are zeroed, must be positive
+    pub dead_man_secs: f32,
+    /// Vision errors in a row before a mission stops the thrusters and gives up, 0 for no limit
+    pub max_vision_errors: usize,
+    /// Confidence, 0-1, above which models report detections for annotated frames.
+    /// Missions only act on detections above their own `threshold`.
+    pub report_confidence_floor: f64,
+    pub preamble: preamble::Config,
+    pub emergency_surface: emergency::Config,
+    /// Gains for missions that hold depth in software
+    pub depth_pid: depth_pid::Config,
+    /// Front camera region detections are kept from
+    pub front_roi: NormalizedRoi,
+    /// Bottom camera region detections are kept from
+    pub bottom_roi: NormalizedRoi,
+    /// Clockwise degrees bottom camera frames are rotated before detection, 0/90/180/270
+    pub bottom_cam_rotation_deg: FrameRotation,
 }
 
 impl Config {
     pub fn new() -> Result<Self> {
-        let config_string = read_to_string(CONFIG_FILE)?;
-        Ok(toml::from_str(&config_string)?)
+        let config_string =
+            read_to_string(CONFIG_FILE).with_context(|| format!("Failed to read {CONFIG_FILE}"))?;
+        Self::parse(&config_string)
+    }
+
+    /// Like [`Self::new`], but also fails on settings that parse yet cannot work,
+    /// see [`Self::validate`]. `None` when there is no config file at all.
+    pub fn load_strict() -> Result<Option<Self>> {
+        let config_string = match read_to_string(CONFIG_FILE) {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            contents => contents.with_context(|| format!("Failed to read {CONFIG_FILE}"))?,
+        };
+        let config = Self::parse(&config_string)?;
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    /// Parse errors name the line and column of the bad entry
+    fn parse(contents: &str) -> Result<Self> {
+        toml::from_str(contents).with_context(|| format!("Failed to parse {CONFIG_FILE}"))
+    }
+
+    /// Checks that the selected color profile exists, that every profile
+    /// is valid, see [`ColorProfile::validate`], that the depth PID and
+    /// dead-man switch have usable periods, and that the gate class remap and
+    /// detection report floor are usable
+    pub fn validate(&self) -> Result<()> {
+        self.color_profile()?;
+        self.depth_pid.period()?;
+        self.dead_man_timeout()?;
+        if !(0.0..=1.0).contains(&self.report_confidence_floor) {
+            bail!(
+                "report_confidence_floor must be within [0, 1], got {}",
+                self.report_confidence_floor
+            );
+        }
+        self.missions
+            .gate
+            .class_remap()
+            .context("gate class_remap")?;
+        for (name, profile) in &self.color_profiles {
+            profile
+                .validate()
+                .with_context(|| format!("color profile '{name}'"))?;
+        }
+        Ok(())
     }
 }
 
 impl Config {
+    /// Heartbeat timeout for the dead-man switch, failing unless
+    /// `dead_man_secs` is a positive duration
+    pub fn dead_man_timeout(&self) -> Result<Duration> {
+        Duration::try_from_secs_f32(self.dead_man_secs)
+            .ok()
+            .filter(|timeout| !timeout.is_zero())
+            .ok_or_else(|| anyhow!("dead_man_secs must be positive, got {}", self.dead_man_secs))
+    }
+
     pub fn get_color_profile(&self) -> Option<&ColorProfile> {
         self.color_profiles.get(&self.color_profile)
     }
+
+    /// Like [`Self::get_color_profile`], but names the available profiles
+    /// when the selected one is missing
+    pub fn color_profile(&self) -> Result<&ColorProfile> {
+        self.named_color_profile(&self.color_profile)
+    }
+
+    /// Profile a mission config names, or the global one when it names none
+    pub fn mission_color_profile(&self, name: Option<&str>) -> Result<&ColorProfile> {
+        self.named_color_profile(name.unwrap_or(&self.color_profile))
+    }
+
+    fn named_color_profile(&self, name: &str) -> Result<&ColorProfile> {
+        self.color_profiles.get(name).ok_or_else(|| {
+            let mut available: Vec<_> = self.color_profiles.keys().map(String::as_str).collect();
+            available.sort_unstable();
+            let available = if available.is_empty() {
+                "none".to_string()
+            } else {
+                available.join(", ")
+            };
+            anyhow!("color profile '{name}' not found; available: {available}")
+        })
+    }
 }
 
 impl Default for Config {
@@ -67,6 +200,8 @@ impl Default for Config {
             meb_path: MEB_PATH.to_string(),
             front_cam_path: FRONT_CAM.to_string(),
             bottom_cam_path: BOTTOM_CAM.to_string(),
+            annotated_dimensions: None,
+            frame_format: FrameFormat::default(),
             sonar: sonar::Config::default(),
             missions: Missions::default(),
             color_profile: "".to_string(),
@@ -74,6 +209,23 @@ impl Default for Config {
             shark: Side::default(),
             saw_fish: Side::default(),
             zed_ros2: ZedRos2Config::default(),
+            run_budget: run_budget::Config::default(),
+            disabled_missions: vec![],
+            annotated_missions: None,
+            plans: HashMap::new(),
+            soft_start_secs: DurationSecs(0.5),
+            ack_timeout_secs: None,
+            command_csv: false,
+            dry_run: false,
+            dead_man_secs: 5.0,
+            max_vision_errors: 30,
+            report_confidence_floor: REPORT_CONFIDENCE_FLOOR,
+            preamble: preamble::Config::default(),
+            emergency_surface: emergency::Config::default(),
+            depth_pid: depth_pid::Config::default(),
+            front_roi: NormalizedRoi::full(),
+            bottom_roi: NormalizedRoi::full(),
+            bottom_cam_rotation_deg: FrameRotation::None,
         }
     }
 }
@@ -106,6 +258,13 @@ pub struct Missions {
     pub octagon: octagon::Config,
     pub coinflip: coinflip::Config,
     pub spin: spin::Config,
+    pub travel: travel::Config,
+    pub hold: hold::Config,
+    pub center: center::Config,
+    pub calibration: calibration::Config,
+    pub thruster_pattern: thruster_pattern::Config,
+    /// Gains tried by the `pid_test` mission
+    pub pid_test: pid::Config,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -117,6 +276,81 @@ pub struct ColorProfile {
     pub black: RangeInclusive<Yuv>,
 }
 
+impl ColorProfile {
+    pub fn color(&self, color: ProfileColor) -> &RangeInclusive<Yuv> {
+        match color {
+            ProfileColor::Red => &self.red,
+            ProfileColor::Orange => &self.orange,
+            ProfileColor::Yellow => &self.yellow,
+            ProfileColor::Purple => &self.purple,
+            ProfileColor::Black => &self.black,
+        }
+    }
+
+    /// Fails on any range whose start is above its end in some channel,
+    /// which would match no pixels and make [`Yuv::clamp`] panic
+    pub fn validate(&self) -> Result<()> {
+        for (color, range) in self.ranges() {
+            let (start, end) = (range.start(), range.end());
+            if start.partial_cmp(end).is_none_or(Ordering::is_gt) {
+                let inverted = start
+                    .channels()
+                    .into_iter()
+                    .zip(end.channels())
+                    .filter(|((_, start), (_, end))| start > end)
+                    .map(|((channel, start), (_, end))| format!("{channel} {start} > {end}"))
+                    .join(", ");
+                bail!("{color:?} range is empty: {inverted}");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ranges(&self) -> [(ProfileColor, &RangeInclusive<Yuv>); 5] {
+        [
+            (ProfileColor::Red, &self.red),
+            (ProfileColor::Orange, &self.orange),
+            (ProfileColor::Yellow, &self.yellow),
+            (ProfileColor::Purple, &self.purple),
+            (ProfileColor::Black, &self.black),
+        ]
+    }
+}
+
+/// Names one of the ranges in a [`ColorProfile`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProfileColor {
+    Red,
+    Orange,
+    Yellow,
+    Purple,
+    Black,
+}
+
+/// Seconds in a config file, fractional values included
+///
+/// Whole numbers parse too, so `3` and `3.0` are the same duration.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DurationSecs(pub f32);
+
+impl DurationSecs {
+    pub const fn secs(self) -> f32 {
+        self.0
+    }
+
+    /// Negative or unrepresentable values are a zero duration
+    pub fn to_duration(self) -> Duration {
+        Duration::try_from_secs_f32(self.0.max(0.0)).unwrap_or_default()
+    }
+}
+
+impl From<DurationSecs> for Duration {
+    fn from(value: DurationSecs) -> Self {
+        value.to_duration()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, Clone)]
 pub enum Side {
     Right,
@@ -128,3 +362,187 @@ impl Default for Side {
         Self::Right
     }
 }
+
+/// Camera a mission reads frames from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CameraSelect {
+    Front,
+    Bottom,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> ColorProfile {
+        let range = Yuv { y: 0, u: 0, v: 0 }..=Yuv {
+            y: 255,
+            u: 255,
+            v: 255,
+        };
+        ColorProfile {
+            red: range.clone(),
+            orange: range.clone(),
+            yellow: range.clone(),
+            purple: range.clone(),
+            black: range,
+        }
+    }
+
+    #[test]
+    fn missing_color_profile_lists_available() {
+        let mut config = Config::default();
+        assert_eq!(
+            config.color_profile().unwrap_err().to_string(),
+            "color profile '' not found; available: none"
+        );
+
+        config.color_profiles.insert("pool".to_string(), profile());
+        config.color_profiles.insert("night".to_string(), profile());
+        config.color_profile = "day".to_string();
+        assert_eq!(
+            config.color_profile().unwrap_err().to_string(),
+            "color profile 'day' not found; available: night, pool"
+        );
+
+        config.color_profile = "pool".to_string();
+        assert!(config.color_profile().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_inverted_range() {
+        let mut config = Config::default();
+        config.color_profiles.insert("pool".to_string(), profile());
+        config.color_profile = "pool".to_string();
+        config.validate().unwrap();
+
+        config.color_profiles.get_mut("pool").unwrap().yellow = Yuv { y: 0, u: 200, v: 0 }..=Yuv {
+            y: 255,
+            u: 100,
+            v: 255,
+        };
+        assert_eq!(
+            format!("{:#}", config.validate().unwrap_err()),
+            "color profile 'pool': Yellow range is empty: u 200 > 100"
+        );
+
+        config.color_profile = "day".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_zero_dead_man_timeout() {
+        let mut config = Config::default();
+        assert_eq!(config.dead_man_timeout().unwrap(), Duration::from_secs(5));
+
+        for secs in [0.0, -1.0, f32::NAN] {
+            config.dead_man_secs = secs;
+            assert!(config.dead_man_timeout().is_err(), "{secs}");
+            assert!(config.validate().is_err(), "{secs}");
+        }
+    }
+
+    #[test]
+    fn validate_rejects_report_floor_outside_unit_range() {
+        let mut config = Config::default();
+        config.color_profiles.insert("pool".to_string(), profile());
+        config.color_profile = "pool".to_string();
+        config.validate().unwrap();
+
+        for floor in [-0.1, 1.5, f64::NAN] {
+            config.report_confidence_floor = floor;
+            assert!(config.validate().is_err(), "{floor}");
+        }
+    }
+
+    #[test]
+    fn validate_rejects_class_remap_collision() {
+        let mut config = Config::default();
+        config.color_profiles.insert("pool".to_string(), profile());
+        config.color_profile = "pool".to_string();
+        config.missions.gate.class_remap = vec![[4, 5], [5, 4]];
+        config.validate().unwrap();
+
+        config.missions.gate.class_remap = vec![[4, 5]];
+        assert_eq!(
+            format!("{:#}", config.validate().unwrap_err()),
+            "gate class_remap: class remap [[4, 5]] sends two classes to the same id"
+        );
+    }
+
+    #[test]
+    fn yuv_componentwise_order() {
+        let yuv = |y, u, v| Yuv { y, u, v };
+        assert_eq!(
+            yuv(1, 2, 3).partial_cmp(&yuv(1, 2, 3)),
+            Some(Ordering::Equal)
+        );
+        assert!(yuv(0, 2, 3) < yuv(1, 2, 3));
+        assert!(yuv(9, 9, 9) > yuv(1, 2, 3));
+        assert_eq!(yuv(0, 200, 0).partial_cmp(&yuv(255, 100, 255)), None);
+
+        assert_eq!(
+            yuv(0, 200, 128).clamp(yuv(10, 10, 10), yuv(100, 100, 100)),
+            yuv(10, 100, 100)
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Timed {
+        wait: DurationSecs,
+    }
+
+    #[test]
+    fn fractional_duration_round_trips() {
+        let timed = Timed {
+            wait: DurationSecs(2.5),
+        };
+        let written = toml::to_string(&timed).unwrap();
+        assert_eq!(toml::from_str::<Timed>(&written).unwrap(), timed);
+
+        // Whole seconds, as integer fields were written before
+        let whole: Timed = toml::from_str("wait = 3").unwrap();
+        assert_eq!(whole.wait.to_duration(), Duration::from_secs(3));
+
+        assert_eq!(DurationSecs(-1.0).to_duration(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fractional_duration_sleeps() {
+        let start = tokio::time::Instant::now();
+        tokio::time::sleep(DurationSecs(0.25).to_duration()).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn parse_error_names_line() {
+        let err = Config::parse("meb_path = \"/dev/ttyACM2\"\nfront_cam_path = ]\n").unwrap_err();
+        assert!(format!("{err:#}").contains("line 2"));
+    }
+
+    #[test]
+    fn mission_color_profile_falls_back_to_global() {
+        let mut config = Config::default();
+        config.color_profiles.insert("pool".to_string(), profile());
+        config.color_profiles.insert("gate".to_string(), profile());
+        config.color_profile = "pool".to_string();
+
+        let pool = &config.color_profiles["pool"];
+        let gate = &config.color_profiles["gate"];
+        assert!(std::ptr::eq(
+            config.mission_color_profile(None).unwrap(),
+            pool
+        ));
+        assert!(std::ptr::eq(
+            config.mission_color_profile(Some("gate")).unwrap(),
+            gate
+        ));
+        assert_eq!(
+            config
+                .mission_color_profile(Some("bin"))
+                .unwrap_err()
+                .to_string(),
+            "color profile 'bin' not found; available: gate, pool"
+        );
+    }
+}
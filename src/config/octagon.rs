@@ -1,10 +1,15 @@
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct Config {}
+pub struct Config {
+    /// Color profile name, the global `color_profile` when unset
+    pub color_profile: Option<String>,
+}
 
 impl Default for Config {
     fn default() -> Self {
-        Self {}
+        Self {
+            color_profile: None,
+        }
     }
 }
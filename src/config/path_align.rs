@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+use super::{CameraSelect, DurationSecs};
+use crate::vision::image_prep::ContourSelect;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub depth: f32,
@@ -7,9 +10,18 @@ pub struct Config {
     pub forward_speed: f32,
     pub strafe_speed: f32,
     pub detections: u8,
+    /// Consecutive detections required before steering on them
+    pub steer_after: u8,
     pub yaw_angle: f32,
-    pub forward_duration: u64,
-    pub yaw_wait: u64,
+    pub forward_duration: DurationSecs,
+    pub yaw_wait: DurationSecs,
+    pub contour_select: ContourSelect,
+    /// Color profile name, the global `color_profile` when unset
+    pub color_profile: Option<String>,
+    /// Camera the path is tracked with
+    pub camera: CameraSelect,
+    /// Path arms aligned to at once, their average is steered on
+    pub segments: usize,
 }
 
 impl Default for Config {
@@ -20,9 +32,14 @@ impl Default for Config {
             forward_speed: 0.3,
             strafe_speed: 0.3,
             detections: 10,
+            steer_after: 3,
             yaw_angle: 15.0,
-            forward_duration: 3,
-            yaw_wait: 3,
+            forward_duration: DurationSecs(3.0),
+            yaw_wait: DurationSecs(3.0),
+            contour_select: ContourSelect::default(),
+            color_profile: None,
+            camera: CameraSelect::Bottom,
+            segments: 1,
         }
     }
 }
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// Gains for a [`Pid`](crate::missions::pid::Pid) correction loop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    /// Largest magnitude the accumulated integral reaches
+    pub max_integral: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            kp: 0.2,
+            ki: 0.0,
+            kd: 0.0,
+            max_integral: 1.0,
+        }
+    }
+}
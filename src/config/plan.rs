@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use super::Side;
+
+/// One entry of a run plan
+///
+/// Written as a bare mission argument, or as
+/// `{ if = { coinflip = "Left" }, then = [..], else = [..] }`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Step {
+    Mission(String),
+    Branch(Branch),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Branch {
+    #[serde(rename = "if")]
+    pub condition: Condition,
+    pub then: Vec<Step>,
+    #[serde(rename = "else")]
+    pub otherwise: Vec<Step>,
+}
+
+/// Run results a branch can check
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// An earlier coinflip mission picked this side
+    Coinflip(Side),
+}
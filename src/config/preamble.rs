@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+use super::DurationSecs;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    /// Depth ramped to before each mission that dives
+    pub depth: f32,
+    pub depth_ramp_secs: DurationSecs,
+    /// Seconds to wait for the IMU and depth sensor before failing the mission
+    pub sensor_timeout_secs: DurationSecs,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            depth: -0.5,
+            depth_ramp_secs: DurationSecs(2.0),
+            sensor_timeout_secs: DurationSecs(5.0),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Total time allowed for the whole mission sequence, in seconds
+    pub total_secs: u64,
+    /// Optional missions are skipped once less than this remains, in seconds
+    pub skip_margin_secs: u64,
+    /// Mission arguments that may be skipped to save time
+    pub optional_missions: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            total_secs: 15 * 60,
+            skip_margin_secs: 60,
+            optional_missions: vec![],
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::ops::RangeInclusive;
 
-use super::Side;
+use super::{approach, completion::CompletionPolicy, pid, DurationSecs, Side};
+use crate::vision::image_prep::ContourSelect;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -11,14 +12,32 @@ pub struct Config {
     pub end_detections: u8,
     pub side: Side,
     pub centered_threshold: f32,
-    pub dumb_strafe_secs: u64,
-    pub init_duration: f32,
-    pub strafe_duration: f32,
-    pub traversal_duration: f32,
+    pub dumb_strafe_secs: DurationSecs,
+    pub init_duration: DurationSecs,
+    pub strafe_duration: DurationSecs,
+    pub traversal_duration: DurationSecs,
     pub yaw_adjustment: f32,
     pub yaw_speed: f32,
     pub area_bounds: RangeInclusive<f64>,
-    pub correction_factor: f32,
+    pub max_pole_angle_dev: f64,
+    /// Yaw correction from the normalized target offset
+    pub correction_pid: pid::Config,
+    pub contour_select: ContourSelect,
+    /// Color profile name, the global `color_profile` when unset
+    pub color_profile: Option<String>,
+    /// When traversal is done, see [`CompletionPolicy`]
+    pub approach_completion: CompletionPolicy,
+    /// Consecutive frames without a detection that count as passed
+    pub lost_frames: u32,
+    /// Slow forward speed while off-center instead of stopping to correct
+    pub approach: Option<approach::Config>,
+    /// Keep tracking the pole while strafing around it
+    pub closed_loop_strafe: bool,
+    /// Normalized distance from center the pole is held at during the
+    /// closed-loop strafe, on the side opposite the strafe
+    pub strafe_target_offset: f32,
+    /// Strafe speed added per unit of pole offset error
+    pub strafe_gain: f32,
 }
 
 impl Default for Config {
@@ -30,14 +49,26 @@ impl Default for Config {
             end_detections: 10,
             side: Side::Left,
             centered_threshold: 0.0,
-            dumb_strafe_secs: 2,
-            init_duration: 1.0,
-            strafe_duration: 2.0,
-            traversal_duration: 6.0,
+            dumb_strafe_secs: DurationSecs(2.0),
+            init_duration: DurationSecs(1.0),
+            strafe_duration: DurationSecs(2.0),
+            traversal_duration: DurationSecs(6.0),
             yaw_adjustment: 15.0,
             yaw_speed: 0.2,
             area_bounds: 1000.0..=11000.0,
-            correction_factor: 0.4,
+            max_pole_angle_dev: 20.0,
+            correction_pid: pid::Config {
+                kp: 0.4,
+                ..Default::default()
+            },
+            contour_select: ContourSelect::default(),
+            color_profile: None,
+            approach_completion: CompletionPolicy::TimedOnly,
+            lost_frames: 10,
+            approach: None,
+            closed_loop_strafe: false,
+            strafe_target_offset: 0.5,
+            strafe_gain: 0.4,
         }
     }
 }
@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+use super::DurationSecs;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub serial_port: PathBuf,
     pub serial_baud_rate: u32,
     pub bootloader: Bootloader,
     pub auto_transmit: AutoTransmit,
+    pub sweep: Sweep,
 }
 
 impl Default for Config {
@@ -16,6 +19,7 @@ impl Default for Config {
             serial_baud_rate: 115200,
             bootloader: Bootloader::default(),
             auto_transmit: AutoTransmit::default(),
+            sweep: Sweep::default(),
         }
     }
 }
@@ -75,3 +79,47 @@ impl Default for Gain {
         Self::Normal
     }
 }
+
+/// Settings for the `sonar_sweep` mission
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Sweep {
+    pub depth: f32,
+    /// Yaw speed the sub rotates at while sweeping
+    pub yaw_speed: f32,
+    /// Bearing bins in the polar map, each keeps its strongest return
+    pub bearing_bins: usize,
+    /// Meters to ignore in front of the transducer, skips ringdown
+    pub min_range: f32,
+    /// Weakest return that can be homed on
+    pub min_intensity: u8,
+    /// Forward speed while homing on the selected return
+    pub home_speed: f32,
+    pub home_secs: DurationSecs,
+    /// Pings taken facing the target before homing, 0 homes without confirming
+    pub confirm_pings: usize,
+    /// Degrees either side of the target a ping has to be within to count
+    pub confirm_window_deg: f32,
+    /// Time to collect `confirm_pings` before giving up on the target
+    pub confirm_secs: DurationSecs,
+    /// Largest standard deviation, in degrees, of confirmed bearings, and
+    /// furthest their mean can be from the swept target
+    pub max_bearing_std: f32,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self {
+            depth: -1.25,
+            yaw_speed: 0.3,
+            bearing_bins: 36,
+            min_range: 0.75,
+            min_intensity: 100,
+            home_speed: 0.3,
+            home_secs: DurationSecs(5.0),
+            confirm_pings: 5,
+            confirm_window_deg: 15.0,
+            confirm_secs: DurationSecs(20.0),
+            max_bearing_std: 5.0,
+        }
+    }
+}
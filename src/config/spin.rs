@@ -1,11 +1,20 @@
 use serde::{Deserialize, Serialize};
 
+use super::DurationSecs;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub depth: f32,
     pub spin_speed: f32,
     pub num_spins: i32,
     pub hysteresis: f32,
+    /// Also estimate revolutions from the commanded rotation rate, used when
+    /// roll stops crossing
+    pub rate_fallback: bool,
+    /// Seconds without a roll crossing before the estimate is trusted
+    pub fallback_stall_secs: DurationSecs,
+    /// Degrees per second the sub rotates at `spin_speed`
+    pub fallback_rate: f32,
 }
 
 impl Default for Config {
@@ -15,6 +24,9 @@ impl Default for Config {
             spin_speed: 1.0,
             num_spins: 2,
             hysteresis: 10.0,
+            rate_fallback: false,
+            fallback_stall_secs: DurationSecs(3.0),
+            fallback_rate: 180.0,
         }
     }
 }
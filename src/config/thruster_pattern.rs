@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// One step of a thruster test pattern
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    /// Thruster in control board order, 0-7
+    pub index: u8,
+    pub speed: f32,
+    /// Seconds to hold `speed` before the next step
+    pub duration: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    pub steps: Vec<Step>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        // Each thruster in turn, gently
+        Self {
+            steps: (0..8)
+                .map(|index| Step {
+                    index,
+                    speed: 0.2,
+                    duration: 1.0,
+                })
+                .collect(),
+        }
+    }
+}
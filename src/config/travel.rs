@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+use super::DurationSecs;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Degrees from the tared yaw if the run was tared, absolute otherwise
+    pub heading: f32,
+    pub depth: f32,
+    pub speed: f32,
+    /// Seconds
+    pub duration: DurationSecs,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            heading: 70.0,
+            depth: -1.3,
+            speed: 0.5,
+            duration: DurationSecs(10.0),
+        }
+    }
+}
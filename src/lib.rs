@@ -1,13 +1,28 @@
 use std::{
+    fmt::{self, Arguments, Display},
     fs::{create_dir, File},
-    sync::{LazyLock, Mutex},
+    io::{self, Write},
+    mem,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Sender},
+        LazyLock, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use chrono::Local;
 use rerun::RecordingStream;
 
-pub static TIMESTAMP: LazyLock<String> =
-    LazyLock::new(|| Local::now().format("%Y-%m-%d_%H:%M:%S").to_string());
+/// When [`TIMESTAMP`] was taken, log records are timed from here
+pub static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+pub static TIMESTAMP: LazyLock<String> = LazyLock::new(|| {
+    LazyLock::force(&START);
+    Local::now().format("%Y-%m-%d_%H:%M:%S").to_string()
+});
 
 pub static LOGFILE: LazyLock<Mutex<File>> = LazyLock::new(|| {
     let _ = create_dir("console");
@@ -19,20 +34,187 @@ pub fn get_recording() -> RecordingStream {
     RecordingStream::global(rerun::StoreKind::Recording).expect("Rerun is not initialized")
 }
 
+static QUIET_LOGGING: AtomicBool = AtomicBool::new(false);
+
+/// When quiet, [`logln`] and the other log macros only write to
+/// [`LOGFILE`], not the terminal.
+pub fn set_quiet_logging(quiet: bool) {
+    QUIET_LOGGING.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet_logging() -> bool {
+    QUIET_LOGGING.load(Ordering::Relaxed)
+}
+
+/// Backing function for [`logln`], always writes to `logfile`.
+pub fn write_log_line(
+    terminal: &mut impl Write,
+    logfile: &mut impl Write,
+    quiet: bool,
+    args: Arguments,
+) {
+    if !quiet {
+        let _ = writeln!(terminal, "{args}");
+    }
+    let _ = writeln!(logfile, "{args}");
+}
+
+/// Severity of a log record, see [`log_info`], [`log_warn`], and [`log_error`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub const fn tag(self) -> &'static str {
+        match self {
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.tag())
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Info, Self::Warn, Self::Error]
+            .into_iter()
+            .find(|level| level.tag() == s)
+            .ok_or_else(|| format!("Unknown log level: {s}"))
+    }
+}
+
+/// Backing function for the leveled log macros, prefixing the line with
+/// `elapsed` seconds since [`START`] and the `level` tag
+pub fn write_log_record(
+    terminal: &mut impl Write,
+    logfile: &mut impl Write,
+    quiet: bool,
+    elapsed: Duration,
+    level: Level,
+    args: Arguments,
+) {
+    write_log_line(
+        terminal,
+        logfile,
+        quiet,
+        format_args!("[{:>10.3}] {level:<5} {args}", elapsed.as_secs_f64()),
+    );
+}
+
+/// Splits a line written by [`write_log_record`] into its elapsed time,
+/// level, and message
+pub fn parse_log_record(line: &str) -> Option<(Duration, Level, &str)> {
+    let (elapsed, rest) = line.strip_prefix('[')?.split_once(']')?;
+    let elapsed = Duration::try_from_secs_f64(elapsed.trim().parse().ok()?).ok()?;
+    let (level, message) = rest.trim_start().split_once(' ')?;
+    Some((elapsed, level.parse().ok()?, message.trim_start()))
+}
+
+#[macro_export]
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::write_log_record(
+            &mut std::io::stdout(),
+            &mut *$crate::LOGFILE.lock().unwrap(),
+            $crate::quiet_logging(),
+            $crate::START.elapsed(),
+            $level,
+            format_args!($($arg)*),
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    () => {
+        $crate::log_info!("")
+    };
+    ($($arg:tt)*) => {
+        $crate::log_at!($crate::Level::Info, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::log_at!($crate::Level::Warn, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::log_at!($crate::Level::Error, $($arg)*)
+    };
+}
+
+/// Info level record, see [`log_info`]
 #[macro_export]
 macro_rules! logln {
-    () => { {
-            use std::io::Write;
-        println!(); let _ = writeln!($crate::LOGFILE.lock().unwrap(), "");
-    }};
     ($($arg:tt)*) => {
-        {
-            use std::io::Write;
+        $crate::log_info!($($arg)*)
+    };
+}
+
+/// Writer whose output goes to a file on its own thread, for logs written
+/// from async code.
+///
+/// Writes are buffered until [`Write::flush`], which hands them to the
+/// thread. `open` runs once on that thread. If it or a write fails, the
+/// thread logs the error and stops, and later flushes fail.
+#[derive(Debug)]
+pub struct BackgroundWriter {
+    buf: Vec<u8>,
+    tx: Sender<Vec<u8>>,
+}
 
-            println!($($arg)*);
-            let _ = writeln!($crate::LOGFILE.lock().unwrap(), $($arg)*);
+impl BackgroundWriter {
+    pub fn spawn<W: Write>(open: impl FnOnce() -> io::Result<W> + Send + 'static) -> Self {
+        let (tx, rx) = channel::<Vec<u8>>();
+        thread::spawn(move || {
+            let res = open().and_then(|mut writer| {
+                for bytes in rx {
+                    writer.write_all(&bytes)?;
+                    writer.flush()?;
+                }
+                Ok(())
+            });
+            if let Err(e) = res {
+                log_warn!("Background writer stopped: {e}");
+            }
+        });
+        Self {
+            buf: Vec::new(),
+            tx,
         }
-    };
+    }
+}
+
+impl Write for BackgroundWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        self.tx
+            .send(mem::take(&mut self.buf))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "background writer stopped"))
+    }
 }
 
 /// Set to `1.0` or `-1.0`.
@@ -50,3 +232,135 @@ pub mod missions;
 pub mod video_source;
 #[cfg(feature = "vision")]
 pub mod vision;
+
+#[cfg(test)]
+mod test_util;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output shared with the test, since the writer thread owns its writer
+    #[derive(Clone, Default)]
+    struct Shared(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Retries `check` for up to a second while the writer thread catches up
+    fn eventually(mut check: impl FnMut() -> bool) -> bool {
+        (0..100).any(|_| {
+            thread::sleep(Duration::from_millis(10));
+            check()
+        })
+    }
+
+    #[test]
+    fn background_writer_sends_on_flush() {
+        let out = Shared::default();
+        let thread_out = out.clone();
+        let mut writer = BackgroundWriter::spawn(move || Ok(thread_out));
+
+        write!(writer, "a,").unwrap();
+        writeln!(writer, "b").unwrap();
+        assert!(out.0.lock().unwrap().is_empty());
+
+        writer.flush().unwrap();
+        assert!(eventually(|| *out.0.lock().unwrap() == b"a,b\n"));
+    }
+
+    #[test]
+    fn background_writer_fails_after_open_error() {
+        let mut writer = BackgroundWriter::spawn(|| Err::<Shared, _>(io::Error::other("no file")));
+        assert!(eventually(|| {
+            writeln!(writer, "row").unwrap();
+            writer.flush().is_err()
+        }));
+    }
+
+    #[test]
+    fn quiet_still_logs_to_file() {
+        let mut terminal = Vec::new();
+        let mut logfile = Vec::new();
+        write_log_line(
+            &mut terminal,
+            &mut logfile,
+            true,
+            format_args!("depth {}", 1.5),
+        );
+
+        assert!(terminal.is_empty());
+        assert_eq!(logfile, b"depth 1.5\n");
+    }
+
+    #[test]
+    fn default_logs_to_both() {
+        let mut terminal = Vec::new();
+        let mut logfile = Vec::new();
+        write_log_line(
+            &mut terminal,
+            &mut logfile,
+            false,
+            format_args!("depth {}", 1.5),
+        );
+
+        assert_eq!(terminal, b"depth 1.5\n");
+        assert_eq!(logfile, b"depth 1.5\n");
+    }
+
+    #[test]
+    fn record_parses_back() {
+        let mut terminal = Vec::new();
+        let mut logfile = Vec::new();
+        for (secs, level) in [(1.25, Level::Warn), (62.5, Level::Error)] {
+            write_log_record(
+                &mut terminal,
+                &mut logfile,
+                true,
+                Duration::from_secs_f64(secs),
+                level,
+                format_args!("depth {}", 1.5),
+            );
+        }
+
+        let logfile = String::from_utf8(logfile).unwrap();
+        let records: Vec<_> = logfile.lines().map(parse_log_record).collect();
+        assert_eq!(
+            records,
+            [
+                Some((Duration::from_millis(1250), Level::Warn, "depth 1.5")),
+                Some((Duration::from_millis(62500), Level::Error, "depth 1.5")),
+            ]
+        );
+        assert_eq!(parse_log_record("depth 1.5"), None);
+    }
+
+    #[test]
+    fn record_times_are_monotonic() {
+        let mut logfile = Vec::new();
+        for _ in 0..2 {
+            write_log_record(
+                &mut Vec::new(),
+                &mut logfile,
+                true,
+                START.elapsed(),
+                Level::Info,
+                format_args!("tick"),
+            );
+        }
+
+        let logfile = String::from_utf8(logfile).unwrap();
+        let times: Vec<_> = logfile
+            .lines()
+            .map(|line| parse_log_record(line).unwrap().0)
+            .collect();
+        assert!(times[0] <= times[1]);
+    }
+}
@@ -1,35 +1,64 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, Context, Result};
 use std::env::temp_dir;
+use std::path::Path;
 
 use std::env;
 use std::process::exit;
 use std::time::Duration;
 use sw9s_lib::{
     comms::{
-        control_board::{ControlBoard, SensorStatuses},
-        meb::MainElectronicsBoard,
+        control_board::{
+            util::{DofSpeeds, ThrusterSpeeds},
+            ControlBoard, SensorStatuses, ANGLES_TIMEOUT,
+        },
+        meb::{dead_man::DeadManSwitch, MainElectronicsBoard},
         zed_ros2::ZedRos2,
     },
-    config::{Config, SHUTDOWN_TIMEOUT},
-    logln,
+    config::{gate, plan::Condition, Config, SHUTDOWN_TIMEOUT},
+    log_error, log_warn, logln,
     missions::{
         action::ActionExec,
-        action_context::FullActionContext,
-        basic::descend_and_go_forward,
+        action_context::{
+            relative_yaw, set_bottom_cam_rotation, tare_yaw, CameraSelect, ContextParts,
+            FullActionContext,
+        },
+        basic::{
+            descend_and_go_forward, hold_station, send_stability_command, set_max_vision_errors,
+            travel,
+        },
+        bench_latency::{bench_latency, DEFAULT_ITERATIONS},
         bin::bin,
+        budget::{MissionInfo, RunBudget},
+        calibration::calibration_overlay,
+        center::center_on_color,
         coinflip::coinflip_procedural,
+        emergency::{emergency_surface, wait_for_hazard},
         example::{initial_descent, pid_test, zed_test},
         fire_torpedo::{FireLeftTorpedo, FireRightTorpedo},
         gate::{gate_run_cv_procedural, gate_run_dead_reckon, gate_run_procedural},
+        interlock::test_interlock,
         meb::WaitArm,
         octagon::octagon,
         path_align::{path_align_procedural, static_align_procedural},
+        plan::{build_plan, PlanContext},
+        preamble::{mission_preamble, with_preamble},
+        registry::Mission,
+        sequence::{run_sequence, RunState},
         slalom::slalom,
-        sonar::sonar,
+        sonar::{sonar, sonar_sweep},
         spin::spin,
-        vision::PIPELINE_KILL,
+        thruster_pattern::thruster_pattern,
+        vision::{set_annotations, set_camera_rois, PIPELINE_KILL},
+    },
+    set_quiet_logging,
+    video_source::{
+        appsink::{Camera, FrameFormat},
+        reconnecting::ReconnectingSource,
+    },
+    vision::{
+        detection_log::toggle_detection_csv, image_prep::set_i420_frames,
+        nn_cv2::set_report_confidence_floor, profiling::set_cv_profiling,
     },
-    video_source::appsink::Camera,
     TIMESTAMP,
 };
 use tokio::{
@@ -39,6 +68,7 @@ use tokio::{
         mpsc::{self, UnboundedSender},
         OnceCell, Semaphore,
     },
+    task::JoinHandle,
     time::{sleep, timeout},
 };
 use tokio_serial::SerialStream;
@@ -48,10 +78,19 @@ static CONFIG_CELL: OnceCell<Config> = OnceCell::const_new();
 async fn config() -> &'static Config {
     CONFIG_CELL
         .get_or_init(|| async {
-            Config::new().unwrap_or_else(|e| {
-                logln!("Error getting config file: {:#?}\nUsing default config", e);
-                Config::default()
-            })
+            // Only a missing file falls back to defaults, a config that is
+            // there but unreadable, unparseable or invalid stops the run
+            match Config::load_strict() {
+                Ok(Some(config)) => config,
+                Ok(None) => {
+                    log_warn!("!!! NO CONFIG FILE, USING DEFAULTS !!!");
+                    Config::default()
+                }
+                Err(e) => {
+                    log_error!("!!! INVALID CONFIG, NOT STARTING !!!\n{e:#}");
+                    exit(1)
+                }
+            }
         })
         .await
 }
@@ -61,25 +100,54 @@ async fn control_board() -> &'static ControlBoard<WriteHalf<SerialStream>> {
     let config = config().await;
     CONTROL_BOARD_CELL
         .get_or_init(|| async {
-            let board = ControlBoard::serial(config.control_board_path.as_str()).await;
-            match board {
+            let ack_timeout = config.ack_timeout_secs.map(Duration::from);
+            let board = ControlBoard::serial(config.control_board_path.as_str(), ack_timeout).await;
+            let board = match board {
                 Ok(x) => x,
                 Err(e) => {
-                    logln!("Error initializing control board: {:#?}", e);
-                    let backup_board =
-                        ControlBoard::serial(config.control_board_backup_path.as_str())
-                            .await
-                            .unwrap();
+                    log_error!("Error initializing control board: {:#?}", e);
+                    let backup_board = ControlBoard::serial(
+                        config.control_board_backup_path.as_str(),
+                        ack_timeout,
+                    )
+                    .await
+                    .unwrap();
                     backup_board.reset().await.unwrap();
-                    ControlBoard::serial(config.control_board_path.as_str())
+                    ControlBoard::serial(config.control_board_path.as_str(), ack_timeout)
                         .await
                         .unwrap()
                 }
+            };
+            board
+                .set_soft_start(config.soft_start_secs.to_duration())
+                .await;
+            if let Err(e) = board.set_command_csv(config.command_csv) {
+                log_warn!("Failed to start command CSV: {:#?}", e);
+            }
+            if config.dry_run {
+                logln!("Dry run: speed commands are logged, not sent");
+                board.set_dry_run(true);
             }
+
+            // Feed arm transitions to the control board soft-start
+            tokio::spawn(async {
+                let meb = meb().await;
+                loop {
+                    control_board()
+                        .await
+                        .update_arm_state(meb.thruster_arm().await)
+                        .await;
+                    sleep(ARM_POLL_INTERVAL).await;
+                }
+            });
+            board
         })
         .await
 }
 
+/// How often the thruster arm state is forwarded to the control board
+const ARM_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 static MEB_CELL: OnceCell<MainElectronicsBoard<WriteHalf<SerialStream>>> = OnceCell::const_new();
 async fn meb() -> &'static MainElectronicsBoard<WriteHalf<SerialStream>> {
     MEB_CELL
@@ -93,45 +161,107 @@ async fn meb() -> &'static MainElectronicsBoard<WriteHalf<SerialStream>> {
         .await
 }
 
-static FRONT_CAM_CELL: OnceCell<Camera> = OnceCell::const_new();
-async fn front_cam() -> &'static Camera {
+/// Time without a fresh frame before a camera pipeline is rebuilt
+const CAMERA_FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens the camera at `camera_path`, replaying it instead when it is a video file
+fn open_camera(
+    camera_path: &str,
+    camera_name: &str,
+    filesink: &Path,
+    annotated_dimensions: Option<(u32, u32)>,
+    frame_format: FrameFormat,
+) -> Result<Camera> {
+    if Path::new(camera_path).is_file() {
+        logln!("Replaying {camera_name} camera from {camera_path}");
+        Camera::from_file(
+            Path::new(camera_path),
+            camera_name,
+            annotated_dimensions,
+            frame_format,
+        )
+    } else {
+        Camera::jetson_new(
+            camera_path,
+            camera_name,
+            filesink,
+            annotated_dimensions,
+            frame_format,
+        )
+    }
+}
+
+fn reconnecting_camera(
+    camera_path: String,
+    camera_name: &'static str,
+    annotated_dimensions: Option<(u32, u32)>,
+    frame_format: FrameFormat,
+) -> ReconnectingSource<Camera> {
+    let filesink = temp_dir().join("cams_".to_string() + &TIMESTAMP);
+    let camera = open_camera(
+        &camera_path,
+        camera_name,
+        &filesink,
+        annotated_dimensions,
+        frame_format,
+    )
+    .unwrap();
+    ReconnectingSource::new(camera, CAMERA_FRAME_TIMEOUT, move || {
+        open_camera(
+            &camera_path,
+            camera_name,
+            &filesink,
+            annotated_dimensions,
+            frame_format,
+        )
+    })
+}
+
+static FRONT_CAM_CELL: OnceCell<ReconnectingSource<Camera>> = OnceCell::const_new();
+async fn front_cam() -> &'static ReconnectingSource<Camera> {
     FRONT_CAM_CELL
         .get_or_init(|| async {
-            Camera::jetson_new(
-                config().await.front_cam_path.as_str(),
+            let config = config().await;
+            reconnecting_camera(
+                config.front_cam_path.clone(),
                 "front",
-                &temp_dir().join("cams_".to_string() + &TIMESTAMP),
+                config.annotated_dimensions,
+                config.frame_format,
             )
-            .unwrap()
         })
         .await
 }
 
-static BOTTOM_CAM_CELL: OnceCell<Camera> = OnceCell::const_new();
-async fn bottom_cam() -> &'static Camera {
+static BOTTOM_CAM_CELL: OnceCell<ReconnectingSource<Camera>> = OnceCell::const_new();
+async fn bottom_cam() -> &'static ReconnectingSource<Camera> {
     BOTTOM_CAM_CELL
         .get_or_init(|| async {
-            Camera::jetson_new(
-                config().await.bottom_cam_path.as_str(),
+            let config = config().await;
+            reconnecting_camera(
+                config.bottom_cam_path.clone(),
                 "bottom",
-                &temp_dir().join("cams_".to_string() + &TIMESTAMP),
+                config.annotated_dimensions,
+                config.frame_format,
             )
-            .unwrap()
         })
         .await
 }
 
 static STATIC_CONTEXT: OnceCell<FullActionContext<WriteHalf<SerialStream>>> = OnceCell::const_new();
+/// Context every mission runs with, wired to all of the other cells once
 async fn static_context() -> &'static FullActionContext<'static, WriteHalf<SerialStream>> {
     STATIC_CONTEXT
         .get_or_init(|| async {
-            FullActionContext::new(
-                control_board().await,
-                meb().await,
-                front_cam().await,
-                bottom_cam().await,
-                zed_ros2().await,
+            ContextParts::resolve(
+                control_board(),
+                meb(),
+                front_cam(),
+                bottom_cam(),
+                zed_ros2(),
+                run_budget(),
             )
+            .await
+            .into()
         })
         .await
 }
@@ -141,16 +271,78 @@ async fn zed_ros2() -> &'static ZedRos2 {
     ZED_CELL
         .get_or_init(|| async {
             let config = config().await;
-            ZedRos2::new(&config.zed_ros2).unwrap()
+            ZedRos2::new(&config.zed_ros2).await.unwrap_or_else(|e| {
+                logln!("ZED ROS2 unavailable, running without it: {e:#}");
+                ZedRos2::offline()
+            })
         })
         .await
 }
 
+static RUN_BUDGET_CELL: OnceCell<RunBudget> = OnceCell::const_new();
+async fn run_budget() -> &'static RunBudget {
+    RUN_BUDGET_CELL
+        .get_or_init(|| async { RunBudget::from_config(&config().await.run_budget) })
+        .await
+}
+
+/// Arguments that enable quiet logging instead of naming a mission
+const QUIET_FLAGS: [&str; 2] = ["-q", "--quiet"];
+/// Environment variable that enables quiet logging when set
+const QUIET_ENV: &str = "SW9S_QUIET";
+/// Environment variable that enables per-stage CV detector timing when set
+const CV_PROFILE_ENV: &str = "SW9S_CV_PROFILE";
+/// Argument that prints every mission name instead of running anything
+const LIST_ARG: &str = "list";
+
 static SHUTDOWN_GUARD: Semaphore = Semaphore::const_new(1);
 
+/// Time between main loop heartbeats to the dead-man switch
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Longest wait for the shutdown handler to stop the motors and exit, on top of
+/// its own wait for missions
+const SHUTDOWN_HANDLER_GRACE: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() {
-    let (shutdown_tx, mission_ct) = shutdown_handler().await;
+    let (quiet_args, mission_args): (Vec<String>, Vec<String>) = env::args()
+        .skip(1)
+        .partition(|arg| QUIET_FLAGS.contains(&arg.as_str()));
+    // Keep the terminal clean over SSH, everything still goes to the logfile
+    set_quiet_logging(!quiet_args.is_empty() || env::var_os(QUIET_ENV).is_some());
+    set_cv_profiling(env::var_os(CV_PROFILE_ENV).is_some());
+
+    if mission_args.iter().any(|arg| arg == LIST_ARG) {
+        Mission::names().for_each(|name| println!("{name}"));
+        return;
+    }
+    // Catch typos before anything is opened or armed
+    for arg in mission_args.iter().filter(|arg| !arg.starts_with("plan:")) {
+        if let Err(e) = Mission::parse_argument(arg) {
+            logln!("{e}");
+            exit(1);
+        }
+    }
+
+    // Detection CSVs get large, so operators capture only the windows they want
+    tokio::spawn(async {
+        match signal::unix::signal(signal::unix::SignalKind::user_defined2()) {
+            Ok(mut toggle) => {
+                while toggle.recv().await.is_some() {
+                    let state = if toggle_detection_csv() {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    };
+                    logln!("Detection CSV logging {state}");
+                }
+            }
+            Err(e) => log_warn!("Failed to listen for SIGUSR2: {e}"),
+        }
+    });
+
+    let (shutdown_tx, mission_ct, shutdown) = shutdown_handler().await;
 
     let stream = rerun::RecordingStreamBuilder::new("SWS9")
         .serve_grpc()
@@ -196,21 +388,92 @@ async fn main() {
         shutdown_tx_clone.send(1).unwrap();
     });
 
-    for arg in env::args().skip(1).collect::<Vec<String>>() {
-        let _guard = SHUTDOWN_GUARD.acquire().await.unwrap();
-        run_mission(&arg, mission_ct.clone()).await.unwrap();
+    let config = config().await;
+    set_camera_rois(config.front_roi, config.bottom_roi);
+    set_bottom_cam_rotation(config.bottom_cam_rotation_deg);
+    set_i420_frames(config.frame_format == FrameFormat::I420);
+    set_max_vision_errors(config.max_vision_errors);
+    set_report_confidence_floor(config.report_confidence_floor);
+
+    // Last line of defense against a hung mission loop
+    let dead_man = DeadManSwitch::new();
+    let dead_man_clone = dead_man.clone();
+    match config.dead_man_timeout() {
+        Ok(timeout) => {
+            tokio::spawn(async move {
+                let control_board = control_board().await;
+                if let Err(e) = dead_man_clone
+                    .watch(timeout, || {
+                        control_board.raw_speed_set(ThrusterSpeeds::ZERO)
+                    })
+                    .await
+                {
+                    log_error!("Dead-man disarm failed: {:#}", e);
+                }
+            });
+        }
+        Err(e) => log_error!("Dead-man switch disabled: {:#}", e),
     }
 
+    // Run clock starts with the first mission
+    let sequence = run_sequence(
+        mission_args
+            .iter()
+            .map(|arg| MissionInfo::from_config(arg, config)),
+        run_budget().await,
+        |mission| {
+            let mission_ct = mission_ct.clone();
+            let dead_man = &dead_man;
+            async move {
+                let _guard = SHUTDOWN_GUARD.acquire().await.unwrap();
+                dead_man
+                    .beat_while(run_argument(mission, mission_ct), HEARTBEAT_INTERVAL)
+                    .await
+            }
+        },
+    );
+    let meb = meb().await;
+    // Hazard watch is dropped with the select once missions finish
+    let exit_status = tokio::select! {
+        res = sequence => {
+            res.unwrap();
+            0
+        },
+        hazard = wait_for_hazard(meb, config.emergency_surface.min_voltage) => {
+            log_warn!("{hazard}, abandoning missions");
+            mission_ct.cancel();
+            let surface = emergency_surface(control_board().await, &config.emergency_surface);
+            if let Err(e) = dead_man.beat_while(surface, HEARTBEAT_INTERVAL).await {
+                log_error!("Emergency surface failed: {:#}", e);
+            }
+            hazard.exit_code()
+        }
+    };
+    dead_man.release();
+
     // Send shutdown signal
-    shutdown_tx.send(0).unwrap();
+    shutdown_tx.send(exit_status).unwrap();
+    if exit_status != 0 {
+        // Shutdown handler stops the motors and exits the process, unless it is stuck
+        if timeout(
+            Duration::from_secs(SHUTDOWN_TIMEOUT) + SHUTDOWN_HANDLER_GRACE,
+            shutdown,
+        )
+        .await
+        .is_err()
+        {
+            log_error!("Shutdown handler did not exit, exiting anyway");
+        }
+        exit(exit_status);
+    }
 }
 
 /// Graceful shutdown, see <https://tokio.rs/tokio/topics/shutdown>
-async fn shutdown_handler() -> (UnboundedSender<i32>, CancellationToken) {
+async fn shutdown_handler() -> (UnboundedSender<i32>, CancellationToken, JoinHandle<()>) {
     let (shutdown_tx, mut shutdown_rx) = mpsc::unbounded_channel::<i32>();
     let mission_ct = CancellationToken::new();
     let mission_ct_clone = mission_ct.clone();
-    tokio::spawn(async move {
+    let handler = tokio::spawn(async move {
         // Wait for shutdown signal
         let exit_status = tokio::select! {
             _ = signal::ctrl_c() => {
@@ -226,20 +489,21 @@ async fn shutdown_handler() -> (UnboundedSender<i32>, CancellationToken) {
 
         let status = control_board().await.sensor_status_query().await;
 
-        match status.unwrap() {
-            SensorStatuses::ImuNr => {
+        match status {
+            Ok(SensorStatuses::ImuNr) => {
                 logln!("imu not ready");
             }
-            SensorStatuses::DepthNr => {
+            Ok(SensorStatuses::DepthNr) => {
                 logln!("depth not ready");
             }
-            _ => {}
+            Ok(_) => {}
+            Err(e) => log_error!("Sensor status query failed: {:#}", e),
         }
 
         // Stop motors
         if let Some(control_board) = CONTROL_BOARD_CELL.get() {
             control_board
-                .relative_dof_speed_set_batch(&[0.0; 6])
+                .relative_dof_speed_set_batch(DofSpeeds::splat(0.0))
                 .await
                 .unwrap();
         };
@@ -259,16 +523,97 @@ async fn shutdown_handler() -> (UnboundedSender<i32>, CancellationToken) {
             .await
             .is_err()
             {
-                logln!("Missions did not exit within {SHUTDOWN_TIMEOUT} seconds")
+                log_warn!("Missions did not exit within {SHUTDOWN_TIMEOUT} seconds")
             }
             println!("EXITING PROCESS");
             exit(exit_status)
         };
     });
-    (shutdown_tx, mission_ct)
+    (shutdown_tx, mission_ct, handler)
+}
+
+/// Results passed between missions of this run
+static RUN_STATE: std::sync::Mutex<RunState> = std::sync::Mutex::new(RunState::new());
+
+/// Gate config, on the side picked by an earlier coinflip if there was one
+fn gate_config(config: &Config) -> gate::Config {
+    RUN_STATE.lock().unwrap().gate_config(&config.missions.gate)
+}
+
+/// Runs missions for a plan from the config, branching on [`RUN_STATE`]
+struct ConfigPlan {
+    cancel: CancellationToken,
+}
+
+impl PlanContext for ConfigPlan {
+    async fn run_mission(&self, mission: &str) -> Result<()> {
+        if !MissionInfo::from_config(mission, config().await).enabled {
+            logln!("Skipping disabled mission {mission}");
+            return Ok(());
+        }
+        run_mission(mission, self.cancel.clone()).await
+    }
+
+    fn check(&self, condition: &Condition) -> bool {
+        RUN_STATE.lock().unwrap().meets(condition)
+    }
+}
+
+/// Runs `plan:<name>` arguments as that configured plan, and anything else as a mission
+async fn run_argument(arg: &str, cancel: CancellationToken) -> Result<()> {
+    match arg.strip_prefix("plan:") {
+        Some(name) => {
+            let steps = config()
+                .await
+                .plans
+                .get(name)
+                .ok_or_else(|| anyhow!("No plan named [{name}]"))?;
+            build_plan(&ConfigPlan { cancel }, steps).execute().await
+        }
+        None => run_mission(arg, cancel).await,
+    }
 }
 
 async fn run_mission(mission: &str, cancel: CancellationToken) -> Result<()> {
+    let (kind, parameter) = Mission::parse_argument(mission)?;
+    let config = config().await;
+    set_annotations(
+        config
+            .annotated_missions
+            .as_ref()
+            .is_none_or(|names| names.iter().any(|name| name.eq_ignore_ascii_case(mission))),
+    );
+    let preamble_cancel = cancel.clone();
+    let res = with_preamble(
+        kind,
+        &config.preamble,
+        || async move {
+            preamble_cancel
+                .run_until_cancelled(mission_preamble(control_board().await, &config.preamble))
+                .await
+                .unwrap_or(Ok(()))
+        },
+        || mission_body(mission, kind, parameter, cancel),
+    )
+    .await;
+
+    // Kill any vision pipelines
+    PIPELINE_KILL.write().unwrap().1 = true;
+    while PIPELINE_KILL.read().unwrap().0 > 0 {
+        sleep(Duration::from_millis(100)).await;
+    }
+    PIPELINE_KILL.write().unwrap().1 = false;
+
+    res
+}
+
+/// Runs the mission itself, after any preamble
+async fn mission_body(
+    mission: &str,
+    kind: Mission,
+    parameter: Option<&str>,
+    cancel: CancellationToken,
+) -> Result<()> {
     /// Wrapper for missions that do not directly use the cancellation token
     macro_rules! ctwrap {
         ($fut:expr) => {{
@@ -277,138 +622,127 @@ async fn run_mission(mission: &str, cancel: CancellationToken) -> Result<()> {
         }};
     }
 
+    /// Like `ctwrap!`, for missions that return a `Result`
+    macro_rules! ctwrap_result {
+        ($fut:expr) => {{
+            cancel.run_until_cancelled($fut).await.unwrap_or(Ok(()))
+        }};
+    }
+
     let config = config().await;
     println!("Running {mission}");
-    let res = match mission.to_lowercase().as_str() {
-        "arm" => ctwrap!(WaitArm::new(static_context().await).execute()),
-        "empty" => {
+    match kind {
+        Mission::Arm => ctwrap!(WaitArm::new(static_context().await).execute()),
+        Mission::ThrusterPattern => {
+            let res = cancel
+                .run_until_cancelled(thruster_pattern(
+                    static_context().await,
+                    &config.missions.thruster_pattern,
+                ))
+                .await;
+            // Cancelling mid-step leaves that thruster running
+            let _ = control_board()
+                .await
+                .raw_speed_set(ThrusterSpeeds::ZERO)
+                .await;
+            res.unwrap_or(Ok(()))
+        }
+        Mission::Empty => {
             let control_board = control_board().await;
             control_board
-                .raw_speed_set([0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0])
+                .raw_speed_set(ThrusterSpeeds::single(8, 1.0)?)
                 .await
                 .unwrap();
             sleep(Duration::from_millis(1000)).await;
             logln!("1");
             control_board
-                .raw_speed_set([0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0])
+                .raw_speed_set(ThrusterSpeeds::single(7, 1.0)?)
                 .await
                 .unwrap();
             sleep(Duration::from_millis(1000)).await;
             logln!("2");
             control_board
-                .raw_speed_set([0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0])
+                .raw_speed_set(ThrusterSpeeds::single(6, 1.0)?)
                 .await
                 .unwrap();
             sleep(Duration::from_millis(1000)).await;
             logln!("3");
             control_board
-                .raw_speed_set([0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0])
+                .raw_speed_set(ThrusterSpeeds::ZERO)
                 .await
                 .unwrap();
             logln!("4");
             Ok(())
         }
-        "depth_test" | "depth-test" => {
+        Mission::DepthTest => {
             let _control_board = control_board().await;
             logln!("Init ctrl");
             sleep(Duration::from_millis(1000)).await;
             logln!("End sleep");
             logln!("Starting depth hold...");
-            loop {
-                if let Ok(ret) = timeout(
-                    Duration::from_secs(1),
-                    control_board()
-                        .await
-                        .stability_1_speed_set(0.0, 0.0, 0.0, 0.0, 0.0, -1.3),
-                )
-                .await
-                {
-                    ret?;
-                    break;
-                }
-            }
+            send_stability_command(|| async {
+                control_board()
+                    .await
+                    .stability_1_speed_set(0.0, 0.0, 0.0, 0.0, 0.0, -1.3)
+                    .await
+            })
+            .await?;
             sleep(Duration::from_secs(5)).await;
             logln!("Finished depth hold");
             Ok(())
         }
-        "travel_test" | "travel-test" => {
+        Mission::TravelTest => {
+            let travel_config = &config.missions.travel;
             logln!("Starting travel...");
-            loop {
-                if let Ok(ret) = timeout(
-                    Duration::from_secs(1),
-                    control_board()
-                        .await
-                        .stability_2_speed_set(0.0, 0.5, 0.0, 0.0, 70.0, -1.3),
-                )
-                .await
-                {
-                    ret?;
-                    break;
-                }
-            }
-            sleep(Duration::from_secs(10)).await;
+            travel(
+                control_board().await,
+                relative_yaw(travel_config.heading),
+                travel_config.depth,
+                travel_config.speed,
+                travel_config.duration.to_duration(),
+            )
+            .await?;
             logln!("Finished travel");
             Ok(())
         }
-        "surface_" | "surface-test" => {
+        Mission::TareYaw => {
+            let cb = control_board().await;
+            cb.bno055_periodic_read(true).await?;
+            let yaw = cb.wait_for_yaw(Some(ANGLES_TIMEOUT)).await?;
+            tare_yaw(yaw);
+            logln!("Tared yaw at {yaw}");
+            Ok(())
+        }
+        Mission::SurfaceTest => {
             logln!("Starting travel...");
-            loop {
-                if let Ok(ret) = timeout(
-                    Duration::from_secs(1),
-                    control_board()
-                        .await
-                        .stability_1_speed_set(0.0, 0.5, 0.0, 0.0, 0.0, 0.0),
-                )
-                .await
-                {
-                    ret?;
-                    break;
-                }
-            }
+            send_stability_command(|| async {
+                control_board()
+                    .await
+                    .stability_1_speed_set(0.0, 0.5, 0.0, 0.0, 0.0, 0.0)
+                    .await
+            })
+            .await?;
             sleep(Duration::from_secs(10)).await;
             logln!("Finished travel");
             Ok(())
         }
-        "descend" | "forward" => ctwrap!(descend_and_go_forward(&FullActionContext::new(
-            control_board().await,
-            meb().await,
-            front_cam().await,
-            bottom_cam().await,
-            zed_ros2().await,
-        ))
-        .execute()),
-        "gate_run_coinflip" => ctwrap!(gate_run_cv_procedural(
-            &FullActionContext::new(
-                control_board().await,
-                meb().await,
-                front_cam().await,
-                bottom_cam().await,
-                zed_ros2().await,
-            ),
-            &config.missions.gate,
-            &config.get_color_profile().unwrap(),
+        Mission::Descend => ctwrap!(descend_and_go_forward(static_context().await).execute()),
+        Mission::GateRunCoinflip => ctwrap_result!(gate_run_cv_procedural(
+            static_context().await,
+            &gate_config(config),
+            config.mission_color_profile(config.missions.gate.color_profile.as_deref())?,
         )),
-        "gate_run_yolo" => ctwrap!(gate_run_procedural(
-            &FullActionContext::new(
-                control_board().await,
-                meb().await,
-                front_cam().await,
-                bottom_cam().await,
-                zed_ros2().await,
-            ),
-            &config.missions.gate
+        Mission::GateRunYolo => ctwrap_result!(gate_run_procedural(
+            static_context().await,
+            &gate_config(config),
+            &config.shark,
+            &config.saw_fish,
         )),
-        "gate_run_reckon" => ctwrap!(gate_run_dead_reckon(
-            &FullActionContext::new(
-                control_board().await,
-                meb().await,
-                front_cam().await,
-                bottom_cam().await,
-                zed_ros2().await,
-            ),
-            &config.missions.gate,
+        Mission::GateRunReckon => ctwrap_result!(gate_run_dead_reckon(
+            static_context().await,
+            &gate_config(config),
         )),
-        "start_cam" => {
+        Mission::StartCam => {
             // This has not been tested
             logln!("Opening camera");
             front_cam().await;
@@ -416,104 +750,174 @@ async fn run_mission(mission: &str, cancel: CancellationToken) -> Result<()> {
             logln!("Opened camera");
             Ok(())
         }
-        "path_align" => ctwrap!(path_align_procedural(
-            &FullActionContext::new(
-                control_board().await,
-                meb().await,
-                front_cam().await,
-                bottom_cam().await,
-                zed_ros2().await,
-            ),
+        Mission::PathAlign => ctwrap_result!(path_align_procedural(
+            static_context().await,
             &config.missions.path_align,
-            &config.get_color_profile().unwrap(),
+            config.mission_color_profile(config.missions.path_align.color_profile.as_deref())?,
         )),
-        "static_align" => ctwrap!(static_align_procedural(
-            &FullActionContext::new(
-                control_board().await,
-                meb().await,
-                front_cam().await,
-                bottom_cam().await,
-                zed_ros2().await,
-            ),
+        Mission::StaticAlign => ctwrap!(static_align_procedural(
+            static_context().await,
             &config.missions.path_align,
         )),
-        "example" => ctwrap!(initial_descent(&FullActionContext::new(
-            control_board().await,
-            meb().await,
-            front_cam().await,
-            bottom_cam().await,
-            zed_ros2().await,
-        ))
-        .execute()),
-        "pid_test" => ctwrap!(pid_test(&FullActionContext::new(
-            control_board().await,
-            meb().await,
-            front_cam().await,
-            bottom_cam().await,
-            zed_ros2().await,
-        ))
-        .execute()),
-        "octagon" => ctwrap!(octagon(
+        Mission::Example => ctwrap!(initial_descent(static_context().await).execute()),
+        Mission::PidTest => cancel
+            .run_until_cancelled(pid_test(static_context().await, &config.missions.pid_test))
+            .await
+            .unwrap_or(Ok(())),
+        Mission::Octagon => ctwrap!(octagon(
             static_context().await,
             &config.missions.octagon,
-            &config.get_color_profile().unwrap()
+            config.mission_color_profile(config.missions.octagon.color_profile.as_deref())?
         )
         .execute()),
-        "spin" => ctwrap!(spin(static_context().await, &config.missions.spin)),
-        "torpedo_only" => {
+        Mission::Spin => ctwrap!(spin(static_context().await, &config.missions.spin)),
+        Mission::TorpedoOnly => {
             FireRightTorpedo::new(static_context().await)
                 .execute()
                 .await;
             FireLeftTorpedo::new(static_context().await).execute().await;
             Ok(())
         }
-        "coinflip" => {
-            ctwrap!(coinflip_procedural(
-                static_context().await,
-                &config.missions.coinflip
-            ))
+        Mission::Coinflip => {
+            let Some(result) = cancel
+                .run_until_cancelled(coinflip_procedural(
+                    static_context().await,
+                    &config.missions.coinflip,
+                    &config.shark,
+                    &config.saw_fish,
+                    // An invalid remap was already reported by `Config::validate`
+                    config.missions.gate.class_remap().ok().flatten(),
+                ))
+                .await
+            else {
+                return Ok(());
+            };
+            RUN_STATE.lock().unwrap().record_coinflip(result?)
         }
         // Just stall out forever
-        "forever" | "infinite" => loop {
-            while control_board().await.raw_speed_set([0.0; 8]).await.is_err() {}
+        Mission::Forever => loop {
+            while control_board()
+                .await
+                .raw_speed_set(ThrusterSpeeds::ZERO)
+                .await
+                .is_err()
+            {}
             sleep(Duration::from_secs(u64::MAX)).await;
         },
-        "open_cam_test" => {
+        Mission::OpenCamTest => {
             Camera::jetson_new(
                 config.bottom_cam_path.as_str(),
                 "front",
                 &temp_dir().join("cams_".to_string() + &TIMESTAMP),
+                config.annotated_dimensions,
+                config.frame_format,
             )
             .unwrap();
             Ok(())
         }
-        "slalom_left" => ctwrap!(slalom(
+        Mission::SlalomLeft => ctwrap_result!(slalom(
             static_context().await,
             &config.missions.slalom,
             false,
-            &config.get_color_profile().unwrap()
+            config.mission_color_profile(config.missions.slalom.color_profile.as_deref())?
         )),
-        "slalom_right" => ctwrap!(slalom(
+        Mission::SlalomRight => ctwrap_result!(slalom(
             static_context().await,
             &config.missions.slalom,
             true,
-            &config.get_color_profile().unwrap()
+            config.mission_color_profile(config.missions.slalom.color_profile.as_deref())?
         )),
-        "sonar" => {
+        Mission::Sonar => {
             let _ = sonar(static_context().await, &config.sonar, cancel).await;
             Ok(())
         }
-        "bin" => ctwrap!(bin(static_context().await)),
-        "zed_test" => ctwrap!(zed_test(static_context().await)),
-        x => bail!("Invalid argument: [{x}]"),
-    };
-
-    // Kill any vision pipelines
-    PIPELINE_KILL.write().unwrap().1 = true;
-    while PIPELINE_KILL.read().unwrap().0 > 0 {
-        sleep(Duration::from_millis(100)).await;
+        Mission::SonarSweep => {
+            let _ = sonar_sweep(static_context().await, &config.sonar, cancel).await;
+            Ok(())
+        }
+        Mission::TestInterlock => {
+            test_interlock(static_context().await)
+                .await
+                .context("Interlock test failed")?;
+            logln!("INTERLOCK TEST PASSED");
+            Ok(())
+        }
+        Mission::Bin => ctwrap!(bin(
+            static_context().await,
+            &config.missions.bin,
+            &config.depth_pid
+        )),
+        Mission::ZedTest => ctwrap!(zed_test(static_context().await)),
+        x @ (Mission::CenterFront | Mission::CenterBottom) => {
+            let center_config = &config.missions.center;
+            let camera = if x == Mission::CenterFront {
+                CameraSelect::Front
+            } else {
+                CameraSelect::Bottom
+            };
+            ctwrap!(center_on_color(
+                static_context().await,
+                config
+                    .mission_color_profile(center_config.color_profile.as_deref())?
+                    .color(center_config.color),
+                camera,
+                center_config.tolerance,
+                center_config,
+            ))
+        }
+        x @ (Mission::CalibrateFront | Mission::CalibrateBottom) => {
+            let calibration_config = &config.missions.calibration;
+            let camera = if x == Mission::CalibrateFront {
+                CameraSelect::Front
+            } else {
+                CameraSelect::Bottom
+            };
+            ctwrap!(calibration_overlay(
+                static_context().await,
+                config
+                    .mission_color_profile(calibration_config.color_profile.as_deref())?
+                    .color(calibration_config.color),
+                camera,
+                calibration_config,
+            ))
+        }
+        Mission::Hold => {
+            let hold_config = &config.missions.hold;
+            let secs = match parameter {
+                Some(secs) => secs
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid hold duration [{secs}]: {e}"))?,
+                None => hold_config.duration.secs(),
+            };
+            hold_station(
+                static_context().await,
+                Duration::try_from_secs_f32(secs)?,
+                &cancel,
+            )
+            .await
+        }
+        Mission::BenchLatency => {
+            let center_config = &config.missions.center;
+            let iterations = match parameter {
+                Some(count) => count
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid iteration count [{count}]: {e}"))?,
+                None => DEFAULT_ITERATIONS,
+            };
+            if let Some(res) = cancel
+                .run_until_cancelled(bench_latency(
+                    static_context().await,
+                    config
+                        .mission_color_profile(center_config.color_profile.as_deref())?
+                        .color(center_config.color),
+                    iterations,
+                    center_config,
+                ))
+                .await
+            {
+                res?;
+            }
+            Ok(())
+        }
     }
-    PIPELINE_KILL.write().unwrap().1 = false;
-
-    res
 }
@@ -1,8 +1,15 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 
 use core::fmt::Debug;
-use std::{marker::PhantomData, sync::Arc, thread};
-use tokio::{join, runtime::Handle, sync::Mutex};
+use std::{marker::PhantomData, sync::Arc};
+use tokio::{
+    join,
+    runtime::Handle,
+    sync::Mutex,
+    task::spawn_blocking,
+    time::{sleep, timeout, Duration},
+};
+use tokio_util::sync::CancellationToken;
 
 /**
  * A trait for an action that can be executed.
@@ -314,6 +321,52 @@ impl<T: Send + Sync, X: Send + Sync, V: ActionMod<X>, W: Action> ActionMod<X>
     }
 }
 
+/**
+ * Runs a runtime-sized list of actions in order, like a nested [`ActionSequence`].
+ *
+ * Outputs every action's result, in execution order.
+ */
+#[derive(Debug, Clone)]
+pub struct ActionVecSequence<U, T> {
+    actions: Vec<T>,
+    _phantom_u: PhantomData<U>,
+}
+
+impl<U, T: Action> Action for ActionVecSequence<U, T> {}
+
+impl<U, T> ActionVecSequence<U, T> {
+    pub const fn new(actions: Vec<T>) -> Self {
+        Self {
+            actions,
+            _phantom_u: PhantomData,
+        }
+    }
+}
+
+impl<U: Send + Sync, T: ActionExec<U>> ActionExec<Vec<U>> for ActionVecSequence<U, T> {
+    async fn execute(&mut self) -> Vec<U> {
+        let mut outputs = Vec::with_capacity(self.actions.len());
+        for action in &mut self.actions {
+            outputs.push(action.execute().await);
+        }
+        outputs
+    }
+}
+
+impl<Input: Send + Sync, U, T: ActionMod<Input>> ActionMod<Input> for ActionVecSequence<U, T> {
+    fn modify(&mut self, input: &Input) {
+        if let Some(first) = self.actions.first_mut() {
+            first.modify(input)
+        }
+    }
+}
+
+/**
+ * Runs two actions at once, each on its own thread
+ *
+ * Dropping the execution, such as from [`Cancellable`], cancels both threads
+ * at their next await point.
+ */
 #[derive(Debug, Clone)]
 pub struct ActionParallel<V: Action, W: Action> {
     first: Arc<Mutex<V>>,
@@ -343,15 +396,30 @@ impl<
         let second = self.second.clone();
         let handle1 = Handle::current();
         let handle2 = Handle::current();
+        // Threads outlive a dropped execution unless told to stop
+        let cancel = CancellationToken::new();
+        let cancel1 = cancel.clone();
+        let cancel2 = cancel.clone();
+        let _stop_on_drop = cancel.drop_guard();
 
         // https://docs.rs/tokio/1.33.0/tokio/runtime/struct.Handle.html#method.block_on
-        let fut1 = thread::spawn(move || {
-            handle1.block_on(async move { first.lock().await.execute().await })
+        let fut1 = spawn_blocking(move || {
+            handle1.block_on(
+                cancel1.run_until_cancelled(async move { first.lock().await.execute().await }),
+            )
         });
-        let fut2 = thread::spawn(move || {
-            handle2.block_on(async move { second.lock().await.execute().await })
+        let fut2 = spawn_blocking(move || {
+            handle2.block_on(
+                cancel2.run_until_cancelled(async move { second.lock().await.execute().await }),
+            )
         });
-        (fut1.join().unwrap(), fut2.join().unwrap())
+        // Awaited rather than joined, so the caller can still be cancelled
+        let (res1, res2) = join!(fut1, fut2);
+        let finished = "Only cancelled when dropped";
+        (
+            res1.unwrap().expect(finished),
+            res2.unwrap().expect(finished),
+        )
     }
 }
 
@@ -450,6 +518,210 @@ impl<U: Send + Sync, T: ActionExec<Result<U>>> ActionExec<Result<U>> for ActionU
     }
 }
 
+impl<Input: Send + Sync, T: ActionMod<Input> + Sync + Send> ActionMod<Input> for ActionUntil<T> {
+    fn modify(&mut self, input: &Input) {
+        self.action.modify(input);
+    }
+}
+
+/// Longest pause [`ActionRetryWithBackoff`] waits between attempts by default
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/**
+ * An action that tries `limit` times for a success, waiting longer after each failure
+ *
+ * Waits `base * 2^(attempt - 1)` after a failed attempt, up to `max_delay`,
+ * so transient errors don't hammer the control board or vision pipeline.
+ */
+#[derive(Debug, Clone)]
+pub struct ActionRetryWithBackoff<T: Action> {
+    action: T,
+    limit: u32,
+    base: Duration,
+    max_delay: Duration,
+}
+
+impl<T: Action> Action for ActionRetryWithBackoff<T> {}
+
+impl<T: Action> ActionRetryWithBackoff<T> {
+    pub const fn new(action: T, limit: u32, base: Duration) -> Self {
+        Self {
+            action,
+            limit,
+            base,
+            max_delay: DEFAULT_MAX_BACKOFF,
+        }
+    }
+
+    pub fn with_max_delay(self, max_delay: Duration) -> Self {
+        Self { max_delay, ..self }
+    }
+
+    /// Pause after failed attempt number `attempt`, starting from 1
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base
+            .checked_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+impl<U: Send + Sync, T: ActionExec<Result<U>>> ActionExec<Result<U>> for ActionRetryWithBackoff<T> {
+    async fn execute(&mut self) -> Result<U> {
+        let mut attempt = 1;
+        let mut result = self.action.execute().await;
+        while result.is_err() && attempt < self.limit {
+            sleep(self.delay(attempt)).await;
+            result = self.action.execute().await;
+            attempt += 1;
+        }
+        result
+    }
+}
+
+impl<Input: Send + Sync, T: ActionMod<Input> + Sync + Send> ActionMod<Input>
+    for ActionRetryWithBackoff<T>
+{
+    fn modify(&mut self, input: &Input) {
+        self.action.modify(input);
+    }
+}
+
+/**
+ * An action that gives up on its inner action after `limit`
+ *
+ * Returns `None` on timeout, so missions can fall back instead of waiting on
+ * a detection that never comes.
+ */
+#[derive(Debug, Clone)]
+pub struct ActionTimeout<T: Action> {
+    action: T,
+    limit: Duration,
+}
+
+impl<T: Action> Action for ActionTimeout<T> {}
+
+impl<T: Action> ActionTimeout<T> {
+    pub const fn new(action: T, limit: Duration) -> Self {
+        Self { action, limit }
+    }
+}
+
+impl<U: Send + Sync, T: ActionExec<U>> ActionExec<Option<U>> for ActionTimeout<T> {
+    async fn execute(&mut self) -> Option<U> {
+        timeout(self.limit, self.action.execute()).await.ok()
+    }
+}
+
+impl<Input: Send + Sync, T: ActionMod<Input> + Sync + Send> ActionMod<Input> for ActionTimeout<T> {
+    fn modify(&mut self, input: &Input) {
+        self.action.modify(input);
+    }
+}
+
+/**
+ * An action that stops its inner action when `token` is cancelled
+ *
+ * Returns `None` when cancelled. The inner execution is dropped, which stops
+ * the children of every combinator in this module.
+ */
+#[derive(Debug, Clone)]
+pub struct Cancellable<T: Action> {
+    action: T,
+    token: CancellationToken,
+}
+
+impl<T: Action> Action for Cancellable<T> {}
+
+impl<T: Action> Cancellable<T> {
+    pub const fn new(action: T, token: CancellationToken) -> Self {
+        Self { action, token }
+    }
+}
+
+impl<U: Send + Sync, T: ActionExec<U>> ActionExec<Option<U>> for Cancellable<T> {
+    async fn execute(&mut self) -> Option<U> {
+        self.token.run_until_cancelled(self.action.execute()).await
+    }
+}
+
+impl<Input: Send + Sync, T: ActionMod<Input> + Sync + Send> ActionMod<Input> for Cancellable<T> {
+    fn modify(&mut self, input: &Input) {
+        self.action.modify(input);
+    }
+}
+
+/**
+ * An [`ActionTimeout`] that fails on timeout, so [`ActionUntil`] can retry it
+ */
+#[derive(Debug, Clone)]
+struct TimedAttempt<T: Action> {
+    action: ActionTimeout<T>,
+}
+
+impl<T: Action> Action for TimedAttempt<T> {}
+
+impl<U: Send + Sync, T: ActionExec<Result<U>>> ActionExec<Result<U>> for TimedAttempt<T> {
+    async fn execute(&mut self) -> Result<U> {
+        self.action
+            .execute()
+            .await
+            .unwrap_or_else(|| Err(anyhow!("Timed out after {:?}", self.action.limit)))
+    }
+}
+
+impl<Input: Send + Sync, T: ActionMod<Input> + Sync + Send> ActionMod<Input> for TimedAttempt<T> {
+    fn modify(&mut self, input: &Input) {
+        self.action.modify(input);
+    }
+}
+
+/**
+ * Gives each attempt of its inner action `limit` to finish, retrying on failure or timeout
+ *
+ * This is an [`ActionUntil`] over an [`ActionTimeout`] per attempt, with up
+ * to `retries` more attempts. Exhausting them gives the last error, noting the count.
+ */
+#[derive(Debug, Clone)]
+pub struct ActionRobust<T: Action> {
+    action: ActionUntil<TimedAttempt<T>>,
+}
+
+impl<T: Action> Action for ActionRobust<T> {}
+
+impl<T: Action> ActionRobust<T> {
+    pub const fn new(action: T, retries: u32, limit: Duration) -> Self {
+        Self {
+            action: ActionUntil::new(
+                TimedAttempt {
+                    action: ActionTimeout::new(action, limit),
+                },
+                retries.saturating_add(1),
+            ),
+        }
+    }
+}
+
+/// Wraps `action` so each attempt times out after `limit`, retrying up to `retries` times
+pub const fn robust<T: Action>(action: T, retries: u32, limit: Duration) -> ActionRobust<T> {
+    ActionRobust::new(action, retries, limit)
+}
+
+impl<U: Send + Sync, T: ActionExec<Result<U>>> ActionExec<Result<U>> for ActionRobust<T> {
+    async fn execute(&mut self) -> Result<U> {
+        let attempts = self.action.limit;
+        self.action
+            .execute()
+            .await
+            .with_context(|| format!("Failed after {attempts} attempts"))
+    }
+}
+
+impl<Input: Send + Sync, T: ActionMod<Input> + Sync + Send> ActionMod<Input> for ActionRobust<T> {
+    fn modify(&mut self, input: &Input) {
+        self.action.modify(input);
+    }
+}
+
 /**
  * An action that runs while true
  */
@@ -598,3 +870,276 @@ impl<Input: Send + Sync, V: ActionMod<Input> + Sync + Send, W: ActionMod<Input>
         self.second.modify(input);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use anyhow::bail;
+    use tokio::time::Instant;
+
+    use super::*;
+
+    /// Fails until its `succeed_on`th execution
+    struct Flaky {
+        attempts: u32,
+        succeed_on: u32,
+        modified: Option<u32>,
+    }
+
+    impl Action for Flaky {}
+
+    impl ActionExec<Result<u32>> for Flaky {
+        async fn execute(&mut self) -> Result<u32> {
+            self.attempts += 1;
+            if self.attempts < self.succeed_on {
+                bail!("Attempt {} failed", self.attempts);
+            }
+            Ok(self.attempts)
+        }
+    }
+
+    impl ActionMod<u32> for Flaky {
+        fn modify(&mut self, input: &u32) {
+            self.modified = Some(*input);
+        }
+    }
+
+    const fn flaky(succeed_on: u32) -> Flaky {
+        Flaky {
+            attempts: 0,
+            succeed_on,
+            modified: None,
+        }
+    }
+
+    /// Records its id into a shared log when run
+    struct Counting {
+        id: u32,
+        log: Arc<std::sync::Mutex<Vec<u32>>>,
+    }
+
+    impl Action for Counting {}
+
+    impl ActionExec<u32> for Counting {
+        async fn execute(&mut self) -> u32 {
+            let mut log = self.log.lock().unwrap();
+            log.push(self.id);
+            log.len() as u32
+        }
+    }
+
+    #[tokio::test]
+    async fn vec_sequence_runs_in_order() {
+        let log = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut sequence = ActionVecSequence::new(
+            [3, 1, 2]
+                .into_iter()
+                .map(|id| Counting {
+                    id,
+                    log: log.clone(),
+                })
+                .collect(),
+        );
+
+        assert_eq!(sequence.execute().await, [1, 2, 3]);
+        assert_eq!(*log.lock().unwrap(), [3, 1, 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn backoff_between_attempts() {
+        let base = Duration::from_millis(100);
+        let mut retry = ActionRetryWithBackoff::new(flaky(3), 5, base);
+        retry.modify(&7);
+        assert_eq!(retry.action.modified, Some(7));
+
+        let start = Instant::now();
+        assert_eq!(retry.execute().await.unwrap(), 3);
+        // 100ms after the first failure, 200ms after the second
+        assert!(start.elapsed() >= Duration::from_millis(300));
+
+        // Out of attempts gives the last error
+        let mut retry = ActionRetryWithBackoff::new(flaky(10), 3, base);
+        let err = retry.execute().await.unwrap_err();
+        assert_eq!(err.to_string(), "Attempt 3 failed");
+        assert_eq!(retry.action.attempts, 3);
+    }
+
+    /// Finishes after `delay`
+    struct Slow {
+        delay: Duration,
+    }
+
+    impl Action for Slow {}
+
+    impl ActionExec<u32> for Slow {
+        async fn execute(&mut self) -> u32 {
+            sleep(self.delay).await;
+            1
+        }
+    }
+
+    impl ActionMod<Duration> for Slow {
+        fn modify(&mut self, input: &Duration) {
+            self.delay = *input;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_gives_up() {
+        let mut action = ActionTimeout::new(
+            Slow {
+                delay: Duration::from_secs(10),
+            },
+            Duration::from_secs(1),
+        );
+        let start = Instant::now();
+        assert_eq!(action.execute().await, None);
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+
+        action.modify(&Duration::from_millis(500));
+        assert_eq!(action.execute().await, Some(1));
+    }
+
+    /// Stalls for its first `stalls` executions, then succeeds
+    struct Stalls {
+        attempts: u32,
+        stalls: u32,
+    }
+
+    impl Action for Stalls {}
+
+    impl ActionExec<Result<u32>> for Stalls {
+        async fn execute(&mut self) -> Result<u32> {
+            self.attempts += 1;
+            if self.attempts <= self.stalls {
+                sleep(Duration::from_secs(10)).await;
+            }
+            Ok(self.attempts)
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn robust_retries_timeouts() {
+        let limit = Duration::from_secs(1);
+        let start = Instant::now();
+        let mut action = robust(
+            Stalls {
+                attempts: 0,
+                stalls: 2,
+            },
+            3,
+            limit,
+        );
+        assert_eq!(action.execute().await.unwrap(), 3);
+        assert_eq!(start.elapsed(), 2 * limit);
+
+        let mut action = robust(
+            Stalls {
+                attempts: 0,
+                stalls: 10,
+            },
+            1,
+            limit,
+        );
+        let err = action.execute().await.unwrap_err();
+        assert_eq!(err.to_string(), "Failed after 2 attempts");
+        assert_eq!(err.root_cause().to_string(), "Timed out after 1s");
+
+        // Errors are retried as well
+        assert_eq!(robust(flaky(2), 1, limit).execute().await.unwrap(), 2);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let retry = ActionRetryWithBackoff::new(flaky(1), 40, Duration::from_millis(100))
+            .with_max_delay(Duration::from_secs(1));
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(4), Duration::from_millis(800));
+        assert_eq!(retry.delay(5), Duration::from_secs(1));
+        assert_eq!(retry.delay(40), Duration::from_secs(1));
+    }
+
+    const TICK: Duration = Duration::from_millis(10);
+
+    /// Counts ticks into a shared total, never finishing on its own
+    struct Ticking(Arc<AtomicU32>);
+
+    impl Action for Ticking {}
+
+    impl ActionExec<u32> for Ticking {
+        async fn execute(&mut self) -> u32 {
+            loop {
+                sleep(TICK).await;
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Cancels `action` after a few ticks, then checks `ticks` stops counting
+    async fn assert_stops<U: Send + Sync>(action: impl ActionExec<U>, ticks: &AtomicU32) {
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            sleep(TICK * 5).await;
+            canceller.cancel();
+        });
+        assert!(Cancellable::new(action, token).execute().await.is_none());
+
+        // Let children mid-tick settle
+        sleep(TICK * 2).await;
+        let stopped = ticks.load(Ordering::SeqCst);
+        assert!(stopped > 0, "Children never ran");
+        sleep(TICK * 10).await;
+        assert_eq!(
+            ticks.load(Ordering::SeqCst),
+            stopped,
+            "Children still running"
+        );
+    }
+
+    fn ticking_pair() -> (Arc<AtomicU32>, Ticking, Ticking) {
+        let ticks = Arc::new(AtomicU32::new(0));
+        (ticks.clone(), Ticking(ticks.clone()), Ticking(ticks))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_stops_race() {
+        let (ticks, first, second) = ticking_pair();
+        assert_stops(RaceAction::new(first, second), &ticks).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_stops_select() {
+        let (ticks, first, second) = ticking_pair();
+        assert_stops(ActionSelect::new(first, second), &ticks).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_stops_concurrent() {
+        let (ticks, first, second) = ticking_pair();
+        assert_stops(ActionConcurrent::new(first, second), &ticks).await;
+
+        let (ticks, first, second) = ticking_pair();
+        assert_stops(DualAction::new(first, second), &ticks).await;
+    }
+
+    // Real time, the paused clock does not advance while blocking threads run
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cancel_stops_parallel_threads() {
+        let (ticks, first, second) = ticking_pair();
+        assert_stops(ActionParallel::new(first, second), &ticks).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn uncancelled_finishes() {
+        let mut action = Cancellable::new(
+            Slow {
+                delay: Duration::from_secs(1),
+            },
+            CancellationToken::new(),
+        );
+        action.modify(&Duration::from_millis(10));
+        assert_eq!(action.execute().await, Some(1));
+    }
+}
@@ -2,12 +2,67 @@ use core::fmt::Debug;
 use opencv::core::Mat;
 #[cfg(feature = "annotated_streams")]
 use opencv::mod_prelude::ToInputArray;
-use tokio::io::{AsyncWriteExt, WriteHalf};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncWriteExt, WriteHalf},
+    sync::Mutex,
+};
 use tokio_serial::SerialStream;
 
-use crate::comms::{control_board::ControlBoard, meb::MainElectronicsBoard, zed_ros2::ZedRos2};
+use super::budget::RunBudget;
+use crate::comms::{
+    control_board::{util::Degrees, ControlBoard},
+    meb::MainElectronicsBoard,
+    zed_ros2::ZedRos2,
+};
+pub use crate::config::CameraSelect;
+use crate::log_warn;
 use crate::video_source::appsink::Camera;
+use crate::video_source::reconnecting::ReconnectingSource;
 use crate::video_source::MatSource;
+use crate::vision::image_prep::FrameRotation;
+
+/// Rotation applied to every bottom camera frame, see [`set_bottom_cam_rotation`]
+static BOTTOM_CAM_ROTATION: RwLock<FrameRotation> = RwLock::new(FrameRotation::None);
+
+/// Sets how bottom camera frames are rotated before detection, so angle
+/// math does not depend on how the camera is mounted
+pub fn set_bottom_cam_rotation(rotation: FrameRotation) {
+    *BOTTOM_CAM_ROTATION.write().unwrap() = rotation;
+}
+
+/// Heading shared by every stage of a run, see [`tare_yaw`]
+static YAW_TARE: RwLock<Option<f32>> = RwLock::new(None);
+
+/// Records `yaw` as the reference heading for the rest of the run
+///
+/// Stages that read their own starting yaw pick up whatever drift came
+/// before them, stages using [`relative_yaw`] all share this frame.
+pub fn tare_yaw(yaw: f32) {
+    *YAW_TARE.write().unwrap() = Some(yaw);
+}
+
+/// Heading recorded by [`tare_yaw`], if any
+pub fn yaw_tare() -> Option<f32> {
+    *YAW_TARE.read().unwrap()
+}
+
+/// Absolute heading `target` degrees from the tared yaw, or `target` itself
+/// when nothing is tared
+pub fn relative_yaw(target: f32) -> f32 {
+    offset_yaw(yaw_tare(), target)
+}
+
+fn offset_yaw(tare: Option<f32>, target: f32) -> f32 {
+    (Degrees::new(tare.unwrap_or(0.0)) + Degrees::new(target)).into()
+}
 /**
  * Inherit this trait if you have a control board
  */
@@ -29,6 +84,13 @@ pub trait GetZedRos2: Send + Sync {
     fn get_zed_ros2(&self) -> &ZedRos2;
 }
 
+/**
+ * Inherit this trait if you track the run deadline
+ */
+pub trait GetRunBudget: Send + Sync {
+    fn time_remaining(&self) -> Duration;
+}
+
 /**
  * Inherit this trait if you have a front camera
  */
@@ -49,6 +111,133 @@ pub trait BottomCamIO {
     async fn annotate_bottom_camera(&self, image: &impl ToInputArray);
 }
 
+/// Frames from whichever camera was selected at runtime
+#[derive(Debug)]
+pub struct SelectedCamera<'a, T> {
+    context: &'a T,
+    camera: CameraSelect,
+}
+
+impl<'a, T> SelectedCamera<'a, T> {
+    pub const fn new(context: &'a T, camera: CameraSelect) -> Self {
+        Self { context, camera }
+    }
+
+    pub const fn camera(&self) -> CameraSelect {
+        self.camera
+    }
+}
+
+impl<T: FrontCamIO + BottomCamIO> SelectedCamera<'_, T> {
+    #[cfg(feature = "annotated_streams")]
+    pub async fn annotate(&self, image: &impl ToInputArray) {
+        match self.camera {
+            CameraSelect::Front => self.context.annotate_front_camera(image).await,
+            CameraSelect::Bottom => self.context.annotate_bottom_camera(image).await,
+        }
+    }
+}
+
+impl<T: FrontCamIO + BottomCamIO + Send + Sync> MatSource for SelectedCamera<'_, T> {
+    async fn get_mat(&self) -> Mat {
+        match self.camera {
+            CameraSelect::Front => self.context.get_front_camera_mat().await,
+            CameraSelect::Bottom => self.context.get_bottom_camera_mat().await,
+        }
+    }
+}
+
+/**
+ * Inherit this trait to pick a camera at runtime
+ */
+pub trait SelectCamera: FrontCamIO + BottomCamIO + Sized {
+    fn selected_camera(&self, camera: CameraSelect) -> SelectedCamera<'_, Self> {
+        SelectedCamera::new(self, camera)
+    }
+}
+
+impl<T: FrontCamIO + BottomCamIO> SelectCamera for T {}
+
+/// One camera's frame, kept until the tick it was captured on is over
+#[derive(Debug, Default)]
+struct CachedFrame {
+    frame: Mutex<Option<(u64, Mat)>>,
+}
+
+impl CachedFrame {
+    /// Frame captured on `tick`, calling `capture` if there is none yet
+    async fn get<F: Future<Output = Mat>>(&self, tick: u64, capture: impl FnOnce() -> F) -> Mat {
+        // Held across the capture so detectors racing for a frame share one
+        let mut cached = self.frame.lock().await;
+        match &*cached {
+            Some((cached_tick, frame)) if *cached_tick == tick => frame.clone(),
+            _ => {
+                let frame = capture().await;
+                *cached = Some((tick, frame.clone()));
+                frame
+            }
+        }
+    }
+}
+
+/// Camera frames shared by every detector within one control iteration
+///
+/// Detectors built on this instead of the context see the same frame per
+/// camera until [`SharedFrames::next_tick`], rather than each capturing
+/// their own.
+#[derive(Debug)]
+pub struct SharedFrames<'a, T> {
+    context: &'a T,
+    tick: AtomicU64,
+    front: CachedFrame,
+    bottom: CachedFrame,
+}
+
+impl<'a, T> SharedFrames<'a, T> {
+    pub fn new(context: &'a T) -> Self {
+        Self {
+            context,
+            tick: AtomicU64::new(0),
+            front: CachedFrame::default(),
+            bottom: CachedFrame::default(),
+        }
+    }
+
+    /// Starts a new control iteration, so the next read of each camera
+    /// captures a fresh frame
+    pub fn next_tick(&self) {
+        self.tick.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn tick(&self) -> u64 {
+        self.tick.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: FrontCamIO + Sync> FrontCamIO for SharedFrames<'_, T> {
+    async fn get_front_camera_mat(&self) -> Mat {
+        self.front
+            .get(self.tick(), || self.context.get_front_camera_mat())
+            .await
+    }
+    #[cfg(feature = "annotated_streams")]
+    async fn annotate_front_camera(&self, image: &impl ToInputArray) {
+        self.context.annotate_front_camera(image).await;
+    }
+}
+
+impl<T: BottomCamIO + Sync> BottomCamIO for SharedFrames<'_, T> {
+    async fn get_bottom_camera_mat(&self) -> Mat {
+        self.bottom
+            .get(self.tick(), || self.context.get_bottom_camera_mat())
+            .await
+    }
+    #[cfg(feature = "annotated_streams")]
+    async fn annotate_bottom_camera(&self, image: &impl ToInputArray) {
+        self.context.annotate_bottom_camera(image).await;
+    }
+}
+
 #[derive(Debug)]
 pub struct EmptyActionContext;
 
@@ -56,21 +245,56 @@ impl Unpin for EmptyActionContext {
     // add code here
 }
 
+/// Subsystems a [`FullActionContext`] is wired to, one per field
+#[derive(Debug, PartialEq)]
+pub struct ContextParts<B, M, C, Z, R> {
+    pub control_board: B,
+    pub main_electronics_board: M,
+    pub front_cam: C,
+    pub bottom_cam: C,
+    pub zed_ros2: Z,
+    pub run_budget: R,
+}
+
+impl<B, M, C, Z, R> ContextParts<B, M, C, Z, R> {
+    /// Resolves every subsystem from its getter in turn, e.g. lazily
+    /// initialized cells
+    pub async fn resolve(
+        control_board: impl Future<Output = B>,
+        main_electronics_board: impl Future<Output = M>,
+        front_cam: impl Future<Output = C>,
+        bottom_cam: impl Future<Output = C>,
+        zed_ros2: impl Future<Output = Z>,
+        run_budget: impl Future<Output = R>,
+    ) -> Self {
+        Self {
+            control_board: control_board.await,
+            main_electronics_board: main_electronics_board.await,
+            front_cam: front_cam.await,
+            bottom_cam: bottom_cam.await,
+            zed_ros2: zed_ros2.await,
+            run_budget: run_budget.await,
+        }
+    }
+}
+
 pub struct FullActionContext<'a, T: AsyncWriteExt + Unpin + Send> {
     control_board: &'a ControlBoard<T>,
     main_electronics_board: &'a MainElectronicsBoard<WriteHalf<SerialStream>>,
-    front_cam: &'a Camera,
-    bottom_cam: &'a Camera,
+    front_cam: &'a ReconnectingSource<Camera>,
+    bottom_cam: &'a ReconnectingSource<Camera>,
     zed_ros2: &'a ZedRos2,
+    run_budget: &'a RunBudget,
 }
 
 impl<'a, T: AsyncWriteExt + Unpin + Send> FullActionContext<'a, T> {
     pub const fn new(
         control_board: &'a ControlBoard<T>,
         main_electronics_board: &'a MainElectronicsBoard<WriteHalf<SerialStream>>,
-        front_cam: &'a Camera,
-        bottom_cam: &'a Camera,
+        front_cam: &'a ReconnectingSource<Camera>,
+        bottom_cam: &'a ReconnectingSource<Camera>,
         zed_ros2: &'a ZedRos2,
+        run_budget: &'a RunBudget,
     ) -> Self {
         Self {
             control_board,
@@ -78,10 +302,42 @@ impl<'a, T: AsyncWriteExt + Unpin + Send> FullActionContext<'a, T> {
             front_cam,
             bottom_cam,
             zed_ros2,
+            run_budget,
         }
     }
 }
 
+impl<'a, T: AsyncWriteExt + Unpin + Send>
+    From<
+        ContextParts<
+            &'a ControlBoard<T>,
+            &'a MainElectronicsBoard<WriteHalf<SerialStream>>,
+            &'a ReconnectingSource<Camera>,
+            &'a ZedRos2,
+            &'a RunBudget,
+        >,
+    > for FullActionContext<'a, T>
+{
+    fn from(
+        parts: ContextParts<
+            &'a ControlBoard<T>,
+            &'a MainElectronicsBoard<WriteHalf<SerialStream>>,
+            &'a ReconnectingSource<Camera>,
+            &'a ZedRos2,
+            &'a RunBudget,
+        >,
+    ) -> Self {
+        Self::new(
+            parts.control_board,
+            parts.main_electronics_board,
+            parts.front_cam,
+            parts.bottom_cam,
+            parts.zed_ros2,
+            parts.run_budget,
+        )
+    }
+}
+
 impl GetControlBoard<WriteHalf<SerialStream>> for FullActionContext<'_, WriteHalf<SerialStream>> {
     fn get_control_board(&self) -> &ControlBoard<WriteHalf<SerialStream>> {
         self.control_board
@@ -100,23 +356,34 @@ impl GetZedRos2 for FullActionContext<'_, WriteHalf<SerialStream>> {
     }
 }
 
+impl GetRunBudget for FullActionContext<'_, WriteHalf<SerialStream>> {
+    fn time_remaining(&self) -> Duration {
+        self.run_budget.time_remaining()
+    }
+}
+
 impl<T: AsyncWriteExt + Unpin + Send> FrontCamIO for FullActionContext<'_, T> {
     async fn get_front_camera_mat(&self) -> Mat {
         self.front_cam.get_mat().await
     }
     #[cfg(feature = "annotated_streams")]
     async fn annotate_front_camera(&self, image: &impl ToInputArray) {
-        self.front_cam.push_annotated_frame(image);
+        self.front_cam.push_annotated_frame(image).await;
     }
 }
 
 impl<T: AsyncWriteExt + Unpin + Send> BottomCamIO for FullActionContext<'_, T> {
     async fn get_bottom_camera_mat(&self) -> Mat {
-        self.bottom_cam.get_mat().await
+        let frame = self.bottom_cam.get_mat().await;
+        let rotation = *BOTTOM_CAM_ROTATION.read().unwrap();
+        rotation.apply(&frame).unwrap_or_else(|e| {
+            log_warn!("Failed to rotate bottom camera frame: {e:#}");
+            frame
+        })
     }
     #[cfg(feature = "annotated_streams")]
     async fn annotate_bottom_camera(&self, image: &impl ToInputArray) {
-        self.bottom_cam.push_annotated_frame(image);
+        self.bottom_cam.push_annotated_frame(image).await;
     }
 }
 
@@ -132,6 +399,13 @@ impl GetMainElectronicsBoard for EmptyActionContext {
     }
 }
 
+/// No deadline is tracked, so time never runs out
+impl GetRunBudget for EmptyActionContext {
+    fn time_remaining(&self) -> Duration {
+        Duration::MAX
+    }
+}
+
 impl FrontCamIO for EmptyActionContext {
     async fn get_front_camera_mat(&self) -> Mat {
         todo!()
@@ -151,3 +425,147 @@ impl BottomCamIO for EmptyActionContext {
         todo!();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use opencv::core::{MatTraitConst, Scalar, CV_8UC1};
+
+    use super::*;
+    use crate::test_util::Calls;
+
+    /// Front frames are all 1, bottom frames are all 2
+    struct MockCameras;
+
+    fn filled(value: f64) -> Mat {
+        Mat::new_rows_cols_with_default(1, 1, CV_8UC1, Scalar::all(value)).unwrap()
+    }
+
+    impl FrontCamIO for MockCameras {
+        async fn get_front_camera_mat(&self) -> Mat {
+            filled(1.0)
+        }
+        #[cfg(feature = "annotated_streams")]
+        async fn annotate_front_camera(&self, _image: &impl ToInputArray) {}
+    }
+
+    impl BottomCamIO for MockCameras {
+        async fn get_bottom_camera_mat(&self) -> Mat {
+            filled(2.0)
+        }
+        #[cfg(feature = "annotated_streams")]
+        async fn annotate_bottom_camera(&self, _image: &impl ToInputArray) {}
+    }
+
+    #[test]
+    fn offset_yaw_from_tare() {
+        // Untared targets are absolute
+        assert_eq!(offset_yaw(None, 20.0), 20.0);
+
+        // Offsets wrap into [-180, 180)
+        assert_eq!(offset_yaw(Some(170.0), 20.0), -170.0);
+        assert_eq!(offset_yaw(Some(170.0), -30.0), 140.0);
+        assert_eq!(offset_yaw(Some(-178.0), -20.0), 162.0);
+
+        // Later stages start wherever the last one left the sub, so the same
+        // target from each stage's own start lands on a different heading
+        let own_start = [172.0, 165.0, -178.0].map(|start| offset_yaw(Some(start), 20.0));
+        assert_eq!(own_start, [-168.0, -175.0, -158.0]);
+    }
+
+    #[tokio::test]
+    async fn context_parts_wire_every_subsystem() {
+        let resolved = Calls::new();
+        let getter = |name: &'static str| {
+            let resolved = &resolved;
+            async move {
+                resolved.push(name);
+                name
+            }
+        };
+
+        let parts = ContextParts::resolve(
+            getter("control_board"),
+            getter("main_electronics_board"),
+            getter("front_cam"),
+            getter("bottom_cam"),
+            getter("zed_ros2"),
+            getter("run_budget"),
+        )
+        .await;
+        assert_eq!(
+            parts,
+            ContextParts {
+                control_board: "control_board",
+                main_electronics_board: "main_electronics_board",
+                front_cam: "front_cam",
+                bottom_cam: "bottom_cam",
+                zed_ros2: "zed_ros2",
+                run_budget: "run_budget",
+            }
+        );
+        // Each cell is resolved exactly once
+        assert_eq!(
+            resolved.values(),
+            [
+                "control_board",
+                "main_electronics_board",
+                "front_cam",
+                "bottom_cam",
+                "zed_ros2",
+                "run_budget"
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_context_has_no_deadline() {
+        assert_eq!(EmptyActionContext.time_remaining(), Duration::MAX);
+    }
+
+    #[tokio::test]
+    async fn selected_camera_frames() {
+        let context = MockCameras;
+
+        let front = context.selected_camera(CameraSelect::Front).get_mat().await;
+        assert_eq!(*front.at_2d::<u8>(0, 0).unwrap(), 1);
+
+        let bottom = context
+            .selected_camera(CameraSelect::Bottom)
+            .get_mat()
+            .await;
+        assert_eq!(*bottom.at_2d::<u8>(0, 0).unwrap(), 2);
+    }
+
+    /// Counts front captures, each frame filled with its capture number
+    #[derive(Default)]
+    struct CountingCamera {
+        captures: AtomicU64,
+    }
+
+    impl FrontCamIO for CountingCamera {
+        async fn get_front_camera_mat(&self) -> Mat {
+            let capture = self.captures.fetch_add(1, Ordering::Relaxed) + 1;
+            filled(capture as f64)
+        }
+        #[cfg(feature = "annotated_streams")]
+        async fn annotate_front_camera(&self, _image: &impl ToInputArray) {}
+    }
+
+    #[tokio::test]
+    async fn detectors_share_frame_within_tick() {
+        let camera = CountingCamera::default();
+        let frames = SharedFrames::new(&camera);
+
+        // Two detectors in one iteration
+        let (first, second) =
+            tokio::join!(frames.get_front_camera_mat(), frames.get_front_camera_mat());
+        assert_eq!(*first.at_2d::<u8>(0, 0).unwrap(), 1);
+        assert_eq!(*second.at_2d::<u8>(0, 0).unwrap(), 1);
+        assert_eq!(camera.captures.load(Ordering::Relaxed), 1);
+
+        frames.next_tick();
+        let next = frames.get_front_camera_mat().await;
+        assert_eq!(*next.at_2d::<u8>(0, 0).unwrap(), 2);
+        assert_eq!(camera.captures.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::Result;
+use tokio::{io::AsyncWriteExt, time::Instant};
+
+use crate::{comms::control_board::ControlBoard, config::approach, log_warn};
+
+use super::{
+    action::{Action, ActionExec},
+    basic::approach_forward,
+    pid::Pid,
+};
+
+/// Where [`CenterYaw`] sends its corrections
+#[allow(async_fn_in_trait)]
+pub trait YawCorrection: Send + Sync {
+    /// Yaws at `yaw_speed` to correct a target at normalized offset `x`
+    async fn correct(&self, x: f32, yaw_speed: f32) -> Result<()>;
+}
+
+/// Yaw corrections through [`ControlBoard::stability_1_speed_set`] at a fixed depth
+#[derive(Debug)]
+pub struct Stability1Yaw<'a, T: AsyncWriteExt + Unpin> {
+    cb: &'a ControlBoard<T>,
+    depth: f32,
+    approach: Option<approach::Config>,
+}
+
+impl<'a, T: AsyncWriteExt + Unpin> Stability1Yaw<'a, T> {
+    pub const fn new(cb: &'a ControlBoard<T>, depth: f32) -> Self {
+        Self {
+            cb,
+            depth,
+            approach: None,
+        }
+    }
+
+    /// Moves forward while correcting, see [`approach_forward`]
+    pub const fn with_approach(self, approach: Option<approach::Config>) -> Self {
+        Self { approach, ..self }
+    }
+}
+
+impl<T: AsyncWriteExt + Unpin + Send + Sync> YawCorrection for Stability1Yaw<'_, T> {
+    async fn correct(&self, x: f32, yaw_speed: f32) -> Result<()> {
+        let forward = approach_forward(self.approach, x, 0.0);
+        self.cb
+            .stability_1_speed_set(0.0, forward, yaw_speed, 0.0, 0.0, self.depth)
+            .await
+    }
+}
+
+/// Yaw corrections that bring a target to the horizontal center of the frame,
+/// for loops that already read their own frames.
+///
+/// Each [`Self::center_on`] call handles one frame's normalized target x, if
+/// seen. Returns true when the target is within `tolerance` of center,
+/// otherwise sends the yaw correction `pid` gives for `x` and returns false.
+/// Nothing is sent without a target, so callers keep their own search.
+/// Losing or centering the target resets `pid`.
+#[derive(Debug)]
+pub struct CenterYaw<C> {
+    correction: C,
+    pid: Pid,
+    tolerance: f32,
+    last_update: Option<Instant>,
+}
+
+impl<C> CenterYaw<C> {
+    pub const fn new(correction: C, pid: Pid, tolerance: f32) -> Self {
+        Self {
+            correction,
+            pid,
+            tolerance,
+            last_update: None,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.pid.reset();
+        self.last_update = None;
+    }
+
+    pub async fn center_on(&mut self, x: Option<f32>) -> bool
+    where
+        C: YawCorrection,
+    {
+        let Some(x) = x else {
+            self.reset();
+            return false;
+        };
+        if x.abs() < self.tolerance {
+            self.reset();
+            return true;
+        }
+
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .map_or(0.0, |last| (now - last).as_secs_f32());
+        self.last_update = Some(now);
+
+        let yaw_speed = self.pid.update(x, dt);
+        if let Err(e) = self.correction.correct(x, yaw_speed).await {
+            log_warn!("Yaw correction failed: {:#}", e);
+        }
+        false
+    }
+}
+
+/// [`CenterYaw`] as an action reading the target x from `vision` each execution
+#[derive(Debug)]
+pub struct YawToCenter<V, C> {
+    vision: V,
+    center: CenterYaw<C>,
+}
+
+impl<V, C> YawToCenter<V, C> {
+    pub const fn new(vision: V, correction: C, pid: Pid, tolerance: f32) -> Self {
+        Self {
+            vision,
+            center: CenterYaw::new(correction, pid, tolerance),
+        }
+    }
+}
+
+impl<V, C> Action for YawToCenter<V, C> {}
+
+impl<V: ActionExec<Option<f32>>, C: YawCorrection> ActionExec<bool> for YawToCenter<V, C> {
+    async fn execute(&mut self) -> bool {
+        let x = self.vision.execute().await;
+        self.center.center_on(x).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::config::pid::Config;
+
+    /// Target that moves halfway to center after each frame
+    struct Approaching(f32);
+
+    impl Action for Approaching {}
+
+    impl ActionExec<Option<f32>> for Approaching {
+        async fn execute(&mut self) -> Option<f32> {
+            let x = self.0;
+            self.0 *= 0.5;
+            Some(x)
+        }
+    }
+
+    impl YawCorrection for Mutex<Vec<f32>> {
+        async fn correct(&self, _x: f32, yaw_speed: f32) -> Result<()> {
+            self.lock().unwrap().push(yaw_speed);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn converges_to_centered() {
+        let pid = Pid::new(Config {
+            kp: 0.5,
+            ki: 0.0,
+            kd: 0.0,
+            max_integral: 0.0,
+        });
+        let mut align = YawToCenter::new(Approaching(0.8), Mutex::new(vec![]), pid, 0.2);
+
+        let mut frames = 0;
+        while !align.execute().await {
+            frames += 1;
+            assert!(frames < 10, "Never centered");
+        }
+
+        // 0.8, 0.4, and 0.2 are corrected, 0.1 is centered
+        assert_eq!(frames, 3);
+        assert_eq!(*align.center.correction.lock().unwrap(), [0.4, 0.2, 0.1]);
+
+        // A lost target is never centered and sends nothing
+        assert!(!align.center.center_on(None).await);
+        assert_eq!(align.center.correction.lock().unwrap().len(), 3);
+    }
+}
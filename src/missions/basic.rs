@@ -1,4 +1,8 @@
-use crate::logln;
+use crate::{
+    comms::control_board::{util::ThrusterSpeeds, ControlBoard, ANGLES_TIMEOUT},
+    config::approach,
+    log_error, log_warn, logln,
+};
 
 use super::{
     action::{Action, ActionChain, ActionExec, ActionSequence},
@@ -8,11 +12,18 @@ use super::{
     movement::{Descend, Stability2Movement, Stability2Pos, StraightMovement, ZeroMovement},
 };
 
+use anyhow::{bail, Result};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 use tokio::{
-    io::WriteHalf,
-    time::{sleep, Duration},
+    io::{AsyncWriteExt, WriteHalf},
+    time::{interval, sleep, timeout, Duration, Interval, MissedTickBehavior},
 };
 use tokio_serial::SerialStream;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Clone)]
 pub struct DelayAction {
@@ -35,6 +46,291 @@ impl DelayAction {
     }
 }
 
+/// Slowest rate accepted by [`loop_interval`]
+const MIN_LOOP_HZ: f64 = 0.1;
+/// Fastest rate accepted by [`loop_interval`]
+const MAX_LOOP_HZ: f64 = 1000.0;
+
+/// Interval that bounds a mission loop to at most `loop_hz` iterations per second.
+///
+/// Call `tick().await` at the top of the loop. Late ticks are delayed instead of
+/// bursting, so a slow vision frame does not cause back-to-back iterations.
+/// Rates outside `MIN_LOOP_HZ..=MAX_LOOP_HZ` are clamped, NaN to the slowest.
+pub fn loop_interval(loop_hz: f64) -> Interval {
+    let loop_hz = loop_hz.max(MIN_LOOP_HZ).min(MAX_LOOP_HZ);
+    let mut interval = interval(Duration::from_secs_f64(1.0 / loop_hz));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
+}
+
+/// Forward speed that slows as the target drifts off-center.
+///
+/// Computed as `speed * (1 - scale * |x|)`, never below `min_speed`, where `x`
+/// is the normalized horizontal offset of the target.
+pub fn proportional_forward(speed: f32, x: f32, scale: f32, min_speed: f32) -> f32 {
+    (speed * (1.0 - scale * x.abs())).max(min_speed)
+}
+
+/// [`proportional_forward`] for `approach`, `fixed` without one
+pub fn approach_forward(approach: Option<approach::Config>, x: f32, fixed: f32) -> f32 {
+    approach.map_or(fixed, |approach| {
+        proportional_forward(approach.speed, x, approach.scale, approach.min_speed)
+    })
+}
+
+/// Mean of the last few alignment errors, to smooth a proportional correction.
+///
+/// Unlike voting, this averages the error signal itself over frames.
+#[derive(Debug, Clone)]
+pub struct ErrorWindow {
+    errors: VecDeque<f32>,
+    len: usize,
+}
+
+impl ErrorWindow {
+    /// Averages over the last `len` errors, a `len` of 0 acts as 1
+    pub fn new(len: usize) -> Self {
+        let len = len.max(1);
+        Self {
+            errors: VecDeque::with_capacity(len),
+            len,
+        }
+    }
+
+    /// Records the latest error, returning the mean over the window
+    pub fn push(&mut self, error: f32) -> f32 {
+        if self.errors.len() == self.len {
+            self.errors.pop_front();
+        }
+        self.errors.push_back(error);
+        self.errors.iter().sum::<f32>() / self.errors.len() as f32
+    }
+
+    /// Forgets past errors, such as after losing the target
+    pub fn clear(&mut self) {
+        self.errors.clear();
+    }
+}
+
+/// Counts frames with a detection over the last few frames, to confirm a
+/// target before acting on it.
+///
+/// Several detections in one frame count once, so a single noisy frame can't
+/// confirm a target alone.
+#[derive(Debug, Clone)]
+pub struct DetectionConfirm {
+    seen: VecDeque<bool>,
+    frames: usize,
+    required: usize,
+}
+
+impl DetectionConfirm {
+    /// Needs `required` frames with a detection within the last `frames`
+    /// frames, a `frames` of 0 acts as 1
+    pub fn new(required: usize, frames: usize) -> Self {
+        let frames = frames.max(1);
+        Self {
+            seen: VecDeque::with_capacity(frames),
+            frames,
+            required,
+        }
+    }
+
+    /// Records the detections in the latest frame, returning if the target is confirmed
+    pub fn push(&mut self, count: usize) -> bool {
+        if self.seen.len() == self.frames {
+            self.seen.pop_front();
+        }
+        self.seen.push_back(count > 0);
+        self.seen.iter().filter(|seen| **seen).count() >= self.required
+    }
+}
+
+/// Consecutive vision errors before missions give up, see [`set_max_vision_errors`]
+static MAX_VISION_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets how many vision errors in a row missions tolerate, 0 for no limit
+pub fn set_max_vision_errors(max: usize) {
+    MAX_VISION_ERRORS.store(max, Ordering::Relaxed);
+}
+
+/// Counts consecutive vision errors, so a dead camera ends the mission
+/// instead of driving search patterns blind.
+#[derive(Debug, Clone)]
+pub struct VisionErrors {
+    consecutive: usize,
+    max: usize,
+}
+
+impl Default for VisionErrors {
+    /// Limited by [`set_max_vision_errors`]
+    fn default() -> Self {
+        Self::new(MAX_VISION_ERRORS.load(Ordering::Relaxed))
+    }
+}
+
+impl VisionErrors {
+    /// Gives up after `max` errors in a row, never with a `max` of 0
+    pub const fn new(max: usize) -> Self {
+        Self {
+            consecutive: 0,
+            max,
+        }
+    }
+
+    /// Detections from a vision result, empty for an error until `max`
+    /// errors in a row, then the error itself
+    pub fn check<T>(&mut self, detections: Result<Vec<T>>) -> Result<Vec<T>> {
+        match detections {
+            Ok(detections) => {
+                self.consecutive = 0;
+                Ok(detections)
+            }
+            Err(e) => {
+                self.consecutive += 1;
+                if self.max != 0 && self.consecutive >= self.max {
+                    return Err(e.context(format!("{} vision errors in a row", self.consecutive)));
+                }
+                log_warn!("Vision error: `{e}`\n\tUsing empty detection vec");
+                Ok(vec![])
+            }
+        }
+    }
+}
+
+impl VisionErrors {
+    /// [`Self::check`], stopping the thrusters with [`safe_stop`] before
+    /// returning the error once the mission should give up
+    pub async fn check_or_stop<T, W: AsyncWriteExt + Unpin>(
+        &mut self,
+        detections: Result<Vec<T>>,
+        cb: &ControlBoard<W>,
+    ) -> Result<Vec<T>> {
+        let ret = self.check(detections);
+        if let Err(e) = &ret {
+            log_warn!("Abandoning mission: {e:#}");
+            safe_stop(cb).await;
+        }
+        ret
+    }
+}
+
+/// Zeroes every thruster, for a mission abandoning the vehicle mid-run
+pub async fn safe_stop<T: AsyncWriteExt + Unpin>(cb: &ControlBoard<T>) {
+    if let Err(e) = cb.raw_speed_set(ThrusterSpeeds::ZERO).await {
+        log_error!("Failed to stop thrusters: {e:#}");
+    }
+}
+
+/// How long to wait for the control board to acknowledge a travel command
+const TRAVEL_COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Unacknowledged attempts allowed by [`send_stability_command`]
+const STABILITY_COMMAND_RETRIES: usize = 10;
+
+/// Sends `command` until the control board acknowledges it within
+/// [`TRAVEL_COMMAND_TIMEOUT`].
+///
+/// Errors after [`STABILITY_COMMAND_RETRIES`] timeouts instead of hanging on
+/// a board that never responds.
+pub async fn send_stability_command<F, Fut>(mut command: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    for _ in 0..STABILITY_COMMAND_RETRIES {
+        if let Ok(ret) = timeout(TRAVEL_COMMAND_TIMEOUT, command()).await {
+            return ret;
+        }
+    }
+    bail!(
+        "Control board not responding to stability command after {STABILITY_COMMAND_RETRIES} attempts"
+    )
+}
+
+/// Holds `heading` and `depth` while moving forward at `speed` for `duration`.
+///
+/// The command is sent with [`send_stability_command`]. Speed is left set
+/// when this returns.
+pub async fn travel<T: AsyncWriteExt + Unpin>(
+    cb: &ControlBoard<T>,
+    heading: f32,
+    depth: f32,
+    speed: f32,
+    duration: Duration,
+) -> Result<()> {
+    travel_with(
+        |x, y, pitch, roll, yaw, depth| cb.stability_2_speed_set(x, y, pitch, roll, yaw, depth),
+        heading,
+        depth,
+        speed,
+        duration,
+    )
+    .await
+}
+
+/// [`travel`] with [`ControlBoard::stability_2_speed_set`] abstracted out
+async fn travel_with<F, Fut>(
+    mut stability_2: F,
+    heading: f32,
+    depth: f32,
+    speed: f32,
+    duration: Duration,
+) -> Result<()>
+where
+    F: FnMut(f32, f32, f32, f32, f32, f32) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    send_stability_command(|| stability_2(0.0, speed, 0.0, 0.0, heading, depth)).await?;
+    sleep(duration).await;
+    Ok(())
+}
+
+/// Holds the current heading and depth for `duration`, or until cancelled.
+///
+/// Both are read once at the start.
+pub async fn hold_station<Con: GetControlBoard<WriteHalf<SerialStream>>>(
+    context: &Con,
+    duration: Duration,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let cb = context.get_control_board();
+    cb.bno055_periodic_read(true).await?;
+    cb.ms5837_periodic_read(true).await?;
+
+    let yaw = cb.wait_for_yaw(Some(ANGLES_TIMEOUT)).await?;
+    let depth = cb.wait_for_depth(Some(ANGLES_TIMEOUT)).await?;
+
+    #[cfg(feature = "logging")]
+    logln!("Holding station at yaw {yaw}, depth {depth} for {duration:?}");
+    hold_with(
+        |x, y, pitch, roll, yaw, depth| cb.stability_2_speed_set(x, y, pitch, roll, yaw, depth),
+        yaw,
+        depth,
+        duration,
+        cancel,
+    )
+    .await
+}
+
+/// [`hold_station`] with [`ControlBoard::stability_2_speed_set`] abstracted out
+async fn hold_with<F, Fut>(
+    stability_2: F,
+    yaw: f32,
+    depth: f32,
+    duration: Duration,
+    cancel: &CancellationToken,
+) -> Result<()>
+where
+    F: FnMut(f32, f32, f32, f32, f32, f32) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    cancel
+        .run_until_cancelled(travel_with(stability_2, yaw, depth, 0.0, duration))
+        .await
+        .unwrap_or(Ok(()))
+}
+
 /**
  *
  * descends and goes forward for a certain duration
@@ -110,3 +406,249 @@ where
         ),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::test_util::Calls;
+
+    #[test]
+    fn error_window_mean() {
+        let mut window = ErrorWindow::new(3);
+        let means = [0.75, 0.0, 0.75, -0.75, 1.5].map(|error| window.push(error));
+        // Fills up, then drops the oldest error
+        assert_eq!(means, [0.75, 0.375, 0.5, 0.0, 0.5]);
+
+        window.clear();
+        assert_eq!(window.push(-0.5), -0.5);
+        assert_eq!(ErrorWindow::new(0).push(0.25), 0.25);
+    }
+
+    #[test]
+    fn vision_errors_abort_when_consecutive() {
+        let failed = || Err::<Vec<()>, _>(anyhow::anyhow!("camera dead"));
+        let mut errors = VisionErrors::new(3);
+        assert!(errors.check(failed()).unwrap().is_empty());
+        assert!(errors.check(failed()).is_ok());
+        // A good frame resets the count
+        assert_eq!(errors.check(Ok(vec![()])).unwrap().len(), 1);
+        assert!(errors.check(failed()).is_ok());
+        assert!(errors.check(failed()).is_ok());
+        assert!(errors.check(failed()).is_err());
+
+        let mut unlimited = VisionErrors::new(0);
+        assert!((0..100).all(|_| unlimited.check(failed()).is_ok()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn travel_retries_then_holds() {
+        let sent = Calls::new();
+        let start = Instant::now();
+
+        travel_with(
+            |_, _, _, _, _, _| {
+                sent.push(());
+                // First command is never acknowledged
+                let acked = sent.len() > 1;
+                async move {
+                    if !acked {
+                        std::future::pending::<()>().await;
+                    }
+                    Ok(())
+                }
+            },
+            70.0,
+            -1.3,
+            0.5,
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            Instant::now() - start,
+            TRAVEL_COMMAND_TIMEOUT + Duration::from_secs(10)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn travel_command_error() {
+        let start = Instant::now();
+        let res = travel_with(
+            |_, _, _, _, _, _| async { anyhow::bail!("Serial write failed") },
+            70.0,
+            -1.3,
+            0.5,
+            Duration::from_secs(10),
+        )
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(Instant::now(), start);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stability_command_gives_up() {
+        let start = Instant::now();
+        let err = travel_with(
+            |_, _, _, _, _, _| std::future::pending::<Result<()>>(),
+            70.0,
+            -1.3,
+            0.5,
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err.to_string().contains("not responding"));
+        assert_eq!(
+            Instant::now() - start,
+            TRAVEL_COMMAND_TIMEOUT * STABILITY_COMMAND_RETRIES as u32
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hold_station_duration() {
+        let sent = Calls::new();
+        let start = Instant::now();
+
+        hold_with(
+            |x, y, pitch, roll, yaw, depth| sent.ok([x, y, pitch, roll, yaw, depth]),
+            45.0,
+            -1.2,
+            Duration::from_secs(5),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        // Station-keeping at the read heading and depth, held for the full duration
+        assert_eq!(
+            sent.timed(),
+            [(Duration::ZERO, [0.0, 0.0, 0.0, 0.0, 45.0, -1.2])]
+        );
+        assert_eq!(Instant::now() - start, Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hold_station_cancelled() {
+        let sent = Calls::new();
+        let cancel = CancellationToken::new();
+        let start = Instant::now();
+
+        let cancel_clone = cancel.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_secs(2)).await;
+            cancel_clone.cancel();
+        });
+
+        hold_with(
+            |x, y, pitch, roll, yaw, depth| sent.ok([x, y, pitch, roll, yaw, depth]),
+            45.0,
+            -1.2,
+            Duration::from_secs(5),
+            &cancel,
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(Instant::now() - start, Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn travel_commands_once_then_holds() {
+        let sent = Calls::new();
+        let start = Instant::now();
+
+        travel_with(
+            |x, y, pitch, roll, yaw, depth| sent.ok([x, y, pitch, roll, yaw, depth]),
+            70.0,
+            -1.3,
+            0.5,
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+
+        // Forward at the speed, holding heading and depth, commanded once
+        // then held for the full duration
+        assert_eq!(
+            sent.timed(),
+            [(Duration::ZERO, [0.0, 0.5, 0.0, 0.0, 70.0, -1.3])]
+        );
+        assert_eq!(Instant::now() - start, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn proportional_forward_scaling() {
+        // Full speed when centered, symmetric in offset
+        assert_eq!(proportional_forward(0.4, 0.0, 1.0, 0.05), 0.4);
+        assert_eq!(proportional_forward(0.4, 0.5, 1.0, 0.05), 0.2);
+        assert_eq!(proportional_forward(0.4, -0.5, 1.0, 0.05), 0.2);
+
+        // Clamped to the minimum when far off-center
+        assert_eq!(proportional_forward(0.4, 0.9, 1.0, 0.05), 0.05);
+        assert_eq!(proportional_forward(0.4, 1.0, 2.0, 0.05), 0.05);
+
+        // Zero scale keeps a constant speed
+        assert_eq!(proportional_forward(0.4, 1.0, 0.0, 0.05), 0.4);
+    }
+
+    #[test]
+    fn approach_forward_falls_back() {
+        let approach = approach::Config {
+            speed: 0.4,
+            scale: 1.0,
+            min_speed: 0.05,
+        };
+        assert_eq!(approach_forward(Some(approach), 0.5, 0.0), 0.2);
+        assert_eq!(approach_forward(None, 0.5, 0.1), 0.1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn loop_interval_spacing() {
+        let mut tick = loop_interval(10.0);
+        let mut times = vec![];
+        for _ in 0..4 {
+            tick.tick().await;
+            times.push(Instant::now());
+        }
+
+        for pair in times.windows(2) {
+            assert_eq!(pair[1] - pair[0], Duration::from_millis(100));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn loop_interval_slow_body() {
+        let mut tick = loop_interval(10.0);
+        tick.tick().await;
+        let start = Instant::now();
+
+        // Body overruns the period, next tick fires immediately
+        sleep(Duration::from_millis(250)).await;
+        tick.tick().await;
+        assert_eq!(Instant::now() - start, Duration::from_millis(250));
+
+        // Following tick is a full period later, not a catch-up burst
+        tick.tick().await;
+        assert_eq!(Instant::now() - start, Duration::from_millis(350));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn loop_interval_clamps_rate() {
+        for (loop_hz, period) in [
+            (f64::INFINITY, Duration::from_millis(1)),
+            (1e300, Duration::from_millis(1)),
+            (0.0, Duration::from_secs(10)),
+            (-5.0, Duration::from_secs(10)),
+            (f64::NAN, Duration::from_secs(10)),
+        ] {
+            let tick = loop_interval(loop_hz);
+            assert_eq!(tick.period(), period, "{loop_hz}");
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    future::{ready, Future},
+    ops::RangeInclusive,
+};
+
+use anyhow::{bail, Result};
+use opencv::core::{Mat, Size};
+use tokio::time::{Duration, Instant};
+
+use crate::{
+    config::center::Config,
+    logln,
+    vision::{color_blob::ColorBlob, RelPos, VisualDetector, Yuv},
+};
+
+use super::action_context::FrontCamIO;
+
+/// Iterations when the mission argument does not give a count
+pub const DEFAULT_ITERATIONS: u32 = 200;
+
+/// Timing of one frame through the pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySample {
+    /// Requesting the frame until it is returned
+    pub capture: Duration,
+    /// Running the detector on the frame
+    pub detect: Duration,
+    /// Turning the detections into a control command
+    pub command: Duration,
+}
+
+impl LatencySample {
+    /// Frame request to command, the glass-to-command latency
+    pub fn total(&self) -> Duration {
+        self.capture + self.detect + self.command
+    }
+}
+
+/// Distribution of total latency over a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    /// None without any samples
+    pub fn new(samples: &[LatencySample]) -> Option<Self> {
+        let mut totals: Vec<_> = samples.iter().map(LatencySample::total).collect();
+        totals.sort_unstable();
+        let count = totals.len();
+        // Nearest rank percentile
+        let percentile = |p: usize| totals[(count * p).div_ceil(100).max(1) - 1];
+
+        Some(Self {
+            count,
+            min: *totals.first()?,
+            mean: totals.iter().sum::<Duration>() / count as u32,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: *totals.last()?,
+        })
+    }
+}
+
+/// Measures glass-to-command latency on the front camera.
+///
+/// Uses the center mission's blob detector and strafe gain as the pipeline.
+/// The command is computed but never written, so the bench can run with the
+/// sub out of the water and excludes serial write time.
+pub async fn bench_latency<Con: FrontCamIO + Send + Sync>(
+    context: &Con,
+    color: &RangeInclusive<Yuv>,
+    iterations: u32,
+    config: &Config,
+) -> Result<LatencyStats> {
+    logln!("Benchmarking vision latency over {iterations} frames");
+
+    let mut model = ColorBlob::new(
+        color.clone(),
+        config.area_bounds.clone(),
+        config.contour_select,
+        Size::from((400, 300)),
+    );
+    let samples = bench_with(
+        iterations,
+        || context.get_front_camera_mat(),
+        |frame| ready(model.detect_frame(&frame)),
+        |detections| {
+            ready(
+                detections
+                    .into_iter()
+                    .find(|d| *d.class())
+                    .map(|d| config.gain * *d.position().offset().x() as f32),
+            )
+        },
+    )
+    .await?;
+
+    let Some(stats) = LatencyStats::new(&samples) else {
+        bail!("No latency samples collected");
+    };
+    logln!("Vision latency: {stats:#?}");
+    Ok(stats)
+}
+
+/// [`bench_latency`] with the pipeline stages abstracted out
+async fn bench_with<F, FFut, D, DFut, Det, C, CFut>(
+    iterations: u32,
+    mut get_frame: F,
+    mut detect: D,
+    mut command: C,
+) -> Result<Vec<LatencySample>>
+where
+    F: FnMut() -> FFut,
+    FFut: Future<Output = Mat>,
+    D: FnMut(Mat) -> DFut,
+    DFut: Future<Output = Result<Det>>,
+    C: FnMut(Det) -> CFut,
+    CFut: Future,
+{
+    let mut samples = Vec::with_capacity(iterations as usize);
+    for _ in 0..iterations {
+        let requested = Instant::now();
+        let frame = get_frame().await;
+        let captured = Instant::now();
+        let detections = detect(frame).await?;
+        let detected = Instant::now();
+        command(detections).await;
+        let commanded = Instant::now();
+
+        samples.push(LatencySample {
+            capture: captured - requested,
+            detect: detected - captured,
+            command: commanded - detected,
+        });
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::sleep;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn accounts_each_stage() {
+        let samples = bench_with(
+            3,
+            || async {
+                sleep(Duration::from_millis(30)).await;
+                Mat::default()
+            },
+            |_| async {
+                sleep(Duration::from_millis(50)).await;
+                Ok(())
+            },
+            |_| sleep(Duration::from_millis(5)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(samples.len(), 3);
+        for sample in &samples {
+            assert_eq!(sample.capture, Duration::from_millis(30));
+            assert_eq!(sample.detect, Duration::from_millis(50));
+            assert_eq!(sample.command, Duration::from_millis(5));
+        }
+
+        let stats = LatencyStats::new(&samples).unwrap();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.mean, Duration::from_millis(85));
+        assert_eq!(stats.p99, Duration::from_millis(85));
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let samples: Vec<_> = (1..=10)
+            .map(|ms| LatencySample {
+                capture: Duration::ZERO,
+                detect: Duration::from_millis(ms),
+                command: Duration::ZERO,
+            })
+            .collect();
+        let stats = LatencyStats::new(&samples).unwrap();
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.p50, Duration::from_millis(5));
+        assert_eq!(stats.p90, Duration::from_millis(9));
+        assert_eq!(stats.max, Duration::from_millis(10));
+        assert!(LatencyStats::new(&[]).is_none());
+    }
+}
@@ -1,18 +1,33 @@
 use tokio::io::WriteHalf;
 use tokio_serial::SerialStream;
 
+use crate::config::{bin::Config, depth_pid};
+
 use super::action_context::{BottomCamIO, GetControlBoard, GetMainElectronicsBoard};
 
 pub async fn bin<
     Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>> + GetMainElectronicsBoard + BottomCamIO,
 >(
     context: &Con,
+    config: &Config,
+    depth_pid: &depth_pid::Config,
 ) {
     #[cfg(feature = "logging")]
     logln!("Starting bin");
 
     let cb = context.get_control_board();
     let _ = cb.bno055_periodic_read(true).await;
+
+    if config.software_depth {
+        if let Err(_e) = cb
+            .software_depth_hold(config.depth, depth_pid, config.descend_secs.to_duration())
+            .await
+        {
+            #[cfg(feature = "logging")]
+            log_warn!("Software depth hold failed: {:#}", _e);
+        }
+    }
+
     #[cfg(feature = "logging")]
     logln!("Finished bin");
 }
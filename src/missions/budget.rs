@@ -0,0 +1,134 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::config::{run_budget, Config};
+
+/// Scheduling information for a mission in the run sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissionInfo<'a> {
+    pub name: &'a str,
+    /// Mission may be skipped when the run is short on time
+    pub optional: bool,
+    /// Mission is always skipped when false
+    pub enabled: bool,
+}
+
+impl<'a> MissionInfo<'a> {
+    /// Enabled mission
+    pub const fn new(name: &'a str, optional: bool) -> Self {
+        Self {
+            name,
+            optional,
+            enabled: true,
+        }
+    }
+
+    /// Builds info for `name` from the optional and disabled mission lists
+    pub fn from_config(name: &'a str, config: &Config) -> Self {
+        let listed = |names: &[String]| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+        Self {
+            name,
+            optional: listed(&config.run_budget.optional_missions),
+            enabled: !listed(&config.disabled_missions),
+        }
+    }
+}
+
+/// Tracks elapsed time against the deadline for a full run
+#[derive(Debug)]
+pub struct RunBudget {
+    start: Instant,
+    total: Duration,
+    skip_margin: Duration,
+}
+
+impl RunBudget {
+    /// Starts the run clock now
+    pub fn new(total: Duration, skip_margin: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            total,
+            skip_margin,
+        }
+    }
+
+    pub fn from_config(config: &run_budget::Config) -> Self {
+        Self::new(
+            Duration::from_secs(config.total_secs),
+            Duration::from_secs(config.skip_margin_secs),
+        )
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Time left before the run deadline, zero once it has passed
+    pub fn time_remaining(&self) -> Duration {
+        self.total.saturating_sub(self.elapsed())
+    }
+
+    /// True if `mission` should not be started with the time left
+    pub fn should_skip(&self, mission: &MissionInfo) -> bool {
+        skip_decision(mission, self.time_remaining(), self.skip_margin)
+    }
+}
+
+/// Optional missions are skipped once `remaining` drops below `margin`,
+/// required missions always run.
+fn skip_decision(mission: &MissionInfo, remaining: Duration, margin: Duration) -> bool {
+    mission.optional && remaining < margin
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARGIN: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn optional_skipped_near_deadline() {
+        let mission = MissionInfo::new("spin", true);
+        assert!(!skip_decision(&mission, Duration::from_secs(600), MARGIN));
+        assert!(!skip_decision(&mission, MARGIN, MARGIN));
+        assert!(skip_decision(&mission, Duration::from_secs(59), MARGIN));
+        assert!(skip_decision(&mission, Duration::ZERO, MARGIN));
+    }
+
+    #[test]
+    fn required_never_skipped() {
+        let mission = MissionInfo::new("gate_run_yolo", false);
+        assert!(!skip_decision(&mission, Duration::from_secs(600), MARGIN));
+        assert!(!skip_decision(&mission, Duration::ZERO, MARGIN));
+    }
+
+    #[test]
+    fn info_from_config() {
+        let config = Config {
+            run_budget: run_budget::Config {
+                optional_missions: vec!["Spin".to_string()],
+                ..Default::default()
+            },
+            disabled_missions: vec!["sonar".to_string()],
+            ..Default::default()
+        };
+        assert!(MissionInfo::from_config("spin", &config).optional);
+        assert!(!MissionInfo::from_config("coinflip", &config).optional);
+        assert!(MissionInfo::from_config("coinflip", &config).enabled);
+        assert!(!MissionInfo::from_config("sonar", &config).enabled);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn remaining_tracks_elapsed() {
+        let budget = RunBudget::new(Duration::from_secs(120), MARGIN);
+        let spin = MissionInfo::new("spin", true);
+        assert!(!budget.should_skip(&spin));
+
+        tokio::time::advance(Duration::from_secs(90)).await;
+        assert_eq!(budget.time_remaining(), Duration::from_secs(30));
+        assert!(budget.should_skip(&spin));
+
+        tokio::time::advance(Duration::from_secs(90)).await;
+        assert_eq!(budget.time_remaining(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,57 @@
+use std::ops::RangeInclusive;
+
+use opencv::core::Size;
+
+use crate::{
+    config::calibration::Config,
+    video_source::MatSource,
+    vision::{calibration::mask_overlay, Yuv},
+};
+
+use super::{
+    action_context::{BottomCamIO, CameraSelect, FrontCamIO, SelectCamera},
+    basic::loop_interval,
+};
+
+/// Streams `camera` with the `color` mask and the selected blob drawn on, until cancelled
+///
+/// For tuning color profiles pool-side: edit the config, rerun, and watch the
+/// annotated stream. Uses the same frame size and contour selection as
+/// [`center_on_color`](super::center::center_on_color), and logs the blob center
+/// of every frame.
+pub async fn calibration_overlay<Con: Send + Sync + FrontCamIO + BottomCamIO>(
+    context: &Con,
+    color: &RangeInclusive<Yuv>,
+    camera: CameraSelect,
+    config: &Config,
+) {
+    #[cfg(feature = "logging")]
+    logln!("Starting calibration overlay with {camera:?} camera");
+
+    let camera = context.selected_camera(camera);
+    let mut loop_tick = loop_interval(config.loop_hz);
+    loop {
+        loop_tick.tick().await;
+        let frame = camera.get_mat().await;
+        match mask_overlay(
+            &frame,
+            color,
+            &config.area_bounds,
+            config.contour_select,
+            Size::new(400, 300),
+        ) {
+            #[allow(unused_variables)]
+            Ok((overlay, center)) => {
+                #[cfg(feature = "logging")]
+                logln!("Blob center: {center:?}");
+                #[cfg(feature = "annotated_streams")]
+                camera.annotate(&overlay).await;
+            }
+            #[allow(unused_variables)]
+            Err(e) => {
+                #[cfg(feature = "logging")]
+                log_warn!("Failed to draw calibration overlay: {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::{future::Future, ops::RangeInclusive};
+
+use anyhow::{bail, Result};
+use opencv::core::Size;
+use tokio::{io::WriteHalf, sync::Mutex};
+use tokio_serial::SerialStream;
+
+use crate::{
+    comms::control_board::ANGLES_TIMEOUT,
+    config::center::Config,
+    vision::{color_blob::ColorBlob, Yuv},
+};
+
+use super::{
+    action::ActionExec,
+    action_context::{BottomCamIO, CameraSelect, FrontCamIO, GetControlBoard},
+    vision::VisionNormSelectedAngle,
+};
+
+/// Centers the sub on the largest blob of `color` seen by `camera`.
+///
+/// Offsets are corrected proportionally at the starting heading and the
+/// configured depth. The front camera only corrects strafe, since its
+/// vertical offset is a depth error. The bottom camera corrects strafe and
+/// forward. Returns once both offsets are within `tolerance` for
+/// `config.centered_frames` consecutive frames, and fails if that takes more
+/// than `config.max_frames` frames.
+pub async fn center_on_color<
+    Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>> + FrontCamIO + BottomCamIO,
+>(
+    context: &Con,
+    color: &RangeInclusive<Yuv>,
+    camera: CameraSelect,
+    tolerance: f64,
+    config: &Config,
+) -> Result<()> {
+    #[cfg(feature = "logging")]
+    logln!("Starting center on color with {camera:?} camera");
+
+    let cb = context.get_control_board();
+    cb.bno055_periodic_read(true).await?;
+
+    let yaw = cb.wait_for_yaw(Some(ANGLES_TIMEOUT)).await?;
+
+    let vision = VisionNormSelectedAngle::<Con, ColorBlob, f64>::new(
+        context,
+        camera,
+        ColorBlob::new(
+            color.clone(),
+            config.area_bounds.clone(),
+            config.contour_select,
+            Size::from((400, 300)),
+        ),
+    );
+    let vision = &Mutex::new(vision);
+
+    center_with(
+        move || async move {
+            #[allow(unused_variables)]
+            let detections = vision.lock().await.execute().await.unwrap_or_else(|e| {
+                #[cfg(feature = "logging")]
+                logln!(
+                    "Getting blob detection resulted in error: `{e}`\n\tUsing empty detection vec"
+                );
+                vec![]
+            });
+            detections
+                .into_iter()
+                .find(|d| *d.class())
+                .map(|d| (*d.position().x(), *d.position().y()))
+        },
+        |x, y| {
+            let strafe = config.gain * x as f32;
+            let forward = match camera {
+                CameraSelect::Front => 0.0,
+                CameraSelect::Bottom => -config.gain * y as f32,
+            };
+            cb.stability_2_speed_set(strafe, forward, 0.0, 0.0, yaw, config.depth)
+        },
+        tolerance,
+        config.centered_frames,
+        config.max_frames,
+    )
+    .await?;
+
+    #[cfg(feature = "logging")]
+    logln!("Centered on color");
+    cb.stability_2_speed_set(0.0, 0.0, 0.0, 0.0, yaw, config.depth)
+        .await
+}
+
+/// [`center_on_color`] with detection and command abstracted out
+///
+/// A frame without a detection resets the centered count and sends a zero
+/// correction, so the last one is not held while the target is out of view.
+async fn center_with<D, DFut, C, CFut>(
+    mut detect: D,
+    mut command: C,
+    tolerance: f64,
+    centered_frames: u32,
+    max_frames: u32,
+) -> Result<()>
+where
+    D: FnMut() -> DFut,
+    DFut: Future<Output = Option<(f64, f64)>>,
+    C: FnMut(f64, f64) -> CFut,
+    CFut: Future<Output = Result<()>>,
+{
+    let mut centered = 0;
+    let mut frames = 0;
+    while centered < centered_frames {
+        if frames == max_frames {
+            bail!("Not centered within {max_frames} frames");
+        }
+        frames += 1;
+
+        let Some((x, y)) = detect().await else {
+            centered = 0;
+            command(0.0, 0.0).await?;
+            continue;
+        };
+
+        if x.abs() < tolerance && y.abs() < tolerance {
+            centered += 1;
+        } else {
+            centered = 0;
+        }
+        command(x, y).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::test_util::Calls;
+
+    #[tokio::test]
+    async fn converges_on_decreasing_offsets() {
+        let offset = &Mutex::new(0.8);
+        let commands = Calls::new();
+
+        center_with(
+            move || async move {
+                let mut offset = offset.lock().unwrap();
+                let current = *offset;
+                // Correction halves the remaining offset each frame
+                *offset *= 0.5;
+                Some((current, -current))
+            },
+            |x, _| commands.ok(x),
+            0.1,
+            3,
+            20,
+        )
+        .await
+        .unwrap();
+
+        // 0.8, 0.4, 0.2, 0.1 are off center, then 3 centered frames
+        let commands = commands.values();
+        assert_eq!(commands.len(), 7);
+        assert!(commands.windows(2).all(|w| w[1] < w[0]));
+    }
+
+    #[tokio::test]
+    async fn lost_detection_resets_count() {
+        let frames = &Mutex::new(vec![
+            Some((0.0, 0.0)),
+            None,
+            Some((0.0, 0.0)),
+            Some((0.0, 0.0)),
+        ]);
+        let commands = Calls::new();
+
+        center_with(
+            move || async move { frames.lock().unwrap().remove(0) },
+            |x, y| commands.ok((x, y)),
+            0.1,
+            2,
+            20,
+        )
+        .await
+        .unwrap();
+
+        // The lost frame stops the correction
+        assert_eq!(commands.len(), 4);
+        assert_eq!(commands.values()[1], (0.0, 0.0));
+        assert!(frames.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_frame_budget() {
+        let commands = Calls::new();
+        let res = center_with(
+            || async { Some((0.5, 0.0)) },
+            |x, _| commands.ok(x),
+            0.1,
+            3,
+            10,
+        )
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(commands.len(), 10);
+    }
+}
@@ -1,34 +1,79 @@
-use itertools::Itertools;
+use anyhow::Result;
 use tokio::io::WriteHalf;
 use tokio_serial::SerialStream;
 
 use crate::{
-    config::coinflip::Config,
+    config::{coinflip::Config, Side},
     vision::{
+        class_map::ClassRemap,
+        class_vote::ClassVoteAccumulator,
         gate_poles::{GatePoles, Target},
         nn_cv2::OnnxModel,
+        yolo_model::filter_confidence,
     },
 };
 
 use super::{
     action::ActionExec,
     action_context::{FrontCamIO, GetControlBoard, GetMainElectronicsBoard},
+    basic::{loop_interval, VisionErrors},
     vision::VisionNorm,
 };
 
+/// Gate side picked by the coinflip
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoinflipResult {
+    pub side: Side,
+    /// Summed confidence of the winning class over the vote window, divided
+    /// by the window length, so frames it was missed in count as zero
+    pub confidence: f32,
+}
+
+impl CoinflipResult {
+    /// Side for the winning `target`, `None` if it is not an animal
+    pub fn from_target(
+        target: &Target,
+        confidence: f32,
+        shark: &Side,
+        saw_fish: &Side,
+    ) -> Option<Self> {
+        let side = match target {
+            Target::Shark => shark.clone(),
+            Target::Sawfish => saw_fish.clone(),
+            _ => return None,
+        };
+        Some(Self { side, confidence })
+    }
+}
+
+/// Searches for the coinflip target, returning the gate side it picked
+///
+/// `class_remap` corrects the gate model's class ids, as in the gate run.
 pub async fn coinflip_procedural<
     Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>> + GetMainElectronicsBoard + FrontCamIO,
 >(
     context: &Con,
     config: &Config,
-) {
+    shark: &Side,
+    saw_fish: &Side,
+    class_remap: Option<ClassRemap>,
+) -> Result<Option<CoinflipResult>> {
     #[cfg(feature = "logging")]
     logln!("Starting path align");
 
     let cb = context.get_control_board();
     let _ = cb.bno055_periodic_read(true).await;
-    let mut vision =
-        VisionNorm::<Con, GatePoles<OnnxModel>, f64>::new(context, GatePoles::default());
+    let mut model = GatePoles::default();
+    model.set_threshold(config.threshold);
+    if let Some(class_remap) = class_remap {
+        model = model.with_class_remap(class_remap);
+    }
+    let min_confidence = *model.threshold();
+    let mut vision = VisionNorm::<Con, GatePoles<OnnxModel>, f64>::with_merge_distance(
+        context,
+        model,
+        config.merge_distance,
+    );
 
     // let initial_yaw = loop {
     //     if let Some(initial_angle) = cb.responses().get_angles().await {
@@ -45,51 +90,48 @@ pub async fn coinflip_procedural<
         .stability_1_speed_set(0.0, 0.0, config.angle_correction, 0.0, 0.0, depth)
         .await;
 
-    let mut true_count = 0;
-    let max_true_count = config.true_count;
+    let mut votes = ClassVoteAccumulator::new(config.vote_window);
 
+    let mut vision_errors = VisionErrors::default();
+    let mut loop_tick = loop_interval(config.loop_hz);
     loop {
-        #[allow(unused_variables)]
-        let detections = vision.execute().await.unwrap_or_else(|e| {
-            #[cfg(feature = "logging")]
-            logln!("Getting path detection resulted in error: `{e}`\n\tUsing empty detection vec");
-            vec![]
-        });
+        loop_tick.tick().await;
+        let detections = vision_errors
+            .check_or_stop(vision.execute().await, cb)
+            .await?;
+        let detections = filter_confidence(detections, min_confidence);
 
         // let gate = detections
         //     .iter()
         //     .filter(|d| matches!(d.class().identifier, Target::Gate))
         //     .collect_vec();
-        let shark = detections
-            .iter()
-            .filter(|d| matches!(d.class().identifier, Target::Shark))
-            .collect_vec();
-        let sawfish = detections
-            .iter()
-            .filter(|d| matches!(d.class().identifier, Target::Sawfish))
-            .collect_vec();
-
-        let left_pole = detections
-            .iter()
-            .filter(|d| matches!(d.class().identifier, Target::LeftPole))
-            .collect_vec();
-
-        // let right_pole = detections
-        //     .iter()
-        //     .filter(|d| matches!(d.class().identifier, Target::RightPole))
-        //     .collect_vec();
+        // Only the animals pick a side, so nothing else may win the vote
+        votes.push_detections(
+            detections
+                .iter()
+                .filter(|d| matches!(d.class().identifier, Target::Shark | Target::Sawfish)),
+        );
 
-        if shark.len() > 0 || sawfish.len() > 0 || !left_pole.is_empty() {
-            if true_count > max_true_count {
+        if let Some(leader) = votes.leader() {
+            if leader.score >= config.vote_threshold {
+                #[cfg(feature = "logging")]
+                logln!(
+                    "Coinflip found {:?}, score {:.2}, margin {:.2}",
+                    leader.class,
+                    leader.score,
+                    leader.margin
+                );
                 let _ = cb
                     .stability_1_speed_set(0.0, 0.0, 0.0, 0.0, 0.0, depth)
                     .await;
-                break;
-            } else {
-                true_count += 1;
+                let confidence = (leader.score / config.vote_window.max(1) as f64) as f32;
+                break Ok(CoinflipResult::from_target(
+                    &leader.class,
+                    confidence,
+                    shark,
+                    saw_fish,
+                ));
             }
-        } else {
-            true_count = 0;
         }
     }
 }
@@ -0,0 +1,77 @@
+use tokio::time::{Duration, Instant};
+
+pub use crate::config::completion::CompletionPolicy;
+
+/// Tracks both completion conditions for one maneuver
+#[derive(Debug)]
+pub struct Completion {
+    policy: CompletionPolicy,
+    deadline: Instant,
+    lost_frames: u32,
+    misses: u32,
+}
+
+impl Completion {
+    /// Starts the timed condition now
+    ///
+    /// # Arguments
+    /// * `duration` - Minimum time for the timed condition
+    /// * `lost_frames` - Consecutive frames without a detection for the vision condition
+    pub fn start(policy: CompletionPolicy, duration: Duration, lost_frames: u32) -> Self {
+        Self {
+            policy,
+            deadline: Instant::now() + duration,
+            lost_frames,
+            misses: 0,
+        }
+    }
+
+    pub const fn policy(&self) -> CompletionPolicy {
+        self.policy
+    }
+
+    /// End of the timed condition
+    pub const fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Records whether the latest frame still saw the target
+    pub fn record(&mut self, detected: bool) {
+        if detected {
+            self.misses = 0;
+        } else {
+            self.misses = self.misses.saturating_add(1);
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.policy.complete(
+            Instant::now() >= self.deadline,
+            self.misses >= self.lost_frames,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::sleep;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn timed_and_vision_needs_both() {
+        let mut completion =
+            Completion::start(CompletionPolicy::TimedAndVision, Duration::from_secs(5), 2);
+
+        completion.record(false);
+        completion.record(false);
+        assert!(!completion.is_complete());
+
+        sleep(Duration::from_secs(5)).await;
+        assert!(completion.is_complete());
+
+        // Reacquiring the target resets the vision condition
+        completion.record(true);
+        assert!(!completion.is_complete());
+    }
+}
@@ -0,0 +1,139 @@
+use std::{fmt::Display, future::Future};
+
+use anyhow::Result;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    time::{sleep, Duration},
+};
+
+use crate::{
+    comms::{
+        control_board::{soft_start::RAMP_STEP, ControlBoard, LAST_DEPTH, LAST_YAW},
+        meb::MainElectronicsBoard,
+    },
+    config::emergency::Config,
+    log_warn, logln,
+};
+
+/// Time between leak and voltage checks
+const HAZARD_POLL: Duration = Duration::from_millis(500);
+/// Longest wait for a depth reading before rising from the last commanded depth
+const DEPTH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Condition that makes the sub surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hazard {
+    Leak,
+    LowVoltage(f32),
+}
+
+impl Display for Hazard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Leak => write!(f, "Leak detected"),
+            Self::LowVoltage(voltage) => write!(f, "System voltage low ({voltage:.2} V)"),
+        }
+    }
+}
+
+impl Hazard {
+    /// Process exit status after surfacing, distinct from ctrl-c and disarm (1)
+    pub const fn exit_code(&self) -> i32 {
+        match self {
+            Self::Leak => 3,
+            Self::LowVoltage(_) => 4,
+        }
+    }
+}
+
+/// Returns once the MEB reports a leak, or a voltage under `min_voltage`
+pub async fn wait_for_hazard<C: AsyncWrite + Unpin>(
+    meb: &MainElectronicsBoard<C>,
+    min_voltage: Option<f32>,
+) -> Hazard {
+    loop {
+        if meb.leak().await == Some(true) {
+            return Hazard::Leak;
+        }
+        if let (Some(min_voltage), Some(voltage)) = (min_voltage, meb.system_voltage().await) {
+            if voltage < min_voltage {
+                return Hazard::LowVoltage(voltage);
+            }
+        }
+        sleep(HAZARD_POLL).await;
+    }
+}
+
+/// Raises the depth target to the surface at `config.ascent_rate`.
+///
+/// Zeroing the thrusters leaves the sub wherever it is, so this keeps
+/// stability assist on and holds the current heading while rising from the
+/// measured depth, or the last commanded depth without a reading.
+pub async fn emergency_surface<T: AsyncWriteExt + Unpin>(
+    cb: &ControlBoard<T>,
+    config: &Config,
+) -> Result<()> {
+    logln!("Emergency surfacing");
+    let from_depth = match cb.wait_for_depth(Some(DEPTH_TIMEOUT)).await {
+        Ok(depth) => depth,
+        Err(e) => {
+            log_warn!("{:#}, rising from the last commanded depth", e);
+            LAST_DEPTH.lock().unwrap().unwrap_or(0.0)
+        }
+    };
+    let yaw = match cb.responses().get_angles().await {
+        Some(angles) => *angles.yaw(),
+        None => LAST_YAW.lock().unwrap().unwrap_or_default(),
+    };
+
+    surface_with(
+        |depth| cb.stability_2_speed_set(0.0, 0.0, 0.0, 0.0, yaw, depth),
+        from_depth,
+        config.ascent_rate,
+    )
+    .await
+}
+
+/// [`emergency_surface`] with the depth command abstracted out
+async fn surface_with<D, DFut>(mut set_depth: D, from_depth: f32, ascent_rate: f32) -> Result<()>
+where
+    D: FnMut(f32) -> DFut,
+    DFut: Future<Output = Result<()>>,
+{
+    let from_depth = from_depth.min(0.0);
+    let step = ascent_rate.abs() * RAMP_STEP.as_secs_f32();
+    // A zero rate goes straight up rather than hovering forever
+    let steps = if step > 0.0 {
+        (-from_depth / step).ceil().max(1.0) as u32
+    } else {
+        1
+    };
+    for idx in 1..=steps {
+        set_depth(from_depth - from_depth * idx as f32 / steps as f32).await?;
+        if idx != steps {
+            sleep(RAMP_STEP).await;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Calls;
+
+    #[tokio::test(start_paused = true)]
+    async fn rises_to_surface() {
+        let depths = Calls::new();
+
+        surface_with(|depth| depths.ok(depth), -1.0, 0.5)
+            .await
+            .unwrap();
+
+        // 0.5 m/s in 50 ms steps
+        let depths = depths.values();
+        assert_eq!(depths.len(), 40);
+        assert!(depths.windows(2).all(|w| w[1] > w[0]));
+        assert_eq!(*depths.last().unwrap(), 0.0);
+    }
+}
@@ -1,19 +1,26 @@
-use tokio::{io::WriteHalf, select};
+use anyhow::Result;
+use tokio::{
+    io::WriteHalf,
+    select,
+    time::{sleep, Duration, Instant},
+};
 use tokio_serial::SerialStream;
 
-use crate::{act_nest, missions::action_context::GetZedRos2};
+use crate::{
+    act_nest,
+    comms::control_board::{util::Degrees, ANGLES_TIMEOUT},
+    config::pid,
+    missions::action_context::GetZedRos2,
+};
 
 use super::{
-    action::{
-        Action, ActionChain, ActionConcurrent, ActionConditional, ActionExec, ActionSequence,
-        RaceAction,
-    },
-    action_context::{FrontCamIO, GetControlBoard, GetMainElectronicsBoard},
-    basic::DelayAction,
-    comms::StartBno055,
-    extra::{AlwaysTrue, OutputType, UnwrapAction},
+    action::{Action, ActionConcurrent, ActionConditional, ActionExec, ActionSequence, RaceAction},
+    action_context::{GetControlBoard, GetMainElectronicsBoard},
+    basic::{loop_interval, DelayAction},
+    extra::{AlwaysTrue, UnwrapAction},
     meb::WaitArm,
-    movement::{Descend, Stability2Movement, Stability2Pos},
+    movement::Descend,
+    pid::Pid,
 };
 use tokio_util::sync::CancellationToken;
 
@@ -37,38 +44,48 @@ where
     )
 }
 
-pub fn pid_test<
-    Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>> + GetMainElectronicsBoard + FrontCamIO,
->(
+/// Holds depth and turns 45 degrees on a software [`Pid`] yaw loop, to tune
+/// `gains` before using them in a mission
+///
+/// The yaw error is normalized to [-1, 1] by dividing by 180 degrees.
+pub async fn pid_test<Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>>>(
     context: &Con,
-) -> impl ActionExec<()> + '_ {
-    let depth: f32 = -1.6;
+    gains: &pid::Config,
+) -> Result<()> {
+    const DEPTH: f32 = -1.6;
+    const TURN: f32 = 45.0;
+    const LOOP_HZ: f64 = 10.0;
+    const DURATION: Duration = Duration::from_secs(10);
 
-    act_nest!(
-        ActionSequence::new,
-        ActionConcurrent::new(
-            ActionChain::new(
-                Stability2Movement::new(
-                    context,
-                    Stability2Pos::new(0.0, 0.0, 0.0, 0.0, None, depth),
-                ),
-                OutputType::<()>::default()
-            ),
-            StartBno055::new(context),
-        ),
-        act_nest!(
-            ActionSequence::new,
-            ActionChain::new(DelayAction::new(5.0), OutputType::<()>::default(),),
-            ActionChain::new(
-                Stability2Movement::new(
-                    context,
-                    Stability2Pos::new(0.0, 0.0, 0.0, 0.0, Some(45.0), depth),
-                ),
-                OutputType::<()>::default()
-            ),
-            DelayAction::new(10.0),
-        ),
-    )
+    let cb = context.get_control_board();
+    cb.bno055_periodic_read(true).await?;
+    let initial = cb.wait_for_angles(Some(ANGLES_TIMEOUT)).await?.heading();
+    cb.stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial.value(), DEPTH)
+        .await?;
+    sleep(Duration::from_secs(5)).await;
+
+    let target = initial + Degrees::new(TURN);
+    let mut pid = Pid::new(gains.clone());
+    let mut loop_tick = loop_interval(LOOP_HZ);
+    let end = Instant::now() + DURATION;
+    let mut last = Instant::now();
+    while Instant::now() < end {
+        loop_tick.tick().await;
+        let Some(angles) = cb.responses().get_angles().await else {
+            continue;
+        };
+        let error = (target - angles.heading()).value() / 180.0;
+        let now = Instant::now();
+        let yaw_speed = pid.update(error, (now - last).as_secs_f32());
+        last = now;
+        #[cfg(feature = "logging")]
+        logln!("PID yaw error: {error:.3}, speed: {yaw_speed:.3}");
+        cb.stability_1_speed_set(0.0, 0.0, yaw_speed.clamp(-1.0, 1.0), 0.0, 0.0, DEPTH)
+            .await?;
+    }
+
+    cb.stability_2_speed_set(0.0, 0.0, 0.0, 0.0, target.value(), DEPTH)
+        .await
 }
 
 /// Example function for Action system
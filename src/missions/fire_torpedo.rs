@@ -3,7 +3,7 @@ use tokio_serial::SerialStream;
 
 use crate::{
     comms::meb::{MainElectronicsBoard, MebCmd},
-    logln,
+    log_warn, logln,
 };
 
 use super::{
@@ -29,7 +29,7 @@ impl<T: GetMainElectronicsBoard> ActionExec<()> for FireRightTorpedo<'_, T> {
         let send_cmd = |meb: &'a MainElectronicsBoard<WriteHalf<SerialStream>>, cmd| async move {
             match meb.send_msg(cmd).await {
                 Ok(()) => logln!("{:#?} success", cmd),
-                Err(e) => logln!("{:#?} failure: {:#?}", cmd, e),
+                Err(e) => log_warn!("{:#?} failure: {:#?}", cmd, e),
             };
         };
 
@@ -58,7 +58,7 @@ impl<T: GetMainElectronicsBoard> ActionExec<()> for FireLeftTorpedo<'_, T> {
         let send_cmd = |meb: &'a MainElectronicsBoard<WriteHalf<SerialStream>>, cmd| async move {
             match meb.send_msg(cmd).await {
                 Ok(()) => logln!("{:#?} success", cmd),
-                Err(e) => logln!("{:#?} failure: {:#?}", cmd, e),
+                Err(e) => log_warn!("{:#?} failure: {:#?}", cmd, e),
             };
         };
 
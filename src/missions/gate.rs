@@ -1,21 +1,31 @@
+use anyhow::{bail, Context, Result};
 use itertools::Itertools;
 use tokio::io::WriteHalf;
-use tokio::time::{sleep, Duration};
+use tokio::time::{sleep, sleep_until, Instant};
 use tokio_serial::SerialStream;
 
 use crate::{
+    comms::control_board::{util::Degrees, ANGLES_TIMEOUT},
     config::{gate::Config, ColorProfile, Side},
     vision::{
-        gate_cv::GateCV,
+        class_vote::ClassVoteAccumulator,
+        gate_cv::{GateCV, GatePole},
         gate_poles::{GatePoles, Target},
-        nn_cv2::OnnxModel,
+        nn_cv2::{OnnxModel, YoloClass},
+        yolo_model::filter_confidence,
+        Offset2D, VisualDetection,
     },
 };
 
 use super::{
     action::ActionExec,
     action_context::{FrontCamIO, GetControlBoard, GetMainElectronicsBoard},
-    basic::DelayAction,
+    basic::{
+        approach_forward, loop_interval, DelayAction, DetectionConfirm, ErrorWindow, VisionErrors,
+    },
+    completion::Completion,
+    pid::Pid,
+    robot::Robot,
     vision::VisionNorm,
 };
 
@@ -24,27 +34,19 @@ pub async fn gate_run_dead_reckon<
 >(
     context: &Con,
     config: &Config,
-) {
+) -> Result<()> {
     #[cfg(feature = "logging")]
     logln!("Starting Procedural Gate");
 
-    let cb = context.get_control_board();
-    let _ = cb.bno055_periodic_read(true).await;
-
-    let initial_yaw = loop {
-        if let Some(initial_angle) = cb.responses().get_angles().await {
-            break *initial_angle.yaw();
-        } else {
-            #[cfg(feature = "logging")]
-            logln!("Failed to get initial angle");
-        }
-    };
+    let robot = Robot::new(context.get_control_board())
+        .await
+        .context("Abandoning gate")?;
 
     // let _ = cb
     //     .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, config.depth)
     //     .await;
 
-    // sleep(Duration::from_secs_f32(config.init_duration)).await;
+    // sleep(config.init_duration.to_duration()).await;
     // let mut mult = 1.0;
     // if let Side::Left = config.side {
     //     mult = -1.0;
@@ -62,17 +64,15 @@ pub async fn gate_run_dead_reckon<
     //     )
     //     .await;
 
-    // sleep(Duration::from_secs_f32(config.strafe_duration)).await;
+    // sleep(config.strafe_duration.to_duration()).await;
 
-    let _ = cb
-        .stability_2_speed_set(0.0, config.speed, 0.0, 0.0, initial_yaw, config.depth)
-        .await;
-
-    sleep(Duration::from_secs_f32(config.traversal_duration)).await;
-
-    let _ = cb
-        .stability_1_speed_set(0.0, 0.0, 0.0, 0.0, 0.0, config.depth)
-        .await;
+    robot
+        .stb2fd_time(
+            config.speed,
+            config.depth,
+            config.traversal_duration.to_duration(),
+        )
+        .await
 }
 
 pub async fn gate_run_cv_procedural<
@@ -81,7 +81,7 @@ pub async fn gate_run_cv_procedural<
     context: &Con,
     config: &Config,
     color_profile: &ColorProfile,
-) {
+) -> Result<()> {
     #[cfg(feature = "logging")]
     logln!("Starting Procedural Gate");
 
@@ -89,17 +89,16 @@ pub async fn gate_run_cv_procedural<
     let _ = cb.bno055_periodic_read(true).await;
 
     // let mut vision = VisionNorm::<Con, GatePoles<OnnxModel>, f64>::new(context, GateCV::default());
-    let mut vision =
-        VisionNorm::<Con, GateCV, f64>::new(context, GateCV::from_color_profile(color_profile));
+    let mut vision = VisionNorm::<Con, GateCV, f64>::with_merge_distance(
+        context,
+        GateCV::from_color_profile(color_profile, config.contour_select),
+        config.merge_distance,
+    );
 
-    let initial_yaw = loop {
-        if let Some(initial_angle) = cb.responses().get_angles().await {
-            break *initial_angle.yaw();
-        } else {
-            #[cfg(feature = "logging")]
-            logln!("Failed to get initial angle");
-        }
-    };
+    let initial_yaw = cb
+        .wait_for_yaw(Some(ANGLES_TIMEOUT))
+        .await
+        .context("Abandoning gate")?;
 
     let _ = cb
         .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, config.depth)
@@ -107,32 +106,56 @@ pub async fn gate_run_cv_procedural<
 
     // const TOLERANCE: f32 = 0.3;
 
-    let mut gate_state = GateState::Align;
-    let mut yaw_target = 0.0;
+    let mut gate_state = GateState::Confirm;
+    let mut confirm = GateConfirm::from_config(config);
+    let mut yaw_target = Degrees::default();
     let mut true_count = 0;
     let mut false_count = 0;
+    let mut error_window = ErrorWindow::new(config.error_window);
+    let mut pid = Pid::new(config.correction_pid.clone());
+    let mut last_correction: Option<Instant> = None;
+    let mut vision_errors = VisionErrors::default();
 
+    let mut loop_tick = loop_interval(config.loop_hz);
     loop {
-        #[allow(unused_variables)]
-        let detections = vision.execute().await.unwrap_or_else(|e| {
-            #[cfg(feature = "logging")]
-            logln!("Getting path detection resulted in error: `{e}`\n\tUsing empty detection vec");
-            vec![]
-        });
+        loop_tick.tick().await;
+        let detections = vision_errors
+            .check_or_stop(vision.execute().await, cb)
+            .await?;
+        let detections = detections
+            .into_iter()
+            .filter(|d| *d.confidence() >= config.min_confidence)
+            .collect_vec();
 
-        let left_pole = detections.iter().filter(|d| *d.class()).collect_vec();
+        // Partial poles confirm the gate, but only sided poles steer
+        let left_pole = detections
+            .iter()
+            .filter(|d| *d.class() == GatePole::Left)
+            .collect_vec();
         let left_pole_avg_x = left_pole
             .iter()
             .map(|d| *d.position().x() as f32)
             .sum::<f32>();
 
-        let right_pole = detections.iter().filter(|d| !*d.class()).collect_vec();
+        let right_pole = detections
+            .iter()
+            .filter(|d| *d.class() == GatePole::Right)
+            .collect_vec();
         let right_pole_avg_x = right_pole
             .iter()
             .map(|d| *d.position().x() as f32)
             .sum::<f32>();
 
         match gate_state {
+            GateState::Confirm => {
+                gate_state = GateState::after_confirm(&mut confirm, detections.len())?;
+                #[cfg(feature = "logging")]
+                if let GateState::Align = gate_state {
+                    logln!("GATE CONFIRMED");
+                } else {
+                    logln!("CONFIRMING GATE");
+                }
+            }
             GateState::Align => match config.side {
                 Side::Left => {
                     if left_pole.len() > 0 {
@@ -144,8 +167,7 @@ pub async fn gate_run_cv_procedural<
                                 #[cfg(feature = "logging")]
                                 logln!("ALIGNED");
                                 if let Some(current_angle) = cb.responses().get_angles().await {
-                                    let current_yaw = *current_angle.yaw();
-                                    yaw_target = current_yaw;
+                                    yaw_target = current_angle.heading();
                                 }
                                 gate_state = GateState::Approach;
                             } else {
@@ -153,14 +175,26 @@ pub async fn gate_run_cv_procedural<
                                 logln!("true_count: {true_count}/4");
                             }
                         } else {
-                            correction = dbg!(config.correction_factor * left_pole_avg_x);
+                            let error = error_window.push(left_pole_avg_x);
+                            correction = dbg!(timed_update(&mut pid, &mut last_correction, error));
+                            let forward = approach_forward(config.approach, error, 0.0);
                             let _ = cb
-                                .stability_1_speed_set(0.0, 0.0, correction, 0.0, 0.0, config.depth)
+                                .stability_1_speed_set(
+                                    0.0,
+                                    forward,
+                                    correction,
+                                    0.0,
+                                    0.0,
+                                    config.depth,
+                                )
                                 .await;
                         }
                     } else {
                         #[cfg(feature = "logging")]
                         logln!("SEARCHING");
+                        error_window.clear();
+                        pid.reset();
+                        last_correction = None;
 
                         let _ = cb
                             .stability_1_speed_set(
@@ -178,7 +212,7 @@ pub async fn gate_run_cv_procedural<
                         logln!("NO DETECTIONS");
                         if false_count >= 100 {
                             #[cfg(feature = "logging")]
-                            logln!("KILLED NO DET");
+                            log_warn!("KILLED NO DET");
                             break;
                         }
                     }
@@ -194,8 +228,7 @@ pub async fn gate_run_cv_procedural<
                                 #[cfg(feature = "logging")]
                                 logln!("ALIGNED");
                                 if let Some(current_angle) = cb.responses().get_angles().await {
-                                    let current_yaw = *current_angle.yaw();
-                                    yaw_target = current_yaw;
+                                    yaw_target = current_angle.heading();
                                 }
                                 gate_state = GateState::Approach;
                             } else {
@@ -203,14 +236,26 @@ pub async fn gate_run_cv_procedural<
                                 logln!("true_count: {true_count}/4");
                             }
                         } else {
-                            correction = dbg!(config.correction_factor * right_pole_avg_x);
+                            let error = error_window.push(right_pole_avg_x);
+                            correction = dbg!(timed_update(&mut pid, &mut last_correction, error));
+                            let forward = approach_forward(config.approach, error, 0.0);
                             let _ = cb
-                                .stability_1_speed_set(0.0, 0.0, correction, 0.0, 0.0, config.depth)
+                                .stability_1_speed_set(
+                                    0.0,
+                                    forward,
+                                    correction,
+                                    0.0,
+                                    0.0,
+                                    config.depth,
+                                )
                                 .await;
                         }
                     } else {
                         #[cfg(feature = "logging")]
                         logln!("SEARCHING");
+                        error_window.clear();
+                        pid.reset();
+                        last_correction = None;
 
                         let _ = cb
                             .stability_1_speed_set(
@@ -228,7 +273,7 @@ pub async fn gate_run_cv_procedural<
                         logln!("NO DETECTIONS");
                         if false_count >= 100 {
                             #[cfg(feature = "logging")]
-                            logln!("KILLED NO DET");
+                            log_warn!("KILLED NO DET");
                             break;
                         }
                     }
@@ -250,36 +295,112 @@ pub async fn gate_run_cv_procedural<
                         0.0,
                         0.0,
                         0.0,
-                        yaw_target,
+                        yaw_target.value(),
                         config.depth,
                     )
                     .await;
 
-                sleep(Duration::from_secs(config.strafe_duration as u64)).await;
+                sleep(config.strafe_duration.to_duration()).await;
 
                 yaw_target = yaw_target
-                    + (if let Side::Left = config.side {
+                    + Degrees::new(if let Side::Left = config.side {
                         config.yaw_adjustment
                     } else {
                         -config.yaw_adjustment
                     });
 
                 let _ = cb
-                    .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, yaw_target, config.depth)
+                    .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, yaw_target.value(), config.depth)
                     .await;
 
-                sleep(Duration::from_secs(config.init_duration as u64)).await;
+                sleep(config.init_duration.to_duration()).await;
 
                 let _ = cb
-                    .stability_2_speed_set(0.0, config.speed, 0.0, 0.0, yaw_target, config.depth)
+                    .stability_2_speed_set(
+                        0.0,
+                        config.speed,
+                        0.0,
+                        0.0,
+                        yaw_target.value(),
+                        config.depth,
+                    )
                     .await;
 
-                sleep(Duration::from_secs(config.traversal_duration as u64)).await;
+                let mut completion = Completion::start(
+                    config.approach_completion,
+                    config.traversal_duration.to_duration(),
+                    config.lost_frames,
+                );
+                while !completion.is_complete() {
+                    if !completion.policy().uses_vision() {
+                        sleep_until(completion.deadline()).await;
+                        continue;
+                    }
+
+                    loop_tick.tick().await;
+                    let detections = vision_errors
+                        .check_or_stop(vision.execute().await, cb)
+                        .await?;
+                    completion.record(!detections.is_empty());
+                }
 
                 break;
             }
         }
     }
+    Ok(())
+}
+
+/// Creature to pass on `side`, given which side each one is on
+///
+/// Falls back to the shark for the left and the sawfish for the right when
+/// neither is placed on `side`.
+fn side_target(side: &Side, shark: &Side, saw_fish: &Side) -> Target {
+    if shark == side {
+        Target::Shark
+    } else if saw_fish == side {
+        Target::Sawfish
+    } else if *side == Side::Left {
+        Target::Shark
+    } else {
+        Target::Sawfish
+    }
+}
+
+/// Updates `pid` with `error`, timed from the update recorded in `last`
+fn timed_update(pid: &mut Pid, last: &mut Option<Instant>, error: f32) -> f32 {
+    let now = Instant::now();
+    let dt = last.map_or(0.0, |last| (now - last).as_secs_f32());
+    *last = Some(now);
+    pid.update(error, dt)
+}
+
+/// Whether `target` leads `votes` with at least `threshold` summed confidence
+fn target_won(votes: &ClassVoteAccumulator<Target>, target: &Target, threshold: f64) -> bool {
+    votes
+        .leader()
+        .is_some_and(|leader| leader.class == *target && leader.score >= threshold)
+}
+
+/// Mean x of the `target` detections, if there are any
+fn target_avg_x(
+    detections: &[VisualDetection<YoloClass<Target>, Offset2D<f64>>],
+    target: &Target,
+) -> Option<f32> {
+    let xs = detections
+        .iter()
+        .filter(|d| d.class().identifier == *target)
+        .map(|d| *d.position().x() as f32)
+        .collect_vec();
+    (!xs.is_empty()).then(|| xs.iter().sum::<f32>() / xs.len() as f32)
+}
+
+/// Gate pole detections, the only classes that confirm the gate
+fn pole_count(detections: &[VisualDetection<YoloClass<Target>, Offset2D<f64>>]) -> usize {
+    detections
+        .iter()
+        .filter(|d| matches!(d.class().identifier, Target::LeftPole | Target::RightPole))
+        .count()
 }
 
 pub async fn gate_run_procedural<
@@ -287,24 +408,37 @@ pub async fn gate_run_procedural<
 >(
     context: &Con,
     config: &Config,
-) {
+    shark: &Side,
+    saw_fish: &Side,
+) -> Result<()> {
     #[cfg(feature = "logging")]
     logln!("Starting Procedural Gate");
 
     let cb = context.get_control_board();
     let _ = cb.bno055_periodic_read(true).await;
 
-    let mut vision =
-        VisionNorm::<Con, GatePoles<OnnxModel>, f64>::new(context, GatePoles::default());
-
-    let initial_yaw = loop {
-        if let Some(initial_angle) = cb.responses().get_angles().await {
-            break *initial_angle.yaw();
-        } else {
-            #[cfg(feature = "logging")]
-            logln!("Failed to get initial angle");
-        }
-    };
+    let target = side_target(&config.side, shark, saw_fish);
+    #[cfg(feature = "logging")]
+    logln!("Aligning to the {target:?} on the {:?} side", config.side);
+
+    let mut model = GatePoles::default();
+    model.set_threshold(config.threshold);
+    match config.class_remap() {
+        Ok(Some(class_remap)) => model = model.with_class_remap(class_remap),
+        Ok(None) => (),
+        Err(e) => logln!("Ignoring gate class remap: {e:#}"),
+    }
+    let min_confidence = *model.threshold();
+    let mut vision = VisionNorm::<Con, GatePoles<OnnxModel>, f64>::with_merge_distance(
+        context,
+        model,
+        config.merge_distance,
+    );
+
+    let initial_yaw = cb
+        .wait_for_yaw(Some(ANGLES_TIMEOUT))
+        .await
+        .context("Abandoning gate")?;
 
     let _ = cb
         .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, config.depth)
@@ -312,15 +446,32 @@ pub async fn gate_run_procedural<
 
     const TOLERANCE: f32 = 0.3;
 
-    let mut true_count = 0;
+    let mut votes = ClassVoteAccumulator::new(config.vote_window);
+    let mut confirm = GateConfirm::from_config(config);
+    let mut confirmed = false;
+    let mut pid = Pid::new(config.correction_pid.clone());
+    let mut last_correction: Option<Instant> = None;
 
+    let mut loop_tick = loop_interval(config.loop_hz);
+    let mut vision_errors = VisionErrors::default();
     loop {
-        #[allow(unused_variables)]
-        let detections = vision.execute().await.unwrap_or_else(|e| {
+        loop_tick.tick().await;
+        let detections = vision_errors
+            .check_or_stop(vision.execute().await, cb)
+            .await?;
+        let detections = filter_confidence(detections, min_confidence);
+
+        // Hold until the gate is confirmed, so a spurious detection isn't chased
+        if !confirmed {
+            confirmed = confirm.push(pole_count(&detections))?;
             #[cfg(feature = "logging")]
-            logln!("Getting path detection resulted in error: `{e}`\n\tUsing empty detection vec");
-            vec![]
-        });
+            if confirmed {
+                logln!("GATE CONFIRMED");
+            } else {
+                logln!("CONFIRMING GATE");
+            }
+            continue;
+        }
 
         // let right_pole = detections
         //     .iter()
@@ -332,31 +483,25 @@ pub async fn gate_run_procedural<
         .filter(|d| matches!(d.class().identifier, Target::Middle))
         .collect_vec(); */
 
-        let shark = detections
-            .iter()
-            .filter(|d| matches!(d.class().identifier, Target::Sawfish))
-            .collect_vec();
-
-        let sawfish = detections
-            .iter()
-            .filter(|d| matches!(d.class().identifier, Target::Shark))
-            .collect_vec();
+        // Every creature votes, so seeing the other one holds off the pass
+        votes.push_detections(
+            detections
+                .iter()
+                .filter(|d| matches!(d.class().identifier, Target::Shark | Target::Sawfish)),
+        );
+        let target_x = target_avg_x(&detections, &target);
 
         let mut traversal_timer = DelayAction::new(8.0); // forward duration in second
 
         match config.side {
             Side::Left => {
-                if !shark.is_empty() {
-                    // Center on average x of blue
-                    let avg_x = shark.iter().map(|d| *d.position().x() as f32).sum::<f32>()
-                        / shark.len() as f32;
-
+                if let Some(avg_x) = target_x {
                     #[cfg(feature = "logging")]
-                    logln!("SHARK AVG X: {}", avg_x);
+                    logln!("{target:?} AVG X: {}", avg_x);
 
                     if avg_x.abs() > TOLERANCE {
-                        let correction = 0.4 * avg_x;
-                        let fwd = 0.0;
+                        let correction = timed_update(&mut pid, &mut last_correction, avg_x);
+                        let fwd = approach_forward(config.approach, avg_x, 0.0);
 
                         let _ = cb
                             .stability_2_speed_set(
@@ -371,9 +516,8 @@ pub async fn gate_run_procedural<
                     } else {
                         let fwd = config.speed;
                         let correction = 0.05;
-                        true_count += 1;
 
-                        if true_count >= config.true_count {
+                        if target_won(&votes, &target, config.vote_threshold) {
                             let _ = cb
                                 .stability_2_speed_set(
                                     correction,
@@ -396,6 +540,8 @@ pub async fn gate_run_procedural<
                     // Fallback search behavior
                     #[cfg(feature = "logging")]
                     logln!("LEFT: Missing Features, Fallback");
+                    pid.reset();
+                    last_correction = None;
 
                     let correction = -0.2;
                     let fwd = 0.05;
@@ -412,20 +558,13 @@ pub async fn gate_run_procedural<
             }
 
             Side::Right => {
-                if !sawfish.is_empty() {
-                    // Center on average x of blue
-                    let avg_x = sawfish
-                        .iter()
-                        .map(|d| *d.position().x() as f32)
-                        .sum::<f32>()
-                        / sawfish.len() as f32;
-
+                if let Some(avg_x) = target_x {
                     #[cfg(feature = "logging")]
-                    logln!("SAWFISH AVG X: {}", avg_x);
+                    logln!("{target:?} AVG X: {}", avg_x);
 
                     if avg_x.abs() > TOLERANCE {
-                        let correction = 0.4 * avg_x;
-                        let fwd = 0.05;
+                        let correction = timed_update(&mut pid, &mut last_correction, avg_x);
+                        let fwd = approach_forward(config.approach, avg_x, 0.05);
 
                         let _ = cb
                             .stability_2_speed_set(
@@ -443,9 +582,8 @@ pub async fn gate_run_procedural<
                     } else {
                         let fwd = config.speed;
                         let correction = 0.05;
-                        true_count += 1;
 
-                        if true_count >= config.true_count {
+                        if target_won(&votes, &target, config.vote_threshold) {
                             let _ = cb
                                 .stability_2_speed_set(
                                     correction,
@@ -468,6 +606,8 @@ pub async fn gate_run_procedural<
                     // Fallback search behavior
                     #[cfg(feature = "logging")]
                     logln!("RIGHT: Missing Features, Fallback");
+                    pid.reset();
+                    last_correction = None;
 
                     let correction = 0.2;
                     let fwd = 0.05;
@@ -482,9 +622,183 @@ pub async fn gate_run_procedural<
             }
         }
     }
+    Ok(())
 }
 
+#[derive(Debug, PartialEq, Eq)]
 enum GateState {
+    /// Waiting for enough pole detections to trust that the gate is in view
+    Confirm,
     Align,
     Approach,
 }
+
+impl GateState {
+    /// State after a confirmation frame with `poles` pole detections
+    fn after_confirm(confirm: &mut GateConfirm, poles: usize) -> Result<Self> {
+        Ok(if confirm.push(poles)? {
+            Self::Align
+        } else {
+            Self::Confirm
+        })
+    }
+}
+
+/// [`DetectionConfirm`] that gives up on the gate after `give_up` frames,
+/// 0 for no limit
+#[derive(Debug)]
+struct GateConfirm {
+    confirm: DetectionConfirm,
+    waited: usize,
+    give_up: usize,
+}
+
+impl GateConfirm {
+    fn new(confirm: DetectionConfirm, give_up: usize) -> Self {
+        Self {
+            confirm,
+            waited: 0,
+            give_up,
+        }
+    }
+
+    fn from_config(config: &Config) -> Self {
+        Self::new(
+            DetectionConfirm::new(config.confirm_detections, config.confirm_frames),
+            config.confirm_give_up_frames,
+        )
+    }
+
+    /// Records a frame with `poles` detections, returning if the gate is
+    /// confirmed. Fails once `give_up` frames pass without confirming.
+    fn push(&mut self, poles: usize) -> Result<bool> {
+        if self.confirm.push(poles) {
+            return Ok(true);
+        }
+        self.waited += 1;
+        if self.give_up != 0 && self.waited >= self.give_up {
+            bail!("Gate not confirmed in {} frames", self.waited);
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn align_waits_for_confirmation() {
+        let mut confirm = GateConfirm::new(DetectionConfirm::new(3, 4), 0);
+        // A frame full of spurious poles counts once and ages out of the
+        // window before more frames see the gate
+        let states = [3, 0, 0, 0, 1, 1, 1]
+            .map(|poles| GateState::after_confirm(&mut confirm, poles).unwrap());
+        assert_eq!(
+            states,
+            [
+                GateState::Confirm,
+                GateState::Confirm,
+                GateState::Confirm,
+                GateState::Confirm,
+                GateState::Confirm,
+                GateState::Confirm,
+                GateState::Align,
+            ]
+        );
+
+        let mut skip = GateConfirm::new(DetectionConfirm::new(0, 0), 0);
+        assert_eq!(
+            GateState::after_confirm(&mut skip, 0).unwrap(),
+            GateState::Align
+        );
+    }
+
+    #[test]
+    fn confirmation_gives_up() {
+        let mut confirm = GateConfirm::new(DetectionConfirm::new(2, 4), 3);
+        assert!(!confirm.push(1).unwrap());
+        assert!(!confirm.push(0).unwrap());
+        assert!(confirm.push(0).is_err());
+
+        // A confirmed gate is never given up on
+        let mut confirm = GateConfirm::new(DetectionConfirm::new(1, 1), 1);
+        assert!(confirm.push(1).unwrap());
+    }
+
+    #[test]
+    fn configured_side_picks_creature() {
+        let detection = |identifier, x| {
+            VisualDetection::new(
+                YoloClass {
+                    identifier,
+                    confidence: 0.9,
+                },
+                Offset2D::new(x, 0.0),
+            )
+        };
+        let detections = [
+            detection(Target::Shark, -0.5),
+            detection(Target::Sawfish, 0.4),
+            detection(Target::Sawfish, 0.6),
+        ];
+
+        // Shark on the left, sawfish on the right
+        let left = side_target(&Side::Left, &Side::Left, &Side::Right);
+        assert_eq!(left, Target::Shark);
+        assert_eq!(target_avg_x(&detections, &left), Some(-0.5));
+
+        let right = side_target(&Side::Right, &Side::Left, &Side::Right);
+        assert_eq!(right, Target::Sawfish);
+        assert_eq!(target_avg_x(&detections, &right), Some(0.5));
+
+        // Swapped placement swaps which one the left side aligns to
+        let left = side_target(&Side::Left, &Side::Right, &Side::Left);
+        assert_eq!(target_avg_x(&detections, &left), Some(0.5));
+
+        assert_eq!(target_avg_x(&detections[..1], &Target::Sawfish), None);
+    }
+
+    #[test]
+    fn only_poles_confirm_the_gate() {
+        let detection = |identifier| {
+            VisualDetection::new(
+                YoloClass {
+                    identifier,
+                    confidence: 0.9,
+                },
+                Offset2D::new(0.0, 0.0),
+            )
+        };
+        let creatures = [
+            detection(Target::Shark),
+            detection(Target::Sawfish),
+            detection(Target::Gate),
+            detection(Target::Middle),
+        ];
+        let mut confirm = GateConfirm::new(DetectionConfirm::new(2, 3), 0);
+        assert!((0..5).all(|_| !confirm.push(pole_count(&creatures)).unwrap()));
+
+        let poles = [detection(Target::LeftPole), detection(Target::Shark)];
+        assert_eq!(pole_count(&poles), 1);
+        assert!(!confirm.push(pole_count(&poles)).unwrap());
+        assert!(confirm.push(pole_count(&poles)).unwrap());
+    }
+
+    #[test]
+    fn target_must_win_the_vote() {
+        let mut votes = ClassVoteAccumulator::new(3);
+        assert!(!target_won(&votes, &Target::Shark, 1.5));
+
+        // Sawfish flickering in as a shark look-alike keeps the lead
+        votes.push_frame([(Target::Sawfish, 0.9)]);
+        votes.push_frame([(Target::Shark, 0.6), (Target::Sawfish, 0.8)]);
+        assert!(!target_won(&votes, &Target::Shark, 1.5));
+
+        votes.push_frame([(Target::Shark, 0.9)]);
+        votes.push_frame([(Target::Shark, 0.9)]);
+        assert!(target_won(&votes, &Target::Shark, 1.5));
+        assert!(!target_won(&votes, &Target::Shark, 3.0));
+        assert!(!target_won(&votes, &Target::Sawfish, 0.5));
+    }
+}
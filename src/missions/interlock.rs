@@ -0,0 +1,170 @@
+use std::future::Future;
+
+use anyhow::{bail, Result};
+use tokio::io::WriteHalf;
+use tokio::time::{sleep, timeout, Duration};
+use tokio_serial::SerialStream;
+
+use crate::{comms::control_board::util::ThrusterSpeeds, log_warn, logln};
+
+use super::action_context::{GetControlBoard, GetMainElectronicsBoard};
+
+/// Raw speed commanded to every thruster during the test
+const TEST_SPEED: f32 = 0.1;
+/// How long each phase holds the test speed
+const COMMAND_DURATION: Duration = Duration::from_secs(2);
+/// How long the operator has to arm the vehicle
+const ARM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Arm state observed by the MEB while thrusters were commanded
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InterlockReport {
+    /// Arm state while commanding before the operator armed
+    pub disarmed_phase: Option<bool>,
+    /// Arm state while commanding after the operator armed
+    pub armed_phase: Option<bool>,
+}
+
+impl InterlockReport {
+    /// Passes only if the vehicle was seen disarmed, then armed
+    pub fn verdict(&self) -> Result<()> {
+        match (self.disarmed_phase, self.armed_phase) {
+            (Some(false), Some(true)) => Ok(()),
+            (None, _) | (_, None) => bail!("MEB did not report arm state: {:?}", self),
+            (Some(true), _) => bail!("Vehicle was armed before the disarmed phase"),
+            (_, Some(false)) => bail!("Vehicle never reported armed"),
+        }
+    }
+}
+
+/// Commands a small thruster speed while disarmed, then again after the
+/// operator arms, logging commanded vs armed state for each phase.
+///
+/// Motion while disarmed must be confirmed by the operator, the MEB has no
+/// thruster feedback.
+pub async fn test_interlock<
+    Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>> + GetMainElectronicsBoard,
+>(
+    context: &Con,
+) -> Result<()> {
+    let cb = context.get_control_board();
+    let meb = context.get_main_electronics_board();
+    let mut report = InterlockReport::default();
+
+    logln!("INTERLOCK: commanding {TEST_SPEED} while disarmed, thrusters must NOT spin");
+    report.disarmed_phase = meb.thruster_arm().await;
+    pulse_with(|speeds| cb.raw_speed_set(speeds)).await?;
+    logln!(
+        "INTERLOCK: commanded {TEST_SPEED}, armed = {:?}",
+        report.disarmed_phase
+    );
+
+    logln!("INTERLOCK: arm the vehicle within {ARM_TIMEOUT:?}");
+    let armed = timeout(ARM_TIMEOUT, async {
+        while meb.thruster_arm().await != Some(true) {
+            sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+
+    if armed.is_ok() {
+        logln!("INTERLOCK: commanding {TEST_SPEED} while armed, thrusters should spin");
+        report.armed_phase = meb.thruster_arm().await;
+        pulse_with(|speeds| cb.raw_speed_set(speeds)).await?;
+        logln!(
+            "INTERLOCK: commanded {TEST_SPEED}, armed = {:?}",
+            report.armed_phase
+        );
+    } else {
+        report.armed_phase = meb.thruster_arm().await;
+        log_warn!("INTERLOCK: timed out waiting for arm");
+    }
+
+    report.verdict()
+}
+
+/// Commands [`TEST_SPEED`] for [`COMMAND_DURATION`], then zeroes the
+/// thrusters. The zero still goes out if the test speed command fails.
+async fn pulse_with<F, Fut>(mut set_speeds: F) -> Result<()>
+where
+    F: FnMut(ThrusterSpeeds) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let pulse = async {
+        set_speeds(ThrusterSpeeds::splat(TEST_SPEED)).await?;
+        sleep(COMMAND_DURATION).await;
+        Ok(())
+    }
+    .await;
+    let stop = set_speeds(ThrusterSpeeds::ZERO).await;
+    pulse.and(stop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Calls;
+
+    #[tokio::test(start_paused = true)]
+    async fn pulse_stops_thrusters() {
+        let sent = Calls::new();
+        pulse_with(|speeds| sent.ok(speeds)).await.unwrap();
+        assert_eq!(
+            sent.timed(),
+            [
+                (Duration::ZERO, ThrusterSpeeds::splat(TEST_SPEED)),
+                (COMMAND_DURATION, ThrusterSpeeds::ZERO),
+            ]
+        );
+
+        // A failed test speed command is still followed by a stop
+        let sent = Calls::new();
+        let res = pulse_with(|speeds| {
+            sent.push(speeds);
+            std::future::ready(if speeds == ThrusterSpeeds::ZERO {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("No ack"))
+            })
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(
+            sent.values(),
+            [ThrusterSpeeds::splat(TEST_SPEED), ThrusterSpeeds::ZERO]
+        );
+    }
+
+    #[test]
+    fn disarmed_then_armed_passes() {
+        let report = InterlockReport {
+            disarmed_phase: Some(false),
+            armed_phase: Some(true),
+        };
+        assert!(report.verdict().is_ok());
+    }
+
+    #[test]
+    fn never_armed_fails() {
+        let report = InterlockReport {
+            disarmed_phase: Some(false),
+            armed_phase: Some(false),
+        };
+        assert!(report.verdict().is_err());
+
+        let report = InterlockReport {
+            disarmed_phase: Some(false),
+            armed_phase: None,
+        };
+        assert!(report.verdict().is_err());
+    }
+
+    #[test]
+    fn armed_from_start_fails() {
+        let report = InterlockReport {
+            disarmed_phase: Some(true),
+            armed_phase: Some(true),
+        };
+        assert!(report.verdict().is_err());
+    }
+}
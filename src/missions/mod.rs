@@ -1,19 +1,34 @@
 pub mod action;
 pub mod action_context;
+pub mod align;
 pub mod basic;
+pub mod bench_latency;
 pub mod bin;
+pub mod budget;
+pub mod calibration;
+pub mod center;
 pub mod coinflip;
 pub mod comms;
+pub mod completion;
+pub mod emergency;
 pub mod example;
 pub mod extra;
 pub mod fire_torpedo;
 pub mod gate;
+pub mod interlock;
 pub mod meb;
 pub mod movement;
 pub mod octagon;
 pub mod path_align;
+pub mod pid;
+pub mod plan;
+pub mod preamble;
+pub mod registry;
 pub mod reset_torpedo;
+pub mod robot;
+pub mod sequence;
 pub mod slalom;
 pub mod sonar;
 pub mod spin;
+pub mod thruster_pattern;
 pub mod vision;
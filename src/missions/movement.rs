@@ -1,4 +1,5 @@
 use crate::comms::control_board::ControlBoard;
+use crate::comms::control_board::ANGLES_TIMEOUT;
 use crate::comms::control_board::LAST_YAW;
 use crate::logln;
 use crate::vision::DrawRect2d;
@@ -127,20 +128,10 @@ impl<T: GetControlBoard<WriteHalf<SerialStream>>> ActionExec<Result<()>>
         }
 
         let cntrl_board = self.context.get_control_board();
-        let mut cur_angles = cntrl_board.responses().get_angles().await;
-        while cur_angles.is_none() {
-            cur_angles = cntrl_board.responses().get_angles().await;
-        }
+        let yaw = cntrl_board.wait_for_yaw(Some(ANGLES_TIMEOUT)).await?;
 
         cntrl_board
-            .stability_2_speed_set(
-                0.0,
-                speed,
-                0.0,
-                0.0,
-                *cur_angles.unwrap().yaw(),
-                self.target_depth,
-            )
+            .stability_2_speed_set(0.0, speed, 0.0, 0.0, yaw, self.target_depth)
             .await
     }
 }
@@ -1,41 +1,80 @@
+use anyhow::{Context, Result};
 use tokio::io::WriteHalf;
 use tokio::time::{sleep, Duration};
 use tokio_serial::SerialStream;
 
 use crate::config::path_align::Config;
 use crate::config::ColorProfile;
-use crate::{missions::vision::VisionNormBottomAngle, vision::path_cv::PathCV};
+use crate::{
+    comms::control_board::ANGLES_TIMEOUT,
+    missions::vision::VisionNormSelectedAngle,
+    vision::path_cv::{mean_segment, PathCV},
+};
 
 use super::{
     action::ActionExec,
-    action_context::{BottomCamIO, GetControlBoard, GetMainElectronicsBoard},
+    action_context::{BottomCamIO, FrontCamIO, GetControlBoard, GetMainElectronicsBoard},
+    basic::VisionErrors,
 };
 
+/// Consecutive detection count, gating steering until it reaches `steer_after`
+#[derive(Debug)]
+struct DetectionStreak {
+    count: u8,
+    steer_after: u8,
+}
+
+impl DetectionStreak {
+    const fn new(steer_after: u8) -> Self {
+        Self {
+            count: 0,
+            steer_after,
+        }
+    }
+
+    const fn count(&self) -> u8 {
+        self.count
+    }
+
+    /// Records one frame, returning true if steering should act on it
+    fn record(&mut self, detected: bool) -> bool {
+        if detected {
+            self.count = self.count.saturating_add(1);
+        } else {
+            self.count = 0;
+        }
+        detected && self.count >= self.steer_after
+    }
+}
+
 pub async fn path_align_procedural<
-    Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>> + GetMainElectronicsBoard + BottomCamIO,
+    Con: Send
+        + Sync
+        + GetControlBoard<WriteHalf<SerialStream>>
+        + GetMainElectronicsBoard
+        + FrontCamIO
+        + BottomCamIO,
 >(
     context: &Con,
     config: &Config,
     color_profile: &ColorProfile,
-) {
+) -> Result<()> {
     #[cfg(feature = "logging")]
     logln!("Starting path align");
 
     let cb = context.get_control_board();
     let _ = cb.bno055_periodic_read(true).await;
-    let mut vision_norm_bottom = VisionNormBottomAngle::<Con, PathCV, f64>::new(
+    let mut vision_norm_bottom = VisionNormSelectedAngle::<Con, PathCV, f64>::new(
         context,
-        PathCV::from_color_profile(color_profile),
+        config.camera,
+        PathCV::from_color_profile(color_profile, config.contour_select)
+            .with_max_segments(config.segments),
     );
 
-    let initial_yaw = loop {
-        if let Some(initial_angle) = cb.responses().get_angles().await {
-            break *initial_angle.yaw();
-        } else {
-            #[cfg(feature = "logging")]
-            logln!("Failed to get initial angle");
-        }
-    };
+    let initial_yaw = cb
+        .wait_for_yaw(Some(ANGLES_TIMEOUT))
+        .await
+        .context("Abandoning path align")?;
 
     // let _ = cb
     //     .stability_1_speed_set(config.speed, 0.1, 0.0, 0.0, 0.0, config.depth)
@@ -57,14 +96,15 @@ pub async fn path_align_procedural<
         )
         .await;
 
+    let mut vision_errors = VisionErrors::default();
     let mut last_set_yaw = initial_yaw;
-    let mut consec_detections = 0;
+    let mut streak = DetectionStreak::new(config.steer_after);
 
     #[cfg(feature = "logging")]
     logln!("Starting path detection");
 
     loop {
-        if consec_detections >= config.detections {
+        if streak.count() >= config.detections {
             #[cfg(feature = "logging")]
             logln!("Finished path align");
 
@@ -74,35 +114,26 @@ pub async fn path_align_procedural<
         if let Some(current_angle) = cb.responses().get_angles().await {
             let current_yaw = *current_angle.yaw();
 
-            // For the opencv impl of path detection, the returned vector is guaranteed to contain 1 item
-            #[allow(unused_variables)]
-            let detections = vision_norm_bottom.execute().await.unwrap_or_else(|e| {
-                #[cfg(feature = "logging")]
-                logln!(
-                    "Getting path detection resulted in error: `{e}`\n\tUsing empty detection vec"
-                );
-                vec![]
-            });
+            // The opencv path detector returns one undetected entry, or one entry per path segment
+            let detections = vision_errors
+                .check_or_stop(vision_norm_bottom.execute().await, cb)
+                .await?;
 
-            let mut positions = detections
+            let positions: Vec<_> = detections
                 .into_iter()
-                .filter_map(|d| d.class().then_some(d.position().clone()));
-
-            let x;
-            let y;
-            let yaw;
-
-            if let Some(position) = positions.next() {
-                x = *position.x() as f32;
-                y = -(*position.y() as f32);
-                yaw = current_yaw + (*position.angle() * -1.0) as f32;
+                .filter_map(|d| d.class().then_some(d.position().clone()))
+                .collect();
 
-                last_set_yaw = yaw;
-                consec_detections += 1;
-            } else {
-                consec_detections = 0;
+            // Track the streak on every frame, but only steer once it is long enough
+            let position = mean_segment(&positions);
+            let steer = streak.record(position.is_some());
+            let Some(position) = position.filter(|_| steer) else {
                 continue;
-            }
+            };
+
+            let x = *position.x() as f32;
+            let y = -(*position.y() as f32);
+            last_set_yaw = current_yaw + (*position.angle() * -1.0) as f32;
 
             #[allow(unused_variables)]
             if let Err(e) = cb
@@ -110,20 +141,21 @@ pub async fn path_align_procedural<
                 .await
             {
                 #[cfg(feature = "logging")]
-                logln!("SASSIST2 command to cb resulted in error: `{e}`");
+                log_warn!("SASSIST2 command to cb resulted in error: `{e}`");
             }
         } else {
             #[cfg(feature = "logging")]
-            logln!("Failed to get current angle");
+            log_warn!("Failed to get current angle");
         }
 
         #[cfg(feature = "logging")]
-        logln!("Positive detection count: {consec_detections}");
+        logln!("Positive detection count: {}", streak.count());
     }
     let _ = cb
         .stability_2_speed_set(0.0, 1.0, 0.0, 0.0, last_set_yaw, config.depth)
         .await;
     sleep(Duration::from_secs(1)).await;
+    Ok(())
 }
 
 pub async fn static_align_procedural<
@@ -138,12 +170,11 @@ pub async fn static_align_procedural<
     let cb = context.get_control_board();
     let _ = cb.bno055_periodic_read(true).await;
 
-    let initial_yaw = loop {
-        if let Some(initial_angle) = cb.responses().get_angles().await {
-            break *initial_angle.yaw();
-        } else {
-            #[cfg(feature = "logging")]
-            logln!("Failed to get initial angle");
+    let initial_yaw = match cb.wait_for_yaw(Some(ANGLES_TIMEOUT)).await {
+        Ok(yaw) => yaw,
+        Err(e) => {
+            log_warn!("Abandoning path align: {e:#}");
+            return;
         }
     };
 
@@ -153,7 +184,7 @@ pub async fn static_align_procedural<
         .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, config.depth)
         .await;
 
-    sleep(Duration::from_secs(config.yaw_wait)).await;
+    sleep(config.yaw_wait.to_duration()).await;
 
     let _ = cb
         .stability_2_speed_set(
@@ -166,9 +197,28 @@ pub async fn static_align_procedural<
         )
         .await;
 
-    sleep(Duration::from_secs(config.forward_duration)).await;
+    sleep(config.forward_duration.to_duration()).await;
 
     let _ = cb
         .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, target_yaw, config.depth)
         .await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steering_waits_for_streak() {
+        let mut streak = DetectionStreak::new(3);
+        assert!(!streak.record(true));
+        assert!(!streak.record(true));
+        assert!(streak.record(true));
+        assert!(streak.record(true));
+
+        // A miss restarts the wait
+        assert!(!streak.record(false));
+        assert!(!streak.record(true));
+        assert_eq!(streak.count(), 1);
+    }
+}
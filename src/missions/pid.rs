@@ -0,0 +1,126 @@
+use crate::config::pid::Config;
+
+/// Correction loop turning a normalized error into a speed
+///
+/// The integral is capped at `max_integral` either way, so a target held
+/// off-center does not wind up a correction that overshoots once it moves.
+/// With an output limit, the integral also stops growing while the output
+/// is saturated.
+#[derive(Debug, Clone)]
+pub struct Pid {
+    config: Config,
+    max_output: Option<f32>,
+    integral: f32,
+    last_error: Option<f32>,
+}
+
+impl Pid {
+    pub const fn new(config: Config) -> Self {
+        Self {
+            config,
+            max_output: None,
+            integral: 0.0,
+            last_error: None,
+        }
+    }
+
+    /// Clamps every output to within `max_output` of zero
+    pub const fn with_max_output(mut self, max_output: f32) -> Self {
+        self.max_output = Some(max_output);
+        self
+    }
+
+    /// Correction for `error`, `dt` seconds after the last update
+    ///
+    /// The derivative term is zero on the first update after a reset.
+    pub fn update(&mut self, error: f32, dt: f32) -> f32 {
+        let derivative = match self.last_error {
+            Some(last_error) if dt > 0.0 => (error - last_error) / dt,
+            _ => 0.0,
+        };
+        self.last_error = Some(error);
+
+        let max_integral = self.config.max_integral.abs();
+        let integral = (self.integral + error * dt).clamp(-max_integral, max_integral);
+        let output =
+            self.config.kp * error + self.config.ki * integral + self.config.kd * derivative;
+
+        match self.max_output {
+            Some(max_output) => {
+                if output.abs() < max_output {
+                    self.integral = integral;
+                }
+                output.clamp(-max_output, max_output)
+            }
+            None => {
+                self.integral = integral;
+                output
+            }
+        }
+    }
+
+    /// Forgets the integral and last error, e.g. after losing the target
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.last_error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integral_windup_is_capped() {
+        let mut pid = Pid::new(Config {
+            kp: 0.0,
+            ki: 1.0,
+            kd: 0.0,
+            max_integral: 0.5,
+        });
+        for _ in 0..10 {
+            pid.update(1.0, 1.0);
+        }
+        assert_eq!(pid.update(1.0, 1.0), 0.5);
+
+        // Unwinds from the cap, not from ten seconds of error
+        assert_eq!(pid.update(-1.0, 0.5), 0.0);
+
+        pid.reset();
+        assert_eq!(pid.update(0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn derivative_on_step() {
+        let mut pid = Pid::new(Config {
+            kp: 0.0,
+            ki: 0.0,
+            kd: 1.0,
+            max_integral: 0.0,
+        });
+        assert_eq!(pid.update(0.0, 0.1), 0.0);
+        assert_eq!(pid.update(1.0, 0.1), 10.0);
+        assert_eq!(pid.update(1.0, 0.1), 0.0);
+
+        // No derivative kick on the first update after a reset
+        pid.reset();
+        assert_eq!(pid.update(1.0, 0.1), 0.0);
+    }
+
+    #[test]
+    fn saturated_output_does_not_wind_up() {
+        let mut pid = Pid::new(Config {
+            kp: 1.0,
+            ki: 1.0,
+            kd: 0.0,
+            max_integral: f32::INFINITY,
+        })
+        .with_max_output(0.5);
+        for _ in 0..10 {
+            assert_eq!(pid.update(1.0, 1.0), 0.5);
+        }
+
+        // Nothing was integrated while saturated
+        assert_eq!(pid.update(0.0, 1.0), 0.0);
+    }
+}
@@ -0,0 +1,209 @@
+use std::future::Future;
+
+use anyhow::Result;
+
+use crate::{
+    config::plan::{Condition, Step},
+    logln,
+};
+
+use super::action::{Action, ActionConditional, ActionExec};
+
+/// Runs the missions of a plan and answers its branch conditions
+pub trait PlanContext: Send + Sync {
+    fn run_mission(&self, mission: &str) -> impl Future<Output = Result<()>>;
+    fn check(&self, condition: &Condition) -> bool;
+}
+
+/// Builds the action that runs `steps` in order, stopping at the first error
+pub fn build_plan<'a, C: PlanContext>(context: &'a C, steps: &[Step]) -> PlanSteps<'a, C> {
+    PlanSteps::new(
+        steps
+            .iter()
+            .map(|step| match step {
+                Step::Mission(mission) => PlanStep::Mission(RunMission::new(context, mission)),
+                Step::Branch(branch) => PlanStep::Branch(ActionConditional::new(
+                    CheckCondition::new(context, &branch.condition),
+                    build_plan(context, &branch.then),
+                    build_plan(context, &branch.otherwise),
+                )),
+            })
+            .collect(),
+    )
+}
+
+/// Runs one mission of a plan
+#[derive(Debug)]
+pub struct RunMission<'a, C> {
+    context: &'a C,
+    mission: String,
+}
+
+impl<C> Action for RunMission<'_, C> {}
+
+impl<'a, C> RunMission<'a, C> {
+    pub fn new(context: &'a C, mission: &str) -> Self {
+        Self {
+            context,
+            mission: mission.to_string(),
+        }
+    }
+}
+
+impl<C: PlanContext> ActionExec<Result<()>> for RunMission<'_, C> {
+    async fn execute(&mut self) -> Result<()> {
+        logln!("Plan running {}", self.mission);
+        self.context.run_mission(&self.mission).await
+    }
+}
+
+/// Checks a branch condition against the run so far
+#[derive(Debug)]
+pub struct CheckCondition<'a, C> {
+    context: &'a C,
+    condition: Condition,
+}
+
+impl<C> Action for CheckCondition<'_, C> {}
+
+impl<'a, C> CheckCondition<'a, C> {
+    pub fn new(context: &'a C, condition: &Condition) -> Self {
+        Self {
+            context,
+            condition: condition.clone(),
+        }
+    }
+}
+
+impl<C: PlanContext> ActionExec<bool> for CheckCondition<'_, C> {
+    async fn execute(&mut self) -> bool {
+        let res = self.context.check(&self.condition);
+        logln!("Plan condition {:?} is {res}", self.condition);
+        res
+    }
+}
+
+/// One built plan [`Step`]
+#[derive(Debug)]
+pub enum PlanStep<'a, C> {
+    Mission(RunMission<'a, C>),
+    Branch(ActionConditional<CheckCondition<'a, C>, PlanSteps<'a, C>, PlanSteps<'a, C>>),
+}
+
+impl<C> Action for PlanStep<'_, C> {}
+
+impl<C: PlanContext> ActionExec<Result<()>> for PlanStep<'_, C> {
+    async fn execute(&mut self) -> Result<()> {
+        match self {
+            Self::Mission(mission) => mission.execute().await,
+            // Branches nest plans, so their futures are boxed
+            Self::Branch(branch) => Box::pin(branch.execute()).await,
+        }
+    }
+}
+
+/// Plan steps, run in order until one fails
+#[derive(Debug)]
+pub struct PlanSteps<'a, C> {
+    steps: Vec<PlanStep<'a, C>>,
+}
+
+impl<C> Action for PlanSteps<'_, C> {}
+
+impl<'a, C> PlanSteps<'a, C> {
+    pub const fn new(steps: Vec<PlanStep<'a, C>>) -> Self {
+        Self { steps }
+    }
+}
+
+impl<C: PlanContext> ActionExec<Result<()>> for PlanSteps<'_, C> {
+    async fn execute(&mut self) -> Result<()> {
+        for step in &mut self.steps {
+            step.execute().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use anyhow::bail;
+
+    use super::*;
+    use crate::config::Side;
+
+    /// Records missions, with a fixed coinflip result
+    struct MockContext {
+        coinflip: Option<Side>,
+        ran: Mutex<Vec<String>>,
+    }
+
+    impl MockContext {
+        fn new(coinflip: Option<Side>) -> Self {
+            Self {
+                coinflip,
+                ran: Mutex::new(vec![]),
+            }
+        }
+    }
+
+    impl PlanContext for MockContext {
+        async fn run_mission(&self, mission: &str) -> Result<()> {
+            self.ran.lock().unwrap().push(mission.to_string());
+            if mission == "invalid" {
+                bail!("Invalid argument: [{mission}]")
+            }
+            Ok(())
+        }
+
+        fn check(&self, condition: &Condition) -> bool {
+            match condition {
+                Condition::Coinflip(side) => self.coinflip.as_ref() == Some(side),
+            }
+        }
+    }
+
+    fn plan(toml: &str) -> Vec<Step> {
+        #[derive(serde::Deserialize)]
+        struct Plan {
+            steps: Vec<Step>,
+        }
+        toml::from_str::<Plan>(toml).unwrap().steps
+    }
+
+    const COINFLIP_PLAN: &str = r#"steps = [
+        "coinflip",
+        { if = { coinflip = "Left" }, then = ["gate_left"], else = [
+            { if = { coinflip = "Right" }, then = ["gate_right"], else = [] },
+        ] },
+        "spin",
+    ]"#;
+
+    #[tokio::test]
+    async fn condition_picks_branch() {
+        let steps = plan(COINFLIP_PLAN);
+
+        for (coinflip, ran) in [
+            (Some(Side::Left), vec!["coinflip", "gate_left", "spin"]),
+            (Some(Side::Right), vec!["coinflip", "gate_right", "spin"]),
+            (None, vec!["coinflip", "spin"]),
+        ] {
+            let context = MockContext::new(coinflip);
+            build_plan(&context, &steps).execute().await.unwrap();
+            assert_eq!(*context.ran.lock().unwrap(), ran);
+        }
+    }
+
+    #[tokio::test]
+    async fn error_stops_plan() {
+        let steps = plan(
+            r#"steps = [{ if = { coinflip = "Left" }, then = ["invalid"], else = [] }, "spin"]"#,
+        );
+
+        let context = MockContext::new(Some(Side::Left));
+        assert!(build_plan(&context, &steps).execute().await.is_err());
+        assert_eq!(*context.ran.lock().unwrap(), ["invalid"]);
+    }
+}
@@ -0,0 +1,229 @@
+use std::future::Future;
+
+use anyhow::{bail, Result};
+use tokio::{
+    io::AsyncWriteExt,
+    time::{sleep, timeout, Duration, Instant},
+};
+
+use crate::{
+    comms::control_board::{
+        soft_start::RAMP_STEP, util::ThrusterSpeeds, ControlBoard, SensorStatuses, LAST_DEPTH,
+        LAST_YAW,
+    },
+    config::preamble::Config,
+    logln,
+};
+
+use super::registry::Mission;
+
+/// Time between sensor status queries while waiting for ready
+const SENSOR_POLL: Duration = Duration::from_millis(250);
+
+/// Puts the sub in a known state before a mission.
+///
+/// Zeroes the thrusters, waits for the IMU and depth sensor, then ramps from
+/// the last commanded depth (the surface if there was none) to
+/// `config.depth` at the current heading.
+pub async fn mission_preamble<T: AsyncWriteExt + Unpin>(
+    cb: &ControlBoard<T>,
+    config: &Config,
+) -> Result<()> {
+    logln!("Running mission preamble");
+    let from_depth = LAST_DEPTH.lock().unwrap().unwrap_or(0.0);
+
+    preamble_with(
+        || cb.raw_speed_set(ThrusterSpeeds::ZERO),
+        || async {
+            Ok(matches!(
+                cb.sensor_status_query().await?,
+                SensorStatuses::AllGood
+            ))
+        },
+        |depth| async move {
+            let yaw = match cb.responses().get_angles().await {
+                Some(angles) => *angles.yaw(),
+                None => LAST_YAW.lock().unwrap().unwrap_or_default(),
+            };
+            cb.stability_2_speed_set(0.0, 0.0, 0.0, 0.0, yaw, depth)
+                .await
+        },
+        from_depth,
+        config,
+    )
+    .await
+}
+
+/// Runs `body`, after `preamble` if it is enabled and `mission` [dives](Mission::dives)
+///
+/// A failed preamble skips the body.
+pub async fn with_preamble<P, PFut, B, BFut>(
+    mission: Mission,
+    config: &Config,
+    preamble: P,
+    body: B,
+) -> Result<()>
+where
+    P: FnOnce() -> PFut,
+    PFut: Future<Output = Result<()>>,
+    B: FnOnce() -> BFut,
+    BFut: Future<Output = Result<()>>,
+{
+    if config.enabled && mission.dives() {
+        preamble().await?;
+    }
+    body().await
+}
+
+/// [`mission_preamble`] with the control board abstracted out
+async fn preamble_with<Z, ZFut, S, SFut, D, DFut>(
+    zero: Z,
+    mut sensors_ready: S,
+    mut set_depth: D,
+    from_depth: f32,
+    config: &Config,
+) -> Result<()>
+where
+    Z: FnOnce() -> ZFut,
+    ZFut: Future<Output = Result<()>>,
+    S: FnMut() -> SFut,
+    SFut: Future<Output = Result<bool>>,
+    D: FnMut(f32) -> DFut,
+    DFut: Future<Output = Result<()>>,
+{
+    zero().await?;
+
+    let sensor_timeout = config.sensor_timeout_secs.to_duration();
+    let ready = timeout(sensor_timeout, async {
+        loop {
+            let poll_start = Instant::now();
+            if sensors_ready().await? {
+                return Ok::<_, anyhow::Error>(());
+            }
+            sleep(SENSOR_POLL.saturating_sub(poll_start.elapsed())).await;
+        }
+    })
+    .await;
+    match ready {
+        Ok(res) => res?,
+        Err(_) => bail!("Sensors not ready within {sensor_timeout:?}"),
+    }
+
+    let ramp = config.depth_ramp_secs.to_duration();
+    let steps = (ramp.as_secs_f32() / RAMP_STEP.as_secs_f32())
+        .ceil()
+        .max(1.0) as u32;
+    for step in 1..=steps {
+        let fraction = step as f32 / steps as f32;
+        set_depth(from_depth + (config.depth - from_depth) * fraction).await?;
+        if step != steps {
+            sleep(RAMP_STEP).await;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::DurationSecs, test_util::Calls};
+
+    #[tokio::test(start_paused = true)]
+    async fn preamble_before_body() {
+        let events = Calls::new();
+        let config = Config {
+            enabled: true,
+            depth: -1.0,
+            depth_ramp_secs: DurationSecs(0.1),
+            sensor_timeout_secs: DurationSecs(5.0),
+        };
+
+        preamble_with(
+            || events.ok("zero".to_string()),
+            || {
+                events.push("sensors".to_string());
+                // Depth sensor comes up on the second query
+                let polls = events.values().iter().filter(|e| *e == "sensors").count();
+                std::future::ready(Ok(polls >= 2))
+            },
+            |depth| events.ok(format!("depth {depth}")),
+            0.0,
+            &config,
+        )
+        .await
+        .unwrap();
+        events.push("body".to_string());
+
+        assert_eq!(
+            events.values(),
+            [
+                "zero",
+                "sensors",
+                "sensors",
+                "depth -0.5",
+                "depth -1",
+                "body"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn preamble_only_before_diving_missions() {
+        let enabled = Config {
+            enabled: true,
+            ..Config::default()
+        };
+        for (mission, config, expected) in [
+            (Mission::GateRunYolo, &enabled, &["preamble", "body"][..]),
+            (Mission::TorpedoOnly, &enabled, &["body"]),
+            (Mission::GateRunYolo, &Config::default(), &["body"]),
+        ] {
+            let events = Calls::new();
+            with_preamble(
+                mission,
+                config,
+                || events.ok("preamble"),
+                || events.ok("body"),
+            )
+            .await
+            .unwrap();
+            assert_eq!(events.values(), expected, "{mission:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_preamble_skips_body() {
+        let config = Config {
+            enabled: true,
+            ..Config::default()
+        };
+        let res = with_preamble(
+            Mission::Hold,
+            &config,
+            || async { bail!("Sensors not ready") },
+            || async { panic!("Body ran after a failed preamble") },
+        )
+        .await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sensors_never_ready() {
+        let config = Config {
+            enabled: true,
+            ..Config::default()
+        };
+        let res = preamble_with(
+            || async { Ok(()) },
+            || async { Ok(false) },
+            |_| async { bail!("Depth set without ready sensors") },
+            0.0,
+            &config,
+        )
+        .await;
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .starts_with("Sensors not ready"));
+    }
+}
@@ -0,0 +1,259 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Error, Result};
+
+/// Missions runnable by name from the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mission {
+    Arm,
+    ThrusterPattern,
+    Empty,
+    DepthTest,
+    TravelTest,
+    TareYaw,
+    SurfaceTest,
+    Descend,
+    GateRunCoinflip,
+    GateRunYolo,
+    GateRunReckon,
+    StartCam,
+    PathAlign,
+    StaticAlign,
+    Example,
+    PidTest,
+    Octagon,
+    Spin,
+    TorpedoOnly,
+    Coinflip,
+    Forever,
+    OpenCamTest,
+    SlalomLeft,
+    SlalomRight,
+    Sonar,
+    SonarSweep,
+    TestInterlock,
+    Bin,
+    ZedTest,
+    CenterFront,
+    CenterBottom,
+    CalibrateFront,
+    CalibrateBottom,
+    Hold,
+    BenchLatency,
+}
+
+/// Every mission with the names it answers to, listed under the first
+///
+/// Names are matched by [`normalize`], so `_` and `-` spellings need no alias.
+const MISSIONS: &[(Mission, &[&str])] = &[
+    (Mission::Arm, &["arm"]),
+    (Mission::ThrusterPattern, &["thruster_pattern"]),
+    (Mission::Empty, &["empty"]),
+    (Mission::DepthTest, &["depth_test"]),
+    (Mission::TravelTest, &["travel_test"]),
+    (Mission::TareYaw, &["tare_yaw"]),
+    (Mission::SurfaceTest, &["surface_test", "surface"]),
+    (Mission::Descend, &["descend", "forward"]),
+    (Mission::GateRunCoinflip, &["gate_run_coinflip"]),
+    (Mission::GateRunYolo, &["gate_run_yolo"]),
+    (Mission::GateRunReckon, &["gate_run_reckon"]),
+    (Mission::StartCam, &["start_cam"]),
+    (Mission::PathAlign, &["path_align"]),
+    (Mission::StaticAlign, &["static_align"]),
+    (Mission::Example, &["example"]),
+    (Mission::PidTest, &["pid_test"]),
+    (Mission::Octagon, &["octagon"]),
+    (Mission::Spin, &["spin"]),
+    (Mission::TorpedoOnly, &["torpedo_only"]),
+    (Mission::Coinflip, &["coinflip"]),
+    (Mission::Forever, &["forever", "infinite"]),
+    (Mission::OpenCamTest, &["open_cam_test"]),
+    (Mission::SlalomLeft, &["slalom_left"]),
+    (Mission::SlalomRight, &["slalom_right"]),
+    (Mission::Sonar, &["sonar"]),
+    (Mission::SonarSweep, &["sonar_sweep"]),
+    (Mission::TestInterlock, &["test_interlock"]),
+    (Mission::Bin, &["bin"]),
+    (Mission::ZedTest, &["zed_test"]),
+    (Mission::CenterFront, &["center_front"]),
+    (Mission::CenterBottom, &["center_bottom"]),
+    (Mission::CalibrateFront, &["calibrate_front"]),
+    (Mission::CalibrateBottom, &["calibrate_bottom"]),
+    (Mission::Hold, &["hold"]),
+    (Mission::BenchLatency, &["bench_latency"]),
+];
+
+/// Largest edit distance from a known name still suggested as a typo
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
+impl Mission {
+    /// Whether the mission accepts a `name:parameter` argument
+    pub const fn takes_parameter(self) -> bool {
+        matches!(self, Self::Hold | Self::BenchLatency)
+    }
+
+    /// Whether the mission takes the sub underwater, so it starts with the
+    /// mission preamble
+    pub const fn dives(self) -> bool {
+        matches!(
+            self,
+            Self::DepthTest
+                | Self::TravelTest
+                | Self::Descend
+                | Self::GateRunCoinflip
+                | Self::GateRunYolo
+                | Self::GateRunReckon
+                | Self::PathAlign
+                | Self::StaticAlign
+                | Self::Example
+                | Self::PidTest
+                | Self::Octagon
+                | Self::Spin
+                | Self::Coinflip
+                | Self::SlalomLeft
+                | Self::SlalomRight
+                | Self::SonarSweep
+                | Self::Bin
+                | Self::CenterFront
+                | Self::CenterBottom
+                | Self::Hold
+        )
+    }
+
+    /// Main name of every mission, in listing order
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        MISSIONS.iter().map(|(_, names)| names[0])
+    }
+
+    /// Parses a `name` or `name:parameter` argument
+    pub fn parse_argument(arg: &str) -> Result<(Self, Option<&str>)> {
+        let (name, parameter) = match arg.split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (arg, None),
+        };
+        let mission: Self = name.parse()?;
+        if parameter.is_some() && !mission.takes_parameter() {
+            bail!("Mission [{name}] takes no parameter: [{arg}]");
+        }
+        Ok((mission, parameter))
+    }
+}
+
+impl FromStr for Mission {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = normalize(s);
+        MISSIONS
+            .iter()
+            .find(|(_, names)| names.iter().any(|known| normalize(known) == name))
+            .map(|(mission, _)| *mission)
+            .ok_or_else(|| match closest_name(&name) {
+                Some(closest) => anyhow!("Invalid argument: [{s}], did you mean [{closest}]?"),
+                None => anyhow!("Invalid argument: [{s}]"),
+            })
+    }
+}
+
+/// Lowercase `name` without `_` or `-` separators
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '_' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Known name nearest to normalized `name`, if it is close enough to be a typo
+fn closest_name(name: &str) -> Option<&'static str> {
+    MISSIONS
+        .iter()
+        .flat_map(|(_, names)| names.iter())
+        .map(|known| (edit_distance(name, &normalize(known)), *known))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+/// Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != *b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn names_are_unique() {
+        let mut seen = HashSet::new();
+        for (mission, names) in MISSIONS {
+            for name in names.iter() {
+                // A repeat after normalizing would shadow a later entry
+                assert!(seen.insert(normalize(name)), "{name} listed twice");
+                assert_eq!(name.parse::<Mission>().unwrap(), *mission);
+            }
+        }
+    }
+
+    #[test]
+    fn separators_are_ignored() {
+        for spelling in ["surface_test", "surface-test", "surfacetest", "SURFACE"] {
+            assert_eq!(spelling.parse::<Mission>().unwrap(), Mission::SurfaceTest);
+        }
+    }
+
+    #[test]
+    fn parses_aliases_and_parameters() {
+        assert_eq!("Depth-Test".parse::<Mission>().unwrap(), Mission::DepthTest);
+        assert_eq!(
+            Mission::parse_argument("hold:12.5").unwrap(),
+            (Mission::Hold, Some("12.5"))
+        );
+        assert_eq!(
+            Mission::parse_argument("forever").unwrap(),
+            (Mission::Forever, None)
+        );
+        assert!(Mission::parse_argument("spin:3").is_err());
+    }
+
+    #[test]
+    fn only_underwater_missions_dive() {
+        for name in [
+            "arm",
+            "tare_yaw",
+            "surface_test",
+            "calibrate_front",
+            "sonar",
+        ] {
+            assert!(!name.parse::<Mission>().unwrap().dives(), "{name}");
+        }
+        for name in ["gate_run_yolo", "slalom_left", "hold", "descend"] {
+            assert!(name.parse::<Mission>().unwrap().dives(), "{name}");
+        }
+    }
+
+    #[test]
+    fn unknown_name_suggests_closest() {
+        let err = "gate_run_yolp".parse::<Mission>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid argument: [gate_run_yolp], did you mean [gate_run_yolo]?"
+        );
+
+        let err = "nonsense_mission".parse::<Mission>().unwrap_err();
+        assert_eq!(err.to_string(), "Invalid argument: [nonsense_mission]");
+    }
+}
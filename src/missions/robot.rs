@@ -0,0 +1,141 @@
+use std::future::Future;
+
+use anyhow::Result;
+use tokio::{
+    io::AsyncWriteExt,
+    time::{sleep, Duration},
+};
+
+use crate::comms::control_board::{ControlBoard, ANGLES_TIMEOUT};
+
+/// Control board and initial heading shared by mission steps
+///
+/// Covers the read-yaw then timed stability 2 drive that missions otherwise
+/// repeat inline.
+#[derive(Debug)]
+pub struct Robot<'a, T: AsyncWriteExt + Unpin + Send> {
+    cb: &'a ControlBoard<T>,
+    initial_yaw: f32,
+}
+
+impl<'a, T: 'static + AsyncWriteExt + Unpin + Send> Robot<'a, T> {
+    /// Reads the current yaw as the initial heading, see [`Self::get_yaw`]
+    pub async fn new(cb: &'a ControlBoard<T>) -> Result<Self> {
+        let mut this = Self {
+            cb,
+            initial_yaw: 0.0,
+        };
+        this.get_yaw().await?;
+        Ok(this)
+    }
+
+    pub const fn cb(&self) -> &'a ControlBoard<T> {
+        self.cb
+    }
+
+    pub const fn initial_yaw(&self) -> f32 {
+        self.initial_yaw
+    }
+
+    /// Waits up to [`ANGLES_TIMEOUT`] for an IMU reading, storing its yaw as
+    /// the initial heading
+    pub async fn get_yaw(&mut self) -> Result<f32> {
+        let _ = self.cb.bno055_periodic_read(true).await;
+        self.initial_yaw = self.cb.wait_for_yaw(Some(ANGLES_TIMEOUT)).await?;
+        Ok(self.initial_yaw)
+    }
+
+    /// Drives forward at `speed` on the initial heading for `duration`, then stops at `depth`
+    pub async fn stb2fd_time(&self, speed: f32, depth: f32, duration: Duration) -> Result<()> {
+        stb2fd_with(
+            |speed| {
+                self.cb
+                    .stability_2_speed_set(0.0, speed, 0.0, 0.0, self.initial_yaw, depth)
+            },
+            || {
+                self.cb
+                    .stability_1_speed_set(0.0, 0.0, 0.0, 0.0, 0.0, depth)
+            },
+            speed,
+            duration,
+        )
+        .await
+    }
+}
+
+/// [`Robot::stb2fd_time`] with the control board abstracted out
+async fn stb2fd_with<D, DFut, S, SFut>(
+    mut drive: D,
+    mut stop: S,
+    speed: f32,
+    duration: Duration,
+) -> Result<()>
+where
+    D: FnMut(f32) -> DFut,
+    DFut: Future<Output = Result<()>>,
+    S: FnMut() -> SFut,
+    SFut: Future<Output = Result<()>>,
+{
+    if let Err(e) = drive(speed).await {
+        // Part of a soft-start ramp may have gone out
+        let _ = stop().await;
+        return Err(e);
+    }
+    sleep(duration).await;
+    stop().await
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::{
+        comms::control_board::mock::{command_floats, mock_control_board},
+        test_util::Calls,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn drives_on_initial_yaw_then_stops() {
+        let (cb, commands) = mock_control_board(90.0).await;
+        let robot = Robot::new(&cb).await.unwrap();
+        assert_approx_eq!(robot.initial_yaw(), 90.0, 1e-3);
+
+        commands.clear();
+        let start = Instant::now();
+        robot
+            .stb2fd_time(0.4, -1.2, Duration::from_secs(3))
+            .await
+            .unwrap();
+
+        let sent = commands.timed();
+        assert_eq!(sent.len(), 2);
+        let [x, y, pitch, roll, yaw, depth] = command_floats(&sent[0].1, b"SASSIST2").unwrap();
+        assert_eq!([x, y, pitch, roll, depth], [0.0, 0.4, 0.0, 0.0, -1.2]);
+        assert_approx_eq!(yaw, 90.0, 1e-3);
+        assert_eq!(
+            command_floats(&sent[1].1, b"SASSIST1"),
+            Some([0.0, 0.0, 0.0, 0.0, 0.0, -1.2])
+        );
+
+        // Stops once the drive has run its duration
+        let driven = sent[1].0 - sent[0].0;
+        assert!(driven >= Duration::from_secs(3), "stopped after {driven:?}");
+        assert!(start.elapsed() < Duration::from_millis(3100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_drive_stops_without_waiting() {
+        let commands = Calls::new();
+        let res = stb2fd_with(
+            |_| std::future::ready(Err(anyhow::anyhow!("No ack"))),
+            || commands.ok(()),
+            0.4,
+            Duration::from_secs(3),
+        )
+        .await;
+        assert!(res.is_err());
+        assert_eq!(commands.timed(), [(Duration::ZERO, ())]);
+    }
+}
@@ -0,0 +1,267 @@
+use std::future::Future;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    config::{gate, plan::Condition, Side},
+    logln,
+};
+
+use super::{
+    budget::{MissionInfo, RunBudget},
+    coinflip::CoinflipResult,
+};
+
+/// Results handed from earlier missions in a sequence to later ones
+#[derive(Debug, Default)]
+pub struct RunState {
+    pub coinflip: Option<CoinflipResult>,
+}
+
+impl RunState {
+    pub const fn new() -> Self {
+        Self { coinflip: None }
+    }
+
+    /// Side for the gate, from the coinflip if it picked one
+    pub fn gate_side(&self, configured: &Side) -> Side {
+        self.coinflip
+            .as_ref()
+            .map(|result| result.side.clone())
+            .unwrap_or_else(|| configured.clone())
+    }
+
+    /// `gate` on the side picked by an earlier coinflip if there was one
+    pub fn gate_config(&self, gate: &gate::Config) -> gate::Config {
+        gate.with_side(self.gate_side(&gate.side))
+    }
+
+    /// Keeps the coinflip result for later missions, failing when it picked
+    /// no side so the run does not go on with the configured default
+    pub fn record_coinflip(&mut self, result: Option<CoinflipResult>) -> Result<()> {
+        self.coinflip = result;
+        let result = self
+            .coinflip
+            .as_ref()
+            .ok_or_else(|| anyhow!("Coinflip picked no side"))?;
+        logln!(
+            "Coinflip picked {:?} ({:.2} confidence)",
+            result.side,
+            result.confidence
+        );
+        Ok(())
+    }
+
+    /// Whether the run so far meets a plan branch condition
+    pub fn meets(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Coinflip(side) => self
+                .coinflip
+                .as_ref()
+                .is_some_and(|result| result.side == *side),
+        }
+    }
+}
+
+/// Why a mission in a sequence was not run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Disabled,
+    OutOfTime,
+}
+
+pub fn skip_reason(mission: &MissionInfo, budget: &RunBudget) -> Option<SkipReason> {
+    if !mission.enabled {
+        Some(SkipReason::Disabled)
+    } else if budget.should_skip(mission) {
+        Some(SkipReason::OutOfTime)
+    } else {
+        None
+    }
+}
+
+/// Runs each mission in order with `run`, skipping disabled missions and
+/// optional missions the budget has no time for.
+///
+/// Stops at the first mission that returns an error.
+pub async fn run_sequence<'a, F, Fut>(
+    missions: impl IntoIterator<Item = MissionInfo<'a>>,
+    budget: &RunBudget,
+    mut run: F,
+) -> Result<()>
+where
+    F: FnMut(&'a str) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    for mission in missions {
+        match skip_reason(&mission, budget) {
+            Some(SkipReason::Disabled) => {
+                logln!("Skipping disabled mission {}", mission.name);
+            }
+            Some(SkipReason::OutOfTime) => {
+                logln!(
+                    "Skipping optional mission {}, {:?} remaining of run budget",
+                    mission.name,
+                    budget.time_remaining()
+                );
+            }
+            None => run(mission.name).await?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    use anyhow::bail;
+
+    use super::*;
+    use crate::{
+        config::plan::Step,
+        missions::{
+            action::ActionExec,
+            plan::{build_plan, PlanContext},
+        },
+        test_util::Calls,
+    };
+
+    fn disabled(name: &str) -> MissionInfo<'_> {
+        MissionInfo {
+            enabled: false,
+            ..MissionInfo::new(name, false)
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled_skipped() {
+        let budget = RunBudget::new(Duration::from_secs(600), Duration::from_secs(60));
+        let missions = [
+            MissionInfo::new("gate_run_yolo", false),
+            disabled("sonar"),
+            MissionInfo::new("spin", true),
+        ];
+
+        let mut ran = vec![];
+        run_sequence(missions, &budget, |name| {
+            ran.push(name);
+            async { Ok(()) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(ran, ["gate_run_yolo", "spin"]);
+    }
+
+    #[tokio::test]
+    async fn optional_skipped_out_of_time() {
+        let budget = RunBudget::new(Duration::from_secs(30), Duration::from_secs(60));
+        let missions = [
+            MissionInfo::new("spin", true),
+            MissionInfo::new("coinflip", false),
+        ];
+
+        let mut ran = vec![];
+        run_sequence(missions, &budget, |name| {
+            ran.push(name);
+            async { Ok(()) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(ran, ["coinflip"]);
+    }
+
+    /// Plan stages over a [`RunState`], with a fixed coinflip result
+    struct StubStages {
+        state: Mutex<RunState>,
+        coinflip: Option<Side>,
+        gate: gate::Config,
+        gate_sides: Calls<Side>,
+    }
+
+    impl StubStages {
+        fn new(coinflip: Option<Side>) -> Self {
+            Self {
+                state: Mutex::new(RunState::new()),
+                coinflip,
+                gate: gate::Config {
+                    side: Side::Right,
+                    ..gate::Config::default()
+                },
+                gate_sides: Calls::new(),
+            }
+        }
+    }
+
+    impl PlanContext for StubStages {
+        async fn run_mission(&self, mission: &str) -> Result<()> {
+            let mut state = self.state.lock().unwrap();
+            match mission {
+                "coinflip" => {
+                    state.record_coinflip(self.coinflip.clone().map(|side| CoinflipResult {
+                        side,
+                        confidence: 0.8,
+                    }))
+                }
+                _ => {
+                    self.gate_sides.push(state.gate_config(&self.gate).side);
+                    Ok(())
+                }
+            }
+        }
+
+        fn check(&self, condition: &Condition) -> bool {
+            self.state.lock().unwrap().meets(condition)
+        }
+    }
+
+    fn coinflip_then_gate() -> Vec<Step> {
+        ["coinflip", "gate_run_yolo"]
+            .map(|mission| Step::Mission(mission.to_string()))
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn coinflip_side_reaches_gate() {
+        let stages = StubStages::new(Some(Side::Left));
+        build_plan(&stages, &coinflip_then_gate())
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(stages.gate_sides.values(), [Side::Left]);
+    }
+
+    #[tokio::test]
+    async fn coinflip_without_side_stops_plan() {
+        let stages = StubStages::new(None);
+        assert!(build_plan(&stages, &coinflip_then_gate())
+            .execute()
+            .await
+            .is_err());
+        assert!(stages.gate_sides.values().is_empty());
+    }
+
+    #[tokio::test]
+    async fn error_stops_sequence() {
+        let budget = RunBudget::new(Duration::from_secs(600), Duration::from_secs(60));
+        let missions = [
+            MissionInfo::new("gate_run_yolo", false),
+            MissionInfo::new("invalid", false),
+            MissionInfo::new("spin", false),
+        ];
+
+        let mut ran = vec![];
+        let res = run_sequence(missions, &budget, |name| {
+            ran.push(name);
+            async move {
+                if name == "invalid" {
+                    bail!("Invalid argument: [{name}]")
+                }
+                Ok(())
+            }
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(ran, ["gate_run_yolo", "invalid"]);
+    }
+}
@@ -1,16 +1,44 @@
+use anyhow::{Context, Result};
 use tokio::{
     io::WriteHalf,
-    time::{sleep, Duration},
+    time::{sleep, sleep_until, Duration, Instant},
 };
 use tokio_serial::SerialStream;
 
-use super::action_context::{FrontCamIO, GetControlBoard, GetMainElectronicsBoard};
+use super::{
+    action_context::{FrontCamIO, GetControlBoard, GetMainElectronicsBoard},
+    align::{CenterYaw, Stability1Yaw},
+    basic::VisionErrors,
+    completion::Completion,
+    pid::Pid,
+};
 use crate::{
+    comms::control_board::{util::Degrees, ANGLES_TIMEOUT},
     config::{slalom::Config, ColorProfile, Side::*},
     missions::{action::ActionExec, vision::VisionNormAngle},
 };
 
-// TODO: Consider filtering detections by angle (poles will always be upright)
+/// Strafe speed in `direction` (-1 left, 1 right) that holds the pole
+/// `target_offset` from center while going around it.
+///
+/// The pole is held on the side opposite the strafe, at `-direction *
+/// target_offset`. Moving toward +x shifts the pole toward -x in frame, so the
+/// correction is proportional to how far right of that target the pole sits.
+/// Without a detection this is the open-loop `speed * direction`.
+fn closed_loop_strafe_speed(
+    direction: f32,
+    speed: f32,
+    pole_x: Option<f32>,
+    target_offset: f32,
+    gain: f32,
+) -> f32 {
+    let base = speed * direction;
+    match pole_x {
+        Some(x) => base + gain * (x + direction * target_offset),
+        None => base,
+    }
+}
+
 pub async fn slalom<
     Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>> + GetMainElectronicsBoard + FrontCamIO,
 >(
@@ -18,7 +46,7 @@ pub async fn slalom<
     config: &Config,
     flip: bool,
     color_profile: &ColorProfile,
-) {
+) -> Result<()> {
     use crate::vision::slalom::Slalom;
     #[cfg(feature = "logging")]
     logln!("Starting slalom");
@@ -28,17 +56,18 @@ pub async fn slalom<
 
     let mut vision = VisionNormAngle::<Con, Slalom, f64>::new(
         context,
-        Slalom::from_color_profile(color_profile, config.area_bounds.clone()),
+        Slalom::from_color_profile(
+            color_profile,
+            config.area_bounds.clone(),
+            config.max_pole_angle_dev,
+            config.contour_select,
+        ),
     );
 
-    let initial_yaw = loop {
-        if let Some(initial_angle) = cb.responses().get_angles().await {
-            break *initial_angle.yaw();
-        } else {
-            #[cfg(feature = "logging")]
-            logln!("Failed to get initial angle");
-        }
-    };
+    let initial_yaw = cb
+        .wait_for_yaw(Some(ANGLES_TIMEOUT))
+        .await
+        .context("Abandoning slalom")?;
 
     let _ = cb
         .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, config.depth)
@@ -46,7 +75,7 @@ pub async fn slalom<
 
     sleep(Duration::from_secs(3)).await;
 
-    let mut yaw_target = 0.0;
+    let mut yaw_target = Degrees::default();
     let mut true_count = 0;
     let mut false_count = 0;
 
@@ -56,6 +85,9 @@ pub async fn slalom<
     }
 
     let mut slalom_state = SlalomState::Align;
+    let correction = Stability1Yaw::new(cb, config.depth).with_approach(config.approach);
+    let mut yaw_to_center =
+        CenterYaw::new(correction, Pid::new(config.correction_pid.clone()), 0.2);
 
     // let _ = cb
     //     .stability_2_speed_set(0.05, config.speed, 0.0, 0.0, initial_yaw, config.depth)
@@ -65,16 +97,12 @@ pub async fn slalom<
     #[cfg(feature = "logging")]
     logln!("Starting slalom detection");
 
+    let mut vision_errors = VisionErrors::default();
     // Default left, right if flipped
     'detections: loop {
-        #[allow(unused_variables)]
-        let detections = vision.execute().await.unwrap_or_else(|e| {
-            #[cfg(feature = "logging")]
-            logln!(
-                "Getting slalom detection resulted in error: `{e}`\n\tUsing empty detection vec"
-            );
-            vec![]
-        });
+        let detections = vision_errors
+            .check_or_stop(vision.execute().await, cb)
+            .await?;
 
         let mut positions = detections
             .into_iter()
@@ -85,33 +113,23 @@ pub async fn slalom<
                 #[cfg(feature = "logging")]
                 logln!("ALIGN");
 
-                if let Some(position) = positions.next() {
+                let x = positions.next().map(|position| *position.x() as f32);
+                if x.is_some() {
                     false_count = 0;
-                    let x = *position.x() as f32;
                     dbg!(&x);
-                    let mut correction = 0.0;
-                    let _ = correction;
-                    if x.abs() < 0.2 {
+                    if yaw_to_center.center_on(x).await {
                         true_count += 1;
                         if true_count >= 4 {
-                            correction = 0.0;
-                            let _ = correction;
                             #[cfg(feature = "logging")]
                             logln!("ALIGNED");
                             slalom_state = SlalomState::Approach;
                             if let Some(current_angle) = cb.responses().get_angles().await {
-                                let current_yaw = *current_angle.yaw();
-                                yaw_target = current_yaw;
+                                yaw_target = current_angle.heading();
                             }
                         } else {
                             #[cfg(feature = "logging")]
                             logln!("true_count: {true_count}/4");
                         }
-                    } else {
-                        correction = dbg!(config.correction_factor * x);
-                        let _ = cb
-                            .stability_1_speed_set(0.0, 0.0, correction, 0.0, 0.0, config.depth)
-                            .await;
                     }
                 } else {
                     #[cfg(feature = "logging")]
@@ -137,7 +155,7 @@ pub async fn slalom<
                     logln!("NO DETECTIONS");
                     if false_count >= 500 {
                         #[cfg(feature = "logging")]
-                        logln!("KILLED NO DET");
+                        log_warn!("KILLED NO DET");
                         break 'detections;
                     }
                 }
@@ -149,31 +167,66 @@ pub async fn slalom<
 
                 let strafe_direction = if let Left = config.side { -1.0 } else { 1.0 };
 
+                let base_strafe = config.speed * strafe_direction;
                 let _ = cb
                     .stability_2_speed_set(
-                        config.speed * strafe_direction,
+                        base_strafe,
                         0.0,
                         0.0,
                         0.0,
-                        yaw_target,
+                        yaw_target.value(),
                         config.depth,
                     )
                     .await;
 
-                sleep(Duration::from_secs(config.strafe_duration as u64)).await;
+                if config.closed_loop_strafe {
+                    let strafe_end = Instant::now() + config.strafe_duration.to_duration();
+                    while Instant::now() < strafe_end {
+                        let pole_x = vision.execute().await.ok().and_then(|detections| {
+                            detections
+                                .into_iter()
+                                .find(|d| *d.class())
+                                .map(|d| *d.position().x() as f32)
+                        });
+
+                        #[cfg(feature = "logging")]
+                        if pole_x.is_none() {
+                            log_warn!("STRAFE: lost pole, holding open-loop speed");
+                        }
+
+                        let _ = cb
+                            .stability_2_speed_set(
+                                closed_loop_strafe_speed(
+                                    strafe_direction,
+                                    config.speed,
+                                    pole_x,
+                                    config.strafe_target_offset,
+                                    config.strafe_gain,
+                                ),
+                                0.0,
+                                0.0,
+                                0.0,
+                                yaw_target.value(),
+                                config.depth,
+                            )
+                            .await;
+                    }
+                } else {
+                    sleep(config.strafe_duration.to_duration()).await;
+                }
 
                 yaw_target = yaw_target
-                    + (if let Left = config.side {
+                    + Degrees::new(if let Left = config.side {
                         config.yaw_adjustment
                     } else {
                         -config.yaw_adjustment
                     });
 
                 let _ = cb
-                    .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, yaw_target, config.depth)
+                    .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, yaw_target.value(), config.depth)
                     .await;
 
-                sleep(Duration::from_secs(config.init_duration as u64)).await;
+                sleep(config.init_duration.to_duration()).await;
 
                 // init_timer.execute().await;
 
@@ -183,11 +236,33 @@ pub async fn slalom<
 
                 //
                 let _ = cb
-                    .stability_2_speed_set(0.0, config.speed, 0.0, 0.0, yaw_target, config.depth)
+                    .stability_2_speed_set(
+                        0.0,
+                        config.speed,
+                        0.0,
+                        0.0,
+                        yaw_target.value(),
+                        config.depth,
+                    )
                     .await;
 
                 // traversal_timer.execute().await;
-                sleep(Duration::from_secs(config.traversal_duration as u64)).await;
+                let mut completion = Completion::start(
+                    config.approach_completion,
+                    config.traversal_duration.to_duration(),
+                    config.lost_frames,
+                );
+                while !completion.is_complete() {
+                    if !completion.policy().uses_vision() {
+                        sleep_until(completion.deadline()).await;
+                        continue;
+                    }
+
+                    let detections = vision_errors
+                        .check_or_stop(vision.execute().await, cb)
+                        .await?;
+                    completion.record(detections.iter().any(|d| *d.class()));
+                }
 
                 break 'detections;
             }
@@ -195,4 +270,41 @@ pub async fn slalom<
 
         // The current implementation is guaranteed to return exactly 1 item
     }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+
+    #[test]
+    fn closed_loop_strafe_tracks_pole() {
+        const SPEED: f32 = 0.3;
+        const TARGET: f32 = 0.5;
+        const GAIN: f32 = 0.4;
+
+        // Strafing left, the pole is held right of center. It drifts right of
+        // target, then left, then is lost
+        let script = [Some(0.5), Some(0.75), Some(0.25), None];
+        let speeds = script.map(|x| closed_loop_strafe_speed(-1.0, SPEED, x, TARGET, GAIN));
+
+        // Held at target, no correction
+        assert_approx_eq!(speeds[0], -SPEED);
+        // Pole too far right, slow the left strafe
+        assert_approx_eq!(speeds[1], -0.2);
+        // Pole too far left, strafe left faster
+        assert_approx_eq!(speeds[2], -0.4);
+        // Detection lapse falls back to open-loop
+        assert_approx_eq!(speeds[3], -SPEED);
+
+        // Strafing right mirrors it, holding the pole left of center
+        let mirrored = script.map(|x| x.map(|x| -x));
+        let speeds = mirrored.map(|x| closed_loop_strafe_speed(1.0, SPEED, x, TARGET, GAIN));
+        assert_approx_eq!(speeds[0], SPEED);
+        assert_approx_eq!(speeds[1], 0.2);
+        assert_approx_eq!(speeds[2], 0.4);
+        assert_approx_eq!(speeds[3], SPEED);
+    }
 }
@@ -5,17 +5,24 @@ use bluerobotics_ping::{
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::{self, OpenOptions},
+    fs::{self, File, OpenOptions},
     io::BufWriter,
     path::PathBuf,
     time::SystemTime,
 };
-use tokio::{io::WriteHalf, select};
+use tokio::{
+    io::WriteHalf,
+    select,
+    time::{sleep, Duration},
+};
 use tokio_serial::{SerialPort, SerialPortBuilderExt, SerialStream};
 use tokio_util::sync::CancellationToken;
 
 use super::action_context::{GetControlBoard, GetMainElectronicsBoard};
-use crate::config::sonar::Config;
+use crate::{
+    comms::control_board::{util::Degrees, ANGLES_TIMEOUT},
+    config::sonar::{AutoTransmit, Config},
+};
 
 pub async fn sonar<
     Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>> + GetMainElectronicsBoard,
@@ -30,6 +37,57 @@ pub async fn sonar<
     let cb = context.get_control_board();
     let _ = cb.bno055_periodic_read(true).await;
 
+    let ping360 = connect(cfg).await;
+
+    let (protocol_version, device_information) =
+        tokio::try_join!(ping360.protocol_version(), ping360.device_information())
+            .expect("Failed to get device data!");
+
+    // let _ = cb
+    //     .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, -1.25)
+    //     .await;
+
+    #[cfg(feature = "logging")]
+    logln!("Opening log file");
+    let file = open_log("");
+
+    #[cfg(feature = "logging")]
+    logln!("Starting sonar auto transmit");
+    let at = cfg.auto_transmit;
+    start_auto_transmit(&ping360, &at).await;
+
+    let mut data: Vec<AutoDeviceDataStruct> = Vec::new();
+
+    #[cfg(feature = "logging")]
+    logln!("Recording data");
+    loop {
+        select! {
+            _ = cancel.cancelled() => { break; },
+            r = ping360.auto_device_data() => {
+                if let Ok(d) = r {
+                    let angle_clone = d.angle;
+                    data.push(d);
+                    #[cfg(feature = "logging")]
+                    logln!("Got data {}", angle_clone);
+                    if angle_clone >= at.stop_angle {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    let log = SonarLogFile {
+        protocol_version,
+        device_information,
+        data,
+    };
+
+    serde_json::to_writer_pretty(file, &log).expect("Failed to write sonar log file");
+}
+
+/// Opens the Ping360 on the configured port and stops its motor
+async fn connect(cfg: &Config) -> Ping360 {
     #[cfg(feature = "logging")]
     logln!("Initializing sonar with: {:?}", cfg.serial_port);
     let port = loop {
@@ -40,7 +98,7 @@ pub async fn sonar<
             #[allow(unused_variables)]
             Err(e) => {
                 #[cfg(feature = "logging")]
-                logln!("Error opening serial port: {}", e);
+                log_error!("Error opening serial port: {}", e);
             }
         }
     };
@@ -49,7 +107,7 @@ pub async fn sonar<
     port.clear(tokio_serial::ClearBuffer::All)
         .unwrap_or_else(|e| {
             #[cfg(feature = "logging")]
-            logln!("Failed to clear sonar serial port: {}", e);
+            log_warn!("Failed to clear sonar serial port: {}", e);
         });
 
     let ping360 = Ping360::new(port);
@@ -70,25 +128,43 @@ pub async fn sonar<
     #[allow(unused_variables)]
     while let Err(e) = ping360.motor_off().await {
         #[cfg(feature = "logging")]
-        logln!("Failed to reset sonar unit: {e:#?}");
+        log_warn!("Failed to reset sonar unit: {e:#?}");
     }
 
-    let (protocol_version, device_information) =
-        tokio::try_join!(ping360.protocol_version(), ping360.device_information())
-            .expect("Failed to get device data!");
+    ping360
+}
 
-    // let _ = cb
-    //     .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, -1.25)
-    //     .await;
+/// Starts streaming device data, see [`Ping360::auto_device_data`]
+async fn start_auto_transmit(ping360: &Ping360, at: &AutoTransmit) {
+    #[allow(unused_variables)]
+    while let Err(e) = ping360
+        .auto_transmit(
+            at.mode,
+            at.gain_setting as u8,
+            at.transmit_duration,
+            at.sample_period,
+            at.transmit_frequency,
+            at.number_of_samples,
+            at.start_angle,
+            at.stop_angle,
+            at.num_steps,
+            at.delay,
+        )
+        .await
+    {
+        #[cfg(feature = "logging")]
+        log_warn!("Failed to start sonar auto transmit: {e:#?}");
+    }
+}
 
-    #[cfg(feature = "logging")]
-    logln!("Opening log file");
+/// Opens a new log file under `logging/sonar/`, named with `prefix` and the time
+fn open_log(prefix: &str) -> BufWriter<File> {
     let time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
     let directory = "./logging/sonar/";
-    let filename = format!("{time}.log");
+    let filename = format!("{prefix}{time}.log");
     let path = PathBuf::from(directory).join(filename);
     let mut open_options = OpenOptions::new();
     open_options.append(true).create(true);
@@ -110,59 +186,273 @@ pub async fn sonar<
             }
         });
 
-    let file = BufWriter::new(file);
+    BufWriter::new(file)
+}
 
-    #[cfg(feature = "logging")]
-    logln!("Starting sonar auto transmit");
-    let at = cfg.auto_transmit;
-    #[allow(unused_variables)]
-    while let Err(e) = ping360
-        .auto_transmit(
-            at.mode,
-            at.gain_setting as u8,
-            at.transmit_duration,
-            at.sample_period,
-            at.transmit_frequency,
-            at.number_of_samples,
-            at.start_angle,
-            at.stop_angle,
-            at.num_steps,
-            at.delay,
-        )
-        .await
-    {
-        #[cfg(feature = "logging")]
-        logln!("Failed to start sonar auto transmit: {e:#?}");
+/// Meters per sample tick, from a ~1500 m/s speed of sound over the round trip
+const METERS_PER_TICK: f32 = 25e-9 * 1500.0 / 2.0;
+
+/// Strongest sample in a ping, the return it came from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SonarReturn {
+    /// Absolute heading of the return, in degrees
+    pub bearing: f32,
+    /// Meters from the transducer
+    pub range: f32,
+    pub intensity: u8,
+}
+
+impl SonarReturn {
+    /// Finds the strongest sample at least `min_range` out, the nearest when tied
+    ///
+    /// `sample_period` is in the Ping360's 25 ns ticks.
+    pub fn from_ping(
+        bearing: f32,
+        samples: &[u8],
+        sample_period: u16,
+        min_range: f32,
+    ) -> Option<Self> {
+        let meters_per_sample = sample_period as f32 * METERS_PER_TICK;
+        samples
+            .iter()
+            .enumerate()
+            .map(|(idx, intensity)| (idx as f32 * meters_per_sample, *intensity))
+            .filter(|(range, _)| *range >= min_range)
+            .fold(
+                None,
+                |strongest: Option<(f32, u8)>, (range, intensity)| match strongest {
+                    Some((_, max)) if max >= intensity => strongest,
+                    _ => Some((range, intensity)),
+                },
+            )
+            .map(|(range, intensity)| Self {
+                bearing: Degrees::new(bearing).value(),
+                range,
+                intensity,
+            })
     }
+}
 
-    let mut data: Vec<AutoDeviceDataStruct> = Vec::new();
+/// Strongest return per bearing bin, covering a full rotation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolarMap {
+    bins: Vec<Option<SonarReturn>>,
+}
+
+impl PolarMap {
+    pub fn new(bins: usize) -> Self {
+        Self {
+            bins: vec![None; bins.max(1)],
+        }
+    }
+
+    fn bin(&self, bearing: f32) -> usize {
+        let width = 360.0 / self.bins.len() as f32;
+        ((Degrees::new(bearing).value() + 180.0) / width) as usize % self.bins.len()
+    }
+
+    /// Keeps `ret` if it is stronger than its bin's return, or as strong and nearer
+    pub fn add(&mut self, ret: SonarReturn) {
+        let idx = self.bin(ret.bearing);
+        let bin = &mut self.bins[idx];
+        if bin.is_none_or(|prev| Self::better(&ret, &prev)) {
+            *bin = Some(ret);
+        }
+    }
+
+    fn better(ret: &SonarReturn, other: &SonarReturn) -> bool {
+        ret.intensity > other.intensity
+            || (ret.intensity == other.intensity && ret.range < other.range)
+    }
+
+    pub fn returns(&self) -> impl Iterator<Item = &SonarReturn> {
+        self.bins.iter().flatten()
+    }
+
+    /// Strongest return of at least `min_intensity`, the nearest when tied
+    pub fn target(&self, min_intensity: u8) -> Option<SonarReturn> {
+        self.returns()
+            .filter(|ret| ret.intensity >= min_intensity)
+            .fold(None, |best: Option<SonarReturn>, ret| match best {
+                Some(best) if !Self::better(ret, &best) => Some(best),
+                _ => Some(*ret),
+            })
+    }
+}
+
+/// Change from `prev` to `curr` yaw, through the shorter direction
+fn yaw_delta(prev: f32, curr: f32) -> f32 {
+    Degrees::new(curr - prev).value()
+}
+
+/// Mean of `bearings` when their standard deviation is within `max_std` degrees
+///
+/// Needs at least two bearings. Spread is measured through the shorter
+/// direction, so bearings on either side of 180 are not far apart.
+fn stable_bearing(bearings: &[f32], max_std: f32) -> Option<f32> {
+    let (&first, rest) = bearings.split_first()?;
+    if rest.is_empty() {
+        return None;
+    }
+    let offsets = bearings
+        .iter()
+        .map(|bearing| yaw_delta(first, *bearing))
+        .collect::<Vec<_>>();
+    let count = offsets.len() as f32;
+    let mean = offsets.iter().sum::<f32>() / count;
+    let variance = offsets
+        .iter()
+        .map(|offset| (offset - mean).powi(2))
+        .sum::<f32>()
+        / count;
+    (variance.sqrt() <= max_std).then(|| Degrees::new(first + mean).value())
+}
 
+/// Whether `bearings` confirm a target swept at `target`
+///
+/// The bearings have to be stable, see [`stable_bearing`], and their mean
+/// has to stay within `max_std` degrees of `target`. A return that moved
+/// further is something else, not a better fix on the target.
+fn confirms_target(target: f32, bearings: &[f32], max_std: f32) -> bool {
+    stable_bearing(bearings, max_std)
+        .is_some_and(|bearing| yaw_delta(target, bearing).abs() <= max_std)
+}
+
+/// Rotates through a full circle mapping sonar returns, then homes on the best one
+///
+/// Returns are placed at the sub's yaw plus the transducer angle, so the
+/// auto transmit can hold the head forward or sweep it as well. The map is
+/// logged to `logging/sonar/` before homing. The sub then faces the target
+/// and collects `confirm_pings` pings within `confirm_window_deg` of it, and
+/// only homes if they agree with the swept bearing.
+pub async fn sonar_sweep<
+    Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>> + GetMainElectronicsBoard,
+>(
+    context: &Con,
+    cfg: &Config,
+    cancel: CancellationToken,
+) -> Option<SonarReturn> {
     #[cfg(feature = "logging")]
-    logln!("Recording data");
-    loop {
+    logln!("Starting sonar sweep");
+    let sweep = cfg.sweep;
+
+    let cb = context.get_control_board();
+    let _ = cb.bno055_periodic_read(true).await;
+    let initial_yaw = match cb.wait_for_yaw(Some(ANGLES_TIMEOUT)).await {
+        Ok(yaw) => yaw,
+        Err(e) => {
+            log_warn!("Abandoning sonar sweep: {e:#}");
+            return None;
+        }
+    };
+
+    let ping360 = connect(cfg).await;
+    start_auto_transmit(&ping360, &cfg.auto_transmit).await;
+
+    let _ = cb
+        .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, sweep.depth)
+        .await;
+    sleep(Duration::from_secs(1)).await;
+    let _ = cb
+        .global_speed_set(0.0, 0.0, 0.0, 0.0, 0.0, sweep.yaw_speed)
+        .await;
+
+    let mut map = PolarMap::new(sweep.bearing_bins);
+    let mut last_yaw = initial_yaw;
+    let mut rotated = 0.0;
+    while rotated < 360.0 {
         select! {
             _ = cancel.cancelled() => { break; },
             r = ping360.auto_device_data() => {
-                if let Ok(d) = r {
-                    let angle_clone = d.angle;
-                    data.push(d);
-                    #[cfg(feature = "logging")]
-                    logln!("Got data {}", angle_clone);
-                    if angle_clone >= at.stop_angle {
-                        break;
-                    }
+                let (Ok(d), Some(angle)) = (r, cb.responses().get_angles().await) else {
+                    continue;
+                };
+                let yaw = *angle.yaw();
+                rotated += yaw_delta(last_yaw, yaw).abs();
+                last_yaw = yaw;
+
+                // Transducer angle is in gradians
+                let bearing = yaw + d.angle as f32 * 0.9;
+                if let Some(ret) =
+                    SonarReturn::from_ping(bearing, &d.data, d.sample_period, sweep.min_range)
+                {
+                    map.add(ret);
                 }
             }
         }
     }
+    let _ = cb
+        .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, last_yaw, sweep.depth)
+        .await;
 
-    let log = SonarLogFile {
-        protocol_version,
-        device_information,
-        data,
+    #[allow(unused_variables)]
+    if let Err(e) = serde_json::to_writer_pretty(open_log("sweep_"), &map) {
+        #[cfg(feature = "logging")]
+        log_warn!("Failed to write sonar sweep map: {e}");
+    }
+
+    let target = map.target(sweep.min_intensity);
+    let Some(target) = target.filter(|_| !cancel.is_cancelled()) else {
+        #[cfg(feature = "logging")]
+        logln!("No sonar return to home on");
+        return target;
     };
 
-    serde_json::to_writer_pretty(file, &log).expect("Failed to write sonar log file");
+    if sweep.confirm_pings > 0 {
+        let _ = cb
+            .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, target.bearing, sweep.depth)
+            .await;
+        sleep(Duration::from_secs(1)).await;
+
+        let mut bearings = Vec::with_capacity(sweep.confirm_pings);
+        let deadline = sleep(sweep.confirm_secs.to_duration());
+        tokio::pin!(deadline);
+        while bearings.len() < sweep.confirm_pings {
+            select! {
+                _ = cancel.cancelled() => { return None; },
+                _ = &mut deadline => { break; },
+                r = ping360.auto_device_data() => {
+                    let (Ok(d), Some(angle)) = (r, cb.responses().get_angles().await) else {
+                        continue;
+                    };
+                    // The head keeps sweeping, only pings facing the target count
+                    let bearing = *angle.yaw() + d.angle as f32 * 0.9;
+                    if yaw_delta(target.bearing, bearing).abs() > sweep.confirm_window_deg {
+                        continue;
+                    }
+                    if let Some(ret) =
+                        SonarReturn::from_ping(bearing, &d.data, d.sample_period, sweep.min_range)
+                            .filter(|ret| ret.intensity >= sweep.min_intensity)
+                    {
+                        bearings.push(ret.bearing);
+                    }
+                }
+            }
+        }
+
+        if !confirms_target(target.bearing, &bearings, sweep.max_bearing_std) {
+            #[cfg(feature = "logging")]
+            logln!(
+                "Sonar bearings {bearings:?} do not confirm {}, not homing",
+                target.bearing
+            );
+            return None;
+        }
+    }
+
+    #[cfg(feature = "logging")]
+    logln!("Homing on sonar return {target:?}");
+    let _ = cb
+        .stability_2_speed_set(0.0, sweep.home_speed, 0.0, 0.0, target.bearing, sweep.depth)
+        .await;
+    select! {
+        _ = cancel.cancelled() => {},
+        _ = sleep(sweep.home_secs.to_duration()) => {},
+    }
+    let _ = cb
+        .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, target.bearing, sweep.depth)
+        .await;
+    Some(target)
 }
 
 #[derive(Serialize, Deserialize)]
@@ -171,3 +461,91 @@ struct SonarLogFile {
     device_information: DeviceInformationStruct,
     data: Vec<AutoDeviceDataStruct>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ret(bearing: f32, range: f32, intensity: u8) -> SonarReturn {
+        SonarReturn {
+            bearing,
+            range,
+            intensity,
+        }
+    }
+
+    #[test]
+    fn strongest_sample_past_min_range() {
+        // 1 m per sample
+        let sample_period = (1.0 / METERS_PER_TICK).round() as u16;
+        let samples = [255, 40, 90, 200, 200, 10];
+
+        let found = SonarReturn::from_ping(370.0, &samples, sample_period, 0.5).unwrap();
+        assert_eq!(found.bearing, 10.0);
+        assert_eq!(found.intensity, 200);
+        // Ringdown is skipped, and the nearer of the tied samples is kept
+        assert!((found.range - 3.0).abs() < 0.01);
+
+        assert!(SonarReturn::from_ping(0.0, &samples, sample_period, 10.0).is_none());
+    }
+
+    #[test]
+    fn polar_map_selects_target() {
+        let mut map = PolarMap::new(36);
+        for sample in [
+            ret(1.0, 4.0, 120),
+            // Same 10 degree bin, weaker
+            ret(5.0, 2.0, 80),
+            ret(91.0, 6.0, 200),
+            // Tied with the bin above, but nearer
+            ret(95.0, 3.0, 200),
+            ret(-179.0, 1.0, 200),
+            ret(-90.0, 1.0, 50),
+        ] {
+            map.add(sample);
+        }
+
+        assert_eq!(map.returns().count(), 4);
+        // -179 is as strong as 95 and nearer
+        assert_eq!(map.target(100), Some(ret(-179.0, 1.0, 200)));
+        assert_eq!(map.target(201), None);
+
+        let mut weak_only = PolarMap::new(36);
+        weak_only.add(ret(0.0, 1.0, 50));
+        assert_eq!(weak_only.target(100), None);
+    }
+
+    #[test]
+    fn noisy_bearings_are_rejected() {
+        let clean = stable_bearing(&[10.0, 11.0, 9.0, 10.5, 9.5], 5.0).unwrap();
+        assert!((clean - 10.0).abs() < 0.01);
+
+        // Multipath returns scattered around the true bearing
+        assert_eq!(stable_bearing(&[10.0, 60.0, -40.0, 15.0, 10.0], 5.0), None);
+
+        // Agreeing bearings across the wraparound are stable
+        let wrapped = stable_bearing(&[178.0, -178.0, 180.0], 5.0).unwrap();
+        assert!(yaw_delta(wrapped, 180.0).abs() < 0.01);
+
+        assert_eq!(stable_bearing(&[10.0], 5.0), None);
+        assert_eq!(stable_bearing(&[], 5.0), None);
+    }
+
+    #[test]
+    fn moved_target_is_not_confirmed() {
+        assert!(confirms_target(10.0, &[10.0, 11.0, 9.0, 10.5], 5.0));
+        assert!(confirms_target(179.0, &[-179.0, 180.0, 178.0], 5.0));
+
+        // Stable, but on something 20 degrees over
+        assert!(!confirms_target(10.0, &[30.0, 31.0, 29.0, 30.5], 5.0));
+        assert!(!confirms_target(10.0, &[10.0, 60.0, -40.0, 15.0], 5.0));
+        assert!(!confirms_target(10.0, &[], 5.0));
+    }
+
+    #[test]
+    fn yaw_delta_wraps() {
+        assert_eq!(yaw_delta(170.0, -170.0), 20.0);
+        assert_eq!(yaw_delta(-170.0, 170.0), -20.0);
+        assert_eq!(yaw_delta(10.0, 30.0), 20.0);
+    }
+}
@@ -1,7 +1,7 @@
 use super::action_context::{BottomCamIO, GetControlBoard, GetMainElectronicsBoard};
-use crate::config::spin::Config;
+use crate::{comms::control_board::ANGLES_TIMEOUT, config::spin::Config};
 use tokio::io::WriteHalf;
-use tokio::time::{sleep, Duration};
+use tokio::time::{sleep, Duration, Instant};
 use tokio_serial::SerialStream;
 
 pub async fn spin<
@@ -16,26 +16,17 @@ pub async fn spin<
     let cb = context.get_control_board();
     let _ = cb.bno055_periodic_read(true).await;
 
-    let initial_roll = loop {
-        if let Some(initial_angle) = cb.responses().get_angles().await {
-            break *initial_angle.roll();
-        } else {
-            #[cfg(feature = "logging")]
-            logln!("Failed to get initial angle");
-        }
-    };
-
-    let initial_yaw = loop {
-        if let Some(initial_angle) = cb.responses().get_angles().await {
-            break *initial_angle.yaw();
-        } else {
-            #[cfg(feature = "logging")]
-            logln!("Failed to get initial angle");
+    let initial_angles = match cb.wait_for_angles(Some(ANGLES_TIMEOUT)).await {
+        Ok(angles) => angles,
+        Err(e) => {
+            log_warn!("Abandoning spin: {e:#}");
+            return;
         }
     };
+    let initial_roll = *initial_angles.roll();
+    let initial_yaw = *initial_angles.yaw();
 
-    let mut spin_count = 0;
-    let mut in_spin = false;
+    let mut counter = SpinCounter::new(initial_roll, config);
 
     let _ = cb
         .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, config.depth)
@@ -44,6 +35,7 @@ pub async fn spin<
     let _ = cb
         .global_speed_set(0.0, 0.0, 0.0, 0.0, config.spin_speed, 0.0)
         .await;
+    counter.start();
 
     loop {
         let curr_roll = loop {
@@ -51,33 +43,141 @@ pub async fn spin<
                 break *angle.roll();
             } else {
                 #[cfg(feature = "logging")]
-                logln!("Failed to get angle");
+                log_warn!("Failed to get angle");
             }
         };
 
-        let mut diff: f32 = curr_roll - initial_roll;
+        if counter.update(curr_roll) >= config.num_spins {
+            break;
+        }
+    }
+    let _ = cb
+        .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, config.depth)
+        .await;
+}
+
+/// Counts revolutions from roll crossings, with an optional estimate from
+/// the commanded rotation rate for when roll stops crossing
+#[derive(Debug)]
+struct SpinCounter {
+    initial_roll: f32,
+    hysteresis: f32,
+    in_spin: bool,
+    spin_count: i32,
+    /// Stall timeout and degrees per second, if the fallback is enabled
+    fallback: Option<(Duration, f32)>,
+    rotated: f32,
+    last_update: Instant,
+    last_crossing: Instant,
+    /// Counting from the commanded rate since roll last crossed
+    stalled: bool,
+}
+
+impl SpinCounter {
+    fn new(initial_roll: f32, config: &Config) -> Self {
+        let now = Instant::now();
+        Self {
+            initial_roll,
+            hysteresis: config.hysteresis,
+            in_spin: false,
+            spin_count: 0,
+            fallback: config.rate_fallback.then(|| {
+                (
+                    config.fallback_stall_secs.to_duration(),
+                    config.fallback_rate,
+                )
+            }),
+            rotated: 0.0,
+            last_update: now,
+            last_crossing: now,
+            stalled: false,
+        }
+    }
+
+    /// Starts integrating the commanded rate from now
+    fn start(&mut self) {
+        self.last_update = Instant::now();
+        self.last_crossing = self.last_update;
+    }
+
+    /// Records a roll reading, returning the revolutions completed so far
+    fn update(&mut self, curr_roll: f32) -> i32 {
+        let now = Instant::now();
+        let elapsed = now - self.last_update;
+        self.last_update = now;
+
+        let mut diff: f32 = curr_roll - self.initial_roll;
         if diff > 180.0 {
             diff -= 360.0;
         } else if diff < -180.0 {
             diff += 360.0;
         }
 
-        if !in_spin && diff.abs() > config.hysteresis {
-            in_spin = true;
+        if !self.in_spin && diff.abs() > self.hysteresis {
+            self.in_spin = true;
+            self.last_crossing = now;
         }
 
-        if in_spin && diff.abs() < config.hysteresis {
-            spin_count += 1;
+        if self.in_spin && diff.abs() < self.hysteresis {
+            self.spin_count += 1;
             #[cfg(feature = "logging")]
-            logln!("Completed spin {}", spin_count);
-            in_spin = false;
+            logln!("Completed spin {}", self.spin_count);
+            self.in_spin = false;
+            self.last_crossing = now;
         }
 
-        if spin_count >= config.num_spins {
-            break;
+        let Some((stall, rate)) = self.fallback else {
+            return self.spin_count;
+        };
+        self.rotated += rate.abs() * elapsed.as_secs_f32();
+        if now - self.last_crossing < stall {
+            self.stalled = false;
+            return self.spin_count;
+        }
+
+        let estimate = (self.rotated / 360.0) as i32;
+        if !self.stalled {
+            self.stalled = true;
+            #[cfg(feature = "logging")]
+            logln!("Roll stalled, estimating spins from rotation rate");
         }
+        self.spin_count.max(estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DurationSecs;
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_fallback_with_static_roll() {
+        let config = Config {
+            num_spins: 2,
+            rate_fallback: true,
+            fallback_stall_secs: DurationSecs(1.0),
+            fallback_rate: 180.0,
+            ..Config::default()
+        };
+        let mut counter = SpinCounter::new(0.0, &config);
+        counter.start();
+
+        let mut frames = 0;
+        while counter.update(0.0) < config.num_spins {
+            sleep(Duration::from_millis(100)).await;
+            frames += 1;
+            assert!(frames < 100, "Spin never completed");
+        }
+        // Two revolutions at 180 degrees per second
+        assert_eq!(frames, 40);
+        assert!(counter.stalled);
+
+        // Roll moving again ends the stall
+        counter.update(90.0);
+        assert!(!counter.stalled);
+
+        let mut no_fallback = SpinCounter::new(0.0, &Config::default());
+        sleep(Duration::from_secs(10)).await;
+        assert_eq!(no_fallback.update(0.0), 0);
     }
-    let _ = cb
-        .stability_2_speed_set(0.0, 0.0, 0.0, 0.0, initial_yaw, config.depth)
-        .await;
 }
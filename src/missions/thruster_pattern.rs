@@ -0,0 +1,130 @@
+use std::future::Future;
+
+use anyhow::{bail, Result};
+use tokio::{
+    io::WriteHalf,
+    time::{sleep, Duration},
+};
+use tokio_serial::SerialStream;
+
+use crate::{
+    comms::control_board::util::{Speed, ThrusterSpeeds},
+    config::thruster_pattern::{Config, Step},
+    logln,
+};
+
+use super::action_context::GetControlBoard;
+
+/// Plays the configured thruster steps in order, then stops every thruster
+///
+/// Each step runs one thruster alone, for checking ESCs and thruster
+/// mapping. The whole pattern is validated before anything is sent.
+pub async fn thruster_pattern<Con: Send + Sync + GetControlBoard<WriteHalf<SerialStream>>>(
+    context: &Con,
+    config: &Config,
+) -> Result<()> {
+    validate(&config.steps)?;
+    let cb = context.get_control_board();
+    play_with(&config.steps, |speeds| cb.raw_speed_set(speeds)).await
+}
+
+/// Errors on the first step with an invalid thruster, speed, or duration
+fn validate(steps: &[Step]) -> Result<()> {
+    for (idx, step) in steps.iter().enumerate() {
+        if step.index > 7 {
+            bail!("Step {idx}: thruster index {} is not 0-7", step.index);
+        }
+        if !(-Speed::MAX..=Speed::MAX).contains(&step.speed) {
+            bail!(
+                "Step {idx}: speed {} is outside [{}, {}]",
+                step.speed,
+                -Speed::MAX,
+                Speed::MAX
+            );
+        }
+        if !step.duration.is_finite() || step.duration < 0.0 {
+            bail!("Step {idx}: duration {} is not a valid time", step.duration);
+        }
+    }
+    Ok(())
+}
+
+/// [`thruster_pattern`] with the thruster command abstracted out
+async fn play_with<F, Fut>(steps: &[Step], mut set_speeds: F) -> Result<()>
+where
+    F: FnMut(ThrusterSpeeds) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    for (idx, step) in steps.iter().enumerate() {
+        logln!(
+            "Thruster pattern step {}/{}: thruster {} at {} for {}s",
+            idx + 1,
+            steps.len(),
+            step.index,
+            step.speed,
+            step.duration
+        );
+        // ThrusterSpeeds numbers thrusters from 1
+        let res = match ThrusterSpeeds::single(step.index + 1, step.speed) {
+            Ok(speeds) => set_speeds(speeds).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            let _ = set_speeds(ThrusterSpeeds::ZERO).await;
+            return Err(e);
+        }
+        sleep(Duration::from_secs_f32(step.duration)).await;
+    }
+
+    logln!("Thruster pattern complete");
+    set_speeds(ThrusterSpeeds::ZERO).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Calls;
+
+    const fn step(index: u8, speed: f32, duration: f32) -> Step {
+        Step {
+            index,
+            speed,
+            duration,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn plays_steps_in_order() {
+        let steps = [step(7, 1.0, 1.0), step(0, -0.5, 0.5), step(3, 0.25, 0.0)];
+        validate(&steps).unwrap();
+
+        let commands = Calls::new();
+        play_with(&steps, |speeds| commands.ok(<[f32; 8]>::from(speeds)))
+            .await
+            .unwrap();
+
+        let single = |index: usize, speed| {
+            let mut speeds = [0.0; 8];
+            speeds[index] = speed;
+            speeds
+        };
+        assert_eq!(
+            commands.timed(),
+            [
+                (Duration::ZERO, single(7, 1.0)),
+                (Duration::from_secs(1), single(0, -0.5)),
+                (Duration::from_millis(1500), single(3, 0.25)),
+                (Duration::from_millis(1500), [0.0; 8]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_steps() {
+        assert!(validate(&[step(8, 0.5, 1.0)]).is_err());
+        assert!(validate(&[step(0, 1.5, 1.0)]).is_err());
+        assert!(validate(&[step(0, f32::NAN, 1.0)]).is_err());
+        assert!(validate(&[step(0, 0.5, -1.0)]).is_err());
+        assert!(validate(&Config::default().steps).is_ok());
+    }
+}
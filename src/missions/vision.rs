@@ -1,22 +1,36 @@
 use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
 use std::ops::{Add, Div, Mul};
-use std::sync::RwLock;
+use std::sync::{
+    atomic::{AtomicBool, Ordering as AtomicOrdering},
+    Arc, Mutex, RwLock,
+};
+use std::time::Duration;
 use std::{iter::Sum, marker::PhantomData};
 
 use super::action::{Action, ActionExec, ActionMod};
-use super::action_context::BottomCamIO;
+use super::action_context::{BottomCamIO, CameraSelect, SelectedCamera};
+use crate::log_warn;
 use crate::logln;
+use crate::video_source::MatSource;
+use crate::vision::calibration::NormalizedRoi;
+use crate::vision::detection_log::{detection_csv, log_detections};
 use crate::vision::{
-    Angle2D, Draw, DrawRect2d, Offset2D, RelPos, RelPosAngle, VisualDetection, VisualDetector,
+    Angle2D, DetectionScore, Draw, DrawRect2d, Offset2D, RelPos, RelPosAngle, VisualDetection,
+    VisualDetector,
 };
 
 use anyhow::{anyhow, Result};
-use num_traits::{Float, FromPrimitive, Num};
+use num_traits::{Float, FromPrimitive, Num, ToPrimitive};
 use opencv::core::{Mat, Rect2d};
+use tokio::{join, task::spawn_blocking, time::Instant};
 use uuid::Uuid;
 
 use crate::missions::action_context::FrontCamIO;
+#[cfg(feature = "annotated_streams")]
+use crate::vision::draw_detections;
+#[cfg(feature = "logging")]
+use crate::vision::image_prep::into_bgr;
 #[cfg(feature = "logging")]
 use opencv::{core::Vector, imgcodecs::imwrite};
 #[cfg(feature = "logging")]
@@ -26,6 +40,130 @@ use std::fs::create_dir_all;
 // All pipelines are cleaned up when count is back to zero.
 pub static PIPELINE_KILL: RwLock<(u64, bool)> = RwLock::new((0, false));
 
+/// Front camera region used by [`VisionNorm`] without its own ROI
+static FRONT_ROI: RwLock<NormalizedRoi> = RwLock::new(NormalizedRoi::full());
+/// Bottom camera region used by [`VisionNormBottomAngle`] without its own ROI
+static BOTTOM_ROI: RwLock<NormalizedRoi> = RwLock::new(NormalizedRoi::full());
+
+/// Sets the regions detections are kept from on each camera
+pub fn set_camera_rois(front: NormalizedRoi, bottom: NormalizedRoi) {
+    *FRONT_ROI.write().unwrap() = front;
+    *BOTTOM_ROI.write().unwrap() = bottom;
+}
+
+/// Whether vision loops push annotated frames, see [`set_annotations`]
+static ANNOTATE: AtomicBool = AtomicBool::new(true);
+
+/// Turns annotated stream output on or off, such as for the mission being debugged
+pub fn set_annotations(enabled: bool) {
+    ANNOTATE.store(enabled, AtomicOrdering::Relaxed);
+}
+
+#[cfg(feature = "annotated_streams")]
+fn annotations_enabled() -> bool {
+    ANNOTATE.load(AtomicOrdering::Relaxed)
+}
+
+/// Pushes `mat` with `detections` (in pixels of `mat`) drawn on it to the annotated stream
+#[cfg(feature = "annotated_streams")]
+async fn annotate_detections<T: FrontCamIO, C: Debug, P>(
+    context: &T,
+    mat: &Mat,
+    detections: &[VisualDetection<C, P>],
+) where
+    VisualDetection<C, P>: Draw,
+    P: RelPos,
+    P::Number: ToPrimitive,
+{
+    match draw_detections(mat, detections) {
+        Ok(annotated) => context.annotate_front_camera(&annotated).await,
+        Err(e) => log_warn!("Failed to draw detections: {e:#?}"),
+    }
+}
+
+/// Whether a normalized detection position is inside `roi`
+fn in_roi<V: Num + ToPrimitive>(roi: &NormalizedRoi, x: &V, y: &V) -> bool {
+    roi.contains_offset(
+        x.to_f64().unwrap_or(f64::NAN),
+        y.to_f64().unwrap_or(f64::NAN),
+    )
+}
+
+/// How often [`VisionNorm`] reports its frame rate
+const FPS_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Measures how often a vision loop completes, averaged over a window
+#[derive(Debug)]
+pub struct FpsCounter {
+    window_start: Option<Instant>,
+    frames: u32,
+    report_interval: Duration,
+    current_fps: f64,
+}
+
+impl FpsCounter {
+    pub const fn new(report_interval: Duration) -> Self {
+        Self {
+            window_start: None,
+            frames: 0,
+            report_interval,
+            current_fps: 0.0,
+        }
+    }
+
+    /// Records one completed frame, returning the new rate when a window closes
+    ///
+    /// The first frame only starts the window.
+    pub fn tick(&mut self) -> Option<f64> {
+        let Some(window_start) = self.window_start else {
+            self.window_start = Some(Instant::now());
+            return None;
+        };
+
+        self.frames += 1;
+        let elapsed = window_start.elapsed();
+        if elapsed >= self.report_interval {
+            self.current_fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.frames = 0;
+            self.window_start = Some(Instant::now());
+            Some(self.current_fps)
+        } else {
+            None
+        }
+    }
+
+    /// Rate over the last completed window, zero before the first
+    pub fn current_fps(&self) -> f64 {
+        self.current_fps
+    }
+}
+
+/// Merges same-class detections whose normalized centers are closer than
+/// `merge_distance`, keeping the highest scoring one of each group.
+///
+/// Cleans up duplicates from fragmented blobs. Ties keep the earlier detection,
+/// and a `merge_distance` of zero leaves the detections unchanged.
+pub fn merge_nearby<C: PartialEq + DetectionScore, V: Float>(
+    detections: Vec<VisualDetection<C, Offset2D<V>>>,
+    merge_distance: V,
+) -> Vec<VisualDetection<C, Offset2D<V>>> {
+    let mut detections = detections;
+    detections.sort_by(|lhs, rhs| rhs.class().score().total_cmp(&lhs.class().score()));
+
+    let mut merged: Vec<VisualDetection<C, Offset2D<V>>> = Vec::with_capacity(detections.len());
+    for detection in detections {
+        let duplicate = merged.iter().any(|kept| {
+            let dx = *kept.position().x() - *detection.position().x();
+            let dy = *kept.position().y() - *detection.position().y();
+            kept.class() == detection.class() && dx.hypot(dy) < merge_distance
+        });
+        if !duplicate {
+            merged.push(detection);
+        }
+    }
+    merged
+}
+
 /// Runs a vision routine to obtain the average of object positions
 ///
 /// The relative position is normalized to [-1, 1] on both axes
@@ -65,11 +203,13 @@ where
 
         #[allow(unused_mut)]
         let mut mat = self.context.get_front_camera_mat().await.clone();
-        let detections = self.model.detect(&mat);
+        let detections = self.model.detect_frame(&mat);
         #[cfg(feature = "logging")]
         logln!("Detect attempt: {}", detections.is_ok());
         let detections = detections?;
         #[cfg(feature = "logging")]
+        let mut mat = into_bgr(mat)?;
+        #[cfg(feature = "logging")]
         {
             detections.iter().for_each(|x| {
                 let x = VisualDetection::new(
@@ -87,7 +227,9 @@ where
             )
             .unwrap();
             #[cfg(feature = "annotated_streams")]
-            self.context.annotate_front_camera(&mat).await;
+            if annotations_enabled() {
+                self.context.annotate_front_camera(&mat).await;
+            }
         }
 
         let positions: Vec<_> = detections
@@ -146,11 +288,13 @@ where
 
         #[allow(unused_mut)]
         let mut mat = self.context.get_bottom_camera_mat().await.clone();
-        let detections = self.model.detect(&mat);
+        let detections = self.model.detect_frame(&mat);
         #[cfg(feature = "logging")]
         logln!("Detect attempt: {}", detections.is_ok());
         let detections = detections?;
         #[cfg(feature = "logging")]
+        let mut mat = into_bgr(mat)?;
+        #[cfg(feature = "logging")]
         {
             detections.iter().for_each(|x| {
                 let x = VisualDetection::new(
@@ -168,7 +312,9 @@ where
             )
             .unwrap();
             #[cfg(feature = "annotated_streams")]
-            self.context.annotate_bottom_camera(&mat).await;
+            if annotations_enabled() {
+                self.context.annotate_bottom_camera(&mat).await;
+            }
         }
 
         let positions: Vec<_> = detections
@@ -193,23 +339,73 @@ where
 /// The relative positions are normalized to [-1, 1] on both axes.
 /// The values are returned without an angle.
 #[derive(Debug)]
-pub struct VisionNorm<'a, T, U, V> {
+pub struct VisionNorm<'a, T, U: VisualDetector<V>, V: Num> {
     context: &'a T,
     model: U,
+    fps: FpsCounter,
+    merge_distance: f64,
+    on_detections: Option<DetectionCallback<U::ClassEnum, V>>,
+    roi: Option<NormalizedRoi>,
     _num: PhantomData<V>,
 }
 
-impl<'a, T, U, V> VisionNorm<'a, T, U, V> {
+/// Observer of the detections a vision loop returns each frame
+pub struct DetectionCallback<C, V: Num>(
+    Box<dyn Fn(&[VisualDetection<C, Offset2D<V>>]) + Send + Sync>,
+);
+
+impl<C, V: Num> DetectionCallback<C, V> {
+    pub fn new(
+        callback: impl Fn(&[VisualDetection<C, Offset2D<V>>]) + Send + Sync + 'static,
+    ) -> Self {
+        Self(Box::new(callback))
+    }
+}
+
+impl<C, V: Num> Debug for DetectionCallback<C, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DetectionCallback")
+    }
+}
+
+impl<'a, T, U: VisualDetector<V>, V: Num> VisionNorm<'a, T, U, V> {
     pub const fn new(context: &'a T, model: U) -> Self {
+        Self::with_merge_distance(context, model, 0.0)
+    }
+
+    /// Also merges detections closer than `merge_distance`, see [`merge_nearby`]
+    pub const fn with_merge_distance(context: &'a T, model: U, merge_distance: f64) -> Self {
         Self {
             context,
             model,
+            fps: FpsCounter::new(FPS_REPORT_INTERVAL),
+            merge_distance,
+            on_detections: None,
+            roi: None,
             _num: PhantomData,
         }
     }
+
+    /// Completed detections per second over the last report window
+    pub fn current_fps(&self) -> f64 {
+        self.fps.current_fps()
+    }
+
+    /// Calls `callback` with the detections of every frame, after merging
+    ///
+    /// Runs inline in [`ActionExec::execute`], so it should be quick. It must
+    /// not panic: the binary's panic hook cancels every mission.
+    pub fn set_on_detections(&mut self, callback: DetectionCallback<U::ClassEnum, V>) {
+        self.on_detections = Some(callback);
+    }
+
+    /// Keeps only detections in `roi` instead of the configured front ROI
+    pub fn set_roi(&mut self, roi: NormalizedRoi) {
+        self.roi = Some(roi);
+    }
 }
 
-impl<T, U, V> Action for VisionNorm<'_, T, U, V> {}
+impl<T, U: VisualDetector<V>, V: Num> Action for VisionNorm<'_, T, U, V> {}
 
 impl<
         T: FrontCamIO + Send + Sync,
@@ -220,7 +416,7 @@ impl<
 where
     U::Position: RelPos<Number = V> + Debug + for<'a> Mul<&'a Mat, Output = U::Position>,
     VisualDetection<U::ClassEnum, U::Position>: Draw,
-    U::ClassEnum: Send + Sync + Debug,
+    U::ClassEnum: Send + Sync + Debug + DetectionScore,
 {
     async fn execute(&mut self) -> Result<Vec<VisualDetection<U::ClassEnum, Offset2D<V>>>> {
         #[cfg(feature = "logging")]
@@ -230,11 +426,32 @@ where
 
         #[allow(unused_mut)]
         let mut mat = self.context.get_front_camera_mat().await.clone();
-        let detections = self.model.detect(&mat);
+        let detections = self.model.detect_frame(&mat);
+        #[cfg_attr(not(feature = "logging"), allow(unused_variables))]
+        let fps = self.fps.tick();
+        #[cfg(feature = "logging")]
+        if let Some(fps) = fps {
+            logln!("Vision FPS: {fps:.2}");
+        }
         #[cfg(feature = "logging")]
         logln!("Detect attempt: {:#?}", detections);
         let detections = detections?;
         #[cfg(feature = "logging")]
+        let mut mat = into_bgr(mat)?;
+        #[cfg(feature = "annotated_streams")]
+        if annotations_enabled() {
+            let scaled = detections
+                .iter()
+                .map(|x| {
+                    VisualDetection::new(
+                        x.class().clone(),
+                        self.model.normalize(x.position()) * &mat,
+                    )
+                })
+                .collect::<Vec<_>>();
+            annotate_detections(self.context, &mat, &scaled).await;
+        }
+        #[cfg(feature = "logging")]
         {
             detections.iter().for_each(|x| {
                 let x = VisualDetection::new(
@@ -250,19 +467,42 @@ where
                 &Vector::default(),
             )
             .unwrap();
-            #[cfg(feature = "annotated_streams")]
-            self.context.annotate_front_camera(&mat).await;
         }
 
-        Ok(detections
+        let roi = self.roi.unwrap_or_else(|| *FRONT_ROI.read().unwrap());
+        let detections = detections
             .into_iter()
             .map(|detect| {
                 VisualDetection::new(
                     detect.class().clone(),
                     self.model.normalize(detect.position()).offset(),
                 )
+                .with_confidence(*detect.confidence())
             })
-            .collect())
+            .filter(|detect| in_roi(&roi, detect.position().x(), detect.position().y()))
+            .collect();
+        let detections = merge_nearby(
+            detections,
+            V::from_f64(self.merge_distance).unwrap_or(V::zero()),
+        );
+
+        if detection_csv() {
+            let rows: Vec<_> = detections
+                .iter()
+                .map(|d| {
+                    (
+                        d.class(),
+                        d.position().x().to_f64().unwrap_or(f64::NAN),
+                        d.position().y().to_f64().unwrap_or(f64::NAN),
+                    )
+                })
+                .collect();
+            log_detections(std::any::type_name::<U>(), &rows);
+        }
+        if let Some(DetectionCallback(callback)) = &self.on_detections {
+            callback(&detections);
+        }
+        Ok(detections)
     }
 }
 
@@ -308,11 +548,26 @@ where
 
         #[allow(unused_mut)]
         let mut mat = self.context.get_front_camera_mat().await.clone();
-        let detections = self.model.detect(&mat);
+        let detections = self.model.detect_frame(&mat);
         #[cfg(feature = "logging")]
         logln!("Detect attempt: {:#?}", detections);
         let detections = detections?;
         #[cfg(feature = "logging")]
+        let mut mat = into_bgr(mat)?;
+        #[cfg(feature = "annotated_streams")]
+        if annotations_enabled() {
+            let scaled = detections
+                .iter()
+                .map(|x| {
+                    VisualDetection::new(
+                        x.class().clone(),
+                        self.model.normalize(x.position()) * &mat,
+                    )
+                })
+                .collect::<Vec<_>>();
+            annotate_detections(self.context, &mat, &scaled).await;
+        }
+        #[cfg(feature = "logging")]
         {
             detections.iter().for_each(|x| {
                 let x = VisualDetection::new(
@@ -328,8 +583,6 @@ where
                 &Vector::default(),
             )
             .unwrap();
-            #[cfg(feature = "annotated_streams")]
-            self.context.annotate_front_camera(&mat).await;
         }
 
         Ok(detections
@@ -339,6 +592,7 @@ where
                     detect.class().clone(),
                     self.model.normalize(detect.position()).offset_angle(),
                 )
+                .with_confidence(*detect.confidence())
             })
             .collect())
     }
@@ -386,11 +640,13 @@ where
 
         #[allow(unused_mut)]
         let mut mat = self.context.get_bottom_camera_mat().await.clone();
-        let detections = self.model.detect(&mat);
+        let detections = self.model.detect_frame(&mat);
         #[cfg(feature = "logging")]
         logln!("Detect attempt: {:#?}", detections);
         let detections = detections?;
         #[cfg(feature = "logging")]
+        let mut mat = into_bgr(mat)?;
+        #[cfg(feature = "logging")]
         {
             detections.iter().for_each(|x| {
                 let x = VisualDetection::new(
@@ -407,7 +663,9 @@ where
             )
             .unwrap();
             #[cfg(feature = "annotated_streams")]
-            self.context.annotate_bottom_camera(&mat).await;
+            if annotations_enabled() {
+                self.context.annotate_bottom_camera(&mat).await;
+            }
         }
 
         Ok(detections
@@ -417,6 +675,7 @@ where
                     detect.class().clone(),
                     self.model.normalize(detect.position()).offset(),
                 )
+                .with_confidence(*detect.confidence())
             })
             .collect())
     }
@@ -430,6 +689,7 @@ where
 pub struct VisionNormBottomAngle<'a, T, U, V> {
     context: &'a T,
     model: U,
+    roi: Option<NormalizedRoi>,
     _num: PhantomData<V>,
 }
 
@@ -438,9 +698,15 @@ impl<'a, T, U, V> VisionNormBottomAngle<'a, T, U, V> {
         Self {
             context,
             model,
+            roi: None,
             _num: PhantomData,
         }
     }
+
+    /// Keeps only detections in `roi` instead of the configured bottom ROI
+    pub fn set_roi(&mut self, roi: NormalizedRoi) {
+        self.roi = Some(roi);
+    }
 }
 
 impl<T, U, V> Action for VisionNormBottomAngle<'_, T, U, V> {}
@@ -464,11 +730,13 @@ where
 
         #[allow(unused_mut)]
         let mut mat = self.context.get_bottom_camera_mat().await.clone();
-        let detections = self.model.detect(&mat);
+        let detections = self.model.detect_frame(&mat);
         #[cfg(feature = "logging")]
         logln!("Detect attempt: {:#?}", detections);
         let detections = detections?;
         #[cfg(feature = "logging")]
+        let mut mat = into_bgr(mat)?;
+        #[cfg(feature = "logging")]
         {
             detections.iter().for_each(|x| {
                 let x = VisualDetection::new(
@@ -485,9 +753,12 @@ where
             )
             .unwrap();
             #[cfg(feature = "annotated_streams")]
-            self.context.annotate_bottom_camera(&mat).await;
+            if annotations_enabled() {
+                self.context.annotate_bottom_camera(&mat).await;
+            }
         }
 
+        let roi = self.roi.unwrap_or_else(|| *BOTTOM_ROI.read().unwrap());
         Ok(detections
             .into_iter()
             .map(|detect| {
@@ -495,11 +766,178 @@ where
                     detect.class().clone(),
                     self.model.normalize(detect.position()).offset_angle(),
                 )
+                .with_confidence(*detect.confidence())
             })
+            .filter(|detect| in_roi(&roi, detect.position().x(), detect.position().y()))
             .collect())
     }
 }
 
+/// Runs a vision routine on a camera chosen at runtime
+///
+/// The relative positions are normalized to [-1, 1] on both axes.
+/// The values are returned with an angle.
+#[derive(Debug)]
+pub struct VisionNormSelectedAngle<'a, T, U, V> {
+    camera: SelectedCamera<'a, T>,
+    model: U,
+    _num: PhantomData<V>,
+}
+
+impl<'a, T, U, V> VisionNormSelectedAngle<'a, T, U, V> {
+    pub const fn new(context: &'a T, camera: CameraSelect, model: U) -> Self {
+        Self {
+            camera: SelectedCamera::new(context, camera),
+            model,
+            _num: PhantomData,
+        }
+    }
+}
+
+impl<T, U, V> Action for VisionNormSelectedAngle<'_, T, U, V> {}
+
+impl<
+        T: FrontCamIO + BottomCamIO + Send + Sync,
+        V: Num + Float + FromPrimitive + Send + Sync,
+        U: VisualDetector<V> + Send + Sync,
+    > ActionExec<Result<Vec<VisualDetection<U::ClassEnum, Angle2D<V>>>>>
+    for VisionNormSelectedAngle<'_, T, U, V>
+where
+    U::Position: RelPosAngle<Number = V> + Debug + for<'a> Mul<&'a Mat, Output = U::Position>,
+    VisualDetection<U::ClassEnum, U::Position>: Draw,
+    U::ClassEnum: Send + Sync + Debug,
+{
+    async fn execute(&mut self) -> Result<Vec<VisualDetection<U::ClassEnum, Angle2D<V>>>> {
+        #[cfg(feature = "logging")]
+        {
+            logln!("Running detection on {:?} camera...", self.camera.camera());
+        }
+
+        #[allow(unused_mut)]
+        let mut mat = self.camera.get_mat().await.clone();
+        let detections = self.model.detect_frame(&mat);
+        #[cfg(feature = "logging")]
+        logln!("Detect attempt: {:#?}", detections);
+        let detections = detections?;
+        #[cfg(feature = "logging")]
+        let mut mat = into_bgr(mat)?;
+        #[cfg(feature = "logging")]
+        {
+            detections.iter().for_each(|x| {
+                let x = VisualDetection::new(
+                    x.class().clone(),
+                    self.model.normalize(x.position()) * &mat,
+                );
+                x.draw(&mut mat).unwrap()
+            });
+            create_dir_all("/tmp/detect").unwrap();
+            imwrite(
+                &("/tmp/detect/".to_string() + &Uuid::new_v4().to_string() + ".jpeg"),
+                &mat,
+                &Vector::default(),
+            )
+            .unwrap();
+            #[cfg(feature = "annotated_streams")]
+            if annotations_enabled() {
+                self.camera.annotate(&mat).await;
+            }
+        }
+
+        Ok(detections
+            .into_iter()
+            .map(|detect| {
+                VisualDetection::new(
+                    detect.class().clone(),
+                    self.model.normalize(detect.position()).offset_angle(),
+                )
+                .with_confidence(*detect.confidence())
+            })
+            .collect())
+    }
+}
+
+/// Normalized detections from one camera, see [`DualModelVision`]
+pub type NormDetections<U, V> =
+    Result<Vec<VisualDetection<<U as VisualDetector<V>>::ClassEnum, Offset2D<V>>>>;
+
+/// Runs a front and a bottom camera model at the same time
+///
+/// Each model detects on the blocking thread pool, so two forward passes
+/// overlap instead of running back to back on the control loop. The
+/// relative positions are normalized to [-1, 1] on both axes, without an
+/// angle. Results are returned as (front, bottom).
+#[derive(Debug)]
+pub struct DualModelVision<'a, T, F, B, V> {
+    context: &'a T,
+    front: Arc<Mutex<F>>,
+    bottom: Arc<Mutex<B>>,
+    _num: PhantomData<V>,
+}
+
+impl<'a, T, F, B, V> DualModelVision<'a, T, F, B, V> {
+    pub fn new(context: &'a T, front: F, bottom: B) -> Self {
+        Self {
+            context,
+            front: Arc::new(Mutex::new(front)),
+            bottom: Arc::new(Mutex::new(bottom)),
+            _num: PhantomData,
+        }
+    }
+}
+
+impl<T, F, B, V> Action for DualModelVision<'_, T, F, B, V> {}
+
+/// Detects and normalizes on the blocking thread pool
+async fn detect_blocking<U, V>(model: Arc<Mutex<U>>, mat: Mat) -> NormDetections<U, V>
+where
+    U: VisualDetector<V> + Send + 'static,
+    U::Position: RelPos<Number = V>,
+    U::ClassEnum: Send,
+    V: Num + Send + 'static,
+{
+    spawn_blocking(move || {
+        let mut model = model
+            .lock()
+            .map_err(|_| anyhow!("Vision model poisoned by an earlier panic"))?;
+        let detections = model.detect_frame(&mat)?;
+        Ok(detections
+            .into_iter()
+            .map(|detect| {
+                VisualDetection::new(
+                    detect.class().clone(),
+                    model.normalize(detect.position()).offset(),
+                )
+                .with_confidence(*detect.confidence())
+            })
+            .collect())
+    })
+    .await?
+}
+
+impl<T, F, B, V> ActionExec<(NormDetections<F, V>, NormDetections<B, V>)>
+    for DualModelVision<'_, T, F, B, V>
+where
+    T: FrontCamIO + BottomCamIO + Send + Sync,
+    F: VisualDetector<V> + Send + Sync + 'static,
+    B: VisualDetector<V> + Send + Sync + 'static,
+    F::Position: RelPos<Number = V>,
+    B::Position: RelPos<Number = V>,
+    F::ClassEnum: Send + Sync,
+    B::ClassEnum: Send + Sync,
+    V: Num + Send + Sync + 'static,
+{
+    async fn execute(&mut self) -> (NormDetections<F, V>, NormDetections<B, V>) {
+        let (front_mat, bottom_mat) = join!(
+            self.context.get_front_camera_mat(),
+            self.context.get_bottom_camera_mat()
+        );
+        join!(
+            detect_blocking(self.front.clone(), front_mat),
+            detect_blocking(self.bottom.clone(), bottom_mat)
+        )
+    }
+}
+
 /// Normalizes vision output.
 ///
 /// The relative positions are normalized to [-1, 1] on both axes.
@@ -588,7 +1026,7 @@ where
         #[allow(unused_mut)]
         let mut mat = self.context.get_front_camera_mat().await.clone();
 
-        self.model.detect(&mat)
+        self.model.detect_frame(&mat)
     }
 }
 
@@ -632,7 +1070,7 @@ where
         #[allow(unused_mut)]
         let mut mat = self.context.get_front_camera_mat().await.clone();
 
-        let det = self.model.detect(&mat);
+        let det = self.model.detect_frame(&mat);
         match det {
             Ok(x) => Ok(x),
             Err(x) => Err(x),
@@ -733,6 +1171,7 @@ where
                     self.model
                         .normalize(&DrawRect2d::from(*detect.bounding_box())),
                 )
+                .with_confidence(*detect.confidence())
             })
             .take(1)
             .collect())
@@ -1129,7 +1568,7 @@ where
                 } else {
                     x.position().clone()
                 };
-                VisualDetection::new(x.class().clone(), offset)
+                VisualDetection::new(x.class().clone(), offset).with_confidence(*x.confidence())
             })
             .collect()
     }
@@ -1221,3 +1660,293 @@ impl<T: Send + Sync + Clone, U: Send + Sync + Clone> ActionMod<Result<Vec<Visual
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use opencv::{
+        core::{MatTraitConst, Rect, Scalar, Size, CV_8UC1, CV_8UC3},
+        imgproc::{cvt_color_def, rectangle_def, COLOR_BGR2YUV_I420},
+    };
+
+    use super::*;
+    use crate::vision::{
+        color_blob::ColorBlob,
+        image_prep::{set_i420_frames, ContourSelect},
+        Yuv,
+    };
+
+    /// Front camera always shows one bright square right of center
+    struct ScriptedFrame;
+
+    impl FrontCamIO for ScriptedFrame {
+        async fn get_front_camera_mat(&self) -> Mat {
+            let mut image =
+                Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+            rectangle_def(&mut image, Rect::new(270, 120, 60, 60), Scalar::all(255.0)).unwrap();
+            image
+        }
+        #[cfg(feature = "annotated_streams")]
+        async fn annotate_front_camera(&self, _image: &impl opencv::core::ToInputArray) {}
+    }
+
+    #[tokio::test]
+    async fn callback_receives_frame_detections() {
+        let blob = ColorBlob::new(
+            (Yuv { y: 200, u: 0, v: 0 })..=(Yuv {
+                y: 255,
+                u: 255,
+                v: 255,
+            }),
+            1000.0..=10000.0,
+            ContourSelect::default(),
+            Size::from((400, 300)),
+        );
+        let mut vision = VisionNorm::<_, _, f64>::new(&ScriptedFrame, blob);
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen_clone = seen.clone();
+        vision.set_on_detections(DetectionCallback::new(move |detections| {
+            seen_clone.lock().unwrap().extend(detections.to_vec())
+        }));
+
+        let detections = vision.execute().await.unwrap();
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(*seen[0].position().x(), *detections[0].position().x());
+        assert!(*seen[0].position().x() > 0.45 && *seen[0].position().x() < 0.55);
+    }
+
+    /// Bright square right of center in front, left of center on the bottom
+    struct SplitCameras;
+
+    fn square_at(x: i32) -> Mat {
+        let mut image =
+            Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+        rectangle_def(&mut image, Rect::new(x, 120, 60, 60), Scalar::all(255.0)).unwrap();
+        image
+    }
+
+    impl FrontCamIO for SplitCameras {
+        async fn get_front_camera_mat(&self) -> Mat {
+            square_at(270)
+        }
+        #[cfg(feature = "annotated_streams")]
+        async fn annotate_front_camera(&self, _image: &impl opencv::core::ToInputArray) {}
+    }
+
+    impl BottomCamIO for SplitCameras {
+        async fn get_bottom_camera_mat(&self) -> Mat {
+            square_at(70)
+        }
+        #[cfg(feature = "annotated_streams")]
+        async fn annotate_bottom_camera(&self, _image: &impl opencv::core::ToInputArray) {}
+    }
+
+    fn bright_blob() -> ColorBlob {
+        ColorBlob::new(
+            (Yuv { y: 200, u: 0, v: 0 })..=(Yuv {
+                y: 255,
+                u: 255,
+                v: 255,
+            }),
+            1000.0..=10000.0,
+            ContourSelect::default(),
+            Size::from((400, 300)),
+        )
+    }
+
+    /// Front camera showing a bright square, counting annotated frames
+    #[cfg(feature = "annotated_streams")]
+    #[derive(Default)]
+    struct CountingAnnotations {
+        annotated: std::sync::atomic::AtomicUsize,
+    }
+
+    #[cfg(feature = "annotated_streams")]
+    impl FrontCamIO for CountingAnnotations {
+        async fn get_front_camera_mat(&self) -> Mat {
+            square_at(270)
+        }
+        async fn annotate_front_camera(&self, _image: &impl opencv::core::ToInputArray) {
+            self.annotated.fetch_add(1, AtomicOrdering::Relaxed);
+        }
+    }
+
+    #[cfg(feature = "annotated_streams")]
+    #[tokio::test]
+    async fn annotations_follow_runtime_flag() {
+        let context = CountingAnnotations::default();
+        let mut vision = VisionNorm::<_, _, f64>::new(&context, bright_blob());
+
+        set_annotations(false);
+        vision.execute().await.unwrap();
+        assert_eq!(context.annotated.load(AtomicOrdering::Relaxed), 0);
+
+        set_annotations(true);
+        vision.execute().await.unwrap();
+        assert_eq!(context.annotated.load(AtomicOrdering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn camera_rois_apply_independently() {
+        let right_half = NormalizedRoi::new(0.5, 0.0, 0.5, 1.0);
+        let left_half = NormalizedRoi::new(0.0, 0.0, 0.5, 1.0);
+
+        let mut front = VisionNorm::<_, _, f64>::new(&SplitCameras, bright_blob());
+        let mut bottom = VisionNormBottomAngle::<_, _, f64>::new(&SplitCameras, bright_blob());
+        front.set_roi(right_half);
+        bottom.set_roi(right_half);
+        assert_eq!(front.execute().await.unwrap().len(), 1);
+        assert!(bottom.execute().await.unwrap().is_empty());
+
+        front.set_roi(left_half);
+        bottom.set_roi(left_half);
+        assert!(front.execute().await.unwrap().is_empty());
+        assert_eq!(bottom.execute().await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn near_coincident_detections_merge() {
+        let detections = vec![
+            VisualDetection::new(true, Offset2D::new(0.10, 0.20)),
+            VisualDetection::new(true, Offset2D::new(0.11, 0.21)),
+            VisualDetection::new(true, Offset2D::new(-0.50, 0.20)),
+            // Same place, different class
+            VisualDetection::new(false, Offset2D::new(0.10, 0.20)),
+        ];
+
+        let merged = merge_nearby(detections, 0.05);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(*merged[0].position().x(), 0.10);
+        assert_eq!(*merged[1].position().x(), -0.50);
+        assert!(!*merged[2].class());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fps_over_timed_frames() {
+        let mut fps = FpsCounter::new(Duration::from_secs(1));
+        assert_eq!(fps.tick(), None);
+
+        // 10 frames at 100 ms closes the first window
+        for _ in 0..9 {
+            tokio::time::advance(Duration::from_millis(100)).await;
+            assert_eq!(fps.tick(), None);
+        }
+        tokio::time::advance(Duration::from_millis(100)).await;
+        assert_eq!(fps.tick(), Some(10.0));
+        assert_eq!(fps.current_fps(), 10.0);
+
+        // Slower second window
+        for _ in 0..3 {
+            tokio::time::advance(Duration::from_millis(250)).await;
+            assert_eq!(fps.tick(), None);
+        }
+        tokio::time::advance(Duration::from_millis(250)).await;
+        assert_eq!(fps.tick(), Some(4.0));
+    }
+
+    /// Front frames are all 1, bottom frames are all 2
+    struct TwoCameras;
+
+    fn filled(value: f64) -> Mat {
+        Mat::new_rows_cols_with_default(1, 1, CV_8UC1, Scalar::all(value)).unwrap()
+    }
+
+    impl FrontCamIO for TwoCameras {
+        async fn get_front_camera_mat(&self) -> Mat {
+            filled(1.0)
+        }
+        #[cfg(feature = "annotated_streams")]
+        async fn annotate_front_camera(&self, _image: &impl opencv::core::ToInputArray) {}
+    }
+
+    impl BottomCamIO for TwoCameras {
+        async fn get_bottom_camera_mat(&self) -> Mat {
+            filled(2.0)
+        }
+        #[cfg(feature = "annotated_streams")]
+        async fn annotate_bottom_camera(&self, _image: &impl opencv::core::ToInputArray) {}
+    }
+
+    /// Slow model that reports which frame and which model it ran on
+    #[derive(Debug)]
+    struct SlowModel {
+        id: u8,
+    }
+
+    impl VisualDetector<f64> for SlowModel {
+        type ClassEnum = (u8, u8);
+        type Position = Offset2D<f64>;
+
+        fn detect(&mut self, image: &Mat) -> Result<Vec<VisualDetection<(u8, u8), Offset2D<f64>>>> {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            let frame = *image.at_2d::<u8>(0, 0)?;
+            Ok(vec![VisualDetection::new(
+                (self.id, frame),
+                Offset2D::new(0.0, 0.0),
+            )])
+        }
+
+        fn normalize(&mut self, pos: &Offset2D<f64>) -> Offset2D<f64> {
+            pos.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn dual_models_run_concurrently() {
+        let mut vision = DualModelVision::<_, _, _, f64>::new(
+            &TwoCameras,
+            SlowModel { id: 10 },
+            SlowModel { id: 20 },
+        );
+
+        let start = std::time::Instant::now();
+        let (front, bottom) = vision.execute().await;
+        // Back to back would take at least 400 ms
+        assert!(start.elapsed() < std::time::Duration::from_millis(390));
+
+        assert_eq!(*front.unwrap()[0].class(), (10, 1));
+        assert_eq!(*bottom.unwrap()[0].class(), (20, 2));
+    }
+
+    /// Reports how many channels the frame it was given has
+    #[derive(Debug)]
+    struct ChannelModel {
+        accepts_i420: bool,
+    }
+
+    impl VisualDetector<f64> for ChannelModel {
+        type ClassEnum = i32;
+        type Position = Offset2D<f64>;
+
+        fn detect(&mut self, image: &Mat) -> Result<Vec<VisualDetection<i32, Offset2D<f64>>>> {
+            Ok(vec![VisualDetection::new(
+                image.channels(),
+                Offset2D::new(0.0, 0.0),
+            )])
+        }
+
+        fn normalize(&mut self, pos: &Offset2D<f64>) -> Offset2D<f64> {
+            pos.clone()
+        }
+
+        fn accepts_i420(&self) -> bool {
+            self.accepts_i420
+        }
+    }
+
+    #[test]
+    fn i420_frames_converted_for_bgr_models() {
+        set_i420_frames(true);
+        let bgr = Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+        let mut i420 = Mat::default();
+        cvt_color_def(&bgr, &mut i420, COLOR_BGR2YUV_I420).unwrap();
+
+        let channels = |accepts_i420, frame: &Mat| {
+            *ChannelModel { accepts_i420 }.detect_frame(frame).unwrap()[0].class()
+        };
+        assert_eq!(channels(false, &i420), 3);
+        assert_eq!(channels(true, &i420), 1);
+        assert_eq!(channels(false, &bgr), 3);
+    }
+}
@@ -0,0 +1,65 @@
+//! Helpers for testing hardware-facing code with the hardware abstracted out
+
+use std::{
+    future::{ready, Ready},
+    sync::Mutex,
+};
+
+use anyhow::Result;
+use tokio::time::{Duration, Instant};
+
+/// Records what mocked hardware closures are called with, and when
+///
+/// Times are from [`Calls::new`], so they follow a paused test clock.
+#[derive(Debug)]
+pub struct Calls<T> {
+    start: Instant,
+    calls: Mutex<Vec<(Duration, T)>>,
+}
+
+impl<T> Calls<T> {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            calls: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn push(&self, call: T) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((self.start.elapsed(), call));
+    }
+
+    /// Records `call` as a command that succeeds
+    pub fn ok(&self, call: T) -> Ready<Result<()>> {
+        self.push(call);
+        ready(Ok(()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.lock().unwrap().len()
+    }
+
+    pub fn clear(&self) {
+        self.calls.lock().unwrap().clear()
+    }
+}
+
+impl<T: Clone> Calls<T> {
+    /// Calls in order, without their times
+    pub fn values(&self) -> Vec<T> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, call)| call.clone())
+            .collect()
+    }
+
+    /// Calls in order, with the time each was made
+    pub fn timed(&self) -> Vec<(Duration, T)> {
+        self.calls.lock().unwrap().clone()
+    }
+}
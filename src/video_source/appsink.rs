@@ -1,13 +1,23 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use opencv::{
     prelude::Mat,
-    videoio::{VideoCapture, VideoCaptureAPIs, VideoCaptureTrait},
+    videoio::{VideoCapture, VideoCaptureAPIs, VideoCaptureTrait, CAP_PROP_CONVERT_RGB},
 };
-use std::{fs::create_dir_all, path::Path, sync::Arc, thread::spawn};
-use tokio::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::create_dir_all,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{sleep, spawn, JoinHandle},
+    time::{Duration, Instant},
+};
+use tokio::sync::{Mutex, Notify};
 
 #[cfg(feature = "logging")]
-use crate::logln;
+use crate::{log_error, log_warn, logln};
 #[cfg(feature = "annotated_streams")]
 use {
     opencv::{
@@ -21,13 +31,123 @@ use {
     std::sync,
 };
 
-use super::MatSource;
+use super::{reconnecting::ReconnectingSource, MatSource};
+
+/// Pixel format frames are handed to vision in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FrameFormat {
+    /// Packed BGR, what OpenCV expects everywhere
+    #[default]
+    Bgr,
+    /// Planar YUV straight from the decoder, which lets the CV detectors skip
+    /// their BGR to YUV conversion (see
+    /// [`is_i420`](crate::vision::image_prep::is_i420)). Annotated streams
+    /// and the YOLO models are handed frames converted back to BGR.
+    I420,
+}
+
+impl FrameFormat {
+    /// Appsink branch of the capture pipeline for this format
+    fn appsink(&self) -> &'static str {
+        match self {
+            Self::Bgr => "videoconvert ! videobalance brightness=0.0 ! appsink ",
+            Self::I420 => "videoconvert ! video/x-raw,format=I420 ! appsink ",
+        }
+    }
+}
+
+/// Latest value from a producer thread, which async readers wait on without polling
+#[derive(Debug, Default)]
+struct FrameSlot<T> {
+    frame: Mutex<Option<T>>,
+    new_frame: Notify,
+}
+
+impl<T> FrameSlot<T> {
+    /// Replaces the stored value, waking a waiting [`take`](Self::take)
+    fn put_blocking(&self, frame: T) {
+        *self.frame.blocking_lock() = Some(frame);
+        self.new_frame.notify_one();
+    }
+
+    /// Waits for a value, leaving the slot empty
+    async fn take(&self) -> T {
+        loop {
+            if let Some(frame) = self.frame.lock().await.take() {
+                return frame;
+            }
+            self.new_frame.notified().await;
+        }
+    }
+}
+
+/// Pause after a read that returned no frame, instead of spinning on it
+const EMPTY_READ_BACKOFF: Duration = Duration::from_millis(10);
 
+/// How often [`CaptureThread::close`] checks whether the thread has exited
+const CLOSE_POLL: Duration = Duration::from_millis(5);
+
+/// Capture loop thread, told to stop on drop and joined on [`close`](Self::close)
+///
+/// Dropping only signals the thread, since a read from a dead camera can
+/// block indefinitely and dropping happens on async tasks. It releases the
+/// capture once its current read returns.
+#[derive(Debug)]
+struct CaptureThread {
+    stop: Arc<AtomicBool>,
+    handle: std::sync::Mutex<Option<JoinHandle<()>>>,
+}
+
+impl CaptureThread {
+    /// Runs `body` on a new thread, passing the flag it should exit on
+    fn spawn<F: FnOnce(&AtomicBool) + Send + 'static>(body: F) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_copy = stop.clone();
+        let handle = spawn(move || body(&stop_copy));
+        Self {
+            stop,
+            handle: std::sync::Mutex::new(Some(handle)),
+        }
+    }
+
+    /// Stops the thread and waits up to `limit` for it to exit.
+    ///
+    /// Blocks, so call it off the async runtime. Returns whether the thread
+    /// exited; one stuck in a read is left detached.
+    fn close(&self, limit: Duration) -> bool {
+        self.stop.store(true, Ordering::Relaxed);
+        let Some(handle) = self.handle.lock().unwrap().take() else {
+            return true;
+        };
+        let deadline = Instant::now() + limit;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            sleep(CLOSE_POLL);
+        }
+        let _ = handle.join();
+        true
+    }
+}
+
+impl Drop for CaptureThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Camera capture, which stops reading and releases the device once dropped
+/// or [closed](MatSource::close)
 #[derive(Debug)]
 pub struct Camera {
-    frame: Arc<Mutex<Option<Mat>>>,
+    /// Latest frame and when it was captured
+    frame: Arc<FrameSlot<(Mat, Instant)>>,
+    produced: Arc<AtomicU64>,
+    consumed: AtomicU64,
     #[cfg(feature = "annotated_streams")]
     output: Arc<sync::Mutex<VideoWriter>>,
+    capture: CaptureThread,
 }
 
 impl Camera {
@@ -37,6 +157,8 @@ impl Camera {
         filesink: &Path,
         camera_dimensions: (u32, u32),
         rtsp: bool,
+        #[allow(unused_variables)] annotated_dimensions: Option<(u32, u32)>,
+        frame_format: FrameFormat,
     ) -> Result<Self> {
         if !filesink.is_dir() {
             create_dir_all(filesink)?
@@ -49,7 +171,8 @@ impl Camera {
         let capture_string =
             pipeline_head(camera_path, camera_dimensions.0, camera_dimensions.1, 30)
                 + " ! jpegdec ! tee name=raw "
-                + "raw. ! queue  ! videoconvert ! videobalance brightness=0.0 ! appsink "
+                + "raw. ! queue  ! "
+                + frame_format.appsink()
                 + "raw. ! queue  ! videoconvert ! "
                 + &h264_enc_pipeline(2048000)
                 + " ! tee name=h264 "
@@ -65,16 +188,6 @@ impl Camera {
         #[cfg(feature = "annotated_streams")]
         let rtsp_string = "h264. ! queue ! h264parse config_interval=-1 ! video/x-h264,stream-format=byte-stream,alignment=au ! rtspclientsink location=rtsp://127.0.0.1:8554/".to_string()
                         + camera_name + "_annotated.mp4 ";
-        #[cfg(feature = "annotated_streams")]
-        let output_string = "appsrc ! videoconvert ! ".to_string()
-            + &h264_enc_pipeline(2048000)
-            // + " ! h264parse config_interval=-1 ! video/x-h264,stream-format=byte-stream,alignment=au"
-            + " ! h264parse config_interval=-1 ! video/x-h264,stream-format=byte-stream,alignment=au !"
-            + " rtspclientsink location=rtsp://127.0.0.1:8554/"
-            + camera_name
-            + "_annotated.mp4 ";
-        #[cfg(feature = "annotated_streams")]
-        dbg!(&output_string);
         // pipeline_head(camera_path, camera_dimensions.0, camera_dimensions.1, 30)
         // "appsrc ! image/jpeg, width=480, height=640, framerate=30/1".to_string()
         //     + " ! jpegdec ! tee name=raw "
@@ -91,8 +204,61 @@ impl Camera {
         //     + camera_name
         //     + "_annotated.mp4\" ";
 
-        let frame: Arc<Mutex<Option<Mat>>> = Arc::default();
+        Ok(Self::from_pipeline(
+            capture_string,
+            camera_name,
+            annotated_dimensions,
+            frame_format,
+            false,
+        ))
+    }
+
+    /// Replays a recorded video through the same pipeline as a live camera
+    ///
+    /// Playback runs at the recorded frame rate and restarts from the beginning
+    /// at the end of the file, so [`get_mat`](MatSource::get_mat) keeps returning frames.
+    pub fn from_file(
+        video_path: &Path,
+        camera_name: &str,
+        annotated_dimensions: Option<(u32, u32)>,
+        frame_format: FrameFormat,
+    ) -> Result<Self> {
+        if !video_path.is_file() {
+            bail!("Replay video {video_path:?} is not a file");
+        }
+        let capture_string = "filesrc location=\"".to_string()
+            + video_path
+                .to_str()
+                .ok_or(anyhow!("video_path is not a string"))?
+            + "\" ! decodebin ! "
+            + frame_format.appsink();
+
+        Ok(Self::from_pipeline(
+            capture_string,
+            camera_name,
+            annotated_dimensions,
+            frame_format,
+            true,
+        ))
+    }
+
+    /// Starts capturing from `capture_string`, reopening it at the end of the stream if `replay`
+    fn from_pipeline(
+        capture_string: String,
+        #[allow(unused_variables)] camera_name: &str,
+        #[allow(unused_variables)] annotated_dimensions: Option<(u32, u32)>,
+        frame_format: FrameFormat,
+        replay: bool,
+    ) -> Self {
+        #[cfg(feature = "annotated_streams")]
+        let output_string = annotated_pipeline(camera_name, annotated_dimensions);
+        #[cfg(feature = "annotated_streams")]
+        dbg!(&output_string);
+
+        let frame: Arc<FrameSlot<(Mat, Instant)>> = Arc::default();
         let frame_copy = frame.clone();
+        let produced: Arc<AtomicU64> = Arc::default();
+        let produced_copy = produced.clone();
 
         #[cfg(feature = "annotated_streams")]
         let output: Arc<sync::Mutex<VideoWriter>> =
@@ -102,10 +268,8 @@ impl Camera {
 
         #[cfg(feature = "logging")]
         logln!("Capture string: {capture_string}");
-        spawn(move || {
-            let mut capture =
-                VideoCapture::from_file(&capture_string, VideoCaptureAPIs::CAP_GSTREAMER as i32)
-                    .unwrap();
+        let capture_thread = CaptureThread::spawn(move |stop| {
+            let mut capture = open_capture(&capture_string, frame_format).unwrap();
 
             #[cfg(feature = "annotated_streams")]
             {
@@ -129,23 +293,81 @@ impl Camera {
                 )
                 .unwrap();
             }
-            loop {
+            while !stop.load(Ordering::Relaxed) {
                 let mut mat = Mat::default();
-                if capture.read(&mut mat).unwrap() {
-                    *frame_copy.blocking_lock() = Some(mat)
+                match capture.read(&mut mat) {
+                    Ok(true) => {
+                        frame_copy.put_blocking((mat, Instant::now()));
+                        produced_copy.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Ok(false) if replay => {
+                        #[cfg(feature = "logging")]
+                        logln!("Replay reached the end, restarting");
+                        match open_capture(&capture_string, frame_format) {
+                            Ok(reopened) => capture = reopened,
+                            #[allow(unused_variables)]
+                            Err(e) => {
+                                #[cfg(feature = "logging")]
+                                log_error!("Failed to restart replay, stopping: {:#?}", e);
+                                break;
+                            }
+                        }
+                    }
+                    Ok(false) => sleep(EMPTY_READ_BACKOFF),
+                    // Let the frame go stale so a wrapping source can rebuild
+                    #[allow(unused_variables)]
+                    Err(e) => {
+                        #[cfg(feature = "logging")]
+                        log_error!("Capture read failed, stopping: {:#?}", e);
+                        break;
+                    }
                 }
             }
         });
 
-        Ok(Self {
+        Self {
             frame,
+            produced,
+            consumed: AtomicU64::new(0),
             #[cfg(feature = "annotated_streams")]
             output,
-        })
+            capture: capture_thread,
+        }
+    }
+
+    pub fn jetson_new(
+        camera_path: &str,
+        camera_name: &str,
+        filesink_dir: &Path,
+        annotated_dimensions: Option<(u32, u32)>,
+        frame_format: FrameFormat,
+    ) -> Result<Self> {
+        Camera::new(
+            camera_path,
+            camera_name,
+            filesink_dir,
+            (640, 480),
+            true,
+            annotated_dimensions,
+            frame_format,
+        )
     }
 
-    pub fn jetson_new(camera_path: &str, camera_name: &str, filesink_dir: &Path) -> Result<Self> {
-        Camera::new(camera_path, camera_name, filesink_dir, (640, 480), true)
+    /// Frames captured and frames taken by [`get_mat`](MatSource::get_mat), since opening
+    ///
+    /// Captured frames that were never taken were replaced by a newer one.
+    pub fn frame_stats(&self) -> (u64, u64) {
+        (
+            self.produced.load(Ordering::Relaxed),
+            self.consumed.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Waits for the next frame, along with when it was captured
+    pub async fn get_mat_timed(&self) -> (Mat, Instant) {
+        let frame = self.frame.take().await;
+        self.consumed.fetch_add(1, Ordering::Relaxed);
+        frame
     }
 
     #[cfg(feature = "annotated_streams")]
@@ -156,16 +378,44 @@ impl Camera {
     }
 }
 
+impl ReconnectingSource<Camera> {
+    /// Frame counts of the current pipeline, which restart when it is rebuilt
+    pub async fn frame_stats(&self) -> (u64, u64) {
+        self.inner().await.frame_stats()
+    }
+}
+
+#[cfg(feature = "annotated_streams")]
+impl ReconnectingSource<Camera> {
+    pub async fn push_annotated_frame(&self, image: &impl ToInputArray) {
+        self.inner().await.push_annotated_frame(image);
+    }
+}
+
 impl MatSource for Camera {
     async fn get_mat(&self) -> Mat {
-        loop {
-            if let Some(mat) = self.frame.lock().await.take() {
-                return mat;
-            }
+        self.get_mat_timed().await.0
+    }
+
+    fn close(&self, limit: Duration) {
+        if !self.capture.close(limit) {
+            #[cfg(feature = "logging")]
+            log_warn!("Capture thread still reading after {limit:?}, detaching it");
         }
     }
 }
 
+/// Opens the GStreamer `capture_string`, set up to hand over `frame_format` frames
+fn open_capture(capture_string: &str, frame_format: FrameFormat) -> Result<VideoCapture> {
+    let mut capture =
+        VideoCapture::from_file(capture_string, VideoCaptureAPIs::CAP_GSTREAMER as i32)?;
+    if frame_format == FrameFormat::I420 {
+        // Hand over the raw planes instead of converting back to BGR
+        let _ = capture.set(CAP_PROP_CONVERT_RGB, 0.0);
+    }
+    Ok(capture)
+}
+
 fn pipeline_head(device_name: &str, width: u32, height: u32, framerate: u32) -> String {
     #[cfg(target_os = "windows")]
     return format!("mfvideosrc device-index={device_name} ! image/jpeg, width={width}, height={height}, framerate={framerate}/1");
@@ -174,6 +424,24 @@ fn pipeline_head(device_name: &str, width: u32, height: u32, framerate: u32) ->
     return format!("v4l2src device={device_name} ! image/jpeg, width={width}, height={height}, framerate={framerate}/1");
 }
 
+/// Pipeline for the annotated stream, downscaled if `dimensions` is set
+#[cfg(feature = "annotated_streams")]
+fn annotated_pipeline(camera_name: &str, dimensions: Option<(u32, u32)>) -> String {
+    let scale = dimensions
+        .map(|(width, height)| {
+            format!("videoscale ! video/x-raw, width={width}, height={height} ! ")
+        })
+        .unwrap_or_default();
+
+    "appsrc ! videoconvert ! ".to_string()
+        + &scale
+        + &h264_enc_pipeline(2048000)
+        + " ! h264parse config_interval=-1 ! video/x-h264,stream-format=byte-stream,alignment=au !"
+        + " rtspclientsink location=rtsp://127.0.0.1:8554/"
+        + camera_name
+        + "_annotated.mp4 "
+}
+
 fn h264_enc_pipeline(bitrate: u32) -> String {
     if Path::new("/etc/nv_tegra_release").exists() {
         format!(
@@ -199,10 +467,139 @@ mod tests {
             // Camera dependent parameter
             (640, 360),
             false,
+            None,
+            FrameFormat::default(),
         )
         .unwrap()
         .get_mat()
         .await;
         logln!("{:?}", output);
     }
+
+    #[tokio::test]
+    async fn take_wakes_on_new_frame() {
+        use std::{thread::sleep, time::Duration};
+
+        let slot: Arc<FrameSlot<u32>> = Arc::default();
+        let producer = slot.clone();
+        let pushed_at = Arc::new(std::sync::Mutex::new(None));
+        let pushed_at_copy = pushed_at.clone();
+        spawn(move || {
+            sleep(Duration::from_millis(50));
+            *pushed_at_copy.lock().unwrap() = Some(Instant::now());
+            producer.put_blocking(7);
+        });
+
+        assert_eq!(slot.take().await, 7);
+        let waited = pushed_at.lock().unwrap().unwrap().elapsed();
+        assert!(
+            waited < Duration::from_millis(20),
+            "woke {waited:?} after the push"
+        );
+
+        // A frame that lands before anyone waits is taken right away
+        let producer = slot.clone();
+        spawn(move || producer.put_blocking(8)).join().unwrap();
+        assert_eq!(slot.take().await, 8);
+    }
+
+    #[test]
+    fn drop_stops_capture_loop() {
+        use std::{sync::atomic::AtomicUsize, thread::yield_now};
+
+        let reads = Arc::new(AtomicUsize::new(0));
+        let reads_copy = reads.clone();
+        let exited = Arc::new(AtomicBool::new(false));
+        let exited_copy = exited.clone();
+        let capture = CaptureThread::spawn(move |stop| {
+            while !stop.load(Ordering::Relaxed) {
+                reads_copy.fetch_add(1, Ordering::Relaxed);
+                sleep(Duration::from_millis(1));
+            }
+            exited_copy.store(true, Ordering::Relaxed);
+        });
+        while reads.load(Ordering::Relaxed) == 0 {
+            yield_now();
+        }
+
+        drop(capture);
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while !exited.load(Ordering::Relaxed) {
+            assert!(Instant::now() < deadline, "capture loop kept running");
+            sleep(Duration::from_millis(1));
+        }
+        let stopped_at = reads.load(Ordering::Relaxed);
+        sleep(Duration::from_millis(20));
+        assert_eq!(reads.load(Ordering::Relaxed), stopped_at);
+    }
+
+    #[test]
+    fn drop_does_not_wait_for_blocked_read() {
+        use std::sync::mpsc::channel;
+
+        // Stands in for a read on a camera that stopped responding
+        let (unblock, blocked) = channel::<()>();
+        let (exited, exit) = channel();
+        let capture = CaptureThread::spawn(move |stop| {
+            while !stop.load(Ordering::Relaxed) {
+                let _ = blocked.recv();
+            }
+            let _ = exited.send(());
+        });
+
+        // Would deadlock if drop joined the thread
+        drop(capture);
+        unblock.send(()).unwrap();
+        exit.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
+
+    #[cfg(feature = "annotated_streams")]
+    #[test]
+    fn annotated_pipeline_scale() {
+        let full = annotated_pipeline("front", None);
+        assert!(!full.contains("videoscale"));
+
+        let scaled = annotated_pipeline("front", Some((320, 240)));
+        assert!(scaled.starts_with(
+            "appsrc ! videoconvert ! videoscale ! video/x-raw, width=320, height=240 ! "
+        ));
+        assert!(scaled.ends_with("front_annotated.mp4 "));
+    }
+
+    #[test]
+    fn close_joins_capture_loop() {
+        let exited = Arc::new(AtomicBool::new(false));
+        let exited_copy = exited.clone();
+        let capture = CaptureThread::spawn(move |stop| {
+            while !stop.load(Ordering::Relaxed) {
+                sleep(Duration::from_millis(1));
+            }
+            exited_copy.store(true, Ordering::Relaxed);
+        });
+
+        assert!(capture.close(Duration::from_secs(1)));
+        assert!(exited.load(Ordering::Relaxed));
+        // Already joined
+        assert!(capture.close(Duration::ZERO));
+    }
+
+    #[test]
+    fn close_gives_up_on_blocked_read() {
+        use std::sync::mpsc::channel;
+
+        let (unblock, blocked) = channel::<()>();
+        let (reading, started) = channel();
+        let capture = CaptureThread::spawn(move |stop| {
+            while !stop.load(Ordering::Relaxed) {
+                let _ = reading.send(());
+                let _ = blocked.recv();
+            }
+        });
+        started.recv().unwrap();
+
+        let start = Instant::now();
+        assert!(!capture.close(Duration::from_millis(50)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        unblock.send(()).unwrap();
+    }
 }
@@ -1,12 +1,20 @@
 use opencv::prelude::Mat;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
 pub mod appsink;
+pub mod reconnecting;
 
 #[allow(async_fn_in_trait)]
 pub trait MatSource: Send + Sync {
     async fn get_mat(&self) -> Mat;
+
+    /// Releases the underlying device, waiting at most `limit` for it.
+    ///
+    /// Blocking, so call it off the async runtime. Sources without a device
+    /// to free need not implement it.
+    fn close(&self, _limit: Duration) {}
 }
 
 #[derive(Debug)]
@@ -0,0 +1,227 @@
+use anyhow::Result;
+use core::fmt::Debug;
+use opencv::prelude::Mat;
+use std::{mem::replace, sync::Arc, time::Duration};
+use tokio::{
+    sync::{Mutex, RwLock},
+    task::spawn_blocking,
+    time::timeout,
+};
+
+#[cfg(feature = "logging")]
+use crate::log_warn;
+
+use super::MatSource;
+
+/// Longest wait for a timed out source to release its device before rebuilding
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Wraps a [`MatSource`], rebuilding it when frames stop arriving.
+///
+/// If the inner source does not produce a frame within `timeout`, it is
+/// [closed](MatSource::close) to free its device, then `rebuild` is called and
+/// the result replaces the inner source. A failed rebuild keeps the closed
+/// source and is retried after the next timeout. Closing and rebuilding run
+/// on a blocking task, off the async runtime.
+///
+/// Callers that time out together rebuild once: the rest wait for that
+/// rebuild and then read from whatever it left in place.
+pub struct ReconnectingSource<S> {
+    inner: RwLock<Generation<S>>,
+    rebuilding: Mutex<()>,
+    rebuild: Arc<dyn Fn() -> Result<S> + Send + Sync>,
+    timeout: Duration,
+}
+
+/// Active source, tagged with how many rebuilds have been attempted
+#[derive(Debug)]
+struct Generation<S> {
+    source: Arc<S>,
+    rebuilds: u64,
+}
+
+impl<S> ReconnectingSource<S> {
+    pub fn new(
+        source: S,
+        timeout: Duration,
+        rebuild: impl Fn() -> Result<S> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            inner: RwLock::new(Generation {
+                source: Arc::new(source),
+                rebuilds: 0,
+            }),
+            rebuilding: Mutex::new(()),
+            rebuild: Arc::new(rebuild),
+            timeout,
+        }
+    }
+
+    /// The currently active source
+    pub async fn inner(&self) -> Arc<S> {
+        self.inner.read().await.source.clone()
+    }
+}
+
+impl<S: Debug> Debug for ReconnectingSource<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReconnectingSource")
+            .field("inner", &self.inner)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: MatSource + 'static> MatSource for ReconnectingSource<S> {
+    async fn get_mat(&self) -> Mat {
+        loop {
+            let (source, rebuilds) = {
+                let active = self.inner.read().await;
+                (active.source.clone(), active.rebuilds)
+            };
+            if let Ok(mat) = timeout(self.timeout, source.get_mat()).await {
+                return mat;
+            }
+
+            let _rebuilding = self.rebuilding.lock().await;
+            if self.inner.read().await.rebuilds != rebuilds {
+                // Another caller rebuilt it while this one was waiting
+                continue;
+            }
+
+            #[cfg(feature = "logging")]
+            log_warn!("No frame within {:?}, rebuilding source", self.timeout);
+
+            // The old source has to let go of the device before it can be reopened
+            let rebuild = self.rebuild.clone();
+            let rebuilt = spawn_blocking(move || {
+                source.close(CLOSE_TIMEOUT);
+                rebuild()
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.into()));
+
+            let mut active = self.inner.write().await;
+            active.rebuilds += 1;
+            match rebuilt {
+                Ok(source) => {
+                    let old = replace(&mut active.source, Arc::new(source));
+                    spawn_blocking(move || drop(old));
+                }
+                #[allow(unused_variables)]
+                Err(e) => {
+                    #[cfg(feature = "logging")]
+                    log_warn!("Failed to rebuild source: {:#?}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    /// Produces frames while alive, hangs forever once dead
+    #[derive(Default)]
+    struct MockSource {
+        alive: bool,
+        closed: Arc<AtomicBool>,
+    }
+
+    impl MockSource {
+        fn new(alive: bool) -> Self {
+            Self {
+                alive,
+                ..Default::default()
+            }
+        }
+    }
+
+    impl MatSource for MockSource {
+        async fn get_mat(&self) -> Mat {
+            if self.alive {
+                Mat::default()
+            } else {
+                std::future::pending().await
+            }
+        }
+
+        fn close(&self, _limit: Duration) {
+            self.closed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn live_source_not_rebuilt() {
+        let rebuilds = Arc::new(AtomicUsize::new(0));
+        let rebuilds_copy = rebuilds.clone();
+        let source = ReconnectingSource::new(
+            MockSource::new(true),
+            Duration::from_millis(50),
+            move || {
+                rebuilds_copy.fetch_add(1, Ordering::SeqCst);
+                Ok(MockSource::new(true))
+            },
+        );
+
+        source.get_mat().await;
+        assert_eq!(rebuilds.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn dead_source_rebuilt() {
+        let rebuilds = Arc::new(AtomicUsize::new(0));
+        let rebuilds_copy = rebuilds.clone();
+        let source = ReconnectingSource::new(
+            MockSource::new(false),
+            Duration::from_millis(50),
+            move || {
+                rebuilds_copy.fetch_add(1, Ordering::SeqCst);
+                Ok(MockSource::new(true))
+            },
+        );
+
+        source.get_mat().await;
+        assert_eq!(rebuilds.load(Ordering::SeqCst), 1);
+        assert!(source.inner().await.alive);
+    }
+
+    #[tokio::test]
+    async fn dead_source_closed_before_rebuild() {
+        let dead = MockSource::new(false);
+        let closed = dead.closed.clone();
+        let closed_at_rebuild = Arc::new(AtomicBool::new(false));
+        let closed_at_rebuild_copy = closed_at_rebuild.clone();
+        let source = ReconnectingSource::new(dead, Duration::from_millis(50), move || {
+            closed_at_rebuild_copy.store(closed.load(Ordering::SeqCst), Ordering::SeqCst);
+            Ok(MockSource::new(true))
+        });
+
+        source.get_mat().await;
+        assert!(closed_at_rebuild.load(Ordering::SeqCst));
+        assert!(!source.inner().await.closed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_rebuild_once() {
+        let rebuilds = Arc::new(AtomicUsize::new(0));
+        let rebuilds_copy = rebuilds.clone();
+        let source = ReconnectingSource::new(
+            MockSource::new(false),
+            Duration::from_millis(50),
+            move || {
+                rebuilds_copy.fetch_add(1, Ordering::SeqCst);
+                Ok(MockSource::new(true))
+            },
+        );
+
+        tokio::join!(source.get_mat(), source.get_mat(), source.get_mat());
+        assert_eq!(rebuilds.load(Ordering::SeqCst), 1);
+    }
+}
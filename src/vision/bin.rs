@@ -5,7 +5,7 @@ use opencv::{core::Size, prelude::Mat};
 use crate::load_onnx;
 
 use super::{
-    nn_cv2::{OnnxModel, VisionModel, YoloClass, YoloDetection},
+    nn_cv2::{report_confidence_floor, OnnxModel, VisionModel, YoloClass, YoloDetection},
     yolo_model::YoloProcessor,
 };
 
@@ -19,6 +19,12 @@ pub enum Target {
     SawFish,
 }
 
+crate::class_names!(Target {
+    Bin,
+    ReefShark,
+    SawFish
+});
+
 impl From<YoloClass<Target>> for Target {
     fn from(value: YoloClass<Target>) -> Self {
         value.identifier
@@ -59,6 +65,7 @@ impl Display for Target {
 #[derive(Debug, Clone, Getters)]
 pub struct Bin<T: VisionModel> {
     model: T,
+    /// Minimum confidence missions act on, applied after detection
     threshold: f64,
 }
 
@@ -86,7 +93,7 @@ impl YoloProcessor for Bin<OnnxModel> {
     type Target = Target;
 
     fn detect_yolo_v5(&mut self, image: &Mat) -> Vec<YoloDetection> {
-        self.model.detect_yolo_v5(image, self.threshold)
+        self.model.detect_yolo_v5(image, report_confidence_floor())
     }
 
     fn model_size(&self) -> Size {
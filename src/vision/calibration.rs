@@ -0,0 +1,348 @@
+use std::ops::RangeInclusive;
+
+use anyhow::{bail, Result};
+use opencv::{
+    core::{add_weighted_def, in_range, no_array, Point, Point2f, Rect, Scalar, Size, Vector},
+    imgproc::{
+        calc_hist, circle, contour_area_def, cvt_color_def, draw_contours, draw_contours_def,
+        find_contours_def, min_area_rect, CHAIN_APPROX_SIMPLE, COLOR_BGR2YUV, FILLED, LINE_8,
+        RETR_EXTERNAL,
+    },
+    prelude::{Mat, MatTrait, MatTraitConst, MatTraitConstManual},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    image_prep::{oriented_size, resize, to_bgr, ContourSelect},
+    Yuv,
+};
+
+#[cfg(feature = "logging")]
+use crate::logln;
+
+/// Region of a frame, with all values as fractions of the frame size
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NormalizedRoi {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl NormalizedRoi {
+    pub const fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The whole frame
+    pub const fn full() -> Self {
+        Self::new(0.0, 0.0, 1.0, 1.0)
+    }
+
+    /// Pixel rectangle for a frame of `size`, clipped to the frame
+    pub fn to_rect(&self, size: Size) -> Result<Rect> {
+        let x = (self.x.clamp(0.0, 1.0) * size.width as f64) as i32;
+        let y = (self.y.clamp(0.0, 1.0) * size.height as f64) as i32;
+        let width = ((self.width.max(0.0) * size.width as f64) as i32).min(size.width - x);
+        let height = ((self.height.max(0.0) * size.height as f64) as i32).min(size.height - y);
+
+        if width <= 0 || height <= 0 {
+            bail!("ROI {:?} is empty for frame size {:?}", self, size);
+        }
+        Ok(Rect::new(x, y, width, height))
+    }
+
+    /// Whether a position normalized to [-1, 1] on both axes is in the region
+    pub fn contains_offset(&self, x: f64, y: f64) -> bool {
+        let (x, y) = ((x + 1.0) / 2.0, (y + 1.0) / 2.0);
+        (self.x..=self.x + self.width).contains(&x) && (self.y..=self.y + self.height).contains(&y)
+    }
+}
+
+/// Per-channel pixel counts over evenly sized bins spanning 0..=255
+#[derive(Debug, Clone, PartialEq)]
+pub struct YuvHistogram {
+    pub y: Vec<f32>,
+    pub u: Vec<f32>,
+    pub v: Vec<f32>,
+}
+
+impl YuvHistogram {
+    /// Width of each bin in channel values
+    pub fn bin_width(&self) -> usize {
+        256 / self.y.len()
+    }
+
+    /// One row per bin: `bin_start,y,u,v`
+    pub fn to_csv(&self) -> String {
+        let bin_width = self.bin_width();
+        let mut csv = "bin_start,y,u,v\n".to_string();
+        for (idx, ((y, u), v)) in self.y.iter().zip(&self.u).zip(&self.v).enumerate() {
+            csv += &format!("{},{y},{u},{v}\n", idx * bin_width);
+        }
+        csv
+    }
+}
+
+/// Computes Y, U, and V histograms of `roi` in a BGR `frame`
+///
+/// Complements mean/deviation calibration by showing the full distribution,
+/// which exposes multi-modal regions (glare, shadows) before picking ranges.
+///
+/// # Arguments
+/// * `frame` - BGR image
+/// * `roi` - Region to sample
+/// * `bins` - Bins per channel, must divide 256
+pub fn yuv_histogram(frame: &Mat, roi: &NormalizedRoi, bins: i32) -> Result<YuvHistogram> {
+    if bins <= 0 || 256 % bins != 0 {
+        bail!("Histogram bins must divide 256, got {bins}");
+    }
+
+    let rect = roi.to_rect(frame.size()?)?;
+    let mut yuv_image = Mat::default();
+    cvt_color_def(&Mat::roi(frame, rect)?, &mut yuv_image, COLOR_BGR2YUV)?;
+    let images = Vector::<Mat>::from_iter([yuv_image]);
+
+    let channel_hist = |channel: i32| -> Result<Vec<f32>> {
+        let mut hist = Mat::default();
+        calc_hist(
+            &images,
+            &Vector::from_slice(&[channel]),
+            &no_array(),
+            &mut hist,
+            &Vector::from_slice(&[bins]),
+            &Vector::from_slice(&[0.0, 256.0]),
+            false,
+        )?;
+        Ok(hist.data_typed::<f32>()?.to_vec())
+    };
+
+    Ok(YuvHistogram {
+        y: channel_hist(0)?,
+        u: channel_hist(1)?,
+        v: channel_hist(2)?,
+    })
+}
+
+/// Computes [`yuv_histogram`] and logs it as CSV
+#[cfg(feature = "logging")]
+pub fn log_yuv_histogram(frame: &Mat, roi: &NormalizedRoi, bins: i32) -> Result<YuvHistogram> {
+    let hist = yuv_histogram(frame, roi, bins)?;
+    logln!("YUV histogram for {:?}\n{}", roi, hist.to_csv());
+    Ok(hist)
+}
+
+/// Mask pixels are blended toward this color in [`mask_overlay`]
+const MASK_TINT: (f64, f64, f64) = (0.0, 255.0, 0.0);
+/// Outline of every contour in [`mask_overlay`]
+const CONTOUR_COLOR: (f64, f64, f64) = (255.0, 0.0, 0.0);
+/// Selected contour and its center in [`mask_overlay`]
+const SELECTED_COLOR: (f64, f64, f64) = (0.0, 0.0, 255.0);
+
+/// Draws what a color blob detector sees, for tuning color profiles by eye
+///
+/// `frame` is resized to `size`, turned to the frame's orientation, like
+/// [`ColorBlob`](super::color_blob::ColorBlob) does. Then pixels inside
+/// `color` are tinted, every contour of the mask is outlined, and the contour
+/// `contour_select` picks is drawn thicker with its center marked, if its
+/// area is within `area_bounds`.
+///
+/// Returns the BGR overlay and the selected center, in overlay pixels.
+pub fn mask_overlay(
+    frame: &Mat,
+    color: &RangeInclusive<Yuv>,
+    area_bounds: &RangeInclusive<f64>,
+    contour_select: ContourSelect,
+    size: Size,
+) -> Result<(Mat, Option<Point2f>)> {
+    let bgr = to_bgr(frame)?;
+    let size = oriented_size(bgr.size()?, size);
+    let bgr = resize(&bgr, &size)?;
+    let mut yuv = Mat::default();
+    cvt_color_def(&bgr, &mut yuv, COLOR_BGR2YUV)?;
+
+    let [lower, upper] = [color.start(), color.end()]
+        .map(|bound| Scalar::new(bound.y as f64, bound.u as f64, bound.v as f64, 0.0));
+    let mut mask = Mat::default();
+    in_range(&yuv, &lower, &upper, &mut mask)?;
+
+    let mut tinted = bgr.clone();
+    tinted.set_to(&Scalar::from(MASK_TINT), &mask)?;
+    let mut overlay = Mat::default();
+    add_weighted_def(&bgr, 0.5, &tinted, 0.5, 0.0, &mut overlay)?;
+
+    let mut contours = Vector::<Vector<Point>>::new();
+    find_contours_def(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)?;
+    draw_contours_def(&mut overlay, &contours, -1, Scalar::from(CONTOUR_COLOR))?;
+
+    let Some(selected) = contour_select.select(&contours, size)? else {
+        return Ok((overlay, None));
+    };
+    if !area_bounds.contains(&contour_area_def(&selected)?) {
+        return Ok((overlay, None));
+    }
+
+    let center = min_area_rect(&selected)?.center;
+    draw_contours(
+        &mut overlay,
+        &Vector::<Vector<Point>>::from_iter([selected]),
+        0,
+        Scalar::from(SELECTED_COLOR),
+        3,
+        LINE_8,
+        &no_array(),
+        i32::MAX,
+        Point::default(),
+    )?;
+    circle(
+        &mut overlay,
+        Point::new(center.x as i32, center.y as i32),
+        5,
+        Scalar::from(SELECTED_COLOR),
+        FILLED,
+        LINE_8,
+        0,
+    )?;
+    Ok((overlay, Some(center)))
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::{
+        core::{Scalar, Vec3b, CV_8UC3},
+        imgproc::{rectangle, COLOR_BGR2YUV_I420, FILLED, LINE_8},
+    };
+
+    use super::*;
+    use crate::vision::image_prep::set_i420_frames;
+
+    /// Top half black | white, bottom half mid gray
+    fn two_tone_image() -> Mat {
+        let mut image =
+            Mat::new_rows_cols_with_default(200, 100, CV_8UC3, Scalar::all(128.0)).unwrap();
+        for (rect, color) in [
+            (Rect::new(0, 0, 50, 100), 0.0),
+            (Rect::new(50, 0, 50, 100), 255.0),
+        ] {
+            rectangle(&mut image, rect, Scalar::all(color), FILLED, LINE_8, 0).unwrap();
+        }
+        image
+    }
+
+    #[test]
+    fn two_tone_bins() {
+        let hist = yuv_histogram(
+            &two_tone_image(),
+            &NormalizedRoi::new(0.0, 0.0, 1.0, 0.5),
+            16,
+        )
+        .unwrap();
+
+        let mut expected_y = vec![0.0; 16];
+        expected_y[0] = 5000.0;
+        expected_y[15] = 5000.0;
+        assert_eq!(hist.y, expected_y);
+
+        // Grays have neutral chroma
+        let mut expected_uv = vec![0.0; 16];
+        expected_uv[8] = 10000.0;
+        assert_eq!(hist.u, expected_uv);
+        assert_eq!(hist.v, expected_uv);
+    }
+
+    #[test]
+    fn csv_rows() {
+        let hist = yuv_histogram(&two_tone_image(), &NormalizedRoi::full(), 4).unwrap();
+        let csv = hist.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("bin_start,y,u,v"));
+        assert_eq!(lines.next(), Some("0,5000,0,0"));
+        assert_eq!(csv.lines().count(), 5);
+    }
+
+    #[test]
+    fn invalid_bins() {
+        assert!(yuv_histogram(&two_tone_image(), &NormalizedRoi::full(), 3).is_err());
+    }
+
+    fn bright() -> RangeInclusive<Yuv> {
+        (Yuv { y: 200, u: 0, v: 0 })..=(Yuv {
+            y: 255,
+            u: 255,
+            v: 255,
+        })
+    }
+
+    #[test]
+    fn overlay_marks_mask_and_blob() {
+        set_i420_frames(true);
+        let mut image =
+            Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+        rectangle(
+            &mut image,
+            Rect::new(250, 50, 60, 60),
+            Scalar::all(255.0),
+            FILLED,
+            LINE_8,
+            0,
+        )
+        .unwrap();
+        let size = Size::new(400, 300);
+
+        for frame in [image.clone(), {
+            let mut i420 = Mat::default();
+            cvt_color_def(&image, &mut i420, COLOR_BGR2YUV_I420).unwrap();
+            i420
+        }] {
+            let (overlay, center) = mask_overlay(
+                &frame,
+                &bright(),
+                &(1000.0..=10000.0),
+                ContourSelect::default(),
+                size,
+            )
+            .unwrap();
+            assert_eq!(overlay.size().unwrap(), size);
+            assert_eq!(overlay.typ(), CV_8UC3);
+
+            let center = center.unwrap();
+            assert!((center.x - 280.0).abs() < 2.0 && (center.y - 80.0).abs() < 2.0);
+            // Center marker over the tinted blob
+            assert_eq!(
+                *overlay.at_2d::<Vec3b>(80, 280).unwrap(),
+                Vec3b::from([0, 0, 255])
+            );
+            // Masked pixels pick up the tint, the background is untouched
+            let masked = *overlay.at_2d::<Vec3b>(60, 300).unwrap();
+            assert!(masked[1] > masked[0] && masked[1] > masked[2]);
+            assert_eq!(*overlay.at_2d::<Vec3b>(200, 50).unwrap(), Vec3b::all(0));
+        }
+    }
+
+    #[test]
+    fn overlay_without_blob() {
+        let image = Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+        let (overlay, center) = mask_overlay(
+            &image,
+            &bright(),
+            &(1000.0..=10000.0),
+            ContourSelect::default(),
+            Size::new(200, 150),
+        )
+        .unwrap();
+        assert!(center.is_none());
+        assert_eq!(overlay.size().unwrap(), Size::new(200, 150));
+    }
+
+    #[test]
+    fn empty_roi() {
+        assert!(NormalizedRoi::new(1.0, 0.0, 0.5, 0.5)
+            .to_rect(Size::new(100, 100))
+            .is_err());
+    }
+}
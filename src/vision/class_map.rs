@@ -0,0 +1,245 @@
+use std::{fs::read_to_string, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+use super::nn_cv2::YoloDetection;
+
+/// Model class id to class name mapping, loaded at runtime
+///
+/// Lets a retrained model reorder its classes without recompiling the
+/// `TryFrom<i32>` mapping of its target enum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassMap {
+    names: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClassMapToml {
+    names: Vec<String>,
+}
+
+impl ClassMap {
+    pub fn new(names: Vec<String>) -> Self {
+        Self { names }
+    }
+
+    /// YOLO `classes.txt` format, one name per line in class id order
+    pub fn from_classes_txt(contents: &str) -> Self {
+        Self::new(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+
+    /// TOML with a `names` array in class id order
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        Ok(Self::new(toml::from_str::<ClassMapToml>(contents)?.names))
+    }
+
+    /// Loads `.toml` files as TOML and anything else as `classes.txt`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            Self::from_toml(&contents)
+        } else {
+            Ok(Self::from_classes_txt(&contents))
+        }
+    }
+
+    /// Mapping stored next to `model_path`, with a `.txt` or `.toml` extension
+    pub fn sidecar(model_path: impl AsRef<Path>) -> Option<Result<Self>> {
+        ["txt", "toml"]
+            .into_iter()
+            .map(|ext| model_path.as_ref().with_extension(ext))
+            .find(|path| path.exists())
+            .map(Self::from_file)
+    }
+
+    pub fn name(&self, id: i32) -> Option<&str> {
+        usize::try_from(id)
+            .ok()
+            .and_then(|idx| self.names.get(idx))
+            .map(String::as_str)
+    }
+
+    /// Resolves `id` through its name in this map
+    pub fn resolve<T: FromStr>(&self, id: i32) -> Result<T> {
+        let name = self
+            .name(id)
+            .ok_or_else(|| anyhow!("{id} is outside the {} mapped classes", self.names.len()))?;
+        name.parse()
+            .map_err(|_| anyhow!("class {id} is named '{name}', which is not a known target"))
+    }
+}
+
+/// Resolves `id` through `map` if there is one, or the compiled mapping otherwise
+pub fn resolve_class<T>(map: Option<&ClassMap>, id: i32) -> Result<T>
+where
+    T: FromStr + TryFrom<i32>,
+    <T as TryFrom<i32>>::Error: std::error::Error + Send + Sync + 'static,
+{
+    match map {
+        Some(map) => map.resolve(id),
+        None => Ok(T::try_from(id)?),
+    }
+}
+
+/// Model class id permutation from config, applied before any mapping to a target
+///
+/// Corrects a retrained model that swapped ids, like the left and right poles,
+/// without touching the model or its class names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassRemap {
+    /// Remapped id, indexed by model id
+    ids: Vec<i32>,
+}
+
+impl ClassRemap {
+    /// Remaps each `[from, to]` pair, leaving unlisted ids in place
+    ///
+    /// Fails unless the result is a bijection over `0..class_count`.
+    pub fn new(pairs: &[[i32; 2]], class_count: usize) -> Result<Self> {
+        let in_range = |id: i32| usize::try_from(id).ok().filter(|idx| *idx < class_count);
+        let mut ids: Vec<i32> = (0..class_count as i32).collect();
+        let mut remapped = vec![false; class_count];
+        for &[from, to] in pairs {
+            let (Some(idx), Some(_)) = (in_range(from), in_range(to)) else {
+                bail!("class remap {from} -> {to} is outside class ids [0, {class_count})");
+            };
+            if remapped[idx] {
+                bail!("class {from} is remapped more than once");
+            }
+            remapped[idx] = true;
+            ids[idx] = to;
+        }
+
+        let mut targets = ids.clone();
+        targets.sort_unstable();
+        if targets.iter().copied().ne(0..class_count as i32) {
+            bail!("class remap {pairs:?} sends two classes to the same id");
+        }
+        Ok(Self { ids })
+    }
+
+    /// Remapped `id`, ids outside the model range pass through
+    pub fn id(&self, id: i32) -> i32 {
+        usize::try_from(id)
+            .ok()
+            .and_then(|idx| self.ids.get(idx))
+            .copied()
+            .unwrap_or(id)
+    }
+
+    /// Applies [`Self::id`] to the class of each detection
+    pub fn detections(&self, detections: Vec<YoloDetection>) -> Vec<YoloDetection> {
+        detections
+            .into_iter()
+            .map(|detection| {
+                YoloDetection::new(
+                    self.id(*detection.class_id()),
+                    *detection.confidence(),
+                    *detection.bounding_box(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Case and separator insensitive name, so `left_pole` matches `LeftPole`
+pub fn normalize_class_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '_' | '-' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Implements [`FromStr`] for a class enum by variant name
+#[macro_export]
+macro_rules! class_names {
+    ($target:ty { $($variant:ident),+ $(,)? }) => {
+        impl std::str::FromStr for $target {
+            type Err = anyhow::Error;
+
+            fn from_str(name: &str) -> anyhow::Result<Self> {
+                let name = $crate::vision::class_map::normalize_class_name(name);
+                $(
+                    if name == $crate::vision::class_map::normalize_class_name(stringify!($variant)) {
+                        return Ok(Self::$variant);
+                    }
+                )+
+                anyhow::bail!("unknown class name '{name}'")
+            }
+        }
+    };
+}
+
+/// Fails if any name in `map` does not resolve to a `T`
+pub fn check_class_map<T: FromStr>(map: &ClassMap) -> Result<()> {
+    for (id, name) in map.names.iter().enumerate() {
+        if name.parse::<T>().is_err() {
+            bail!("class {id} is named '{name}', which is not a known target");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use super::*;
+    use crate::vision::gate_poles::Target;
+
+    #[test]
+    fn resolves_through_loaded_file() {
+        let dir = std::env::temp_dir().join("sw9s_class_map");
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("gate.toml");
+        write(&path, "names = [\"shark\", \"left_pole\", \"Gate\"]\n").unwrap();
+
+        let map = ClassMap::sidecar(dir.join("gate.onnx")).unwrap().unwrap();
+        assert_eq!(map, ClassMap::from_file(&path).unwrap());
+        assert_eq!(
+            resolve_class::<Target>(Some(&map), 0).unwrap(),
+            Target::Shark
+        );
+        assert_eq!(
+            resolve_class::<Target>(Some(&map), 1).unwrap(),
+            Target::LeftPole
+        );
+        assert!(resolve_class::<Target>(Some(&map), 3).is_err());
+        check_class_map::<Target>(&map).unwrap();
+
+        // Compiled mapping without a file
+        assert_eq!(resolve_class::<Target>(None, 0).unwrap(), Target::Gate);
+    }
+
+    #[test]
+    fn remap_swaps_poles() {
+        let remap = ClassRemap::new(&[[4, 5], [5, 4]], 6).unwrap();
+        let targets: Vec<Target> = [0, 4, 5]
+            .into_iter()
+            .map(|id| resolve_class(None, remap.id(id)).unwrap())
+            .collect();
+        assert_eq!(targets, [Target::Gate, Target::LeftPole, Target::RightPole]);
+
+        // Not a bijection
+        assert!(ClassRemap::new(&[[4, 5]], 6).is_err());
+        assert!(ClassRemap::new(&[[4, 5], [4, 3]], 6).is_err());
+        assert!(ClassRemap::new(&[[5, 6], [6, 5]], 6).is_err());
+    }
+
+    #[test]
+    fn classes_txt_skips_blank_lines() {
+        let map = ClassMap::from_classes_txt("Gate\n\n  Middle \nnot_a_target\n");
+        assert_eq!(map.name(1), Some("Middle"));
+        assert_eq!(map.name(3), None);
+        assert!(check_class_map::<Target>(&map).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+};
+
+use super::{nn_cv2::YoloClass, VisualDetection};
+
+/// Leading class reported by [`ClassVoteAccumulator`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassVote<T> {
+    pub class: T,
+    /// Summed confidence of `class` over the window
+    pub score: f64,
+    /// Lead over the runner-up score, equal to `score` if unopposed
+    pub margin: f64,
+}
+
+/// Tallies confidence-weighted class votes over the last `window` frames.
+///
+/// Each detection votes for its class with its confidence, so a steady
+/// high-confidence class beats one that flickers in at low confidence.
+#[derive(Debug)]
+pub struct ClassVoteAccumulator<T> {
+    window: usize,
+    frames: VecDeque<Vec<(T, f64)>>,
+}
+
+impl<T: Eq + Hash + Clone> ClassVoteAccumulator<T> {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            frames: VecDeque::with_capacity(window.max(1)),
+        }
+    }
+
+    /// Adds one frame of `(class, confidence)` votes, dropping the oldest
+    /// frame once the window is full
+    pub fn push_frame(&mut self, votes: impl IntoIterator<Item = (T, f64)>) {
+        if self.frames.len() == self.window {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(votes.into_iter().collect());
+    }
+
+    /// Adds one frame of YOLO detections
+    pub fn push_detections<'a, U: 'a>(
+        &mut self,
+        detections: impl IntoIterator<Item = &'a VisualDetection<YoloClass<T>, U>>,
+    ) where
+        T: 'a,
+    {
+        self.push_frame(detections.into_iter().map(|detection| {
+            (
+                detection.class().identifier.clone(),
+                detection.class().confidence,
+            )
+        }))
+    }
+
+    /// True once `window` frames have been pushed
+    pub fn is_full(&self) -> bool {
+        self.frames.len() == self.window
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Summed confidence per class over the window
+    pub fn tallies(&self) -> HashMap<T, f64> {
+        let mut tallies = HashMap::new();
+        self.frames
+            .iter()
+            .flatten()
+            .for_each(|(class, confidence)| {
+                *tallies.entry(class.clone()).or_insert(0.0) += confidence;
+            });
+        tallies
+    }
+
+    /// Highest scoring class, `None` if the window has no votes
+    ///
+    /// Ties are broken arbitrarily, with a margin of zero.
+    pub fn leader(&self) -> Option<ClassVote<T>> {
+        let mut tallies: Vec<_> = self.tallies().into_iter().collect();
+        tallies.sort_by(|(_, lhs), (_, rhs)| rhs.total_cmp(lhs));
+
+        let mut tallies = tallies.into_iter();
+        let (class, score) = tallies.next()?;
+        let runner_up = tallies.next().map(|(_, score)| score).unwrap_or(0.0);
+        Some(ClassVote {
+            class,
+            score,
+            margin: score - runner_up,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    enum Target {
+        Shark,
+        Sawfish,
+    }
+
+    #[test]
+    fn weighted_leader() {
+        let mut votes = ClassVoteAccumulator::new(4);
+        assert_eq!(votes.leader(), None);
+
+        // Sawfish shows up more often, but shark is far more confident
+        votes.push_frame([(Target::Shark, 0.9), (Target::Sawfish, 0.3)]);
+        votes.push_frame([(Target::Sawfish, 0.3)]);
+        votes.push_frame([(Target::Shark, 0.8), (Target::Sawfish, 0.4)]);
+        votes.push_frame([]);
+
+        let leader = votes.leader().unwrap();
+        assert_eq!(leader.class, Target::Shark);
+        assert_approx_eq!(leader.score, 1.7);
+        assert_approx_eq!(leader.margin, 0.7);
+    }
+
+    #[test]
+    fn window_drops_old_frames() {
+        let mut votes = ClassVoteAccumulator::new(2);
+        votes.push_frame([(Target::Shark, 0.9)]);
+        assert!(!votes.is_full());
+        votes.push_frame([(Target::Sawfish, 0.5)]);
+        assert!(votes.is_full());
+        votes.push_frame([(Target::Sawfish, 0.5)]);
+
+        let leader = votes.leader().unwrap();
+        assert_eq!(leader.class, Target::Sawfish);
+        assert_approx_eq!(leader.score, 1.0);
+        // Unopposed once the shark frame leaves the window
+        assert_approx_eq!(leader.margin, 1.0);
+    }
+
+    #[test]
+    fn detections_vote_with_confidence() {
+        let detection = |identifier, confidence| VisualDetection {
+            class: YoloClass {
+                identifier,
+                confidence,
+            },
+            position: (),
+            confidence: 1.0,
+        };
+
+        let mut votes = ClassVoteAccumulator::new(3);
+        votes.push_detections(&[
+            detection(Target::Shark, 0.6),
+            detection(Target::Sawfish, 0.7),
+        ]);
+        votes.push_detections(&[detection(Target::Shark, 0.6)]);
+
+        let leader = votes.leader().unwrap();
+        assert_eq!(leader.class, Target::Shark);
+        assert_approx_eq!(leader.margin, 0.5);
+    }
+}
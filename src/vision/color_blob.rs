@@ -0,0 +1,181 @@
+use super::{
+    image_prep::{contour_confidence, input_yuv, is_i420, resize_input, ContourSelect},
+    MatWrapper, PosVector, VisualDetection, VisualDetector, Yuv,
+};
+use opencv::{
+    core::{in_range, Point, Scalar, Size, Vector},
+    imgproc::{
+        contour_area_def, find_contours_def, min_area_rect, CHAIN_APPROX_SIMPLE, RETR_EXTERNAL,
+    },
+    prelude::{Mat, MatTraitConst},
+};
+use std::ops::RangeInclusive;
+
+/// Finds a single blob of one color, with no shape constraints
+///
+/// Reports nothing unless the selected contour's area is within `area_bounds`,
+/// and is most confident in contours near the middle of that range.
+#[derive(Debug)]
+pub struct ColorBlob {
+    color_bounds: RangeInclusive<Yuv>,
+    area_bounds: RangeInclusive<f64>,
+    contour_select: ContourSelect,
+    size: Size,
+    image: MatWrapper,
+}
+
+impl ColorBlob {
+    pub fn new(
+        color_bounds: RangeInclusive<Yuv>,
+        area_bounds: RangeInclusive<f64>,
+        contour_select: ContourSelect,
+        size: Size,
+    ) -> Self {
+        Self {
+            color_bounds,
+            area_bounds,
+            contour_select,
+            size,
+            image: Mat::default().into(),
+        }
+    }
+}
+
+impl VisualDetector<f64> for ColorBlob {
+    type ClassEnum = bool;
+    type Position = PosVector;
+
+    fn detect(
+        &mut self,
+        input_image: &Mat,
+    ) -> anyhow::Result<Vec<VisualDetection<Self::ClassEnum, Self::Position>>> {
+        let i420 = is_i420(input_image);
+        self.image = resize_input(input_image, &self.size)?.into();
+        let yuv_image = input_yuv(&self.image.0, i420)?;
+
+        let color_start = self.color_bounds.start();
+        let color_end = self.color_bounds.end();
+        let lower = Scalar::new(
+            color_start.y as f64,
+            color_start.u as f64,
+            color_start.v as f64,
+            0.,
+        );
+        let upper = Scalar::new(
+            color_end.y as f64,
+            color_end.u as f64,
+            color_end.v as f64,
+            0.,
+        );
+
+        let mut mask = Mat::default();
+        in_range(&*yuv_image, &lower, &upper, &mut mask)?;
+
+        let mut contours = Vector::<Vector<Point>>::new();
+        find_contours_def(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)?;
+
+        let Some(contour) = self.contour_select.select(&contours, self.image.size()?)? else {
+            return Ok(vec![]);
+        };
+        let area = contour_area_def(&contour)?;
+        if !self.area_bounds.contains(&area) {
+            return Ok(vec![]);
+        }
+
+        let center = min_area_rect(&contour)?.center;
+        Ok(vec![VisualDetection {
+            class: true,
+            position: PosVector::new(center.x as f64, center.y as f64, 0., 0.),
+            confidence: contour_confidence(&contour, Some(&self.area_bounds))?,
+        }])
+    }
+
+    fn normalize(&mut self, pos: &Self::Position) -> Self::Position {
+        let img_size = self.image.size().unwrap();
+        Self::Position::normalized(
+            ((*pos.x() / (img_size.width as f64)) - 0.5) * 2.0,
+            ((*pos.y() / (img_size.height as f64)) - 0.5) * 2.0,
+            0.,
+            *pos.angle(),
+        )
+    }
+
+    fn accepts_i420(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use opencv::{
+        core::{Rect, CV_8UC3},
+        imgproc::{cvt_color_def, rectangle_def, COLOR_BGR2YUV_I420},
+    };
+
+    use super::*;
+    use crate::vision::image_prep::set_i420_frames;
+
+    #[test]
+    fn finds_blob_center() {
+        let mut image =
+            Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+        rectangle_def(&mut image, Rect::new(250, 50, 60, 60), Scalar::all(255.0)).unwrap();
+
+        let mut blob = ColorBlob::new(
+            (Yuv { y: 200, u: 0, v: 0 })..=(Yuv {
+                y: 255,
+                u: 255,
+                v: 255,
+            }),
+            1000.0..=10000.0,
+            ContourSelect::default(),
+            Size::from((400, 300)),
+        );
+        let detections = <ColorBlob as VisualDetector<f64>>::detect(&mut blob, &image).unwrap();
+        assert_eq!(detections.len(), 1);
+
+        let position = blob.normalize(detections[0].position());
+        assert!(*position.x() > 0.35 && *position.x() < 0.45);
+        assert!(*position.y() > -0.45 && *position.y() < -0.35);
+    }
+
+    #[test]
+    fn i420_skips_conversion() {
+        // Frames from a camera opened for I420
+        set_i420_frames(true);
+        let mut image =
+            Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+        rectangle_def(&mut image, Rect::new(60, 180, 60, 60), Scalar::all(255.0)).unwrap();
+        let mut i420 = Mat::default();
+        cvt_color_def(&image, &mut i420, COLOR_BGR2YUV_I420).unwrap();
+        assert!(is_i420(&i420));
+        assert!(!is_i420(&image));
+
+        let size = Size::from((400, 300));
+        let resized = resize_input(&i420, &size).unwrap();
+        assert!(matches!(
+            input_yuv(&resized, true).unwrap(),
+            Cow::Borrowed(_)
+        ));
+
+        let mut blob = ColorBlob::new(
+            (Yuv { y: 200, u: 0, v: 0 })..=(Yuv {
+                y: 255,
+                u: 255,
+                v: 255,
+            }),
+            1000.0..=10000.0,
+            ContourSelect::default(),
+            size,
+        );
+        let [bgr, yuv] = [&image, &i420].map(|frame| {
+            let detections = <ColorBlob as VisualDetector<f64>>::detect(&mut blob, frame).unwrap();
+            assert_eq!(detections.len(), 1);
+            blob.normalize(detections[0].position())
+        });
+        assert!((bgr.x() - yuv.x()).abs() < 0.01);
+        assert!((bgr.y() - yuv.y()).abs() < 0.01);
+    }
+}
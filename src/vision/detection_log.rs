@@ -0,0 +1,125 @@
+use std::{
+    fmt::Debug,
+    fs::{create_dir_all, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{log_warn, BackgroundWriter, TIMESTAMP};
+
+static DETECTION_CSV: AtomicBool = AtomicBool::new(false);
+
+/// Open detection CSV, kept across frames once the first row is logged
+static DETECTION_WRITER: Mutex<Option<BackgroundWriter>> = Mutex::new(None);
+
+const HEADER: &str = "unix_ms,source,class,x,y\n";
+
+/// When enabled, vision loops append their detections to a CSV under `logging/`.
+pub fn set_detection_csv(enabled: bool) {
+    DETECTION_CSV.store(enabled, Ordering::Relaxed);
+}
+
+pub fn detection_csv() -> bool {
+    DETECTION_CSV.load(Ordering::Relaxed)
+}
+
+/// Flips detection CSV logging, returning the new state
+pub fn toggle_detection_csv() -> bool {
+    toggle(&DETECTION_CSV)
+}
+
+fn toggle(flag: &AtomicBool) -> bool {
+    !flag.fetch_xor(true, Ordering::Relaxed)
+}
+
+/// Appends `(class, x, y)` rows from `source` to the detection CSV, if enabled.
+///
+/// The file is opened once, by a [`BackgroundWriter`], so vision loops never
+/// wait on it. A failed write disables detection logging.
+pub fn log_detections<C: Debug>(source: &str, detections: &[(C, f64, f64)]) {
+    if !detection_csv() {
+        return;
+    }
+    let mut writer = DETECTION_WRITER.lock().unwrap();
+    let res = append_rows(
+        &DETECTION_CSV,
+        writer.get_or_insert_with(|| BackgroundWriter::spawn(open_csv)),
+        source,
+        detections,
+    );
+
+    if let Err(e) = res {
+        log_warn!("Failed to write detection CSV, disabling: {e}");
+        DETECTION_CSV.store(false, Ordering::Relaxed);
+        *writer = None;
+    }
+}
+
+/// Opens this run's detection CSV for appending, writing the header if new
+fn open_csv() -> io::Result<impl Write> {
+    let path = format!("logging/detections{}.csv", &*TIMESTAMP);
+    create_dir_all("logging")?;
+    let new = !Path::new(&path).exists();
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    if new {
+        file.write_all(HEADER.as_bytes())?;
+    }
+    Ok(file)
+}
+
+/// Writes one row per detection to `writer` while `enabled` is set
+fn append_rows<W: Write, C: Debug>(
+    enabled: &AtomicBool,
+    writer: &mut W,
+    source: &str,
+    detections: &[(C, f64, f64)],
+) -> io::Result<()> {
+    if !enabled.load(Ordering::Relaxed) || detections.is_empty() {
+        return Ok(());
+    }
+
+    let unix_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.as_millis())
+        .unwrap_or_default();
+    let source = quoted(source);
+    for (class, x, y) in detections {
+        let class = quoted(&format!("{class:?}"));
+        writeln!(writer, "{unix_ms},{source},{class},{x},{y}")?;
+    }
+    writer.flush()
+}
+
+/// Debug output and type names can hold commas and quotes
+fn quoted(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_gates_rows() {
+        let enabled = AtomicBool::new(false);
+        let mut csv = Vec::new();
+        let detections = [(true, 0.5, -0.25)];
+
+        append_rows(&enabled, &mut csv, "gate", &detections).unwrap();
+        assert!(csv.is_empty());
+
+        assert!(toggle(&enabled));
+        append_rows(&enabled, &mut csv, "gate", &detections).unwrap();
+        let row = String::from_utf8(csv.clone()).unwrap();
+        assert!(row.ends_with(",\"gate\",\"true\",0.5,-0.25\n"));
+
+        assert!(!toggle(&enabled));
+        append_rows(&enabled, &mut csv, "gate", &detections).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), row);
+    }
+}
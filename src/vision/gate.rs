@@ -4,7 +4,7 @@ use opencv::{core::Size, prelude::Mat};
 use crate::load_onnx;
 
 use super::{
-    nn_cv2::{OnnxModel, VisionModel, YoloDetection},
+    nn_cv2::{report_confidence_floor, OnnxModel, VisionModel, YoloDetection},
     yolo_model::YoloProcessor,
 };
 
@@ -18,6 +18,12 @@ pub enum Target {
     Abydos,
 }
 
+crate::class_names!(Target {
+    LargeGate,
+    Earth,
+    Abydos
+});
+
 #[derive(Debug)]
 pub struct TargetError {
     x: i32,
@@ -52,6 +58,7 @@ impl Display for Target {
 #[derive(Debug)]
 pub struct Gate<T: VisionModel> {
     model: T,
+    /// Minimum confidence missions act on, applied after detection
     threshold: f64,
 }
 
@@ -88,7 +95,7 @@ impl YoloProcessor for Gate<OnnxModel> {
     type Target = Target;
 
     fn detect_yolo_v5(&mut self, image: &Mat) -> Vec<YoloDetection> {
-        self.model.detect_yolo_v5(image, self.threshold)
+        self.model.detect_yolo_v5(image, report_confidence_floor())
     }
 
     fn model_size(&self) -> Size {
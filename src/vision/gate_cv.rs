@@ -1,42 +1,101 @@
 use crate::config::ColorProfile;
 
-use super::{image_prep::resize, MatWrapper, PosVector, VisualDetection, VisualDetector, Yuv};
+use super::{
+    image_prep::{contour_confidence, input_yuv, is_i420, resize_input, ContourSelect},
+    profiling::{StageTimer, StageTimings},
+    DetectionScore, Draw, MatWrapper, PosVector, VisualDetection, VisualDetector, Yuv,
+};
 use opencv::{
     core::{in_range, Point, Scalar, Size, Vector},
     imgproc::{
-        contour_area_def, cvt_color_def, find_contours_def, min_area_rect, CHAIN_APPROX_SIMPLE,
-        COLOR_BGR2YUV, RETR_EXTERNAL,
+        self, contour_area_def, find_contours_def, min_area_rect, CHAIN_APPROX_SIMPLE, LINE_8,
+        RETR_EXTERNAL,
     },
     prelude::{Mat, MatTraitConst},
 };
 use std::ops::RangeInclusive;
 
+/// Smallest single color contour, in working size pixels, trusted as a pole
+const MIN_PARTIAL_AREA: f64 = 100.0;
+
+/// Which pole a [`GateCV`] detection is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GatePole {
+    Left,
+    Right,
+    /// Only one of the pole's colors was found, common at distance.
+    ///
+    /// The side needs both colors, so it is unknown. Confidence counts the
+    /// missing color as an empty contour, keeping it under a paired detection
+    /// of the same fill.
+    Partial,
+}
+
+/// CV detections carry no class score, so all are equally strong
+impl DetectionScore for GatePole {
+    fn score(&self) -> f64 {
+        0.0
+    }
+}
+
+impl Draw for VisualDetection<GatePole, PosVector> {
+    fn draw(&self, canvas: &mut Mat) -> anyhow::Result<()> {
+        let color = match self.class() {
+            GatePole::Left => Scalar::from((0.0, 255.0, 0.0)),
+            GatePole::Right => Scalar::from((0.0, 0.0, 255.0)),
+            GatePole::Partial => Scalar::from((0.0, 255.0, 255.0)),
+        };
+
+        imgproc::circle(
+            canvas,
+            Point::new(*self.position().x() as i32, *self.position().y() as i32),
+            10,
+            color,
+            2,
+            LINE_8,
+            0,
+        )?;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct GateCV {
     color_bounds_red: RangeInclusive<Yuv>,
     color_bounds_black: RangeInclusive<Yuv>,
+    contour_select: ContourSelect,
     size: Size,
     image: MatWrapper,
+    timings: StageTimings,
 }
 
 impl GateCV {
+    /// Stage timings of the last [`VisualDetector::detect`] call
+    pub fn timings(&self) -> &StageTimings {
+        &self.timings
+    }
+
     pub fn new(
         color_bounds_red: RangeInclusive<Yuv>,
         color_bounds_black: RangeInclusive<Yuv>,
+        contour_select: ContourSelect,
         size: Size,
     ) -> Self {
         Self {
             color_bounds_red,
             color_bounds_black,
+            contour_select,
             size,
             image: Mat::default().into(),
+            timings: StageTimings::default(),
         }
     }
 
-    pub fn from_color_profile(color_profile: &ColorProfile) -> Self {
+    pub fn from_color_profile(color_profile: &ColorProfile, contour_select: ContourSelect) -> Self {
         Self::new(
             color_profile.red.clone(),
             color_profile.black.clone(),
+            contour_select,
             Size::from((400, 300)),
         )
     }
@@ -64,23 +123,26 @@ impl Default for GateCV {
                 u: 135,
                 v: 255,
             }),
+            ContourSelect::default(),
             Size::from((400, 300)),
         )
     }
 }
 
 impl VisualDetector<f64> for GateCV {
-    type ClassEnum = bool;
+    type ClassEnum = GatePole;
     type Position = PosVector;
 
     fn detect(
         &mut self,
         input_image: &Mat,
     ) -> anyhow::Result<Vec<VisualDetection<Self::ClassEnum, Self::Position>>> {
-        self.image = resize(input_image, &self.size)?.into();
-        let mut yuv_image = Mat::default();
-
-        cvt_color_def(&self.image.0, &mut yuv_image, COLOR_BGR2YUV)?;
+        let mut timer = StageTimer::start();
+        let i420 = is_i420(input_image);
+        self.image = resize_input(input_image, &self.size)?.into();
+        timer.lap(|t| &mut t.resize);
+        let yuv_image = input_yuv(&self.image.0, i420)?;
+        timer.lap(|t| &mut t.color_convert);
 
         let red_start = self.color_bounds_red.start();
         let red_end = self.color_bounds_red.end();
@@ -108,10 +170,11 @@ impl VisualDetector<f64> for GateCV {
         );
 
         let mut red_mask = Mat::default();
-        let _ = in_range(&yuv_image, &lower_red, &upper_red, &mut red_mask);
+        let _ = in_range(&*yuv_image, &lower_red, &upper_red, &mut red_mask);
 
         let mut black_mask = Mat::default();
-        let _ = in_range(&yuv_image, &lower_black, &upper_black, &mut black_mask);
+        let _ = in_range(&*yuv_image, &lower_black, &upper_black, &mut black_mask);
+        timer.lap(|t| &mut t.in_range);
 
         let mut contours_red = Vector::<Vector<Point>>::new();
         find_contours_def(
@@ -128,23 +191,16 @@ impl VisualDetector<f64> for GateCV {
             CHAIN_APPROX_SIMPLE,
         )?;
 
-        let max_contour_red = contours_red.iter().max_by(|x, y| {
-            contour_area_def(&x)
-                .unwrap()
-                .partial_cmp(&contour_area_def(&y).unwrap())
-                .unwrap()
-        });
-        let max_contour_black = contours_black.iter().max_by(|x, y| {
-            contour_area_def(&x)
-                .unwrap()
-                .partial_cmp(&contour_area_def(&y).unwrap())
-                .unwrap()
-        });
-
-        if let Some(contour_red) = max_contour_red {
-            if let Some(contour_black) = max_contour_black {
-                let red_rect = min_area_rect(&contour_red).unwrap();
-                let black_rect = min_area_rect(&contour_black).unwrap();
+        let size = self.image.size()?;
+        let max_contour_red = self.contour_select.select(&contours_red, size)?;
+        let max_contour_black = self.contour_select.select(&contours_black, size)?;
+        timer.lap(|t| &mut t.contours);
+        self.timings = timer.finish("GateCV");
+
+        if let Some(contour_red) = &max_contour_red {
+            if let Some(contour_black) = &max_contour_black {
+                let red_rect = min_area_rect(contour_red).unwrap();
+                let black_rect = min_area_rect(contour_black).unwrap();
                 let center_red = red_rect.center;
                 let center_black = black_rect.center;
 
@@ -156,34 +212,95 @@ impl VisualDetector<f64> for GateCV {
                     let red_y = center_red.y;
                     let black_y = center_black.y;
                     if (red_x - black_x).abs() < 50.0 {
-                        let side;
-                        if black_y > red_y {
-                            // Right
-                            side = false;
+                        let side = if black_y > red_y {
+                            GatePole::Right
                         } else {
-                            // Left
-                            side = true;
-                        }
+                            GatePole::Left
+                        };
                         let pole_x = (red_x + black_x) / 2.0;
                         let pole_y = (red_y + black_y) / 2.0;
+                        let confidence = (contour_confidence(contour_red, None)?
+                            + contour_confidence(contour_black, None)?)
+                            / 2.0;
                         return Ok(vec![VisualDetection {
                             class: side,
                             position: PosVector::new(pole_x as f64, pole_y as f64, 0.0, 0.0),
+                            confidence,
                         }]);
                     }
                 }
             }
         }
-        Ok(vec![])
+
+        // Only one color of the pole, common at distance
+        let single = match (&max_contour_red, &max_contour_black) {
+            (Some(contour), None) | (None, Some(contour)) => contour,
+            _ => return Ok(vec![]),
+        };
+        if contour_area_def(single)? < MIN_PARTIAL_AREA {
+            return Ok(vec![]);
+        }
+        let center = min_area_rect(single)?.center;
+        Ok(vec![VisualDetection::new(
+            GatePole::Partial,
+            PosVector::new(center.x as f64, center.y as f64, 0.0, 0.0),
+        )
+        .with_confidence(contour_confidence(single, None)? / 2.0)])
     }
 
     fn normalize(&mut self, pos: &Self::Position) -> Self::Position {
         let img_size = self.image.size().unwrap();
-        Self::Position::new(
+        Self::Position::normalized(
             ((*pos.x() / (img_size.width as f64)) - 0.5) * 2.0,
             ((*pos.y() / (img_size.height as f64)) - 0.5) * 2.0,
             0.,
             *pos.angle(),
         )
     }
+
+    fn accepts_i420(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::{
+        core::{Rect, CV_8UC3},
+        imgproc::rectangle_def,
+    };
+
+    use super::*;
+
+    #[test]
+    fn red_only_gives_partial_detection() {
+        let mut image =
+            Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(128.0)).unwrap();
+        // Bright stripe stands in for red, nothing is dark enough for black
+        rectangle_def(&mut image, Rect::new(60, 100, 20, 150), Scalar::all(255.0)).unwrap();
+
+        let mut gate = GateCV::new(
+            (Yuv { y: 200, u: 0, v: 0 })..=(Yuv {
+                y: 255,
+                u: 255,
+                v: 255,
+            }),
+            (Yuv { y: 0, u: 0, v: 0 })..=(Yuv {
+                y: 20,
+                u: 255,
+                v: 255,
+            }),
+            ContourSelect::default(),
+            Size::from((400, 300)),
+        );
+        let detections = gate.detect(&image).unwrap();
+        assert_eq!(detections.len(), 1);
+
+        let detection = &detections[0];
+        assert_eq!(*detection.class(), GatePole::Partial);
+        // A filled rectangle, halved for the missing color
+        assert!((*detection.confidence() - 0.5).abs() < 0.05);
+        assert!((*detection.position().x() - 70.0).abs() < 2.0);
+        assert!((*detection.position().y() - 175.0).abs() < 2.0);
+    }
 }
@@ -7,16 +7,20 @@ use opencv::{
     prelude::{Mat, MatTraitConst},
 };
 
-use crate::load_onnx;
+use crate::{load_onnx, logln};
 
 use super::{
-    nn_cv2::{OnnxModel, VisionModel, YoloClass, YoloDetection},
+    class_map::{check_class_map, resolve_class, ClassMap, ClassRemap},
+    nn_cv2::{iou, report_confidence_floor, OnnxModel, VisionModel, YoloClass, YoloDetection},
     yolo_model::YoloProcessor,
 };
 
 use core::hash::Hash;
 use std::{error::Error, fmt::Display};
 
+/// Left and right pole boxes overlapping more than this are taken as one pole
+pub const MAX_POLE_IOU: f64 = 0.5;
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Target {
     Red,
@@ -30,6 +34,18 @@ pub enum Target {
     Middle,
 }
 
+crate::class_names!(Target {
+    Red,
+    LeftPole,
+    RightPole,
+    Shark,
+    Sawfish,
+    Pole,
+    Blue,
+    Gate,
+    Middle
+});
+
 impl From<YoloClass<Target>> for Target {
     fn from(value: YoloClass<Target>) -> Self {
         value.identifier
@@ -49,6 +65,9 @@ impl Display for TargetError {
 
 impl Error for TargetError {}
 
+/// Class ids the compiled [`Target`] mapping covers
+pub const CLASS_COUNT: usize = 6;
+
 impl TryFrom<i32> for Target {
     type Error = TargetError;
     fn try_from(value: i32) -> std::result::Result<Self, Self::Error> {
@@ -80,20 +99,62 @@ impl Display for Target {
 #[derive(Debug, Clone, Getters)]
 pub struct GatePoles<T: VisionModel> {
     model: T,
+    /// Minimum confidence missions act on, applied after detection
     threshold: f64,
+    #[getter(skip)]
+    class_map: Option<ClassMap>,
+    #[getter(skip)]
+    class_remap: Option<ClassRemap>,
 }
 
 impl GatePoles<OnnxModel> {
+    /// Also loads a class mapping stored next to the model, see [`ClassMap::sidecar`]
     pub fn new(model_name: &str, model_size: i32, threshold: f64) -> Result<Self> {
         let model = OnnxModel::from_file(model_name, model_size, 5)?;
+        let class_map = ClassMap::sidecar(model_name).transpose()?;
+        if let Some(class_map) = &class_map {
+            check_class_map::<Target>(class_map)?;
+            logln!("Loaded class mapping for {model_name}: {class_map:?}");
+        }
 
-        Ok(Self { model, threshold })
+        Ok(Self {
+            model,
+            threshold,
+            class_map,
+            class_remap: None,
+        })
     }
 
     pub fn load_640(threshold: f64) -> Self {
         let model = load_onnx!("models/dummy_model.onnx", 640, 6);
 
-        Self { model, threshold }
+        Self {
+            model,
+            threshold,
+            class_map: None,
+            class_remap: None,
+        }
+    }
+
+    /// Minimum confidence missions act on, tunable without a rebuild
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.threshold = threshold;
+    }
+
+    /// Resolves class ids through `class_map` instead of the compiled mapping
+    pub fn with_class_map(self, class_map: ClassMap) -> Self {
+        Self {
+            class_map: Some(class_map),
+            ..self
+        }
+    }
+
+    /// Remaps model class ids before they are resolved to targets
+    pub fn with_class_remap(self, class_remap: ClassRemap) -> Self {
+        Self {
+            class_remap: Some(class_remap),
+            ..self
+        }
     }
 }
 
@@ -140,12 +201,66 @@ impl YoloProcessor for GatePoles<OnnxModel> {
         dbg!(image.dims());
         dbg!(dilated.dims());
 
-        self.model.detect_yolo_v5(&dilated, self.threshold)
+        let mut detections = self
+            .model
+            .detect_yolo_v5(&dilated, report_confidence_floor());
+        if let Some(class_remap) = &self.class_remap {
+            detections = class_remap.detections(detections);
+        }
+        reject_duplicate_poles(detections, self.class_map.as_ref(), MAX_POLE_IOU)
     }
 
     fn model_size(&self) -> Size {
         self.model.size()
     }
+
+    fn class_map(&self) -> Option<&ClassMap> {
+        self.class_map.as_ref()
+    }
+}
+
+/// Drops left and right pole detections that overlap each other beyond `max_iou`
+///
+/// Such a pair is usually one pole split into two boxes, and averaging it
+/// would put the gate center on that pole.
+pub fn reject_duplicate_poles(
+    detections: Vec<YoloDetection>,
+    class_map: Option<&ClassMap>,
+    max_iou: f64,
+) -> Vec<YoloDetection> {
+    let targets: Vec<_> = detections
+        .iter()
+        .map(|detection| resolve_class::<Target>(class_map, *detection.class_id()).ok())
+        .collect();
+    let poles = |side: Target| {
+        targets
+            .iter()
+            .enumerate()
+            .filter(move |(_, target)| **target == Some(side.clone()))
+            .map(|(idx, _)| idx)
+    };
+
+    let mut rejected = vec![false; detections.len()];
+    for left in poles(Target::LeftPole) {
+        for right in poles(Target::RightPole) {
+            let overlap = iou(
+                detections[left].bounding_box(),
+                detections[right].bounding_box(),
+            );
+            if overlap > max_iou {
+                logln!("Rejecting gate pole pair overlapping by {overlap:.2} IoU");
+                rejected[left] = true;
+                rejected[right] = true;
+            }
+        }
+    }
+
+    detections
+        .into_iter()
+        .zip(rejected)
+        .filter(|(_, rejected)| !rejected)
+        .map(|(detection, _)| detection)
+        .collect()
 }
 
 /*
@@ -168,3 +283,41 @@ impl GatePoles<OnnxModel> {
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use opencv::core::Rect2d;
+
+    use super::*;
+
+    /// Class ids from the compiled [`Target`] mapping
+    const LEFT_POLE: i32 = 5;
+    const RIGHT_POLE: i32 = 4;
+    const SHARK: i32 = 2;
+
+    fn detection(class_id: i32, x: f64) -> YoloDetection {
+        YoloDetection::new(class_id, 0.9, Rect2d::new(x, 100.0, 40.0, 200.0))
+    }
+
+    #[test]
+    fn overlapping_pole_pair_rejected() {
+        let detections = vec![
+            detection(LEFT_POLE, 100.0),
+            detection(RIGHT_POLE, 105.0),
+            detection(SHARK, 100.0),
+        ];
+        assert_eq!(
+            reject_duplicate_poles(detections, None, MAX_POLE_IOU),
+            [detection(SHARK, 100.0)]
+        );
+    }
+
+    #[test]
+    fn separated_pole_pair_kept() {
+        let detections = vec![detection(LEFT_POLE, 100.0), detection(RIGHT_POLE, 500.0)];
+        assert_eq!(
+            reject_duplicate_poles(detections.clone(), None, MAX_POLE_IOU),
+            detections
+        );
+    }
+}
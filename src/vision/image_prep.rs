@@ -1,17 +1,21 @@
+use std::borrow::Cow;
 use std::hash::Hash;
-use std::ops::Deref;
+use std::ops::{Deref, RangeInclusive};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use itertools::Itertools;
 use opencv::{
     core::{
-        pca_compute2, DataType, Mat_, Point_, Scalar, Size, TermCriteria, VecN, Vector, CMP_EQ,
-        CV_32F, CV_32FC3, CV_64F, CV_8U, KMEANS_PP_CENTERS, ROTATE_90_COUNTERCLOCKWISE,
+        pca_compute2, DataType, Mat_, Point, Point_, Scalar, Size, TermCriteria, VecN, Vector,
+        CMP_EQ, CV_32F, CV_32FC3, CV_64F, CV_8U, CV_8UC1, KMEANS_PP_CENTERS, ROTATE_180,
+        ROTATE_90_CLOCKWISE, ROTATE_90_COUNTERCLOCKWISE,
     },
     imgproc::{self},
     prelude::{Mat, MatSizeTraitConst, MatTrait, MatTraitConst, MatTraitConstManual},
 };
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 
 /// Creates a new Mat with the specified size
 ///
@@ -45,6 +49,111 @@ pub fn resize(frame: &Mat, target_size: &Size) -> Result<Mat> {
     Ok(res)
 }
 
+/// Whether cameras hand over I420 frames, see [`set_i420_frames`]
+static I420_FRAMES: AtomicBool = AtomicBool::new(false);
+
+/// Tags camera frames as I420 instead of BGR, matching the configured
+/// [`FrameFormat`](crate::video_source::appsink::FrameFormat)
+pub fn set_i420_frames(i420: bool) {
+    I420_FRAMES.store(i420, Ordering::Relaxed);
+}
+
+/// Whether `frame` holds planar I420 YUV instead of packed BGR.
+///
+/// Only frames from cameras tagged with [`set_i420_frames`] are I420. The
+/// layout is still checked, a single channel Y plane followed by quarter
+/// size U and V planes for 3/2 the rows, so BGR frames from elsewhere are
+/// never misread.
+pub fn is_i420(frame: &Mat) -> bool {
+    let height = frame.rows() / 3 * 2;
+    I420_FRAMES.load(Ordering::Relaxed)
+        && frame.typ() == CV_8UC1
+        && frame.rows() % 3 == 0
+        && height % 2 == 0
+        && frame.cols() % 2 == 0
+        && !frame.empty()
+}
+
+/// `frame` as packed BGR, converting it if [`is_i420`]
+pub fn to_bgr(frame: &Mat) -> Result<Cow<'_, Mat>> {
+    if !is_i420(frame) {
+        return Ok(Cow::Borrowed(frame));
+    }
+    let mut bgr = Mat::default();
+    imgproc::cvt_color_def(frame, &mut bgr, imgproc::COLOR_YUV2BGR_I420)?;
+    Ok(Cow::Owned(bgr))
+}
+
+/// [`to_bgr`] for an owned frame, which is returned as is if already BGR
+pub fn into_bgr(frame: Mat) -> Result<Mat> {
+    if !is_i420(&frame) {
+        return Ok(frame);
+    }
+    Ok(to_bgr(&frame)?.into_owned())
+}
+
+/// `target_size` turned to the orientation of `frame_size`
+///
+/// A portrait frame, such as one rotated by [`FrameRotation`], keeps its
+/// aspect ratio instead of being squashed into a landscape size.
+pub fn oriented_size(frame_size: Size, target_size: Size) -> Size {
+    let portrait = |size: Size| size.height > size.width;
+    if portrait(frame_size) == portrait(target_size) {
+        target_size
+    } else {
+        Size::new(target_size.height, target_size.width)
+    }
+}
+
+/// Resizes a detector input to `target_size`, see [`oriented_size`].
+///
+/// BGR frames stay BGR. [`is_i420`] frames have each plane resized and are
+/// merged into packed YUV, so [`input_yuv`] has nothing left to convert.
+pub fn resize_input(frame: &Mat, target_size: &Size) -> Result<Mat> {
+    if !is_i420(frame) {
+        return resize(frame, &oriented_size(frame.size()?, *target_size));
+    }
+
+    let continuous;
+    let frame = if frame.is_continuous() {
+        frame
+    } else {
+        continuous = frame.try_clone()?;
+        &continuous
+    };
+    let height = frame.rows() / 3 * 2;
+    let target_size = &oriented_size(Size::new(frame.cols(), height), *target_size);
+    let luma = (height * frame.cols()) as usize;
+    let (y, chroma) = frame.data_bytes()?.split_at(luma);
+    let (u, v) = chroma.split_at(luma / 4);
+
+    let plane = |bytes: &[u8], rows: i32| -> Result<Mat> {
+        resize(&Mat::from_slice(bytes)?.reshape(1, rows)?, target_size)
+    };
+    let planes = Vector::<Mat>::from_iter([
+        plane(y, height)?,
+        plane(u, height / 2)?,
+        plane(v, height / 2)?,
+    ]);
+    let mut yuv = Mat::default();
+    opencv::core::merge(&planes, &mut yuv)?;
+    Ok(yuv)
+}
+
+/// Packed YUV of a frame from [`resize_input`]
+///
+/// # Arguments
+/// * `resized` - Output of [`resize_input`]
+/// * `i420` - Whether the original frame was I420, which is already YUV
+pub fn input_yuv(resized: &Mat, i420: bool) -> Result<Cow<'_, Mat>> {
+    if i420 {
+        return Ok(Cow::Borrowed(resized));
+    }
+    let mut yuv = Mat::default();
+    imgproc::cvt_color_def(resized, &mut yuv, imgproc::COLOR_BGR2YUV)?;
+    Ok(Cow::Owned(yuv))
+}
+
 /// Returns true if the image size is within the bounds
 ///
 /// # Arguments
@@ -101,6 +210,171 @@ pub fn slice_number(image: &Mat, num_x: i32, num_y: i32) -> Result<Size> {
     ))
 }
 
+/// Strategy for picking one contour out of a mask
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContourSelect {
+    /// Largest enclosed area
+    #[default]
+    MaxArea,
+    /// Highest bounding box top edge in the image
+    Topmost,
+    /// Center closest to the image center
+    Centermost,
+    /// Long axis closest to vertical
+    MostVertical,
+}
+
+impl ContourSelect {
+    /// Returns the best contour according to this strategy
+    ///
+    /// # Arguments
+    /// * `contours` - Candidate contours
+    /// * `image_size` - Dimensions of the image the contours came from
+    pub fn select(
+        &self,
+        contours: &Vector<Vector<Point>>,
+        image_size: Size,
+    ) -> Result<Option<Vector<Point>>> {
+        let scored = contours
+            .iter()
+            .map(|contour| Ok((self.score(&contour, image_size)?, contour)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(scored
+            .into_iter()
+            .max_by(|(lhs, _), (rhs, _)| lhs.partial_cmp(rhs).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(_, contour)| contour))
+    }
+
+    /// Higher scores are preferred
+    fn score(&self, contour: &Vector<Point>, image_size: Size) -> Result<f64> {
+        Ok(match self {
+            Self::MaxArea => imgproc::contour_area_def(contour)?,
+            Self::Topmost => -f64::from(imgproc::bounding_rect(contour)?.y),
+            Self::Centermost => {
+                let center = imgproc::min_area_rect(contour)?.center;
+                let dx = f64::from(center.x) - f64::from(image_size.width) / 2.0;
+                let dy = f64::from(center.y) - f64::from(image_size.height) / 2.0;
+                -dx.hypot(dy)
+            }
+            Self::MostVertical => {
+                let rect = imgproc::min_area_rect(contour)?;
+                // The width edge sits at `angle` from horizontal
+                let deviation = if rect.size.width >= rect.size.height {
+                    f64::from(rect.angle) - 90.0
+                } else {
+                    f64::from(rect.angle)
+                }
+                .rem_euclid(180.0);
+                -deviation.min(180.0 - deviation)
+            }
+        })
+    }
+}
+
+/// Clockwise rotation that brings a camera's frames to the canonical orientation
+///
+/// Written in config as whole degrees, one of 0, 90, 180, or 270.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u16", into = "u16")]
+pub enum FrameRotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl FrameRotation {
+    /// Rotates `frame`, converting it to BGR first if [`is_i420`], since
+    /// rotating the stacked I420 planes as one image would mix them
+    pub fn apply(self, frame: &Mat) -> Result<Mat> {
+        let code = match self {
+            Self::None => return Ok(frame.clone()),
+            Self::Cw90 => ROTATE_90_CLOCKWISE,
+            Self::Cw180 => ROTATE_180,
+            Self::Cw270 => ROTATE_90_COUNTERCLOCKWISE,
+        };
+        let mut rotated = Mat::default();
+        opencv::core::rotate(&*to_bgr(frame)?, &mut rotated, code)?;
+        Ok(rotated)
+    }
+}
+
+impl TryFrom<u16> for FrameRotation {
+    type Error = anyhow::Error;
+
+    fn try_from(degrees: u16) -> Result<Self> {
+        Ok(match degrees {
+            0 => Self::None,
+            90 => Self::Cw90,
+            180 => Self::Cw180,
+            270 => Self::Cw270,
+            x => bail!("Frame rotation must be 0, 90, 180, or 270 degrees, not {x}"),
+        })
+    }
+}
+
+impl From<FrameRotation> for u16 {
+    fn from(rotation: FrameRotation) -> Self {
+        match rotation {
+            FrameRotation::None => 0,
+            FrameRotation::Cw90 => 90,
+            FrameRotation::Cw180 => 180,
+            FrameRotation::Cw270 => 270,
+        }
+    }
+}
+
+/// How much of its min area rect a contour fills, 0-1
+///
+/// Poles and stripes are close to rectangular, so a low fill suggests a
+/// blob of noise or several objects merged together.
+pub fn contour_fill(contour: &Vector<Point>) -> Result<f64> {
+    let rect = imgproc::min_area_rect(contour)?;
+    let rect_area = f64::from(rect.size.width) * f64::from(rect.size.height);
+    if rect_area <= 0.0 {
+        return Ok(0.0);
+    }
+    Ok((imgproc::contour_area_def(contour)? / rect_area).clamp(0.0, 1.0))
+}
+
+/// Confidence of a contour right at the edge of its expected area range
+pub const AREA_EDGE_CONFIDENCE: f64 = 0.5;
+
+/// How typical `area` is for `area_bounds`, 0-1
+///
+/// 1.0 at the middle of the range, tapering linearly to
+/// [`AREA_EDGE_CONFIDENCE`] at either bound and 0.0 outside it.
+pub fn area_confidence(area: f64, area_bounds: &RangeInclusive<f64>) -> f64 {
+    if !area_bounds.contains(&area) {
+        return 0.0;
+    }
+    let half_width = (area_bounds.end() - area_bounds.start()) / 2.0;
+    if half_width <= 0.0 {
+        return 1.0;
+    }
+    let center = area_bounds.start() + half_width;
+    let from_center = (area - center).abs() / half_width;
+    1.0 - from_center * (1.0 - AREA_EDGE_CONFIDENCE)
+}
+
+/// Confidence of a contour detection, 0-1, the one scale the CV detectors share
+///
+/// The contour's [fill](contour_fill) times how typical its area is for
+/// `area_bounds`. Detectors with no expected area range pass `None`, leaving
+/// only the fill.
+pub fn contour_confidence(
+    contour: &Vector<Point>,
+    area_bounds: Option<&RangeInclusive<f64>>,
+) -> Result<f64> {
+    let fill = contour_fill(contour)?;
+    Ok(match area_bounds {
+        Some(bounds) => fill * area_confidence(imgproc::contour_area_def(contour)?, bounds),
+        None => fill,
+    })
+}
+
 /// Read-only struct for results from PCA computation
 #[derive(Debug, Default)]
 pub struct PcaData {
@@ -332,3 +606,146 @@ pub fn kmeans(img: &Mat, n_clusters: i32, attempts: i32) -> Mat {
     draw.convert_to(&mut draw_8u, CV_8U, 1.0, 0.0).unwrap();
     draw_8u
 }
+
+#[cfg(test)]
+mod tests {
+    use opencv::{
+        core::{Rect, CV_8UC1, CV_8UC3},
+        imgproc::{find_contours_def, CHAIN_APPROX_SIMPLE, FILLED, LINE_8, RETR_EXTERNAL},
+    };
+
+    use super::*;
+
+    /// Four blobs, each the unique winner of one strategy
+    fn multi_blob_contours() -> (Vector<Vector<Point>>, Size) {
+        let size = Size::new(400, 300);
+        let mut mask = Mat::new_size_with_default(size, CV_8UC1, Scalar::all(0.0)).unwrap();
+
+        [
+            // Largest, wide and low
+            Rect::new(10, 200, 160, 80),
+            // Small, near the top
+            Rect::new(330, 20, 40, 30),
+            // Centered, wide
+            Rect::new(180, 140, 40, 20),
+            // Thin and upright
+            Rect::new(300, 150, 10, 100),
+        ]
+        .into_iter()
+        .for_each(|rect| {
+            imgproc::rectangle(&mut mask, rect, Scalar::all(255.0), FILLED, LINE_8, 0).unwrap()
+        });
+
+        let mut contours = Vector::<Vector<Point>>::new();
+        find_contours_def(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE).unwrap();
+        (contours, size)
+    }
+
+    fn selected_x(select: ContourSelect) -> i32 {
+        let (contours, size) = multi_blob_contours();
+        let contour = select.select(&contours, size).unwrap().unwrap();
+        imgproc::bounding_rect(&contour).unwrap().x
+    }
+
+    #[test]
+    fn rotated_frame_keeps_aspect_ratio() {
+        set_i420_frames(true);
+        // Landscape, with a white marker in the top left corner
+        let mut bgr = Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+        imgproc::rectangle(
+            &mut bgr,
+            Rect::new(0, 0, 40, 20),
+            Scalar::all(255.0),
+            FILLED,
+            LINE_8,
+            0,
+        )
+        .unwrap();
+        let mut i420 = Mat::default();
+        imgproc::cvt_color_def(&bgr, &mut i420, imgproc::COLOR_BGR2YUV_I420).unwrap();
+
+        for frame in [bgr, i420] {
+            let rotated = FrameRotation::Cw90.apply(&frame).unwrap();
+            assert_eq!(rotated.typ(), CV_8UC3);
+            assert_eq!(rotated.size().unwrap(), Size::new(300, 400));
+            // Top left turns to the top right
+            assert!(rotated.at_2d::<VecN<u8, 3>>(5, 295).unwrap()[0] > 200);
+            assert!(rotated.at_2d::<VecN<u8, 3>>(5, 5).unwrap()[0] < 50);
+
+            let resized = resize_input(&rotated, &Size::new(200, 150)).unwrap();
+            assert_eq!(resized.size().unwrap(), Size::new(150, 200));
+        }
+    }
+
+    #[test]
+    fn max_area() {
+        assert_eq!(selected_x(ContourSelect::MaxArea), 10);
+    }
+
+    #[test]
+    fn topmost() {
+        assert_eq!(selected_x(ContourSelect::Topmost), 330);
+    }
+
+    #[test]
+    fn centermost() {
+        assert_eq!(selected_x(ContourSelect::Centermost), 180);
+    }
+
+    #[test]
+    fn most_vertical() {
+        assert_eq!(selected_x(ContourSelect::MostVertical), 300);
+    }
+
+    #[test]
+    fn empty() {
+        let contours = Vector::<Vector<Point>>::new();
+        assert!(ContourSelect::MaxArea
+            .select(&contours, Size::new(400, 300))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn fill_of_rectangle_and_triangle() {
+        let rect = Vector::from_slice(&[
+            Point::new(0, 0),
+            Point::new(40, 0),
+            Point::new(40, 20),
+            Point::new(0, 20),
+        ]);
+        assert!((contour_fill(&rect).unwrap() - 1.0).abs() < 1e-6);
+
+        let triangle =
+            Vector::from_slice(&[Point::new(0, 0), Point::new(40, 0), Point::new(0, 20)]);
+        assert!((contour_fill(&triangle).unwrap() - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn area_confidence_peaks_mid_range() {
+        let bounds = 1000.0..=3000.0;
+        assert_eq!(area_confidence(2000.0, &bounds), 1.0);
+        assert!(area_confidence(1500.0, &bounds) < 1.0);
+        assert!(area_confidence(1500.0, &bounds) > area_confidence(1100.0, &bounds));
+        assert_eq!(area_confidence(1000.0, &bounds), AREA_EDGE_CONFIDENCE);
+        assert_eq!(area_confidence(3000.0, &bounds), AREA_EDGE_CONFIDENCE);
+        assert_eq!(area_confidence(999.0, &bounds), 0.0);
+        assert_eq!(area_confidence(5000.0, &bounds), 0.0);
+    }
+
+    #[test]
+    fn contour_confidence_scales_fill_by_area() {
+        let triangle =
+            Vector::from_slice(&[Point::new(0, 0), Point::new(40, 0), Point::new(0, 20)]);
+        let fill = contour_fill(&triangle).unwrap();
+        assert_eq!(contour_confidence(&triangle, None).unwrap(), fill);
+
+        // Area 400 is at the edge of the range, and outside of the second
+        let confidence = contour_confidence(&triangle, Some(&(400.0..=1000.0))).unwrap();
+        assert!((confidence - fill * AREA_EDGE_CONFIDENCE).abs() < 1e-6);
+        assert_eq!(
+            contour_confidence(&triangle, Some(&(500.0..=1000.0))).unwrap(),
+            0.0
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::Result;
 use derive_getters::Getters;
 use itertools::Itertools;
+#[cfg(feature = "annotated_streams")]
+use num_traits::ToPrimitive;
 use num_traits::{zero, FromPrimitive, Num};
 use opencv::{
     core::{MatTraitConst, Point, Rect2d, Scalar, VecN, Vector},
@@ -9,13 +11,22 @@ use opencv::{
 };
 use serde::{Deserialize, Serialize};
 use std::{
+    cmp::Ordering,
     fmt::Debug,
     hash::Hash,
     iter::Sum,
     ops::{Add, Deref, DerefMut, Div, Mul},
 };
 
+#[cfg(feature = "logging")]
+use crate::logln;
+
 pub mod bin;
+pub mod calibration;
+pub mod class_map;
+pub mod class_vote;
+pub mod color_blob;
+pub mod detection_log;
 pub mod gate;
 pub mod gate_cv;
 pub mod gate_poles;
@@ -23,6 +34,7 @@ pub mod image_prep;
 pub mod nn_cv2;
 pub mod octagon;
 pub mod path_cv;
+pub mod profiling;
 pub mod slalom;
 pub mod slalom_yolo;
 pub mod yolo_model;
@@ -43,6 +55,45 @@ pub trait Draw {
     }
 }
 
+/// Pixels between a detection's position and its class label
+#[cfg(feature = "annotated_streams")]
+const LABEL_RISE: i32 = 14;
+
+/// Copy of `mat` with every detection drawn and labeled with its class
+///
+/// Positions are in pixels of `mat`, like normalized positions scaled back
+/// with `* &mat`. Shapes come from each detection's [`Draw`] impl.
+#[cfg(feature = "annotated_streams")]
+pub fn draw_detections<C, P>(mat: &Mat, detections: &[VisualDetection<C, P>]) -> Result<Mat>
+where
+    VisualDetection<C, P>: Draw,
+    C: Debug,
+    P: RelPos,
+    P::Number: ToPrimitive,
+{
+    let mut canvas = mat.clone();
+    for detection in detections {
+        detection.draw(&mut canvas)?;
+        let offset = detection.position().offset();
+        let anchor = Point::new(
+            offset.x().to_i32().unwrap_or_default(),
+            offset.y().to_i32().unwrap_or_default() - LABEL_RISE,
+        );
+        imgproc::put_text(
+            &mut canvas,
+            &format!("{:?}", detection.class()),
+            anchor,
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            0.5,
+            Scalar::from((255.0, 255.0, 255.0)),
+            1,
+            LINE_8,
+            false,
+        )?;
+    }
+    Ok(canvas)
+}
+
 /// Holds x and y offset of object in frame
 #[derive(Debug, Getters, Clone, Copy, Default)]
 pub struct Offset2D<T: Num> {
@@ -217,17 +268,62 @@ pub trait VisualDetector<T: Num>: Debug {
 
     /// Adjusts position to [-1, 1] on both axes
     fn normalize(&mut self, pos: &Self::Position) -> Self::Position;
+
+    /// Whether [`Self::detect`] takes I420 frames as they are, see
+    /// [`is_i420`](image_prep::is_i420)
+    fn accepts_i420(&self) -> bool {
+        false
+    }
+
+    /// [`Self::detect`] on a camera frame, converted to BGR first unless
+    /// the detector [`accepts_i420`](Self::accepts_i420)
+    fn detect_frame(
+        &mut self,
+        frame: &Mat,
+    ) -> Result<Vec<VisualDetection<Self::ClassEnum, Self::Position>>> {
+        if self.accepts_i420() {
+            self.detect(frame)
+        } else {
+            self.detect(&image_prep::to_bgr(frame)?)
+        }
+    }
+}
+
+/// Relative strength of a detection, used to pick between overlapping ones
+pub trait DetectionScore {
+    fn score(&self) -> f64;
+}
+
+/// CV detections carry no confidence, so all are equally strong
+impl DetectionScore for bool {
+    fn score(&self) -> f64 {
+        0.0
+    }
 }
 
 #[derive(Debug, Clone, Getters)]
 pub struct VisualDetection<T, U> {
     class: T,
     position: U,
+    /// How far to trust the detection, 0-1
+    ///
+    /// Models give their class confidence and the CV detectors
+    /// [`contour_confidence`](image_prep::contour_confidence). Detectors
+    /// without a measure give 1.0.
+    confidence: f64,
 }
 
 impl<T, U> VisualDetection<T, U> {
     pub fn new(class: T, position: U) -> Self {
-        Self { class, position }
+        Self {
+            class,
+            position,
+            confidence: 1.0,
+        }
+    }
+
+    pub fn with_confidence(self, confidence: f64) -> Self {
+        Self { confidence, ..self }
     }
 }
 
@@ -423,6 +519,47 @@ pub struct Yuv {
     pub v: u8,
 }
 
+impl Yuv {
+    /// Channels as `(name, value)` pairs, in `y`, `u`, `v` order
+    pub const fn channels(&self) -> [(&'static str, u8); 3] {
+        [("y", self.y), ("u", self.u), ("v", self.v)]
+    }
+
+    /// Clamps each channel to lie between the same channel of `min` and `max`
+    ///
+    /// # Panics
+    ///
+    /// If any channel of `min` is above the same channel of `max`, as
+    /// [`u8::clamp`] does. Ranges from a [`ColorProfile`](crate::config::ColorProfile)
+    /// that passed validation never are.
+    pub fn clamp(self, min: Self, max: Self) -> Self {
+        Self {
+            y: self.y.clamp(min.y, max.y),
+            u: self.u.clamp(min.u, max.u),
+            v: self.v.clamp(min.v, max.v),
+        }
+    }
+}
+
+/// Componentwise order: one color is below another only if no channel is above it
+///
+/// Colors with some channels above and some below are unordered, so a range
+/// is nonempty exactly when `start <= end`.
+impl PartialOrd for Yuv {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.channels()
+            .into_iter()
+            .zip(other.channels())
+            .map(|((_, lhs), (_, rhs))| lhs.cmp(&rhs))
+            .try_fold(Ordering::Equal, |acc, channel| match (acc, channel) {
+                (acc, Ordering::Equal) => Some(acc),
+                (Ordering::Equal, channel) => Some(channel),
+                (acc, channel) if acc == channel => Some(acc),
+                _ => None,
+            })
+    }
+}
+
 impl From<&VecN<u8, 3>> for Yuv {
     fn from(value: &VecN<u8, 3>) -> Self {
         Self {
@@ -451,6 +588,24 @@ impl PosVector {
     fn new(x: f64, y: f64, z: f64, angle: f64) -> Self {
         Self { x, y, z, angle }
     }
+
+    /// Normalized position, for `normalize` implementations
+    ///
+    /// Clamps `x`, `y`, and `z` to [-1, 1] and wraps `angle` into [-180, 180),
+    /// logging the out of range input with the `logging` feature, so a detector
+    /// math error cannot drive commands past their range.
+    pub fn normalized(x: f64, y: f64, z: f64, angle: f64) -> Self {
+        #[cfg(feature = "logging")]
+        if ![x, y, z].iter().all(|value| (-1.0..=1.0).contains(value)) {
+            logln!("Normalized position ({x}, {y}, {z}) is outside [-1, 1], clamping");
+        }
+        Self {
+            x: x.clamp(-1.0, 1.0),
+            y: y.clamp(-1.0, 1.0),
+            z: z.clamp(-1.0, 1.0),
+            angle: (angle + 180.0).rem_euclid(360.0) - 180.0,
+        }
+    }
 }
 
 impl RelPosAngle for PosVector {
@@ -499,3 +654,20 @@ impl Draw for VisualDetection<bool, Offset2D<f64>> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalized_position_in_range() {
+        assert_eq!(
+            PosVector::normalized(0.5, -1.0, 0.0, 90.0),
+            PosVector::new(0.5, -1.0, 0.0, 90.0)
+        );
+        assert_eq!(
+            PosVector::normalized(1.5, -3.0, 0.0, 190.0),
+            PosVector::new(1.0, -1.0, 0.0, -170.0)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use derive_getters::Getters;
 use opencv::{
     core::{Rect2d, Scalar, Size, VecN, Vector, CV_32F},
@@ -9,9 +9,91 @@ use std::hash::Hash;
 use std::{
     fmt::Debug,
     ops::{Deref, DerefMut},
-    sync::Mutex,
+    sync::{Mutex, RwLock},
 };
 
+use super::DetectionScore;
+#[cfg(feature = "logging")]
+use crate::log_warn;
+use crate::logln;
+
+/// Default confidence floor for reporting raw model detections.
+///
+/// Kept low so annotated frames show weak detections too; missions filter
+/// with their own `min_confidence` before acting (see
+/// [`filter_confidence`](super::yolo_model::filter_confidence)).
+pub const REPORT_CONFIDENCE_FLOOR: f64 = 0.25;
+
+/// Floor models currently report above, see [`set_report_confidence_floor`]
+static REPORT_FLOOR: RwLock<f64> = RwLock::new(REPORT_CONFIDENCE_FLOOR);
+
+/// Sets the confidence floor models report detections above, from config
+pub fn set_report_confidence_floor(floor: f64) {
+    *REPORT_FLOOR.write().unwrap() = floor;
+}
+
+/// Floor set by [`set_report_confidence_floor`], [`REPORT_CONFIDENCE_FLOOR`] by default
+pub fn report_confidence_floor() -> f64 {
+    *REPORT_FLOOR.read().unwrap()
+}
+
+/// Overlap above which the weaker of two same-class detections is dropped
+pub const DEFAULT_IOU_THRESHOLD: f64 = 0.45;
+
+/// Intersection over union of two boxes, 0 when either is empty
+pub fn iou(first: &Rect2d, second: &Rect2d) -> f64 {
+    let width = (first.x + first.width).min(second.x + second.width) - first.x.max(second.x);
+    let height = (first.y + first.height).min(second.y + second.height) - first.y.max(second.y);
+    let intersection = width.max(0.0) * height.max(0.0);
+    let union = first.area() + second.area() - intersection;
+    if union > 0.0 {
+        intersection / union
+    } else {
+        0.0
+    }
+}
+
+/// Keeps the most confident detection of each cluster of same-class boxes
+/// overlapping by more than `iou_threshold`
+pub fn non_max_suppression(
+    mut detections: Vec<YoloDetection>,
+    iou_threshold: f64,
+) -> Vec<YoloDetection> {
+    detections.sort_by(|lhs, rhs| rhs.confidence.total_cmp(&lhs.confidence));
+    let mut kept: Vec<YoloDetection> = Vec::with_capacity(detections.len());
+    for detection in detections {
+        if !kept.iter().any(|other| {
+            other.class_id == detection.class_id
+                && iou(&other.bounding_box, &detection.bounding_box) > iou_threshold
+        }) {
+            kept.push(detection);
+        }
+    }
+    kept
+}
+
+/// Frame size that model detection boxes are reported in
+pub const DETECTION_FRAME: Size = Size {
+    width: 800,
+    height: 600,
+};
+
+/// Per-axis scale from model input coordinates to [`DETECTION_FRAME`] coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoxScale {
+    x: f64,
+    y: f64,
+}
+
+impl BoxScale {
+    pub fn new(model_size: Size) -> Self {
+        Self {
+            x: f64::from(DETECTION_FRAME.width) / f64::from(model_size.width),
+            y: f64::from(DETECTION_FRAME.height) / f64::from(model_size.height),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Getters, PartialEq)]
 pub struct YoloDetection {
     class_id: i32,
@@ -19,6 +101,16 @@ pub struct YoloDetection {
     bounding_box: Rect2d,
 }
 
+impl YoloDetection {
+    pub const fn new(class_id: i32, confidence: f64, bounding_box: Rect2d) -> Self {
+        Self {
+            class_id,
+            confidence,
+            bounding_box,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Getters)]
 pub struct YoloClass<T> {
     pub identifier: T,
@@ -39,6 +131,12 @@ impl<T: PartialEq> PartialEq for YoloClass<T> {
 
 impl<T: PartialEq> Eq for YoloClass<T> {}
 
+impl<T> DetectionScore for YoloClass<T> {
+    fn score(&self) -> f64 {
+        self.confidence
+    }
+}
+
 impl<T: Hash> Hash for YoloClass<T> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.identifier.hash(state)
@@ -59,12 +157,34 @@ where
     }
 }
 
+/// Number of classes decoded from each output head of a model
+///
+/// Each head row is `[x, y, w, h, confidence, class scores...]`, so its
+/// width must be 5 plus the head's class count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeadClasses {
+    /// Every head has the same class count
+    Uniform(usize),
+    /// One class count per head, in output order
+    PerHead(Vec<usize>),
+}
+
+impl HeadClasses {
+    /// Class count of the head at `idx`, `None` past the listed heads
+    pub fn for_head(&self, idx: usize) -> Option<usize> {
+        match self {
+            Self::Uniform(num_objects) => Some(*num_objects),
+            Self::PerHead(heads) => heads.get(idx).copied(),
+        }
+    }
+}
+
 pub trait VisionModel: Debug + Sync + Send + Clone {
     type PostProcessArgs;
     type ModelOutput;
 
     /// Forward pass the matrix through the model, skipping post-processing
-    fn forward(&mut self, image: &Mat) -> Self::ModelOutput;
+    fn forward(&mut self, image: &Mat) -> Result<Self::ModelOutput>;
     /// Convert output from a model into detections
     fn post_process_args(&self) -> Self::PostProcessArgs;
     fn post_process(
@@ -73,14 +193,37 @@ pub trait VisionModel: Debug + Sync + Send + Clone {
         threshold: f64,
     ) -> Vec<YoloDetection>;
 
-    /// Full input -> output processing
+    /// Full input -> output processing, see [`forward_with_retry`] for failures
     fn detect_yolo_v5(&mut self, image: &Mat, threshold: f64) -> Vec<YoloDetection> {
-        let model_output = self.forward(image);
-        Self::post_process(self.post_process_args(), model_output, threshold)
+        match forward_with_retry(|| self.forward(image)) {
+            Some(model_output) => {
+                Self::post_process(self.post_process_args(), model_output, threshold)
+            }
+            None => vec![],
+        }
     }
     fn size(&self) -> Size;
 }
 
+/// Runs `forward`, retrying once on error
+///
+/// OpenCV errors here are usually transient, so a frame that fails twice is
+/// dropped as `None` instead of taking down the vision task. Failures are
+/// only logged with the `logging` feature, as they can repeat every frame.
+pub fn forward_with_retry<T>(mut forward: impl FnMut() -> Result<T>) -> Option<T> {
+    forward()
+        .or_else(|_e| {
+            #[cfg(feature = "logging")]
+            log_warn!("Model forward failed, retrying: {:#}", _e);
+            forward()
+        })
+        .inspect_err(|_e| {
+            #[cfg(feature = "logging")]
+            log_warn!("Model forward failed again, skipping frame: {:#}", _e);
+        })
+        .ok()
+}
+
 /* -------------------------------------------------- */
 /* --------------- ONNX implementation -------------- */
 /* -------------------------------------------------- */
@@ -114,14 +257,16 @@ pub struct OnnxModel {
     net: Mutex<NetWrapper>,
     //out_blob_names: Vec<String>,
     num_objects: usize,
+    head_classes: HeadClasses,
     //output: Vec<usize>,
     //output_description: Vec<Rect2d>,
     model_size: Size,
-    factor: f64,
+    scale: BoxScale,
+    iou_threshold: f64,
 }
 
 impl OnnxModel {
-    /// Creates model from in memory byte buffer
+    /// Creates model with a square input from in memory byte buffer
     ///
     /// # Arguments:
     /// * `model_bytes` - ONNX model in u8
@@ -146,17 +291,25 @@ impl OnnxModel {
         model_size: i32,
         num_objects: usize,
     ) -> Result<Self> {
-        let net = read_net_from_onnx_buffer(model_bytes)?;
+        Self::from_bytes_sized(model_bytes, Size::new(model_size, model_size), num_objects)
+    }
 
-        Ok(Self {
-            net: Mutex::new(NetWrapper(net)),
-            num_objects,
-            model_size: Size::new(model_size, model_size),
-            factor: Self::size_to_factor(model_size),
-        })
+    /// Creates model from in memory byte buffer
+    ///
+    /// # Arguments:
+    /// * `model_bytes` - ONNX model in u8
+    /// * `model_size` - input image width and height (e.g. 640x480)
+    /// * `num_objects` - number of objects model can output
+    pub fn from_bytes_sized(
+        model_bytes: &Vector<u8>,
+        model_size: Size,
+        num_objects: usize,
+    ) -> Result<Self> {
+        let net = read_net_from_onnx_buffer(model_bytes)?;
+        Ok(Self::from_net(net, model_size, num_objects))
     }
 
-    /// Creates model from file (use a local path)
+    /// Creates model with a square input from file (use a local path)
     ///
     /// # Arguments:
     /// * `model_name` - path to ONNX model (from working directory)
@@ -171,19 +324,29 @@ impl OnnxModel {
     /// OnnxModel::from_file("src/vision/models/buoy_320.onnx", 320, 4).unwrap();
     /// ```
     pub fn from_file(model_name: &str, model_size: i32, num_objects: usize) -> Result<Self> {
+        Self::from_file_sized(model_name, Size::new(model_size, model_size), num_objects)
+    }
+
+    /// Creates model from file (use a local path)
+    ///
+    /// # Arguments:
+    /// * `model_name` - path to ONNX model (from working directory)
+    /// * `model_size` - input image width and height (e.g. 640x480)
+    /// * `num_objects` - number of objects model can output
+    pub fn from_file_sized(model_name: &str, model_size: Size, num_objects: usize) -> Result<Self> {
         let net = read_net_from_onnx(model_name)?;
+        Ok(Self::from_net(net, model_size, num_objects))
+    }
 
-        Ok(Self {
+    fn from_net(net: Net, model_size: Size, num_objects: usize) -> Self {
+        Self {
             net: Mutex::new(NetWrapper(net)),
             num_objects,
-            model_size: Size::new(model_size, model_size),
-            factor: Self::size_to_factor(model_size),
-        })
-    }
-
-    /// Calculates coordinate factor based on model size
-    fn size_to_factor(model_size: i32) -> f64 {
-        640.0 / model_size as f64
+            head_classes: HeadClasses::Uniform(num_objects),
+            model_size,
+            scale: BoxScale::new(model_size),
+            iou_threshold: DEFAULT_IOU_THRESHOLD,
+        }
     }
 
     fn get_output_names(net: &Net) -> Vector<String> {
@@ -212,6 +375,20 @@ impl OnnxModel {
     pub fn get_model_size(&self) -> Size {
         self.model_size
     }
+
+    /// Overlap above which same-class detections are merged, see [`non_max_suppression`]
+    pub fn set_iou_threshold(&mut self, iou_threshold: f64) {
+        self.iou_threshold = iou_threshold;
+    }
+
+    /// Decodes each output head with its own class count, for multi-head
+    /// models whose heads differ
+    pub fn with_head_classes(self, head_classes: Vec<usize>) -> Self {
+        Self {
+            head_classes: HeadClasses::PerHead(head_classes),
+            ..self
+        }
+    }
 }
 
 impl Clone for OnnxModel {
@@ -219,8 +396,10 @@ impl Clone for OnnxModel {
         Self {
             net: Mutex::new(self.net.lock().unwrap().clone()),
             num_objects: self.num_objects,
+            head_classes: self.head_classes.clone(),
             model_size: self.model_size,
-            factor: self.factor,
+            scale: self.scale,
+            iou_threshold: self.iou_threshold,
         }
     }
 }
@@ -258,13 +437,16 @@ macro_rules! load_onnx {
 
 impl VisionModel for OnnxModel {
     fn detect_yolo_v5(&mut self, image: &Mat, threshold: f64) -> Vec<YoloDetection> {
-        let result = self.forward(image);
-        let post_processing = Self::process_net(self.num_objects, self.factor, result, threshold);
+        let Some(result) = forward_with_retry(|| self.forward(image)) else {
+            return vec![];
+        };
+        let post_processing =
+            Self::process_heads(&self.head_classes, self.scale, result, threshold);
 
-        post_processing
+        non_max_suppression(post_processing, self.iou_threshold)
     }
 
-    fn forward(&mut self, image: &Mat) -> Self::ModelOutput {
+    fn forward(&mut self, image: &Mat) -> Result<Self::ModelOutput> {
         let mut result: Vector<Mat> = Vector::new();
         let result_names = Self::get_output_names(&self.net.lock().unwrap());
         let blob = blob_from_image(
@@ -275,29 +457,26 @@ impl VisionModel for OnnxModel {
             true,
             false,
             CV_32F,
-        )
-        .unwrap();
+        )?;
 
         self.net
             .lock()
             .unwrap()
-            .set_input(&blob, "", 1.0, Scalar::from(0.0))
-            .unwrap();
+            .set_input(&blob, "", 1.0, Scalar::from(0.0))?;
         self.net
             .lock()
             .unwrap()
-            .forward(&mut result, &result_names)
-            .unwrap();
+            .forward(&mut result, &result_names)?;
 
-        result
+        Ok(result)
     }
 
     type ModelOutput = Vector<Mat>;
 
-    type PostProcessArgs = (usize, f64);
+    type PostProcessArgs = (HeadClasses, BoxScale, f64);
 
     fn post_process_args(&self) -> Self::PostProcessArgs {
-        (self.num_objects, self.factor)
+        (self.head_classes.clone(), self.scale, self.iou_threshold)
     }
 
     fn post_process(
@@ -305,9 +484,9 @@ impl VisionModel for OnnxModel {
         output: Self::ModelOutput,
         threshold: f64,
     ) -> Vec<YoloDetection> {
-        let post_processing = Self::process_net(args.0, args.1, output, threshold);
+        let post_processing = Self::process_heads(&args.0, args.1, output, threshold);
 
-        post_processing
+        non_max_suppression(post_processing, args.2)
     }
 
     fn size(&self) -> Size {
@@ -317,14 +496,32 @@ impl VisionModel for OnnxModel {
 
 impl OnnxModel {
     #[allow(unused)]
-    /// Returns all detections from a net's output
+    /// Returns all detections from a net's output, with the same class
+    /// count on every head
     ///
     /// # Arguments
     /// * `result` - iterator of net output
     /// * `threshold` - minimum confidence
     fn process_net<I>(
         num_objects: usize,
-        factor: f64,
+        scale: BoxScale,
+        result: I,
+        threshold: f64,
+    ) -> Vec<YoloDetection>
+    where
+        I: IntoIterator<Item = Mat>,
+    {
+        Self::process_heads(&HeadClasses::Uniform(num_objects), scale, result, threshold)
+    }
+
+    /// Returns all detections from a net's output, decoding each head with
+    /// its own class count
+    ///
+    /// Heads whose geometry does not match their class count are skipped
+    /// instead of being reshaped into garbage rows.
+    fn process_heads<I>(
+        head_classes: &HeadClasses,
+        scale: BoxScale,
         result: I,
         threshold: f64,
     ) -> Vec<YoloDetection>
@@ -333,65 +530,270 @@ impl OnnxModel {
     {
         result
             .into_iter()
-            .flat_map(|level| -> Vec<YoloDetection> {
-                // This reshape is always valid as per the model design
-                let level = level
-                    .reshape(1, (level.total() / (5 + num_objects)) as i32)
-                    .unwrap();
-
-                (0..level.rows())
-                    .map(|idx| level.row(idx).unwrap())
-                    .filter_map(|row| -> Option<YoloDetection> {
-                        // Cols is always > 5. The column range can always be constructed, since
-                        // row always has level.cols number of columns.
-                        let scores = row
-                            .col_range(&opencv::core::Range::new(5, level.cols()).unwrap())
-                            .unwrap();
-
-                        let mut max_loc = 5;
-                        for idx in 6..level.cols() {
-                            if row.at::<VecN<f32, 1>>(max_loc).unwrap()[0]
-                                < row.at::<VecN<f32, 1>>(idx).unwrap()[0]
-                            {
-                                max_loc = idx;
-                            }
-                        }
-                        max_loc -= 5;
-
-                        // Always a valid index access
-                        let confidence: f64 = row.at::<VecN<f32, 1>>(4).unwrap()[0].into();
-
-                        if confidence > threshold {
-                            // The given constant values are always valid indicies
-                            let adjust_base = |idx: i32| -> f64 {
-                                f64::from(row.at::<VecN<f32, 1>>(idx).unwrap()[0]) * factor
-                            };
-
-                            let x_adjust = |idx: i32| -> f64 { adjust_base(idx) / 640.0 * 800.0 };
-                            let y_adjust = |idx: i32| -> f64 { adjust_base(idx) / 640.0 * 600.0 };
-
-                            let (center_x, center_y, width, height) =
-                                (x_adjust(0), y_adjust(1), x_adjust(2), y_adjust(3));
-
-                            let left = center_x - width / 2.0;
-                            let top = center_y - height / 2.0;
-
-                            Some(YoloDetection {
-                                class_id: max_loc,
-                                confidence,
-                                bounding_box: Rect2d {
-                                    x: left,
-                                    y: top,
-                                    width,
-                                    height,
-                                },
-                            })
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
+            .enumerate()
+            .flat_map(|(idx, level)| {
+                let Some(num_objects) = head_classes.for_head(idx) else {
+                    logln!("Skipping output head {idx}: no class count given");
+                    return vec![];
+                };
+                Self::decode_head(&level, num_objects, scale, threshold).unwrap_or_else(|e| {
+                    logln!("Skipping output head {idx}: {e:#}");
+                    vec![]
+                })
             })
             .collect()
     }
+
+    /// Decodes one output head with `num_objects` class scores per row
+    fn decode_head(
+        level: &Mat,
+        num_objects: usize,
+        scale: BoxScale,
+        threshold: f64,
+    ) -> Result<Vec<YoloDetection>> {
+        let width = 5 + num_objects;
+        if level.total() == 0 {
+            return Ok(vec![]);
+        }
+        let row_width = level.mat_size().last().copied().unwrap_or_default();
+        if row_width != width as i32 || level.total() % width != 0 {
+            bail!(
+                "rows are {row_width} wide, expected {width} for {num_objects} classes ({} values)",
+                level.total()
+            );
+        }
+        let level = level.reshape(1, (level.total() / width) as i32)?;
+
+        Ok((0..level.rows())
+            .map(|idx| level.row(idx).unwrap())
+            .filter_map(|row| -> Option<YoloDetection> {
+                let mut max_loc = 5;
+                for idx in 6..level.cols() {
+                    if row.at::<VecN<f32, 1>>(max_loc).unwrap()[0]
+                        < row.at::<VecN<f32, 1>>(idx).unwrap()[0]
+                    {
+                        max_loc = idx;
+                    }
+                }
+                max_loc -= 5;
+
+                // Always a valid index access
+                let confidence: f64 = row.at::<VecN<f32, 1>>(4).unwrap()[0].into();
+
+                if confidence > threshold {
+                    // The given constant values are always valid indicies
+                    let adjust_base =
+                        |idx: i32| -> f64 { f64::from(row.at::<VecN<f32, 1>>(idx).unwrap()[0]) };
+
+                    let x_adjust = |idx: i32| -> f64 { adjust_base(idx) * scale.x };
+                    let y_adjust = |idx: i32| -> f64 { adjust_base(idx) * scale.y };
+
+                    let (center_x, center_y, width, height) =
+                        (x_adjust(0), y_adjust(1), x_adjust(2), y_adjust(3));
+
+                    let left = center_x - width / 2.0;
+                    let top = center_y - height / 2.0;
+
+                    Some(YoloDetection {
+                        class_id: max_loc,
+                        confidence,
+                        bounding_box: Rect2d {
+                            x: left,
+                            y: top,
+                            width,
+                            height,
+                        },
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vision::{
+        gate_poles::Target, yolo_model::filter_confidence, DrawRect2d, VisualDetection,
+    };
+
+    use super::*;
+
+    const SCALE_640: BoxScale = BoxScale {
+        x: 800.0 / 640.0,
+        y: 600.0 / 640.0,
+    };
+
+    #[test]
+    fn low_confidence_reported_then_filtered() {
+        // Two objects: [x, y, w, h, confidence, class scores...]
+        let output = Mat::from_slice_2d(&[
+            [100.0_f32, 100.0, 20.0, 20.0, 0.3, 0.9, 0.1],
+            [200.0_f32, 200.0, 20.0, 20.0, 0.9, 0.1, 0.9],
+        ])
+        .unwrap();
+
+        let raw = OnnxModel::process_net(2, SCALE_640, vec![output], REPORT_CONFIDENCE_FLOOR);
+        assert_eq!(raw.len(), 2);
+
+        let detections = raw.into_iter().map(|detection| {
+            VisualDetection::new(
+                YoloClass::<Target> {
+                    identifier: (*detection.class_id()).try_into().unwrap(),
+                    confidence: *detection.confidence(),
+                },
+                DrawRect2d::from(*detection.bounding_box()),
+            )
+        });
+
+        let filtered = filter_confidence(detections, 0.75);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].class().identifier, Target::Middle);
+    }
+
+    #[test]
+    fn two_heads_with_different_class_counts() {
+        // Two classes per row on the first head, three on the second
+        let first = Mat::from_slice_2d(&[[100.0_f32, 100.0, 20.0, 20.0, 0.9, 0.1, 0.8]]).unwrap();
+        let second = Mat::from_slice_2d(&[
+            [300.0_f32, 300.0, 40.0, 40.0, 0.9, 0.1, 0.2, 0.7],
+            [400.0_f32, 400.0, 40.0, 40.0, 0.1, 0.9, 0.0, 0.0],
+        ])
+        .unwrap();
+
+        let decoded = OnnxModel::process_heads(
+            &HeadClasses::PerHead(vec![2, 3]),
+            SCALE_640,
+            vec![first.clone(), second.clone()],
+            0.5,
+        );
+        assert_eq!(
+            decoded
+                .iter()
+                .map(|d| (*d.class_id(), d.bounding_box().width))
+                .collect::<Vec<_>>(),
+            [(1, 25.0), (2, 50.0)]
+        );
+
+        // A single class count cannot decode the wider head, so it is skipped
+        let uniform = OnnxModel::process_net(2, SCALE_640, vec![first, second], 0.5);
+        assert_eq!(uniform.len(), 1);
+        assert_eq!(*uniform[0].class_id(), 1);
+    }
+
+    #[test]
+    fn box_scale_follows_model_size() {
+        assert_eq!(BoxScale::new(Size::new(640, 640)), SCALE_640);
+        let rounded = |rect: &Rect2d| [rect.x, rect.y, rect.width, rect.height].map(f64::round);
+
+        // Centered box covering a quarter of a 416x416 input
+        let square = Mat::from_slice_2d(&[[208.0_f32, 208.0, 208.0, 208.0, 0.9, 0.9]]).unwrap();
+        let square =
+            OnnxModel::process_net(1, BoxScale::new(Size::new(416, 416)), vec![square], 0.5);
+        assert_eq!(
+            rounded(square[0].bounding_box()),
+            [200.0, 150.0, 400.0, 300.0]
+        );
+
+        // Bottom right quarter of a 416x208 input
+        let wide = Mat::from_slice_2d(&[[312.0_f32, 156.0, 208.0, 104.0, 0.9, 0.9]]).unwrap();
+        let wide = OnnxModel::process_net(1, BoxScale::new(Size::new(416, 208)), vec![wide], 0.5);
+        assert_eq!(
+            rounded(wide[0].bounding_box()),
+            [400.0, 300.0, 400.0, 300.0]
+        );
+    }
+
+    #[test]
+    fn overlapping_boxes_suppressed() {
+        let detection = |class_id, confidence, x| YoloDetection {
+            class_id,
+            confidence,
+            bounding_box: Rect2d::new(x, 100.0, 100.0, 100.0),
+        };
+        let detections = vec![
+            detection(0, 0.6, 0.0),
+            detection(0, 0.9, 10.0),
+            detection(0, 0.7, 20.0),
+            // Overlaps the others, but is another class
+            detection(1, 0.5, 10.0),
+            // Same class, too far to overlap
+            detection(0, 0.4, 300.0),
+        ];
+
+        assert_eq!(
+            non_max_suppression(detections, DEFAULT_IOU_THRESHOLD),
+            [
+                detection(0, 0.9, 10.0),
+                detection(1, 0.5, 10.0),
+                detection(0, 0.4, 300.0),
+            ]
+        );
+        assert_eq!(
+            iou(
+                &Rect2d::new(0.0, 0.0, 10.0, 10.0),
+                &Rect2d::new(5.0, 0.0, 10.0, 10.0)
+            ),
+            50.0 / 150.0
+        );
+    }
+
+    /// Fails its first `failures` forward passes
+    #[derive(Debug, Clone)]
+    struct FailingModel {
+        failures: usize,
+        calls: usize,
+    }
+
+    impl VisionModel for FailingModel {
+        type PostProcessArgs = ();
+        type ModelOutput = Vec<YoloDetection>;
+
+        fn forward(&mut self, _image: &Mat) -> Result<Self::ModelOutput> {
+            self.calls += 1;
+            if self.calls <= self.failures {
+                bail!("Forced forward failure");
+            }
+            Ok(vec![YoloDetection {
+                class_id: 0,
+                confidence: 0.9,
+                bounding_box: Rect2d::new(0.0, 0.0, 1.0, 1.0),
+            }])
+        }
+
+        fn post_process_args(&self) -> Self::PostProcessArgs {}
+
+        fn post_process(
+            _args: Self::PostProcessArgs,
+            output: Self::ModelOutput,
+            _threshold: f64,
+        ) -> Vec<YoloDetection> {
+            output
+        }
+
+        fn size(&self) -> Size {
+            Size::new(1, 1)
+        }
+    }
+
+    #[test]
+    fn forward_error_gives_empty_detections() {
+        let image = Mat::default();
+
+        let mut broken = FailingModel {
+            failures: usize::MAX,
+            calls: 0,
+        };
+        assert!(broken.detect_yolo_v5(&image, 0.5).is_empty());
+        assert_eq!(broken.calls, 2);
+
+        // A single failure is retried
+        let mut flaky = FailingModel {
+            failures: 1,
+            calls: 0,
+        };
+        assert_eq!(flaky.detect_yolo_v5(&image, 0.5).len(), 1);
+        assert_eq!(flaky.calls, 2);
+    }
 }
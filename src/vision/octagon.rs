@@ -141,6 +141,7 @@ impl VisualDetector<f64> for Octagon {
                 .map(|contour| VisualDetection {
                     position: Offset2D::new(contour.x as f64, contour.y as f64),
                     class: true,
+                    confidence: 1.0,
                 })
                 .collect())
         } else {
@@ -1,10 +1,14 @@
-use super::{image_prep::resize, MatWrapper, PosVector, VisualDetection, VisualDetector, Yuv};
+use super::{
+    image_prep::{input_yuv, is_i420, resize_input, ContourSelect},
+    profiling::{StageTimer, StageTimings},
+    MatWrapper, PosVector, VisualDetection, VisualDetector, Yuv,
+};
 use crate::{config::ColorProfile, vision::Draw};
 use opencv::{
     core::{in_range, Point, Scalar, Size, Vector},
     imgproc::{
-        self, contour_area_def, cvt_color_def, find_contours_def, min_area_rect,
-        CHAIN_APPROX_SIMPLE, COLOR_BGR2YUV, LINE_8, RETR_EXTERNAL,
+        self, contour_area_def, find_contours_def, min_area_rect, CHAIN_APPROX_SIMPLE, LINE_8,
+        RETR_EXTERNAL,
     },
     prelude::{Mat, MatTraitConst, MatTraitConstManual},
 };
@@ -62,28 +66,88 @@ impl Draw for VisualDetection<bool, PosVector> {
 #[derive(Debug)]
 pub struct PathCV {
     color_bounds: RangeInclusive<Yuv>,
+    contour_select: ContourSelect,
+    /// Path segments reported per frame, see [`Self::with_max_segments`]
+    max_segments: usize,
     size: Size,
     image: MatWrapper,
+    timings: StageTimings,
 }
 
 impl PathCV {
+    /// Stage timings of the last [`VisualDetector::detect`] call
+    pub fn timings(&self) -> &StageTimings {
+        &self.timings
+    }
+
     pub fn image(&self) -> Mat {
         (*self.image).clone()
     }
 }
 
 impl PathCV {
-    pub fn new(color_bounds: RangeInclusive<Yuv>, size: Size) -> Self {
+    pub fn new(
+        color_bounds: RangeInclusive<Yuv>,
+        contour_select: ContourSelect,
+        size: Size,
+    ) -> Self {
         Self {
             color_bounds,
+            contour_select,
+            max_segments: 1,
             size,
             image: Mat::default().into(),
+            timings: StageTimings::default(),
+        }
+    }
+
+    pub fn from_color_profile(color_profile: &ColorProfile, contour_select: ContourSelect) -> Self {
+        Self::new(
+            dbg!(color_profile.orange.clone()),
+            contour_select,
+            Size::from((400, 300)),
+        )
+    }
+
+    /// Reports up to `max_segments` of the largest contours over
+    /// [`MIN_SEGMENT_AREA`] as separate detections, largest first
+    ///
+    /// The default of 1 keeps the single contour picked by `contour_select`.
+    /// More lets both arms of a bent path be seen at once.
+    pub fn with_max_segments(self, max_segments: usize) -> Self {
+        Self {
+            max_segments,
+            ..self
         }
     }
+}
 
-    pub fn from_color_profile(color_profile: &ColorProfile) -> Self {
-        Self::new(dbg!(color_profile.orange.clone()), Size::from((400, 300)))
+/// Smallest contour area, in resized pixels, reported as a path segment
+pub const MIN_SEGMENT_AREA: f64 = 5000.0;
+
+/// Averages the position and angle of path segments, `None` if there are none
+///
+/// Aligning to every visible arm of the path is steadier than to whichever
+/// arm happens to be largest in a frame. Segment angles are axial, 90 and -90
+/// are the same heading, so they are averaged as doubled angles.
+pub fn mean_segment(segments: &[PosVector]) -> Option<PosVector> {
+    if segments.is_empty() {
+        return None;
     }
+    let count = segments.len() as f64;
+    let mean = |field: fn(&PosVector) -> &f64| segments.iter().map(field).sum::<f64>() / count;
+    let (sin, cos) = segments
+        .iter()
+        .map(|segment| (2.0 * segment.angle()).to_radians())
+        .fold((0.0, 0.0), |(sin, cos), angle| {
+            (sin + angle.sin(), cos + angle.cos())
+        });
+    Some(PosVector::normalized(
+        mean(PosVector::x),
+        mean(PosVector::y),
+        mean(PosVector::z),
+        sin.atan2(cos).to_degrees() / 2.0,
+    ))
 }
 
 impl Default for PathCV {
@@ -94,23 +158,23 @@ impl Default for PathCV {
                 u: 127,
                 v: 255,
             }),
+            ContourSelect::default(),
             Size::from((400, 300)),
         )
     }
 }
 
-impl VisualDetector<i32> for PathCV {
-    type ClassEnum = bool;
-    type Position = PosVector;
-
-    fn detect(
+impl PathCV {
+    fn detect_segments(
         &mut self,
         input_image: &Mat,
-    ) -> anyhow::Result<Vec<VisualDetection<Self::ClassEnum, Self::Position>>> {
-        self.image = resize(input_image, &self.size)?.into();
-        let mut yuv_image = Mat::default();
-
-        cvt_color_def(&self.image.0, &mut yuv_image, COLOR_BGR2YUV)?;
+    ) -> anyhow::Result<Vec<VisualDetection<bool, PosVector>>> {
+        let mut timer = StageTimer::start();
+        let i420 = is_i420(input_image);
+        self.image = resize_input(input_image, &self.size)?.into();
+        timer.lap(|t| &mut t.resize);
+        let yuv_image = input_yuv(&self.image.0, i420)?;
+        timer.lap(|t| &mut t.color_convert);
 
         let color_start = self.color_bounds.start();
         let color_end = self.color_bounds.end();
@@ -128,84 +192,108 @@ impl VisualDetector<i32> for PathCV {
         );
 
         let mut mask = Mat::default();
-        let _ = in_range(&yuv_image, &lower_orange, &upper_orange, &mut mask);
+        let _ = in_range(&*yuv_image, &lower_orange, &upper_orange, &mut mask);
+        timer.lap(|t| &mut t.in_range);
 
         let mut contours = Vector::<Vector<Point>>::new();
         find_contours_def(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)?;
 
-        let max_contour = contours.iter().max_by(|x, y| {
-            contour_area_def(&x)
-                .unwrap()
-                .partial_cmp(&contour_area_def(&y).unwrap())
-                .unwrap()
-        });
-
-        if let Some(contour) = max_contour {
-            let area = contour_area_def(&contour)?;
-            if area > 5000.0 {
-                let rect = min_area_rect(&contour)?;
-
-                let mut box_rect = Mat::default();
-                imgproc::box_points(rect, &mut box_rect)?;
-
-                let box_vec: Vec<Vec<f32>> = box_rect.to_vec_2d()?;
-
-                let zero = box_vec[0].clone();
-                let one = box_vec[1].clone();
-                let two = box_vec[2].clone();
-
-                let edge1 = (one[0] - zero[0], one[1] - zero[1]);
-                let edge2 = (two[0] - one[0], two[1] - one[1]);
-
-                // let longest_edge = max_by_key(edge1, edge2, |e| (e.0.powf(2.0) + e.1.powf(2.0)).sqrt());
-                let edge1mag = (edge1.0.powf(2.0) + edge1.1.powf(2.0)).sqrt();
-                let edge2mag = (edge2.0.powf(2.0) + edge2.1.powf(2.0)).sqrt();
-                let longest_edge = if edge2mag > edge1mag { edge2 } else { edge1 };
-
-                let mut angle = -(longest_edge.0 / longest_edge.1).atan().to_degrees();
-
-                angle = ((angle + 180.0) % 360.0) - 180.0;
-                if angle < -90.0 {
-                    angle += 180.0;
-                }
-
-                println!("{angle:?}");
-
-                let center_adjusted_x = rect.center.x as f64;
-                let center_adjusted_y = rect.center.y as f64;
-
-                Ok(vec![VisualDetection {
-                    class: true,
-                    position: PosVector::new(
-                        center_adjusted_x,
-                        center_adjusted_y,
-                        0.,
-                        angle as f64,
-                    ),
-                }])
-            } else {
-                Ok(vec![VisualDetection {
-                    class: false,
-                    position: PosVector::new(0., 0., 0., 0.),
-                }])
+        let segments = if self.max_segments <= 1 {
+            match self.contour_select.select(&contours, self.image.size()?)? {
+                Some(contour) if contour_area_def(&contour)? > MIN_SEGMENT_AREA => vec![contour],
+                _ => vec![],
             }
         } else {
-            Ok(vec![VisualDetection {
+            let mut sized = contours
+                .iter()
+                .map(|contour| Ok((contour_area_def(&contour)?, contour)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            sized.retain(|(area, _)| *area > MIN_SEGMENT_AREA);
+            sized.sort_by(|(lhs, _), (rhs, _)| rhs.total_cmp(lhs));
+            sized
+                .into_iter()
+                .take(self.max_segments)
+                .map(|(_, contour)| contour)
+                .collect()
+        };
+        timer.lap(|t| &mut t.contours);
+        self.timings = timer.finish("PathCV");
+
+        if segments.is_empty() {
+            return Ok(vec![VisualDetection {
                 class: false,
                 position: PosVector::new(0., 0., 0., 0.),
-            }])
+                confidence: 1.0,
+            }]);
         }
+        segments.iter().map(Self::segment).collect()
+    }
+
+    /// Center and heading of one path contour
+    fn segment(contour: &Vector<Point>) -> anyhow::Result<VisualDetection<bool, PosVector>> {
+        let rect = min_area_rect(contour)?;
+
+        let mut box_rect = Mat::default();
+        imgproc::box_points(rect, &mut box_rect)?;
+
+        let box_vec: Vec<Vec<f32>> = box_rect.to_vec_2d()?;
+
+        let zero = box_vec[0].clone();
+        let one = box_vec[1].clone();
+        let two = box_vec[2].clone();
+
+        let edge1 = (one[0] - zero[0], one[1] - zero[1]);
+        let edge2 = (two[0] - one[0], two[1] - one[1]);
+
+        // let longest_edge = max_by_key(edge1, edge2, |e| (e.0.powf(2.0) + e.1.powf(2.0)).sqrt());
+        let edge1mag = (edge1.0.powf(2.0) + edge1.1.powf(2.0)).sqrt();
+        let edge2mag = (edge2.0.powf(2.0) + edge2.1.powf(2.0)).sqrt();
+        let longest_edge = if edge2mag > edge1mag { edge2 } else { edge1 };
+
+        let mut angle = -(longest_edge.0 / longest_edge.1).atan().to_degrees();
+
+        angle = ((angle + 180.0) % 360.0) - 180.0;
+        if angle < -90.0 {
+            angle += 180.0;
+        }
+
+        println!("{angle:?}");
+
+        let center_adjusted_x = rect.center.x as f64;
+        let center_adjusted_y = rect.center.y as f64;
+
+        Ok(VisualDetection {
+            class: true,
+            position: PosVector::new(center_adjusted_x, center_adjusted_y, 0., angle as f64),
+            confidence: 1.0,
+        })
+    }
+}
+
+impl VisualDetector<i32> for PathCV {
+    type ClassEnum = bool;
+    type Position = PosVector;
+
+    fn detect(
+        &mut self,
+        input_image: &Mat,
+    ) -> anyhow::Result<Vec<VisualDetection<Self::ClassEnum, Self::Position>>> {
+        self.detect_segments(input_image)
     }
 
     fn normalize(&mut self, pos: &Self::Position) -> Self::Position {
         let img_size = self.image.size().unwrap();
-        Self::Position::new(
+        Self::Position::normalized(
             ((*pos.x() / (img_size.width as f64)) - 0.5) * 2.0,
             ((*pos.y() / (img_size.height as f64)) - 0.5) * 2.0,
             0.,
             *pos.angle(),
         )
     }
+
+    fn accepts_i420(&self) -> bool {
+        true
+    }
 }
 
 impl VisualDetector<f64> for PathCV {
@@ -216,105 +304,22 @@ impl VisualDetector<f64> for PathCV {
         &mut self,
         input_image: &Mat,
     ) -> anyhow::Result<Vec<VisualDetection<Self::ClassEnum, Self::Position>>> {
-        self.image = resize(input_image, &self.size)?.into();
-        let mut yuv_image = Mat::default();
-
-        cvt_color_def(&self.image.0, &mut yuv_image, COLOR_BGR2YUV)?;
-
-        let color_start = self.color_bounds.start();
-        let color_end = self.color_bounds.end();
-        let lower_orange = Scalar::new(
-            color_start.y as f64,
-            color_start.u as f64,
-            color_start.v as f64,
-            0.,
-        );
-        let upper_orange = Scalar::new(
-            color_end.y as f64,
-            color_end.u as f64,
-            color_end.v as f64,
-            0.,
-        );
-
-        let mut mask = Mat::default();
-        let _ = in_range(&yuv_image, &lower_orange, &upper_orange, &mut mask);
-
-        let mut contours = Vector::<Vector<Point>>::new();
-        find_contours_def(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)?;
-
-        let max_contour = contours.iter().max_by(|x, y| {
-            contour_area_def(&x)
-                .unwrap()
-                .partial_cmp(&contour_area_def(&y).unwrap())
-                .unwrap()
-        });
-
-        if let Some(contour) = max_contour {
-            let area = contour_area_def(&contour)?;
-            if area > 5000.0 {
-                let rect = min_area_rect(&contour)?;
-
-                let mut box_rect = Mat::default();
-                imgproc::box_points(rect, &mut box_rect)?;
-
-                let box_vec: Vec<Vec<f32>> = box_rect.to_vec_2d()?;
-
-                let zero = box_vec[0].clone();
-                let one = box_vec[1].clone();
-                let two = box_vec[2].clone();
-
-                let edge1 = (one[0] - zero[0], one[1] - zero[1]);
-                let edge2 = (two[0] - one[0], two[1] - one[1]);
-
-                // let longest_edge = max_by_key(edge1, edge2, |e| (e.0.powf(2.0) + e.1.powf(2.0)).sqrt());
-                let edge1mag = (edge1.0.powf(2.0) + edge1.1.powf(2.0)).sqrt();
-                let edge2mag = (edge2.0.powf(2.0) + edge2.1.powf(2.0)).sqrt();
-                let longest_edge = if edge2mag > edge1mag { edge2 } else { edge1 };
-
-                let mut angle = -(longest_edge.0 / longest_edge.1).atan().to_degrees();
-
-                angle = ((angle + 180.0) % 360.0) - 180.0;
-                if angle < -90.0 {
-                    angle += 180.0;
-                }
-
-                println!("{angle:?}");
-
-                let center_adjusted_x = rect.center.x as f64;
-                let center_adjusted_y = rect.center.y as f64;
-
-                Ok(vec![VisualDetection {
-                    class: true,
-                    position: PosVector::new(
-                        center_adjusted_x,
-                        center_adjusted_y,
-                        0.,
-                        angle as f64,
-                    ),
-                }])
-            } else {
-                Ok(vec![VisualDetection {
-                    class: false,
-                    position: PosVector::new(0., 0., 0., 0.),
-                }])
-            }
-        } else {
-            Ok(vec![VisualDetection {
-                class: false,
-                position: PosVector::new(0., 0., 0., 0.),
-            }])
-        }
+        self.detect_segments(input_image)
     }
 
     fn normalize(&mut self, pos: &Self::Position) -> Self::Position {
         let img_size = self.image.size().unwrap();
-        Self::Position::new(
+        Self::Position::normalized(
             ((*pos.x() / (img_size.width as f64)) - 0.5) * 2.0,
             ((*pos.y() / (img_size.height as f64)) - 0.5) * 2.0,
             0.,
             *pos.angle(),
         )
     }
+
+    fn accepts_i420(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
@@ -322,11 +327,17 @@ mod tests {
     use std::fs::create_dir_all;
 
     use opencv::{
-        core::Vector,
+        core::{
+            rotate, Point2f, Rect, RotatedRect, Size2f, Vector, CV_8UC3, ROTATE_90_COUNTERCLOCKWISE,
+        },
         imgcodecs::{imread, imwrite, IMREAD_COLOR},
+        imgproc::{box_points, fill_convex_poly, rectangle_def},
     };
 
-    use crate::{logln, vision::Draw};
+    use crate::{
+        logln,
+        vision::{image_prep::FrameRotation, Draw},
+    };
 
     use super::*;
 
@@ -351,4 +362,80 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn mean_segment_across_vertical() {
+        assert_eq!(mean_segment(&[]), None);
+
+        let mean = mean_segment(&[
+            PosVector::normalized(-0.5, 0.25, 0.0, 80.0),
+            PosVector::normalized(0.5, -0.25, 0.0, -80.0),
+        ])
+        .unwrap();
+        assert!(mean.x().abs() < 1e-9 && mean.y().abs() < 1e-9);
+        // Both arms lean off vertical, not toward horizontal
+        assert!((mean.angle().abs() - 90.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn max_segments_reports_both_arms() {
+        let mut frame =
+            Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+        let orange = Scalar::new(0.0, 128.0, 255.0, 0.0);
+        for (x, width) in [(40, 60), (260, 80)] {
+            rectangle_def(&mut frame, Rect::new(x, 50, width, 200), orange).unwrap();
+        }
+
+        let detect = |path: &mut PathCV| {
+            <PathCV as VisualDetector<f64>>::detect(path, &frame)
+                .unwrap()
+                .into_iter()
+                .filter(|detection| *detection.class())
+                .map(|detection| *detection.position().x())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(detect(&mut PathCV::default()).len(), 1);
+        // Largest first
+        let centers = detect(&mut PathCV::default().with_max_segments(3));
+        assert_eq!(centers.len(), 2);
+        assert!(centers[0] > centers[1]);
+    }
+
+    #[test]
+    fn rotation_restores_angle() {
+        // Orange bar tilted 30 degrees in a frame the detector does not resize
+        let mut reference =
+            Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap();
+        let bar =
+            RotatedRect::new(Point2f::new(200.0, 150.0), Size2f::new(40.0, 200.0), 30.0).unwrap();
+        let mut corners = Mat::default();
+        box_points(bar, &mut corners).unwrap();
+        let corners: Vector<Point> = corners
+            .to_vec_2d::<f32>()
+            .unwrap()
+            .iter()
+            .map(|corner| Point::new(corner[0].round() as i32, corner[1].round() as i32))
+            .collect();
+        fill_convex_poly(
+            &mut reference,
+            &corners,
+            Scalar::new(0.0, 128.0, 255.0, 0.0),
+            LINE_8,
+            0,
+        )
+        .unwrap();
+
+        // Camera mounted a quarter turn counterclockwise
+        let mut mounted = Mat::default();
+        rotate(&reference, &mut mounted, ROTATE_90_COUNTERCLOCKWISE).unwrap();
+        let restored = FrameRotation::Cw90.apply(&mounted).unwrap();
+
+        let angle = |frame: &Mat| {
+            let detections =
+                <PathCV as VisualDetector<f64>>::detect(&mut PathCV::default(), frame).unwrap();
+            assert!(*detections[0].class());
+            *detections[0].position().angle()
+        };
+        assert_eq!(angle(&restored), angle(&reference));
+    }
 }
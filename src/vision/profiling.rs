@@ -0,0 +1,83 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "logging")]
+use crate::logln;
+
+static CV_PROFILING: AtomicBool = AtomicBool::new(false);
+
+/// When enabled, CV detectors time each stage of `detect` and log the breakdown.
+pub fn set_cv_profiling(enabled: bool) {
+    CV_PROFILING.store(enabled, Ordering::Relaxed);
+}
+
+pub fn cv_profiling() -> bool {
+    CV_PROFILING.load(Ordering::Relaxed)
+}
+
+/// Time spent in each stage of the last CV `detect` call
+///
+/// All zero unless profiling was enabled for that call.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StageTimings {
+    pub resize: Duration,
+    pub color_convert: Duration,
+    pub in_range: Duration,
+    /// Contour finding and selection
+    pub contours: Duration,
+}
+
+impl StageTimings {
+    pub fn total(&self) -> Duration {
+        self.resize + self.color_convert + self.in_range + self.contours
+    }
+
+    #[cfg(feature = "logging")]
+    pub fn log(&self, detector: &str) {
+        logln!(
+            "{detector} stages: resize {:?}, color convert {:?}, in_range {:?}, contours {:?} (total {:?})",
+            self.resize,
+            self.color_convert,
+            self.in_range,
+            self.contours,
+            self.total()
+        );
+    }
+}
+
+/// Measures consecutive stages, doing nothing unless profiling is enabled
+#[derive(Debug)]
+pub struct StageTimer {
+    last: Option<Instant>,
+    timings: StageTimings,
+}
+
+impl StageTimer {
+    pub fn start() -> Self {
+        Self {
+            last: cv_profiling().then(Instant::now),
+            timings: StageTimings::default(),
+        }
+    }
+
+    /// Adds the time since the previous lap to the stage chosen by `stage`
+    pub fn lap(&mut self, stage: impl FnOnce(&mut StageTimings) -> &mut Duration) {
+        if let Some(last) = &mut self.last {
+            let now = Instant::now();
+            *stage(&mut self.timings) += now - *last;
+            *last = now;
+        }
+    }
+
+    /// Final timings, logged under `detector` if profiling was enabled
+    #[allow(unused_variables)]
+    pub fn finish(self, detector: &str) -> StageTimings {
+        #[cfg(feature = "logging")]
+        if self.last.is_some() {
+            self.timings.log(detector);
+        }
+        self.timings
+    }
+}
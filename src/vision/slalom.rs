@@ -1,11 +1,15 @@
 use crate::config::ColorProfile;
 
-use super::{image_prep::resize, MatWrapper, PosVector, VisualDetection, VisualDetector, Yuv};
+use super::{
+    image_prep::{contour_confidence, input_yuv, is_i420, resize_input, ContourSelect},
+    profiling::{StageTimer, StageTimings},
+    MatWrapper, PosVector, VisualDetection, VisualDetector, Yuv,
+};
 use opencv::{
     core::{in_range, Point, Scalar, Size, Vector},
     imgproc::{
-        box_points, contour_area_def, cvt_color_def, find_contours_def, min_area_rect,
-        CHAIN_APPROX_SIMPLE, COLOR_BGR2YUV, RETR_EXTERNAL,
+        box_points, contour_area_def, find_contours_def, min_area_rect, CHAIN_APPROX_SIMPLE,
+        RETR_EXTERNAL,
     },
     prelude::{Mat, MatTraitConst, MatTraitConstManual},
 };
@@ -15,31 +19,49 @@ use std::ops::RangeInclusive;
 pub struct Slalom {
     color_bounds: RangeInclusive<Yuv>,
     area_bounds: RangeInclusive<f64>,
+    /// Maximum deviation from vertical (degrees) before a contour is rejected
+    max_pole_angle_dev: f64,
+    contour_select: ContourSelect,
     size: Size,
     image: MatWrapper,
+    timings: StageTimings,
 }
 
 impl Slalom {
+    /// Stage timings of the last [`VisualDetector::detect`] call
+    pub fn timings(&self) -> &StageTimings {
+        &self.timings
+    }
+
     pub fn new(
         color_bounds: RangeInclusive<Yuv>,
         area_bounds: RangeInclusive<f64>,
+        max_pole_angle_dev: f64,
+        contour_select: ContourSelect,
         size: Size,
     ) -> Self {
         Self {
             color_bounds,
             area_bounds,
+            max_pole_angle_dev,
+            contour_select,
             size,
             image: Mat::default().into(),
+            timings: StageTimings::default(),
         }
     }
 
     pub fn from_color_profile(
         color_profile: &ColorProfile,
         area_bounds: RangeInclusive<f64>,
+        max_pole_angle_dev: f64,
+        contour_select: ContourSelect,
     ) -> Self {
         Self::new(
             color_profile.red.clone(),
             area_bounds,
+            max_pole_angle_dev,
+            contour_select,
             Size::from((400, 300)),
         )
     }
@@ -59,6 +81,8 @@ impl Default for Slalom {
                 v: 255,
             }),
             1000.0..=11000.0,
+            20.0,
+            ContourSelect::default(),
             Size::from((400, 300)),
         )
     }
@@ -76,10 +100,12 @@ impl VisualDetector<f64> for Slalom {
         let min_area = areas.start();
         let max_area = areas.end();
 
-        self.image = resize(input_image, &self.size)?.into();
-        let mut yuv_image = Mat::default();
-
-        cvt_color_def(&self.image.0, &mut yuv_image, COLOR_BGR2YUV)?;
+        let mut timer = StageTimer::start();
+        let i420 = is_i420(input_image);
+        self.image = resize_input(input_image, &self.size)?.into();
+        timer.lap(|t| &mut t.resize);
+        let yuv_image = input_yuv(&self.image.0, i420)?;
+        timer.lap(|t| &mut t.color_convert);
 
         let color_start = self.color_bounds.start();
         let color_end = self.color_bounds.end();
@@ -97,17 +123,15 @@ impl VisualDetector<f64> for Slalom {
         );
 
         let mut mask = Mat::default();
-        let _ = in_range(&yuv_image, &lower_red, &upper_red, &mut mask);
+        let _ = in_range(&*yuv_image, &lower_red, &upper_red, &mut mask);
+        timer.lap(|t| &mut t.in_range);
 
         let mut contours = Vector::<Vector<Point>>::new();
         find_contours_def(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)?;
 
-        let max_contour = contours.iter().max_by(|x, y| {
-            contour_area_def(&x)
-                .unwrap()
-                .partial_cmp(&contour_area_def(&y).unwrap())
-                .unwrap()
-        });
+        let max_contour = self.contour_select.select(&contours, self.image.size()?)?;
+        timer.lap(|t| &mut t.contours);
+        self.timings = timer.finish("Slalom");
 
         if let Some(contour) = max_contour {
             let area = contour_area_def(&contour)?;
@@ -142,6 +166,18 @@ impl VisualDetector<f64> for Slalom {
 
                 println!("{angle:?}");
 
+                // Poles are always upright, so off-angle contours are lane lines or shadows
+                if (angle as f64).abs() > self.max_pole_angle_dev {
+                    #[cfg(feature = "logging")]
+                    logln!("Rejecting off-vertical contour: {angle}");
+
+                    return Ok(vec![VisualDetection {
+                        class: false,
+                        position: PosVector::new(0., 0., 0., 0.),
+                        confidence: 1.0,
+                    }]);
+                }
+
                 let center_adjusted_x = rect.center.x as f64;
                 let center_adjusted_y = rect.center.y as f64;
 
@@ -153,28 +189,122 @@ impl VisualDetector<f64> for Slalom {
                         0.,
                         angle as f64,
                     ),
+                    confidence: contour_confidence(&contour, Some(&areas))?,
                 }])
             } else {
                 Ok(vec![VisualDetection {
                     class: false,
                     position: PosVector::new(0., 0., 0., 0.),
+                    confidence: 1.0,
                 }])
             }
         } else {
             Ok(vec![VisualDetection {
                 class: false,
                 position: PosVector::new(0., 0., 0., 0.),
+                confidence: 1.0,
             }])
         }
     }
 
     fn normalize(&mut self, pos: &Self::Position) -> Self::Position {
         let img_size = self.image.size().unwrap();
-        Self::Position::new(
+        Self::Position::normalized(
             ((*pos.x() / (img_size.width as f64)) - 0.5) * 2.0,
             ((*pos.y() / (img_size.height as f64)) - 0.5) * 2.0,
             0.,
             *pos.angle(),
         )
     }
+
+    fn accepts_i420(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::{
+        core::{Point, Scalar, CV_8UC3},
+        imgproc::{fill_convex_poly_def, rectangle_def},
+        prelude::Mat,
+    };
+
+    use super::*;
+
+    /// Detector that picks up anything bright, independent of color profile
+    fn bright_slalom() -> Slalom {
+        Slalom::new(
+            (Yuv { y: 200, u: 0, v: 0 })..=(Yuv {
+                y: 255,
+                u: 255,
+                v: 255,
+            }),
+            1000.0..=11000.0,
+            20.0,
+            ContourSelect::default(),
+            Size::from((400, 300)),
+        )
+    }
+
+    fn blank_image() -> Mat {
+        Mat::new_rows_cols_with_default(300, 400, CV_8UC3, Scalar::all(0.0)).unwrap()
+    }
+
+    #[test]
+    fn vertical_pole_accepted() {
+        let mut image = blank_image();
+        rectangle_def(
+            &mut image,
+            opencv::core::Rect::new(190, 60, 20, 150),
+            Scalar::all(255.0),
+        )
+        .unwrap();
+
+        let mut slalom = bright_slalom();
+        let detections = <Slalom as VisualDetector<f64>>::detect(&mut slalom, &image).unwrap();
+        assert_eq!(detections.len(), 1);
+        assert!(*detections[0].class());
+    }
+
+    #[test]
+    fn angled_pole_rejected() {
+        let mut image = blank_image();
+        // 20x150 bar leaning roughly 45 degrees
+        let bar = Vector::<Point>::from_iter([
+            Point::new(120, 220),
+            Point::new(134, 234),
+            Point::new(240, 128),
+            Point::new(226, 114),
+        ]);
+        fill_convex_poly_def(&mut image, &bar, Scalar::all(255.0)).unwrap();
+
+        let mut slalom = bright_slalom();
+        let detections = <Slalom as VisualDetector<f64>>::detect(&mut slalom, &image).unwrap();
+        assert_eq!(detections.len(), 1);
+        assert!(!*detections[0].class());
+    }
+
+    #[test]
+    fn profiling_records_stage_timings() {
+        let mut image = blank_image();
+        rectangle_def(
+            &mut image,
+            opencv::core::Rect::new(190, 60, 20, 150),
+            Scalar::all(255.0),
+        )
+        .unwrap();
+
+        let previous = crate::vision::profiling::cv_profiling();
+        crate::vision::profiling::set_cv_profiling(true);
+        let mut slalom = bright_slalom();
+        <Slalom as VisualDetector<f64>>::detect(&mut slalom, &image).unwrap();
+        crate::vision::profiling::set_cv_profiling(previous);
+
+        let timings = slalom.timings();
+        assert!(!timings.resize.is_zero());
+        assert!(!timings.color_convert.is_zero());
+        assert!(!timings.in_range.is_zero());
+        assert!(!timings.contours.is_zero());
+    }
 }
@@ -5,7 +5,7 @@ use opencv::{core::Size, prelude::Mat};
 use crate::load_onnx;
 
 use super::{
-    nn_cv2::{OnnxModel, VisionModel, YoloClass, YoloDetection},
+    nn_cv2::{report_confidence_floor, OnnxModel, VisionModel, YoloClass, YoloDetection},
     yolo_model::YoloProcessor,
 };
 
@@ -18,6 +18,8 @@ pub enum Target {
     Side,
 }
 
+crate::class_names!(Target { Middle, Side });
+
 impl From<YoloClass<Target>> for Target {
     fn from(value: YoloClass<Target>) -> Self {
         value.identifier
@@ -57,6 +59,7 @@ impl Display for Target {
 #[derive(Debug, Clone, Getters)]
 pub struct Slalom<T: VisionModel> {
     model: T,
+    /// Minimum confidence missions act on, applied after detection
     threshold: f64,
 }
 
@@ -84,7 +87,7 @@ impl YoloProcessor for Slalom<OnnxModel> {
     type Target = Target;
 
     fn detect_yolo_v5(&mut self, image: &Mat) -> Vec<YoloDetection> {
-        self.model.detect_yolo_v5(image, self.threshold)
+        self.model.detect_yolo_v5(image, report_confidence_floor())
     }
 
     fn model_size(&self) -> Size {
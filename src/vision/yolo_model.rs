@@ -1,9 +1,11 @@
 use std::{
     fmt::{Debug, Display},
     hash::Hash,
+    str::FromStr,
 };
 
 use super::{
+    class_map::{resolve_class, ClassMap},
     nn_cv2::{YoloClass, YoloDetection},
     Draw, DrawRect2d, RelPos, VisualDetection, VisualDetector,
 };
@@ -17,10 +19,15 @@ use opencv::{
 pub trait YoloTarget: PartialEq + Eq + Hash + Clone + Debug + TryFrom<i32> {}
 
 pub trait YoloProcessor: Debug {
-    type Target: PartialEq + Eq + Hash + Clone + Debug + TryFrom<i32>;
+    type Target: PartialEq + Eq + Hash + Clone + Debug + TryFrom<i32> + FromStr;
 
     fn detect_yolo_v5(&mut self, image: &Mat) -> Vec<YoloDetection>;
     fn model_size(&self) -> Size;
+
+    /// Runtime class mapping, the compiled `TryFrom<i32>` mapping if `None`
+    fn class_map(&self) -> Option<&ClassMap> {
+        None
+    }
 }
 
 impl<T: YoloProcessor> VisualDetector<f64> for T
@@ -35,19 +42,21 @@ where
         &mut self,
         image: &Mat,
     ) -> Result<Vec<VisualDetection<Self::ClassEnum, Self::Position>>> {
-        Ok(self
-            .detect_yolo_v5(image)
+        self.detect_yolo_v5(image)
             .into_iter()
-            .map(|detection| VisualDetection {
-                class: YoloClass {
-                    identifier: detection.class_id().to_owned().try_into().unwrap(),
+            .map(|detection| {
+                Ok(VisualDetection {
+                    class: YoloClass {
+                        identifier: resolve_class(self.class_map(), *detection.class_id())?,
+                        confidence: *detection.confidence(),
+                    },
+                    position: DrawRect2d {
+                        inner: *detection.bounding_box(),
+                    },
                     confidence: *detection.confidence(),
-                },
-                position: DrawRect2d {
-                    inner: *detection.bounding_box(),
-                },
+                })
             })
-            .collect::<Vec<_>>())
+            .collect()
     }
 
     fn normalize(&mut self, pos: &Self::Position) -> Self::Position {
@@ -65,14 +74,34 @@ where
     }
 }
 
+/// Keeps only detections at or above `min_confidence`.
+///
+/// Models report everything above [`report_confidence_floor`], so missions
+/// apply this before acting on detections.
+///
+/// [`report_confidence_floor`]: super::nn_cv2::report_confidence_floor
+pub fn filter_confidence<T, U>(
+    detections: impl IntoIterator<Item = VisualDetection<YoloClass<T>, U>>,
+    min_confidence: f64,
+) -> Vec<VisualDetection<YoloClass<T>, U>> {
+    detections
+        .into_iter()
+        .filter(|detection| detection.class().confidence >= min_confidence)
+        .collect()
+}
+
 impl<T: Display> Draw for VisualDetection<YoloClass<T>, DrawRect2d> {
     fn draw(&self, canvas: &mut Mat) -> Result<()> {
         self.position.draw(canvas)?;
 
+        // Red at zero confidence, fading to green at full confidence
+        let confidence = self.class.confidence.clamp(0.0, 1.0);
+        let color = Scalar::from((0.0, 255.0 * confidence, 255.0 * (1.0 - confidence)));
+
         let center_point = self.position.offset();
         imgproc::put_text(
             canvas,
-            &self.class.identifier.to_string(),
+            &format!("{} {:.2}", self.class.identifier, self.class.confidence),
             Point::new(
                 // Adjust x to 1/4 from left b/c draw starts bottom left
                 ((self.position.x + center_point.x) / 2.0) as i32,
@@ -80,7 +109,7 @@ impl<T: Display> Draw for VisualDetection<YoloClass<T>, DrawRect2d> {
             ),
             imgproc::FONT_HERSHEY_COMPLEX,
             0.75,
-            Scalar::from((255.0, 122.5, 0.0)),
+            color,
             1,
             LINE_AA,
             false,